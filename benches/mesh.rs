@@ -0,0 +1,107 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use criterion::{criterion_group, criterion_main, Criterion};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+fn uplink_frame(uplink_id: u16) -> gw::UplinkFrame {
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Uplink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id,
+                dr: 0,
+                rssi: -60,
+                snr: 6,
+                channel: 2,
+                border_id: 0,
+            },
+            relay_id: [1, 2, 3, 4],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![0x40; 51],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -110,
+            snr: -3.5,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+// Measures the Border Gateway handle_mesh path, from the mesh uplink published by the mock mesh
+// Concentratord until the unwrapped uplink is received by the mock Forwarder. This includes the
+// ZeroMQ (IPC) round-trip.
+fn bench_handle_mesh(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(common::setup(true));
+
+    // The mock setup logs at trace level, which would dominate the measurement.
+    log::set_max_level(log::LevelFilter::Warn);
+
+    // The uplink ID is incremented on every iteration, as otherwise the packet would be
+    // dropped as duplicate.
+    let mut uplink_id: u16 = 0;
+
+    c.bench_function("handle_mesh/border_gateway_uplink", |b| {
+        b.iter(|| {
+            uplink_id = (uplink_id + 1) % 4096;
+            let up = uplink_frame(uplink_id);
+
+            rt.block_on(async {
+                {
+                    let mut event_sock =
+                        common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+                    event_sock
+                        .send(
+                            vec![
+                                bytes::Bytes::from("up"),
+                                bytes::Bytes::from(up.encode_to_vec()),
+                            ]
+                            .try_into()
+                            .unwrap(),
+                        )
+                        .await
+                        .unwrap();
+                }
+
+                let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+                event_sock.recv().await.unwrap()
+            })
+        })
+    });
+}
+
+criterion_group!(benches, bench_handle_mesh);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+
+    let proto_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let proto_dir = Path::new(&proto_dir).join("proto");
+
+    prost_build::Config::new()
+        .out_dir(out_dir)
+        .file_descriptor_set_path(out_dir.join("proto_descriptor.bin"))
+        .compile_well_known_types()
+        .extern_path(".google.protobuf", "::pbjson_types")
+        .compile_protos(
+            &[proto_dir.join("mesh.proto").to_str().unwrap()],
+            &[proto_dir.to_str().unwrap()],
+        )?;
+
+    let descriptor_set = std::fs::read(out_dir.join("proto_descriptor.bin"))?;
+    pbjson_build::Builder::new()
+        .register_descriptors(&descriptor_set)?
+        .ignore_unknown_fields()
+        .out_dir(out_dir)
+        .build(&[".mesh"])?;
+
+    println!("cargo:rerun-if-changed=proto/mesh.proto");
+
+    Ok(())
+}
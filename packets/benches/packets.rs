@@ -0,0 +1,93 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use chirpstack_gateway_mesh_packets::aes128::Aes128Key;
+use chirpstack_gateway_mesh_packets::{
+    HeartbeatPayload, MeshPacket, Packet, Payload, PayloadType, RelayPath, UplinkMetadata,
+    UplinkPayload, MHDR,
+};
+
+fn uplink_packet() -> MeshPacket {
+    let mut packet = MeshPacket {
+        mhdr: MHDR {
+            payload_type: PayloadType::Uplink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: Payload::Uplink(UplinkPayload {
+            metadata: UplinkMetadata {
+                uplink_id: 1024,
+                dr: 5,
+                rssi: -80,
+                snr: 7,
+                channel: 2,
+                border_id: 0,
+            },
+            relay_id: [1, 2, 3, 4],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![0x40; 51],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+    packet
+}
+
+fn heartbeat_packet() -> MeshPacket {
+    let mut packet = MeshPacket {
+        mhdr: MHDR {
+            payload_type: PayloadType::Heartbeat,
+            hop_count: 8,
+            key_index: 0,
+        },
+        payload: Payload::Heartbeat(HeartbeatPayload {
+            timestamp: 1_700_000_000_000,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            relay_path: (0..7)
+                .map(|i| RelayPath {
+                    relay_id: [i, i, i, i],
+                    rssi: -100,
+                    snr: -5,
+                })
+                .collect(),
+            config_checksum: None,
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+    packet
+}
+
+fn bench_packets(c: &mut Criterion) {
+    for (name, packet) in [
+        ("uplink", uplink_packet()),
+        ("heartbeat", heartbeat_packet()),
+    ] {
+        let b = packet.to_vec().unwrap();
+
+        c.bench_function(&format!("{}/from_slice", name), |bench| {
+            bench.iter(|| Packet::from_slice(black_box(&b)).unwrap())
+        });
+
+        c.bench_function(&format!("{}/to_vec", name), |bench| {
+            bench.iter(|| black_box(&packet).to_vec().unwrap())
+        });
+
+        c.bench_function(&format!("{}/set_mic", name), |bench| {
+            let mut packet = packet.clone();
+            bench.iter(|| packet.set_mic(black_box(Aes128Key::null())).unwrap())
+        });
+
+        c.bench_function(&format!("{}/validate_mic", name), |bench| {
+            bench.iter(|| {
+                black_box(&packet)
+                    .validate_mic(black_box(Aes128Key::null()))
+                    .unwrap()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_packets);
+criterion_main!(benches);
@@ -1,12 +1,17 @@
-use std::fmt;
-use std::str::FromStr;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
 
+#[cfg(feature = "serde")]
+use alloc::string::ToString;
 use anyhow::{Error, Result};
+#[cfg(feature = "serde")]
 use serde::{
     de::{self, Visitor},
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
+/// AES128 key, used as mesh signing key.
 #[derive(Copy, Clone, PartialEq, Eq, Default)]
 pub struct Aes128Key([u8; 16]);
 
@@ -56,11 +61,12 @@ impl FromStr for Aes128Key {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut bytes: [u8; 16] = [0; 16];
-        hex::decode_to_slice(s, &mut bytes)?;
+        hex::decode_to_slice(s, &mut bytes).map_err(|e| anyhow!("{}", e))?;
         Ok(Aes128Key(bytes))
     }
 }
 
+#[cfg(feature = "serde")]
 impl Serialize for Aes128Key {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -70,6 +76,7 @@ impl Serialize for Aes128Key {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Aes128Key {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -79,8 +86,10 @@ impl<'de> Deserialize<'de> for Aes128Key {
     }
 }
 
+#[cfg(feature = "serde")]
 struct Aes128KeyVisitor;
 
+#[cfg(feature = "serde")]
 impl<'de> Visitor<'de> for Aes128KeyVisitor {
     type Value = Aes128Key;
 
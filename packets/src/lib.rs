@@ -0,0 +1,3356 @@
+//! Encoding and decoding of ChirpStack Gateway Mesh packets.
+//!
+//! Mesh packets are sent as LoRa frames using the LoRaWAN proprietary MType
+//! (`111`), which makes it possible to distinguish them from LoRaWAN frames.
+//!
+//! # Packet format
+//!
+//! There are two versions of the packet format (see [`Version`]). Version 1
+//! packets have the following layout:
+//!
+//! ```text
+//! | MHDR (1) | Payload (n) | MIC (4) |
+//! ```
+//!
+//! The MHDR contains the proprietary MType (3 bits), the [`PayloadType`] (2
+//! bits) and the hop count (3 bits). The MIC is the first 4 bytes of the
+//! AES128 CMAC over the MHDR and payload, using the signing key.
+//!
+//! Version 2 packets are marked by the payload type bits `11` in the MHDR,
+//! which is followed by an MHDR extension byte:
+//!
+//! ```text
+//! | MHDR (1) | MHDR extension (1) | Payload (n) | MIC (4) |
+//!
+//! MHDR extension: | Payload type (2) | RFU (2) | Key index (4) |
+//! ```
+//!
+//! Version 2 adds:
+//!
+//! * The key index, identifying the signing key used to calculate the MIC,
+//!   such that signing keys can be rotated without re-configuring all
+//!   gateways at once. Version 1 packets are signed with key index 0. As mesh
+//!   packets are signed but not encrypted, the signing key is the only key
+//!   that needs to be rotated.
+//! * The [`PayloadType::Event`] payload type.
+//! * Heartbeat timestamps with millisecond precision and a boot-relative
+//!   (monotonic) fallback, encoded in 6 bytes. Version 1 heartbeats contain
+//!   a 4 byte timestamp (seconds since the UNIX epoch).
+//! * A timestamp in each relayed uplink, used for replay protection.
+//!
+//! A packet is encoded using version 1, unless it uses one of the above (see
+//! [`MeshPacket::version`]). Decoding a version 2 packet that could have been
+//! encoded using version 1 fails, such that re-encoding a decoded packet
+//! (e.g. when relaying it) always results in the same bytes and MIC.
+//!
+//! Gateways that only implement version 1 drop version 2 packets, as the
+//! payload type `11` is unknown to them. Features that depend on version 2
+//! must therefore only be enabled once all gateways support it.
+//!
+//! The `std` feature is enabled by default. Without it, this crate is `no_std`
+//! and only depends on `alloc`, so that it can be used on embedded targets.
+//!
+//! # Example
+//!
+//! ```
+//! use chirpstack_gateway_mesh_packets::aes128::Aes128Key;
+//! use chirpstack_gateway_mesh_packets::{
+//!     HeartbeatPayload, MeshPacket, Packet, Payload, PayloadType, MHDR,
+//! };
+//!
+//! let mut packet = MeshPacket {
+//!     mhdr: MHDR {
+//!         payload_type: PayloadType::Heartbeat,
+//!         hop_count: 1,
+//!         key_index: 0,
+//!     },
+//!     payload: Payload::Heartbeat(HeartbeatPayload {
+//!         timestamp: 1_700_000_000_000,
+//!         timestamp_monotonic: false,
+//!         relay_id: [1, 2, 3, 4],
+//!         relay_path: vec![],
+//!         config_checksum: None,
+//!     }),
+//!     mic: None,
+//! };
+//! packet.set_mic(Aes128Key::null()).unwrap();
+//!
+//! let b = packet.to_vec().unwrap();
+//! assert_eq!(Packet::Mesh(packet), Packet::from_slice(&b).unwrap());
+//! ```
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[macro_use]
+extern crate anyhow;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use aes::Aes128;
+use anyhow::Result;
+use cmac::{Cmac, Mac};
+
+use aes128::Aes128Key;
+
+pub mod aes128;
+
+/// MHDR bits (LoRaWAN proprietary MType) identifying a mesh packet.
+pub const MHDR_PREFIX: u8 = 0xe0;
+
+/// Minimum hop count that can be encoded in the MHDR.
+pub const MIN_HOP_COUNT: u8 = 1;
+
+/// Maximum hop count that can be encoded in the MHDR.
+pub const MAX_HOP_COUNT: u8 = 8;
+
+/// Size of the encoded [`MHDR`] in bytes, excluding the version 2 MHDR
+/// extension.
+pub const MHDR_SIZE: usize = 1;
+
+/// Size of the version 2 MHDR extension in bytes.
+pub const MHDR_EXTENSION_SIZE: usize = 1;
+
+/// Max key index that can be encoded in the (version 2) MHDR extension.
+pub const MAX_KEY_INDEX: u8 = 15;
+
+/// Size of the MIC in bytes.
+pub const MIC_SIZE: usize = 4;
+
+/// Size of a Relay ID in bytes.
+pub const RELAY_ID_SIZE: usize = 4;
+
+/// Size of the encoded [`UplinkMetadata`] in bytes.
+pub const UPLINK_METADATA_SIZE: usize = 5;
+
+/// Size of the encoded [`DownlinkMetadata`] in bytes.
+pub const DOWNLINK_METADATA_SIZE: usize = 6;
+
+/// Size of an encoded [`RelayPath`] entry in bytes.
+pub const RELAY_PATH_SIZE: usize = 6;
+
+/// Size of an encoded (version 2) timestamp in bytes.
+pub const TIMESTAMP_SIZE: usize = 6;
+
+/// Size of the encoded version 1 [`HeartbeatPayload`] timestamp in bytes.
+pub const HEARTBEAT_TIMESTAMP_V1_SIZE: usize = 4;
+
+/// Size of the encoded [`HeartbeatPayload`] config checksum in bytes.
+pub const HEARTBEAT_CONFIG_CHECKSUM_SIZE: usize = 4;
+
+/// Max value of an encoded (version 2) timestamp (47 bits).
+pub const TIMESTAMP_MAX: u64 = (1 << 47) - 1;
+
+/// Max size of a single encoded [`Event`] value in bytes.
+pub const EVENT_MAX_SIZE: usize = 255;
+
+/// Packet format version, see the crate documentation.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+impl Version {
+    /// Returns the version of the packet starting with the given MHDR byte.
+    pub fn from_mhdr_byte(b: u8) -> Self {
+        if (b >> 3) & 0x03 == 0x03 {
+            Version::V2
+        } else {
+            Version::V1
+        }
+    }
+
+    /// Returns the size of the MHDR, including the MHDR extension.
+    pub fn mhdr_size(&self) -> usize {
+        match self {
+            Version::V1 => MHDR_SIZE,
+            Version::V2 => MHDR_SIZE + MHDR_EXTENSION_SIZE,
+        }
+    }
+}
+
+/// A frame received or transmitted by a gateway.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Packet {
+    /// Mesh encapsulated packet.
+    Mesh(MeshPacket),
+    /// Any other LoRa frame (e.g. LoRaWAN), as-is.
+    Lora(Vec<u8>),
+}
+
+impl Packet {
+    /// Decodes the given bytes into a mesh packet if the MHDR has the
+    /// proprietary MType, or into a LoRa packet otherwise.
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        if b.is_empty() {
+            return Err(anyhow!("Input is empty"));
+        }
+
+        // Check for proprietary "111" bits prefix.
+        if b[0] & MHDR_PREFIX == MHDR_PREFIX {
+            Ok(Packet::Mesh(MeshPacket::from_slice(b)?))
+        } else {
+            Ok(Packet::Lora(b.to_vec()))
+        }
+    }
+
+    /// Encodes the packet into bytes.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        match self {
+            Packet::Mesh(v) => v.to_vec(),
+            Packet::Lora(v) => Ok(v.clone()),
+        }
+    }
+}
+
+/// Mesh packet.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MeshPacket {
+    pub mhdr: MHDR,
+    pub payload: Payload,
+    /// MIC, this must be set (see [`MeshPacket::set_mic`]) before encoding.
+    pub mic: Option<[u8; 4]>,
+}
+
+impl MeshPacket {
+    /// Decodes the given bytes into a mesh packet. This does not validate
+    /// the MIC (see [`MeshPacket::validate_mic`]).
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        let len = b.len();
+
+        if len == 0 {
+            return Err(anyhow!("Input is empty"));
+        }
+
+        let version = Version::from_mhdr_byte(b[0]);
+        let mhdr_size = version.mhdr_size();
+        if len < mhdr_size + MIC_SIZE {
+            return Err(anyhow!("Not enough bytes to decode mhdr + mic"));
+        }
+
+        let mhdr = MHDR::from_slice(&b[0..mhdr_size])?;
+        let mut mic: [u8; 4] = [0; 4];
+        mic.copy_from_slice(&b[len - MIC_SIZE..len]);
+
+        let payload_b = &b[mhdr_size..len - MIC_SIZE];
+
+        let packet = MeshPacket {
+            payload: match mhdr.payload_type {
+                PayloadType::Uplink => {
+                    Payload::Uplink(UplinkPayload::from_slice(payload_b, version)?)
+                }
+                PayloadType::Downlink => Payload::Downlink(DownlinkPayload::from_slice(payload_b)?),
+                PayloadType::Heartbeat => {
+                    Payload::Heartbeat(HeartbeatPayload::from_slice(payload_b, version)?)
+                }
+                PayloadType::Event => Payload::Event(EventPayload::from_slice(payload_b)?),
+            },
+            mic: Some(mic),
+            mhdr,
+        };
+
+        if packet.version() != version {
+            return Err(anyhow!("Packet must be encoded using version 1"));
+        }
+
+        Ok(packet)
+    }
+
+    /// Encodes the mesh packet into bytes. This returns an error if the MIC
+    /// is not set.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut b = self.mic_bytes()?;
+
+        if let Some(mic) = self.mic {
+            b.extend_from_slice(&mic);
+        } else {
+            return Err(anyhow!("MIC is None"));
+        }
+
+        Ok(b)
+    }
+
+    /// Returns the packet format version used to encode the packet. This is
+    /// the lowest version that supports both the MHDR and the payload.
+    pub fn version(&self) -> Version {
+        let payload_version = match &self.payload {
+            Payload::Uplink(v) => v.version(),
+            Payload::Downlink(_) => Version::V1,
+            Payload::Heartbeat(v) => v.version(),
+            Payload::Event(_) => Version::V2,
+        };
+
+        self.mhdr.version().max(payload_version)
+    }
+
+    fn mic_bytes(&self) -> Result<Vec<u8>> {
+        let version = self.version();
+
+        let mut b = self.mhdr.to_vec(version)?;
+        b.extend_from_slice(&match &self.payload {
+            Payload::Uplink(v) => v.to_vec(version)?,
+            Payload::Downlink(v) => v.to_vec()?,
+            Payload::Heartbeat(v) => v.to_vec(version)?,
+            Payload::Event(v) => v.to_vec()?,
+        });
+
+        Ok(b)
+    }
+
+    /// Calculates and sets the MIC using the given signing key.
+    pub fn set_mic(&mut self, key: Aes128Key) -> Result<()> {
+        self.mic = Some(self.calculate_mic(key)?);
+        Ok(())
+    }
+
+    /// Returns true if the MIC is valid for the given signing key.
+    pub fn validate_mic(&self, key: Aes128Key) -> Result<bool> {
+        if let Some(mic) = self.mic {
+            if mic == self.calculate_mic(key)? {
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        } else {
+            Err(anyhow!("MIC is None"))
+        }
+    }
+
+    fn calculate_mic(&self, key: Aes128Key) -> Result<[u8; 4]> {
+        let mut mac = Cmac::<Aes128>::new_from_slice(&key.to_bytes()).unwrap();
+        mac.update(&self.mic_bytes()?);
+        let cmac_f = mac.finalize().into_bytes();
+        // sanity Check
+        if cmac_f.len() < 4 {
+            return Err(anyhow!("cmac_f is less than 4 bytes"));
+        }
+
+        let mut mic: [u8; 4] = [0; 4];
+        mic.clone_from_slice(&cmac_f[0..4]);
+        Ok(mic)
+    }
+}
+
+impl fmt::Display for MeshPacket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.payload {
+            Payload::Uplink(v) => write!(
+                f,
+                "[{:?} hop_count: {}, uplink_id: {}, relay_id: {}, timestamp: {:?}, mic: {}]",
+                self.mhdr.payload_type,
+                self.mhdr.hop_count,
+                v.metadata.uplink_id,
+                hex::encode(v.relay_id),
+                v.timestamp,
+                self.mic.map(hex::encode).unwrap_or_default(),
+            ),
+            Payload::Downlink(v) => write!(
+                f,
+                "[{:?} hop_count: {}, uplink_id: {}, relay_id: {}, mic: {}]",
+                self.mhdr.payload_type,
+                self.mhdr.hop_count,
+                v.metadata.uplink_id,
+                hex::encode(v.relay_id),
+                self.mic.map(hex::encode).unwrap_or_default(),
+            ),
+            Payload::Heartbeat(v) => write!(
+                f,
+                "[{:?} hop_count: {}, timestamp: {}, timestamp_monotonic: {}, relay_id: {}]",
+                self.mhdr.payload_type,
+                self.mhdr.hop_count,
+                v.timestamp,
+                v.timestamp_monotonic,
+                hex::encode(v.relay_id),
+            ),
+            Payload::Event(v) => write!(
+                f,
+                "[{:?} hop_count: {}, timestamp: {}, timestamp_monotonic: {}, relay_id: {}, events: {:?}]",
+                self.mhdr.payload_type,
+                self.mhdr.hop_count,
+                v.timestamp,
+                v.timestamp_monotonic,
+                hex::encode(v.relay_id),
+                v.events.iter().map(|v| v.event_type()).collect::<Vec<u8>>(),
+            ),
+        }
+    }
+}
+
+/// Mesh header.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MHDR {
+    pub payload_type: PayloadType,
+    pub hop_count: u8, // 000 = 1, ... 111 = 8
+    /// Index of the signing key used to calculate the MIC (max
+    /// [`MAX_KEY_INDEX`]). Key index 0 is encoded using version 1, other
+    /// key indices require version 2.
+    pub key_index: u8,
+}
+
+impl MHDR {
+    /// Decodes the MHDR from the given bytes. The MHDR extension byte is only
+    /// read in case of a version 2 MHDR.
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        if b.is_empty() {
+            return Err(anyhow!("Input is empty"));
+        }
+
+        if b[0] & MHDR_PREFIX != MHDR_PREFIX {
+            return Err(anyhow!("Invalid MType"));
+        }
+
+        let hop_count = (b[0] & 0x07) + 1;
+
+        match Version::from_mhdr_byte(b[0]) {
+            Version::V1 => Ok(MHDR {
+                payload_type: PayloadType::from_byte((b[0] >> 3) & 0x03)?,
+                hop_count,
+                key_index: 0,
+            }),
+            Version::V2 => {
+                if b.len() < MHDR_SIZE + MHDR_EXTENSION_SIZE {
+                    return Err(anyhow!("Not enough bytes to decode mhdr extension"));
+                }
+
+                if b[1] & 0x30 != 0 {
+                    return Err(anyhow!("RFU bits of mhdr extension must be 0"));
+                }
+
+                Ok(MHDR {
+                    payload_type: PayloadType::from_byte(b[1] >> 6)?,
+                    hop_count,
+                    key_index: b[1] & 0x0f,
+                })
+            }
+        }
+    }
+
+    /// Encodes the MHDR into bytes, using the given version (see
+    /// [`MHDR::version`]).
+    pub fn to_vec(&self, version: Version) -> Result<Vec<u8>> {
+        if self.hop_count < MIN_HOP_COUNT {
+            return Err(anyhow!("Min hop_count is {}", MIN_HOP_COUNT));
+        }
+
+        if self.hop_count > MAX_HOP_COUNT {
+            return Err(anyhow!("Max hop_count is {}", MAX_HOP_COUNT));
+        }
+
+        if self.key_index > MAX_KEY_INDEX {
+            return Err(anyhow!("Max key_index is {}", MAX_KEY_INDEX));
+        }
+
+        match version {
+            Version::V1 => {
+                if self.version() != Version::V1 {
+                    return Err(anyhow!(
+                        "Event payload type and key_index {} require version 2",
+                        self.key_index
+                    ));
+                }
+
+                Ok(vec![
+                    MHDR_PREFIX | self.payload_type.to_byte() << 3 | (self.hop_count - 1),
+                ])
+            }
+            Version::V2 => Ok(vec![
+                MHDR_PREFIX | 0x03 << 3 | (self.hop_count - 1),
+                self.payload_type.to_byte() << 6 | self.key_index,
+            ]),
+        }
+    }
+
+    /// Returns the lowest version that can encode the MHDR.
+    pub fn version(&self) -> Version {
+        if self.payload_type == PayloadType::Event || self.key_index != 0 {
+            Version::V2
+        } else {
+            Version::V1
+        }
+    }
+
+    /// Decodes a version 1 MHDR.
+    #[deprecated(note = "use MHDR::from_slice, which also decodes version 2 MHDRs")]
+    pub fn from_byte(b: u8) -> Result<Self> {
+        if Version::from_mhdr_byte(b) != Version::V1 {
+            return Err(anyhow!("Version 2 MHDR, use MHDR::from_slice"));
+        }
+
+        MHDR::from_slice(&[b])
+    }
+
+    /// Encodes the MHDR as version 1 MHDR.
+    #[deprecated(note = "use MHDR::to_vec, which also encodes version 2 MHDRs")]
+    pub fn to_byte(&self) -> Result<u8> {
+        Ok(self.to_vec(Version::V1)?[0])
+    }
+}
+
+/// Mesh payload type.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PayloadType {
+    Uplink,
+    Downlink,
+    Heartbeat,
+    Event,
+}
+
+impl PayloadType {
+    pub fn from_byte(b: u8) -> Result<Self> {
+        Ok(match b {
+            0x00 => PayloadType::Uplink,
+            0x01 => PayloadType::Downlink,
+            0x02 => PayloadType::Heartbeat,
+            0x03 => PayloadType::Event,
+            _ => return Err(anyhow!("Unexpected PayloadType: {}", b)),
+        })
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            PayloadType::Uplink => 0x00,
+            PayloadType::Downlink => 0x01,
+            PayloadType::Heartbeat => 0x02,
+            PayloadType::Event => 0x03,
+        }
+    }
+}
+
+/// Mesh payload.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Payload {
+    Uplink(UplinkPayload),
+    Downlink(DownlinkPayload),
+    Heartbeat(HeartbeatPayload),
+    Event(EventPayload),
+}
+
+/// Relayed uplink (Relay Gateway to Border Gateway).
+///
+/// Version 2 uplinks contain a timestamp, which is encoded after the Relay
+/// ID: `| Metadata (5) | Relay ID (4) | Timestamp (6) | PHYPayload (n) |`.
+/// A zero timestamp (without monotonic flag) is encoded for uplinks without
+/// timestamp, e.g. a version 1 uplink that is re-signed by a relay using a
+/// key index other than 0.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UplinkPayload {
+    pub metadata: UplinkMetadata,
+    pub relay_id: [u8; 4],
+    /// Timestamp (milliseconds, max [`TIMESTAMP_MAX`]), see
+    /// [`HeartbeatPayload::timestamp`]. This requires version 2 and must be
+    /// unique per Relay Gateway, as it is used for replay protection. A zero
+    /// wall-clock timestamp is reserved.
+    pub timestamp: Option<u64>,
+    pub timestamp_monotonic: bool,
+    pub phy_payload: Vec<u8>,
+}
+
+impl UplinkPayload {
+    pub fn from_slice(b: &[u8], version: Version) -> Result<UplinkPayload> {
+        let min_size = UPLINK_METADATA_SIZE
+            + RELAY_ID_SIZE
+            + match version {
+                Version::V1 => 0,
+                Version::V2 => TIMESTAMP_SIZE,
+            };
+
+        if b.len() < min_size {
+            return Err(anyhow!("At least {} bytes are expected", min_size));
+        }
+
+        let mut md = [0; 5];
+        let mut gw_id = [0; 4];
+        md.copy_from_slice(&b[0..5]);
+        gw_id.copy_from_slice(&b[5..9]);
+
+        let (timestamp, timestamp_monotonic) = match version {
+            Version::V1 => (None, false),
+            Version::V2 => match decode_timestamp(&b[9..min_size]) {
+                (0, false) => (None, false),
+                (timestamp, timestamp_monotonic) => (Some(timestamp), timestamp_monotonic),
+            },
+        };
+
+        Ok(UplinkPayload {
+            metadata: UplinkMetadata::from_bytes(md),
+            relay_id: gw_id,
+            timestamp,
+            timestamp_monotonic,
+            phy_payload: b[min_size..].to_vec(),
+        })
+    }
+
+    pub fn to_vec(&self, version: Version) -> Result<Vec<u8>> {
+        let mut b = self.metadata.to_bytes()?.to_vec();
+        b.extend_from_slice(&self.relay_id);
+        if self.timestamp.is_none() && self.timestamp_monotonic {
+            return Err(anyhow!("timestamp_monotonic requires a timestamp"));
+        }
+        if self.timestamp == Some(0) && !self.timestamp_monotonic {
+            return Err(anyhow!("Uplink timestamp 0 is reserved"));
+        }
+        match (version, self.timestamp) {
+            (Version::V1, None) => {}
+            (Version::V1, Some(_)) => return Err(anyhow!("Uplink timestamp requires version 2")),
+            (Version::V2, timestamp) => {
+                b.extend_from_slice(&encode_timestamp(
+                    timestamp.unwrap_or_default(),
+                    self.timestamp_monotonic,
+                )?);
+            }
+        }
+        b.extend_from_slice(&self.phy_payload);
+        Ok(b)
+    }
+
+    /// Returns the lowest version that can encode the payload.
+    pub fn version(&self) -> Version {
+        if self.timestamp.is_some() {
+            Version::V2
+        } else {
+            Version::V1
+        }
+    }
+}
+
+/// Metadata of a relayed uplink.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UplinkMetadata {
+    /// Uplink ID (12 bits).
+    pub uplink_id: u16,
+    /// Data-rate index (4 bits).
+    pub dr: u8,
+    /// RSSI (-255 - 0).
+    pub rssi: i16,
+    /// SNR (-32 - 31).
+    pub snr: i8,
+    /// Channel index.
+    pub channel: u8,
+    /// Target Border Gateway ID (2 bits), 0 means any Border Gateway.
+    pub border_id: u8,
+}
+
+impl UplinkMetadata {
+    pub fn from_bytes(b: [u8; 5]) -> Self {
+        let snr = b[3] & 0x3f;
+        let snr = if snr > 31 {
+            (snr as i8) - 64
+        } else {
+            snr as i8
+        };
+
+        UplinkMetadata {
+            uplink_id: u16::from_be_bytes([b[0], b[1]]) >> 4,
+            dr: b[1] & 0x0f,
+            rssi: -(b[2] as i16),
+            snr,
+            channel: b[4],
+            border_id: b[3] >> 6,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; 5]> {
+        if self.uplink_id > 4095 {
+            return Err(anyhow!("Max uplink_id value is 4095"));
+        }
+
+        if self.dr > 15 {
+            return Err(anyhow!("Max dr value is 15"));
+        }
+
+        if self.rssi > 0 {
+            return Err(anyhow!("Max rssi value is 0"));
+        }
+
+        if self.rssi < -255 {
+            return Err(anyhow!("Min rssi value is -255"));
+        }
+
+        if self.snr < -32 {
+            return Err(anyhow!("Min snr value is -32"));
+        }
+        if self.snr > 31 {
+            return Err(anyhow!("Max snr value is 31"));
+        }
+
+        if self.border_id > 3 {
+            return Err(anyhow!("Max border_id value is 3"));
+        }
+
+        let uplink_id_b = (self.uplink_id << 4).to_be_bytes();
+
+        Ok([
+            uplink_id_b[0],
+            uplink_id_b[1] | self.dr,
+            -self.rssi as u8,
+            (self.border_id << 6)
+                | if self.snr < 0 {
+                    (self.snr + 64) as u8
+                } else {
+                    self.snr as u8
+                },
+            self.channel,
+        ])
+    }
+}
+
+/// Relayed downlink (Border Gateway to Relay Gateway).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DownlinkPayload {
+    pub metadata: DownlinkMetadata,
+    pub relay_id: [u8; 4],
+    pub phy_payload: Vec<u8>,
+}
+
+impl DownlinkPayload {
+    pub fn from_slice(b: &[u8]) -> Result<Self> {
+        if b.len() < 10 {
+            return Err(anyhow!("At least 10 bytes are expected"));
+        }
+
+        let mut md = [0; 6];
+        let mut gw_id = [0; 4];
+        md.copy_from_slice(&b[0..6]);
+        gw_id.copy_from_slice(&b[6..10]);
+
+        Ok(DownlinkPayload {
+            metadata: DownlinkMetadata::from_bytes(md),
+            relay_id: gw_id,
+            phy_payload: b[10..].to_vec(),
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut b = self.metadata.to_bytes()?.to_vec();
+        b.extend_from_slice(&self.relay_id);
+        b.extend_from_slice(&self.phy_payload);
+        Ok(b)
+    }
+}
+
+/// Metadata of a relayed downlink.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DownlinkMetadata {
+    /// Uplink ID (12 bits) of the uplink to which this downlink responds.
+    pub uplink_id: u16,
+    /// Data-rate index (4 bits).
+    pub dr: u8,
+    /// Frequency (Hz), see [`encode_freq`].
+    pub frequency: u32,
+    /// TX power index (4 bits).
+    pub tx_power: u8,
+    /// Delay in seconds (1 - 16), relative to the uplink.
+    pub delay: u8,
+}
+
+impl DownlinkMetadata {
+    pub fn from_bytes(b: [u8; 6]) -> Self {
+        DownlinkMetadata {
+            uplink_id: u16::from_be_bytes([b[0], b[1]]) >> 4,
+            dr: b[1] & 0x0f,
+            frequency: decode_freq(&b[2..5]).unwrap(),
+            tx_power: (b[5] & 0xf0) >> 4,
+            delay: (b[5] & 0x0f) + 1,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; 6]> {
+        if self.uplink_id > 4095 {
+            return Err(anyhow!("Max uplink_id value is 4095"));
+        }
+
+        if self.dr > 15 {
+            return Err(anyhow!("Max dr value is 15"));
+        }
+
+        if self.delay < 1 {
+            return Err(anyhow!("Min delay value is 1"));
+        }
+
+        if self.tx_power > 15 {
+            return Err(anyhow!("Max tx_power value is 15"));
+        }
+
+        if self.delay > 16 {
+            return Err(anyhow!("Max delay value is 16"));
+        }
+
+        let uplink_id_b = (self.uplink_id << 4).to_be_bytes();
+        let freq_b = encode_freq(self.frequency)?;
+
+        Ok([
+            uplink_id_b[0],
+            uplink_id_b[1] | self.dr,
+            freq_b[0],
+            freq_b[1],
+            freq_b[2],
+            (self.tx_power << 4) | (self.delay - 1),
+        ])
+    }
+}
+
+/// Relay Gateway heartbeat.
+///
+/// Version 1 heartbeats contain the timestamp in seconds (4 bytes), version 2
+/// heartbeats in milliseconds, including the monotonic flag (6 bytes).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HeartbeatPayload {
+    /// Timestamp (milliseconds, max [`TIMESTAMP_MAX`]). This is
+    /// relative to the UNIX epoch, or relative to the boot of the Relay
+    /// Gateway if `timestamp_monotonic` is set. Only wall-clock timestamps
+    /// in whole seconds can be encoded using version 1.
+    pub timestamp: u64,
+    /// Set when the Relay Gateway does not have a valid wall-clock time (e.g.
+    /// no RTC or NTP), in which case the timestamp is boot-relative.
+    pub timestamp_monotonic: bool,
+    pub relay_id: [u8; 4],
+    pub relay_path: Vec<RelayPath>,
+    /// Checksum of the configuration of the Relay Gateway (optional). This
+    /// is encoded after the Relay ID. As the checksum is 4 bytes and a relay
+    /// path entry 6 bytes, its presence follows from the payload size.
+    pub config_checksum: Option<[u8; 4]>,
+}
+
+impl HeartbeatPayload {
+    pub fn from_slice(b: &[u8], version: Version) -> Result<HeartbeatPayload> {
+        let timestamp_size = match version {
+            Version::V1 => HEARTBEAT_TIMESTAMP_V1_SIZE,
+            Version::V2 => TIMESTAMP_SIZE,
+        };
+        let min_size = timestamp_size + RELAY_ID_SIZE;
+
+        if b.len() < min_size {
+            return Err(anyhow!("At least {} bytes are expected", min_size));
+        }
+
+        let config_checksum = match (b.len() - min_size) % RELAY_PATH_SIZE {
+            0 => None,
+            HEARTBEAT_CONFIG_CHECKSUM_SIZE => {
+                let mut config_checksum: [u8; 4] = [0; 4];
+                config_checksum
+                    .copy_from_slice(&b[min_size..min_size + HEARTBEAT_CONFIG_CHECKSUM_SIZE]);
+                Some(config_checksum)
+            }
+            _ => {
+                return Err(anyhow!("Invalid amount of Relay path bytes"));
+            }
+        };
+
+        let (timestamp, timestamp_monotonic) = match version {
+            Version::V1 => {
+                let mut ts_b: [u8; 4] = [0; 4];
+                ts_b.copy_from_slice(&b[0..timestamp_size]);
+                (u64::from(u32::from_be_bytes(ts_b)) * 1000, false)
+            }
+            Version::V2 => decode_timestamp(&b[0..timestamp_size]),
+        };
+
+        let mut relay_id: [u8; 4] = [0; 4];
+        relay_id.copy_from_slice(&b[timestamp_size..min_size]);
+
+        let relay_path_offset = if config_checksum.is_some() {
+            min_size + HEARTBEAT_CONFIG_CHECKSUM_SIZE
+        } else {
+            min_size
+        };
+
+        let relay_path: Vec<RelayPath> = b[relay_path_offset..]
+            .chunks(RELAY_PATH_SIZE)
+            .map(|v| {
+                let mut b: [u8; 6] = [0; 6];
+                b.copy_from_slice(v);
+                RelayPath::from_bytes(b)
+            })
+            .collect();
+
+        Ok(HeartbeatPayload {
+            timestamp,
+            timestamp_monotonic,
+            relay_id,
+            relay_path,
+            config_checksum,
+        })
+    }
+
+    pub fn to_vec(&self, version: Version) -> Result<Vec<u8>> {
+        let mut b = match version {
+            Version::V1 => {
+                if self.version() != Version::V1 {
+                    return Err(anyhow!(
+                        "Monotonic and millisecond precision timestamps require version 2"
+                    ));
+                }
+                ((self.timestamp / 1000) as u32).to_be_bytes().to_vec()
+            }
+            Version::V2 => encode_timestamp(self.timestamp, self.timestamp_monotonic)?,
+        };
+        b.extend_from_slice(&self.relay_id);
+        if let Some(config_checksum) = &self.config_checksum {
+            b.extend_from_slice(config_checksum);
+        }
+        for relay_path in &self.relay_path {
+            b.extend_from_slice(&relay_path.to_bytes()?);
+        }
+        Ok(b)
+    }
+
+    /// Returns the lowest version that can encode the payload.
+    pub fn version(&self) -> Version {
+        if self.timestamp_monotonic
+            || self.timestamp % 1000 != 0
+            || self.timestamp / 1000 > u32::MAX.into()
+        {
+            Version::V2
+        } else {
+            Version::V1
+        }
+    }
+}
+
+/// Relay path entry, added by each Relay Gateway relaying a heartbeat.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RelayPath {
+    pub relay_id: [u8; 4],
+    /// RSSI (-255 - 0) of the received heartbeat.
+    pub rssi: i16,
+    /// SNR (-32 - 31) of the received heartbeat.
+    pub snr: i8,
+}
+
+impl RelayPath {
+    pub fn from_bytes(b: [u8; 6]) -> Self {
+        let mut relay_id = [0; 4];
+        relay_id.copy_from_slice(&b[0..4]);
+
+        let snr = b[5] & 0x3f;
+        let snr = if snr > 31 {
+            (snr as i8) - 64
+        } else {
+            snr as i8
+        };
+
+        RelayPath {
+            relay_id,
+            snr,
+            rssi: -(b[4] as i16),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; 6]> {
+        if self.rssi > 0 {
+            return Err(anyhow!("Max rssi value is 0"));
+        }
+        if self.rssi < -255 {
+            return Err(anyhow!("Min rssi value is -255"));
+        }
+        if self.snr < -32 {
+            return Err(anyhow!("Min snr value is -32"));
+        }
+        if self.snr > 31 {
+            return Err(anyhow!("Max snr value is 31"));
+        }
+
+        Ok([
+            self.relay_id[0],
+            self.relay_id[1],
+            self.relay_id[2],
+            self.relay_id[3],
+            -self.rssi as u8,
+            if self.snr < 0 {
+                (self.snr + 64) as u8
+            } else {
+                self.snr as u8
+            },
+        ])
+    }
+}
+
+/// Event(s) reported by a Relay Gateway (Relay Gateway to Border Gateway).
+/// Event payloads require version 2.
+///
+/// The events are encoded as `| Type (1) | Length (1) | Value (n) |`, such
+/// that event types that are unknown to the receiver can be skipped.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EventPayload {
+    /// Timestamp (milliseconds, max [`TIMESTAMP_MAX`]), see
+    /// [`HeartbeatPayload::timestamp`].
+    pub timestamp: u64,
+    pub timestamp_monotonic: bool,
+    pub relay_id: [u8; 4],
+    pub events: Vec<Event>,
+}
+
+impl EventPayload {
+    pub fn from_slice(b: &[u8]) -> Result<EventPayload> {
+        let min_size = TIMESTAMP_SIZE + RELAY_ID_SIZE;
+
+        if b.len() < min_size {
+            return Err(anyhow!("At least {} bytes are expected", min_size));
+        }
+
+        let (timestamp, timestamp_monotonic) = decode_timestamp(&b[0..TIMESTAMP_SIZE]);
+
+        let mut relay_id: [u8; 4] = [0; 4];
+        relay_id.copy_from_slice(&b[TIMESTAMP_SIZE..min_size]);
+
+        let mut events = Vec::new();
+        let mut b = &b[min_size..];
+        while !b.is_empty() {
+            if b.len() < 2 {
+                return Err(anyhow!("Not enough bytes to decode event type + length"));
+            }
+
+            let len = b[1] as usize;
+            if b.len() < 2 + len {
+                return Err(anyhow!("Not enough bytes to decode event value"));
+            }
+
+            events.push(Event::from_slice(b[0], &b[2..2 + len])?);
+            b = &b[2 + len..];
+        }
+
+        Ok(EventPayload {
+            timestamp,
+            timestamp_monotonic,
+            relay_id,
+            events,
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut b = encode_timestamp(self.timestamp, self.timestamp_monotonic)?;
+        b.extend_from_slice(&self.relay_id);
+        for event in &self.events {
+            let value = event.to_vec()?;
+            if value.len() > EVENT_MAX_SIZE {
+                return Err(anyhow!("Max event size is {}", EVENT_MAX_SIZE));
+            }
+
+            b.push(event.event_type());
+            b.push(value.len() as u8);
+            b.extend_from_slice(&value);
+        }
+        Ok(b)
+    }
+}
+
+/// Relay Gateway event.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Event {
+    Attestation(AttestationEvent),
+    TxAirtime(TxAirtimeEvent),
+    UplinkAck(UplinkAckEvent),
+    TdmaBeacon(TdmaBeaconEvent),
+    Stats(StatsEvent),
+    DownlinkAck(DownlinkAckEvent),
+    /// Relay Gateway is shutting down (no value).
+    Shutdown,
+    Boot(BootEvent),
+    Location(LocationEvent),
+    Telemetry(TelemetryEvent),
+    MaxHopCount(MaxHopCountEvent),
+    Challenge(ChallengeEvent),
+    ChallengeResponse(ChallengeEvent),
+    /// Event type unknown to this implementation (type, value).
+    Unknown(u8, Vec<u8>),
+}
+
+impl Event {
+    pub fn from_slice(event_type: u8, b: &[u8]) -> Result<Event> {
+        Ok(match event_type {
+            0x01 => Event::Attestation(AttestationEvent::from_slice(b)?),
+            0x02 => Event::TxAirtime(TxAirtimeEvent::from_slice(b)?),
+            0x03 => Event::UplinkAck(UplinkAckEvent::from_slice(b)?),
+            0x04 => Event::TdmaBeacon(TdmaBeaconEvent::from_slice(b)?),
+            0x05 => Event::Stats(StatsEvent::from_slice(b)?),
+            0x06 => Event::DownlinkAck(DownlinkAckEvent::from_slice(b)?),
+            0x07 => {
+                if !b.is_empty() {
+                    return Err(anyhow!("0 bytes are expected"));
+                }
+                Event::Shutdown
+            }
+            0x08 => Event::Boot(BootEvent::from_slice(b)?),
+            0x09 => Event::Location(LocationEvent::from_slice(b)?),
+            0x0a => Event::Telemetry(TelemetryEvent::from_slice(b)?),
+            0x0b => Event::MaxHopCount(MaxHopCountEvent::from_slice(b)?),
+            0x0c => Event::Challenge(ChallengeEvent::from_slice(b)?),
+            0x0d => Event::ChallengeResponse(ChallengeEvent::from_slice(b)?),
+            _ => Event::Unknown(event_type, b.to_vec()),
+        })
+    }
+
+    pub fn event_type(&self) -> u8 {
+        match self {
+            Event::Attestation(_) => 0x01,
+            Event::TxAirtime(_) => 0x02,
+            Event::UplinkAck(_) => 0x03,
+            Event::TdmaBeacon(_) => 0x04,
+            Event::Stats(_) => 0x05,
+            Event::DownlinkAck(_) => 0x06,
+            Event::Shutdown => 0x07,
+            Event::Boot(_) => 0x08,
+            Event::Location(_) => 0x09,
+            Event::Telemetry(_) => 0x0a,
+            Event::MaxHopCount(_) => 0x0b,
+            Event::Challenge(_) => 0x0c,
+            Event::ChallengeResponse(_) => 0x0d,
+            Event::Unknown(t, _) => *t,
+        }
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        match self {
+            Event::Attestation(v) => v.to_vec(),
+            Event::TxAirtime(v) => Ok(v.to_vec()),
+            Event::UplinkAck(v) => Ok(v.to_vec()),
+            Event::TdmaBeacon(v) => v.to_vec(),
+            Event::Stats(v) => v.to_vec(),
+            Event::DownlinkAck(v) => Ok(v.to_vec()),
+            Event::Shutdown => Ok(vec![]),
+            Event::Boot(v) => v.to_vec(),
+            Event::Location(v) => Ok(v.to_vec()),
+            Event::Telemetry(v) => Ok(v.to_vec()),
+            Event::MaxHopCount(v) => v.to_vec(),
+            Event::Challenge(v) => Ok(v.to_vec()),
+            Event::ChallengeResponse(v) => Ok(v.to_vec()),
+            Event::Unknown(_, v) => Ok(v.clone()),
+        }
+    }
+}
+
+/// Identity of a Relay Gateway, used to verify the consistency of a fleet.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AttestationEvent {
+    /// Checksum of the (relevant) configuration.
+    pub config_checksum: [u8; 4],
+    /// Bitmask of the enabled features.
+    pub features: u8,
+    /// Gateway Mesh version.
+    pub version: String,
+    /// Firmware version of the gateway.
+    pub firmware_version: String,
+}
+
+impl AttestationEvent {
+    pub fn from_slice(b: &[u8]) -> Result<AttestationEvent> {
+        if b.len() < 6 {
+            return Err(anyhow!("At least 6 bytes are expected"));
+        }
+
+        let mut config_checksum: [u8; 4] = [0; 4];
+        config_checksum.copy_from_slice(&b[0..4]);
+
+        let version_len = b[5] as usize;
+        if b.len() < 6 + version_len {
+            return Err(anyhow!("Not enough bytes to decode version"));
+        }
+
+        Ok(AttestationEvent {
+            config_checksum,
+            features: b[4],
+            version: String::from_utf8(b[6..6 + version_len].to_vec())
+                .map_err(|_| anyhow!("Invalid version"))?,
+            firmware_version: String::from_utf8(b[6 + version_len..].to_vec())
+                .map_err(|_| anyhow!("Invalid firmware_version"))?,
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        if self.version.len() > u8::MAX as usize {
+            return Err(anyhow!("Max version length is {}", u8::MAX));
+        }
+
+        let mut b = self.config_checksum.to_vec();
+        b.push(self.features);
+        b.push(self.version.len() as u8);
+        b.extend_from_slice(self.version.as_bytes());
+        b.extend_from_slice(self.firmware_version.as_bytes());
+        Ok(b)
+    }
+}
+
+/// Mesh TX airtime of a Relay Gateway.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TxAirtimeEvent {
+    /// Mesh TX airtime (milliseconds) during the past hour.
+    pub airtime: u32,
+}
+
+impl TxAirtimeEvent {
+    pub fn from_slice(b: &[u8]) -> Result<TxAirtimeEvent> {
+        if b.len() != 4 {
+            return Err(anyhow!("4 bytes are expected"));
+        }
+
+        let mut airtime: [u8; 4] = [0; 4];
+        airtime.copy_from_slice(b);
+
+        Ok(TxAirtimeEvent {
+            airtime: u32::from_be_bytes(airtime),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.airtime.to_be_bytes().to_vec()
+    }
+}
+
+/// Acknowledgement of a relayed uplink, sent by the Border Gateway.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UplinkAckEvent {
+    /// Relay ID of the Relay Gateway that relayed the uplink.
+    pub relay_id: [u8; 4],
+    /// Uplink ID of the relayed uplink.
+    pub uplink_id: u16,
+}
+
+impl UplinkAckEvent {
+    pub fn from_slice(b: &[u8]) -> Result<UplinkAckEvent> {
+        if b.len() != 6 {
+            return Err(anyhow!("6 bytes are expected"));
+        }
+
+        let mut relay_id: [u8; 4] = [0; 4];
+        relay_id.copy_from_slice(&b[0..4]);
+
+        Ok(UplinkAckEvent {
+            relay_id,
+            uplink_id: u16::from_be_bytes([b[4], b[5]]),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut b = self.relay_id.to_vec();
+        b.extend_from_slice(&self.uplink_id.to_be_bytes());
+        b
+    }
+}
+
+/// Acknowledgement of a relayed downlink, sent by the Relay Gateway that
+/// unwrapped the downlink.
+///
+/// Encoded as `| Uplink ID (2) | Status (1) |`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DownlinkAckEvent {
+    /// Uplink ID of the relayed downlink.
+    pub uplink_id: u16,
+    /// TX acknowledgement status, as returned by the Concentratord.
+    pub status: u8,
+}
+
+impl DownlinkAckEvent {
+    pub fn from_slice(b: &[u8]) -> Result<DownlinkAckEvent> {
+        if b.len() != 3 {
+            return Err(anyhow!("3 bytes are expected"));
+        }
+
+        Ok(DownlinkAckEvent {
+            uplink_id: u16::from_be_bytes([b[0], b[1]]),
+            status: b[2],
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut b = self.uplink_id.to_be_bytes().to_vec();
+        b.push(self.status);
+        b
+    }
+}
+
+/// Startup of a Relay Gateway.
+///
+/// Encoded as `| Reason (1) | Features (1) | Boot ID (4) | Version length (1) |
+/// Version (n) | Crash summary (0 or 8) |`, with the crash summary encoded as
+/// `| Panic hash (4) | Uptime (4) |`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BootEvent {
+    /// Reason of the (re)start.
+    pub reason: BootReason,
+    /// Bitmask of the enabled features.
+    pub features: u8,
+    /// Identifier of the boot of the Relay Gateway, which changes on every
+    /// reboot. Boot-relative (monotonic) timestamps restart when this
+    /// changes.
+    pub boot_id: [u8; 4],
+    /// Gateway Mesh version.
+    pub version: String,
+    /// Summary of the panic that caused the previous process to exit, if any.
+    pub crash: Option<CrashSummary>,
+}
+
+impl BootEvent {
+    pub fn from_slice(b: &[u8]) -> Result<BootEvent> {
+        if b.len() < 7 {
+            return Err(anyhow!("At least 7 bytes are expected"));
+        }
+
+        let version_end = 7 + b[6] as usize;
+        if b.len() < version_end {
+            return Err(anyhow!("Not enough bytes to decode version"));
+        }
+
+        let crash = match b.len() - version_end {
+            0 => None,
+            8 => Some(CrashSummary {
+                panic_hash: b[version_end..version_end + 4]
+                    .try_into()
+                    .map_err(|_| anyhow!("Invalid crash summary"))?,
+                uptime: u32::from_be_bytes(
+                    b[version_end + 4..version_end + 8]
+                        .try_into()
+                        .map_err(|_| anyhow!("Invalid crash summary"))?,
+                ),
+            }),
+            _ => return Err(anyhow!("Invalid crash summary length")),
+        };
+
+        Ok(BootEvent {
+            reason: BootReason::from_byte(b[0])?,
+            features: b[1],
+            boot_id: b[2..6].try_into().map_err(|_| anyhow!("Invalid boot ID"))?,
+            version: String::from_utf8(b[7..version_end].to_vec())
+                .map_err(|_| anyhow!("Invalid version"))?,
+            crash,
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        if self.version.len() > u8::MAX as usize {
+            return Err(anyhow!("Max version length is {}", u8::MAX));
+        }
+
+        let mut b = vec![self.reason.to_byte(), self.features];
+        b.extend_from_slice(&self.boot_id);
+        b.push(self.version.len() as u8);
+        b.extend_from_slice(self.version.as_bytes());
+        if let Some(crash) = &self.crash {
+            b.extend_from_slice(&crash.panic_hash);
+            b.extend_from_slice(&crash.uptime.to_be_bytes());
+        }
+        Ok(b)
+    }
+}
+
+/// Summary of the panic that caused a Relay Gateway to exit.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CrashSummary {
+    /// Hash of the panic message, such that identical panics can be grouped.
+    pub panic_hash: [u8; 4],
+    /// Uptime (seconds) of the process before the panic.
+    pub uptime: u32,
+}
+
+/// Reason of the (re)start of a Relay Gateway.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BootReason {
+    /// First start after a power cycle or reboot of the gateway.
+    PowerCycle,
+    /// Restart after a clean shutdown.
+    Restart,
+    /// Restart after the previous process exited without clean shutdown, e.g. a crash.
+    CrashRecovery,
+}
+
+impl BootReason {
+    pub fn from_byte(b: u8) -> Result<Self> {
+        Ok(match b {
+            0x00 => BootReason::PowerCycle,
+            0x01 => BootReason::Restart,
+            0x02 => BootReason::CrashRecovery,
+            _ => return Err(anyhow!("Unexpected BootReason: {}", b)),
+        })
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            BootReason::PowerCycle => 0x00,
+            BootReason::Restart => 0x01,
+            BootReason::CrashRecovery => 0x02,
+        }
+    }
+}
+
+/// Location of a Relay Gateway.
+///
+/// Encoded as `| Latitude (4) | Longitude (4) | Altitude (2) |`, with the latitude and
+/// longitude in units of 1e-7 degrees.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LocationEvent {
+    /// Latitude (1e-7 degrees).
+    pub latitude: i32,
+    /// Longitude (1e-7 degrees).
+    pub longitude: i32,
+    /// Altitude (meters).
+    pub altitude: i16,
+}
+
+impl LocationEvent {
+    pub fn from_slice(b: &[u8]) -> Result<LocationEvent> {
+        if b.len() != 10 {
+            return Err(anyhow!("10 bytes are expected"));
+        }
+
+        Ok(LocationEvent {
+            latitude: i32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+            longitude: i32::from_be_bytes([b[4], b[5], b[6], b[7]]),
+            altitude: i16::from_be_bytes([b[8], b[9]]),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(10);
+        b.extend_from_slice(&self.latitude.to_be_bytes());
+        b.extend_from_slice(&self.longitude.to_be_bytes());
+        b.extend_from_slice(&self.altitude.to_be_bytes());
+        b
+    }
+}
+
+/// Power telemetry of a Relay Gateway.
+///
+/// Encoded as `| Flags (1) | Battery voltage (0 or 2) | Solar current (0 or 2) |
+/// Temperature (0 or 2) |`. Each value is only present if its flag is set:
+/// 0x01 for the battery voltage, 0x02 for the solar current and 0x04 for the
+/// temperature.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TelemetryEvent {
+    /// Battery voltage (mV).
+    pub battery_voltage: Option<u16>,
+    /// Solar current (mA).
+    pub solar_current: Option<u16>,
+    /// Temperature (0.1 degrees Celsius).
+    pub temperature: Option<i16>,
+}
+
+impl TelemetryEvent {
+    pub fn from_slice(b: &[u8]) -> Result<TelemetryEvent> {
+        if b.is_empty() {
+            return Err(anyhow!("At least 1 byte is expected"));
+        }
+
+        let flags = b[0];
+        if flags & 0xf8 != 0 {
+            return Err(anyhow!("Unexpected telemetry flags: {}", flags));
+        }
+
+        let expected_len = 1 + 2 * flags.count_ones() as usize;
+        if b.len() != expected_len {
+            return Err(anyhow!("{} bytes are expected", expected_len));
+        }
+
+        let mut values = b[1..].chunks(2).map(|v| [v[0], v[1]]);
+        let mut next_value = |flag: u8| {
+            if flags & flag != 0 {
+                values.next()
+            } else {
+                None
+            }
+        };
+
+        Ok(TelemetryEvent {
+            battery_voltage: next_value(0x01).map(u16::from_be_bytes),
+            solar_current: next_value(0x02).map(u16::from_be_bytes),
+            temperature: next_value(0x04).map(i16::from_be_bytes),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut b = vec![0];
+        if let Some(v) = self.battery_voltage {
+            b[0] |= 0x01;
+            b.extend_from_slice(&v.to_be_bytes());
+        }
+        if let Some(v) = self.solar_current {
+            b[0] |= 0x02;
+            b.extend_from_slice(&v.to_be_bytes());
+        }
+        if let Some(v) = self.temperature {
+            b[0] |= 0x04;
+            b.extend_from_slice(&v.to_be_bytes());
+        }
+        b
+    }
+}
+
+/// TDMA schedule, sent by the Border Gateway.
+///
+/// Encoded as `| Slot duration (2) | Slot count (1) | Relay IDs (4 * n) |`.
+/// The n-th Relay ID is assigned to the n-th slot of the frame. Relay
+/// Gateways without assigned slot share the remaining slots.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TdmaBeaconEvent {
+    /// Slot duration (milliseconds).
+    pub slot_duration: u16,
+    /// Number of slots per frame.
+    pub slot_count: u8,
+    /// Relay IDs, by slot.
+    pub relay_ids: Vec<[u8; 4]>,
+}
+
+impl TdmaBeaconEvent {
+    pub fn from_slice(b: &[u8]) -> Result<TdmaBeaconEvent> {
+        if b.len() < 3 || (b.len() - 3) % RELAY_ID_SIZE != 0 {
+            return Err(anyhow!("3 + n * {} bytes are expected", RELAY_ID_SIZE));
+        }
+
+        let relay_ids: Vec<[u8; 4]> = b[3..]
+            .chunks(RELAY_ID_SIZE)
+            .map(|v| {
+                let mut relay_id: [u8; 4] = [0; 4];
+                relay_id.copy_from_slice(v);
+                relay_id
+            })
+            .collect();
+
+        if relay_ids.len() > b[2] as usize {
+            return Err(anyhow!("Number of Relay IDs exceeds slot count"));
+        }
+
+        Ok(TdmaBeaconEvent {
+            slot_duration: u16::from_be_bytes([b[0], b[1]]),
+            slot_count: b[2],
+            relay_ids,
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        if self.relay_ids.len() > self.slot_count as usize {
+            return Err(anyhow!("Number of Relay IDs exceeds slot count"));
+        }
+
+        let mut b = self.slot_duration.to_be_bytes().to_vec();
+        b.push(self.slot_count);
+        for relay_id in &self.relay_ids {
+            b.extend_from_slice(relay_id);
+        }
+        Ok(b)
+    }
+}
+
+/// Max. hop count advertised by the Border Gateway, based on the network diameter.
+///
+/// Encoded as `| Max. hop count (1) |`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MaxHopCountEvent {
+    /// Max. hop count (1 - MAX_HOP_COUNT).
+    pub max_hop_count: u8,
+}
+
+impl MaxHopCountEvent {
+    pub fn from_slice(b: &[u8]) -> Result<MaxHopCountEvent> {
+        if b.len() != 1 {
+            return Err(anyhow!("1 byte is expected"));
+        }
+
+        let out = MaxHopCountEvent {
+            max_hop_count: b[0],
+        };
+        out.validate()?;
+        Ok(out)
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        self.validate()?;
+        Ok(vec![self.max_hop_count])
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.max_hop_count == 0 || self.max_hop_count > MAX_HOP_COUNT {
+            return Err(anyhow!("Max hop count must be 1 - {}", MAX_HOP_COUNT));
+        }
+        Ok(())
+    }
+}
+
+/// Random challenge, sent by the Border Gateway and echoed by the Relay
+/// Gateways in the challenge response event after their next heartbeat.
+///
+/// Encoded as `| Challenge (4) |`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ChallengeEvent {
+    pub challenge: [u8; 4],
+}
+
+impl ChallengeEvent {
+    pub fn from_slice(b: &[u8]) -> Result<ChallengeEvent> {
+        if b.len() != 4 {
+            return Err(anyhow!("4 bytes are expected"));
+        }
+
+        let mut challenge: [u8; 4] = [0; 4];
+        challenge.copy_from_slice(b);
+        Ok(ChallengeEvent { challenge })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.challenge.to_vec()
+    }
+}
+
+/// Gateway statistics of a Relay Gateway, aggregated since the previous
+/// stats event.
+///
+/// Encoded as `| Gateway ID (8) | RX received (4) | RX received OK (4) |
+/// TX received (4) | TX emitted (4) | TX airtime (4) | Uplinks ACK'ed (2) |
+/// Uplinks not ACK'ed (2) | RX frequency count (1) | RX per frequency (6 * n) |
+/// TX per frequency (6 * m) |`, with each frequency count encoded as
+/// `| Frequency (4) | Count (2) |`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StatsEvent {
+    /// Gateway ID of the Relay Gateway.
+    pub gateway_id: [u8; 8],
+    pub rx_packets_received: u32,
+    pub rx_packets_received_ok: u32,
+    pub tx_packets_received: u32,
+    pub tx_packets_emitted: u32,
+    /// Mesh TX airtime (milliseconds) during the past hour.
+    pub tx_airtime: u32,
+    /// Relayed uplinks acknowledged by the Border Gateway.
+    pub uplinks_acked: u16,
+    /// Relayed uplinks not acknowledged by the Border Gateway, after all
+    /// retransmissions.
+    pub uplinks_not_acked: u16,
+    pub rx_packets_per_frequency: Vec<FrequencyCount>,
+    pub tx_packets_per_frequency: Vec<FrequencyCount>,
+}
+
+/// Number of packets on a frequency.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FrequencyCount {
+    pub frequency: u32,
+    pub count: u16,
+}
+
+impl StatsEvent {
+    pub fn from_slice(b: &[u8]) -> Result<StatsEvent> {
+        if b.len() < 33 || (b.len() - 33) % 6 != 0 {
+            return Err(anyhow!("33 + n * 6 bytes are expected"));
+        }
+
+        let rx_count = b[32] as usize;
+        let counts: Vec<FrequencyCount> = b[33..]
+            .chunks(6)
+            .map(|v| FrequencyCount {
+                frequency: u32::from_be_bytes([v[0], v[1], v[2], v[3]]),
+                count: u16::from_be_bytes([v[4], v[5]]),
+            })
+            .collect();
+
+        if rx_count > counts.len() {
+            return Err(anyhow!("RX frequency count exceeds number of frequencies"));
+        }
+
+        let mut gateway_id: [u8; 8] = [0; 8];
+        gateway_id.copy_from_slice(&b[0..8]);
+        let u32_at = |i: usize| u32::from_be_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+
+        Ok(StatsEvent {
+            gateway_id,
+            rx_packets_received: u32_at(8),
+            rx_packets_received_ok: u32_at(12),
+            tx_packets_received: u32_at(16),
+            tx_packets_emitted: u32_at(20),
+            tx_airtime: u32_at(24),
+            uplinks_acked: u16::from_be_bytes([b[28], b[29]]),
+            uplinks_not_acked: u16::from_be_bytes([b[30], b[31]]),
+            rx_packets_per_frequency: counts[..rx_count].to_vec(),
+            tx_packets_per_frequency: counts[rx_count..].to_vec(),
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        if self.rx_packets_per_frequency.len() > u8::MAX as usize {
+            return Err(anyhow!("Max RX frequency count is {}", u8::MAX));
+        }
+
+        let mut b = self.gateway_id.to_vec();
+        b.extend_from_slice(&self.rx_packets_received.to_be_bytes());
+        b.extend_from_slice(&self.rx_packets_received_ok.to_be_bytes());
+        b.extend_from_slice(&self.tx_packets_received.to_be_bytes());
+        b.extend_from_slice(&self.tx_packets_emitted.to_be_bytes());
+        b.extend_from_slice(&self.tx_airtime.to_be_bytes());
+        b.extend_from_slice(&self.uplinks_acked.to_be_bytes());
+        b.extend_from_slice(&self.uplinks_not_acked.to_be_bytes());
+        b.push(self.rx_packets_per_frequency.len() as u8);
+        for v in self
+            .rx_packets_per_frequency
+            .iter()
+            .chain(self.tx_packets_per_frequency.iter())
+        {
+            b.extend_from_slice(&v.frequency.to_be_bytes());
+            b.extend_from_slice(&v.count.to_be_bytes());
+        }
+        Ok(b)
+    }
+}
+
+// Decodes the timestamp. The MSB of the timestamp field is the monotonic flag.
+fn decode_timestamp(b: &[u8]) -> (u64, bool) {
+    let mut ts_b: [u8; 8] = [0; 8];
+    ts_b[2..].copy_from_slice(b);
+    let timestamp_monotonic = ts_b[2] & 0x80 != 0;
+    ts_b[2] &= 0x7f;
+    (u64::from_be_bytes(ts_b), timestamp_monotonic)
+}
+
+fn encode_timestamp(timestamp: u64, timestamp_monotonic: bool) -> Result<Vec<u8>> {
+    if timestamp > TIMESTAMP_MAX {
+        return Err(anyhow!("Max timestamp value is {}", TIMESTAMP_MAX));
+    }
+
+    let mut b = timestamp.to_be_bytes()[2..].to_vec();
+    if timestamp_monotonic {
+        b[0] |= 0x80;
+    }
+    Ok(b)
+}
+
+/// Encodes the frequency (Hz) into 3 bytes, using a step of 100Hz (200Hz for
+/// 2.4GHz frequencies).
+pub fn encode_freq(freq: u32) -> Result<[u8; 3]> {
+    let mut freq = freq;
+    // Support LoRaWAN 2.4GHz, in which case the stepping is 200Hz:
+    // See Frequency Encoding in MAC Commands
+    // https://lora-developers.semtech.com/documentation/tech-papers-and-guides/physical-layer-proposal-2.4ghz/
+    if freq >= 2400000000 {
+        freq /= 2;
+    }
+
+    if freq / 100 >= (1 << 24) {
+        return Err(anyhow!("Max frequency value is 2^24 - 1"));
+    }
+    if freq % 100 != 0 {
+        return Err(anyhow!("Frequency must be multiple of 100"));
+    }
+
+    let mut b = [0; 3];
+    b[0..3].copy_from_slice(&(freq / 100).to_be_bytes()[1..4]);
+    Ok(b)
+}
+
+/// Decodes the 3 bytes frequency (see [`encode_freq`]) into Hz.
+pub fn decode_freq(b: &[u8]) -> Result<u32> {
+    if b.len() != 3 {
+        return Err(anyhow!("3 bytes expected for frequency"));
+    }
+    let mut freq_b: [u8; 4] = [0; 4];
+    freq_b[1..4].copy_from_slice(&b[0..3]);
+    let mut freq = u32::from_be_bytes(freq_b);
+
+    if freq >= 12000000 {
+        // 2.4GHz frequency
+        freq *= 200
+    } else {
+        freq *= 100
+    }
+
+    Ok(freq)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mhdr_from_slice() {
+        struct Test {
+            name: String,
+            bytes: Vec<u8>,
+            expected_mhdr: Option<MHDR>,
+            expected_error: Option<String>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "uplink + hop count 3".to_string(),
+                bytes: vec![0xe2],
+                expected_mhdr: Some(MHDR {
+                    payload_type: PayloadType::Uplink,
+                    hop_count: 3,
+                    key_index: 0,
+                }),
+                expected_error: None,
+            },
+            Test {
+                name: "downlink + hop count 8".to_string(),
+                bytes: vec![0xef],
+                expected_mhdr: Some(MHDR {
+                    payload_type: PayloadType::Downlink,
+                    hop_count: 8,
+                    key_index: 0,
+                }),
+                expected_error: None,
+            },
+            Test {
+                name: "v2 + event + hop count 1".to_string(),
+                bytes: vec![0xf8, 0xc0],
+                expected_mhdr: Some(MHDR {
+                    payload_type: PayloadType::Event,
+                    hop_count: 1,
+                    key_index: 0,
+                }),
+                expected_error: None,
+            },
+            Test {
+                name: "v2 + heartbeat + hop count 2 + key index 3".to_string(),
+                bytes: vec![0xf9, 0x83],
+                expected_mhdr: Some(MHDR {
+                    payload_type: PayloadType::Heartbeat,
+                    hop_count: 2,
+                    key_index: 3,
+                }),
+                expected_error: None,
+            },
+            Test {
+                name: "empty".to_string(),
+                bytes: vec![],
+                expected_mhdr: None,
+                expected_error: Some("Input is empty".into()),
+            },
+            Test {
+                name: "v2 + missing mhdr extension".to_string(),
+                bytes: vec![0xf9],
+                expected_mhdr: None,
+                expected_error: Some("Not enough bytes to decode mhdr extension".into()),
+            },
+            Test {
+                name: "v2 + RFU bits set".to_string(),
+                bytes: vec![0xf9, 0x93],
+                expected_mhdr: None,
+                expected_error: Some("RFU bits of mhdr extension must be 0".into()),
+            },
+            Test {
+                name: "invalid MType".to_string(),
+                bytes: vec![0x00],
+                expected_mhdr: None,
+                expected_error: Some("Invalid MType".into()),
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let res = MHDR::from_slice(&tst.bytes);
+
+            if let Some(mhdr) = &tst.expected_mhdr {
+                assert_eq!(mhdr, &res.unwrap());
+            } else if let Some(err) = &tst.expected_error {
+                assert_eq!(err.to_string(), res.unwrap_err().to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_mhdr_to_vec() {
+        struct Test {
+            name: String,
+            mhdr: MHDR,
+            version: Version,
+            expected_bytes: Option<Vec<u8>>,
+            expected_error: Option<String>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "uplink + hop count 3".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Uplink,
+                    hop_count: 3,
+                    key_index: 0,
+                },
+                version: Version::V1,
+                expected_bytes: Some(vec![0xe2]),
+                expected_error: None,
+            },
+            Test {
+                name: "downlink + hop count 8".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Downlink,
+                    hop_count: 8,
+                    key_index: 0,
+                },
+                version: Version::V1,
+                expected_bytes: Some(vec![0xef]),
+                expected_error: None,
+            },
+            Test {
+                name: "v2 + uplink + hop count 3".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Uplink,
+                    hop_count: 3,
+                    key_index: 0,
+                },
+                version: Version::V2,
+                expected_bytes: Some(vec![0xfa, 0x00]),
+                expected_error: None,
+            },
+            Test {
+                name: "v2 + heartbeat + hop count 2 + key index 3".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Heartbeat,
+                    hop_count: 2,
+                    key_index: 3,
+                },
+                version: Version::V2,
+                expected_bytes: Some(vec![0xf9, 0x83]),
+                expected_error: None,
+            },
+            Test {
+                name: "v1 + key index 3".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Heartbeat,
+                    hop_count: 2,
+                    key_index: 3,
+                },
+                version: Version::V1,
+                expected_bytes: None,
+                expected_error: Some("Event payload type and key_index 3 require version 2".into()),
+            },
+            Test {
+                name: "v1 + event".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Event,
+                    hop_count: 1,
+                    key_index: 0,
+                },
+                version: Version::V1,
+                expected_bytes: None,
+                expected_error: Some("Event payload type and key_index 0 require version 2".into()),
+            },
+            Test {
+                name: "key index exceeds max value".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Uplink,
+                    hop_count: 1,
+                    key_index: 16,
+                },
+                version: Version::V2,
+                expected_bytes: None,
+                expected_error: Some("Max key_index is 15".into()),
+            },
+            Test {
+                name: "hop count exceeds max value".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Uplink,
+                    hop_count: 9,
+                    key_index: 0,
+                },
+                version: Version::V1,
+                expected_bytes: None,
+                expected_error: Some("Max hop_count is 8".into()),
+            },
+            Test {
+                name: "hop count is 0".to_string(),
+                mhdr: MHDR {
+                    payload_type: PayloadType::Uplink,
+                    hop_count: 0,
+                    key_index: 0,
+                },
+                version: Version::V1,
+                expected_bytes: None,
+                expected_error: Some("Min hop_count is 1".into()),
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let res = tst.mhdr.to_vec(tst.version);
+
+            if let Some(b) = &tst.expected_bytes {
+                assert_eq!(b, &res.unwrap());
+            } else if let Some(err) = &tst.expected_error {
+                assert_eq!(err.to_string(), res.unwrap_err().to_string());
+            }
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_mhdr_from_to_byte() {
+        let mhdr = MHDR {
+            payload_type: PayloadType::Uplink,
+            hop_count: 3,
+            key_index: 0,
+        };
+        assert_eq!(mhdr, MHDR::from_byte(0xe2).unwrap());
+        assert_eq!(0xe2, mhdr.to_byte().unwrap());
+
+        assert_eq!(
+            "Version 2 MHDR, use MHDR::from_slice",
+            MHDR::from_byte(0xf9).unwrap_err().to_string()
+        );
+        assert!(MHDR {
+            key_index: 1,
+            ..mhdr
+        }
+        .to_byte()
+        .is_err());
+    }
+
+    #[test]
+    fn test_uplink_metadata_to_bytes() {
+        struct Test {
+            name: String,
+            metadata: UplinkMetadata,
+            expected_bytes: Option<[u8; 5]>,
+            expected_error: Option<String>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "Uplink ID exceeds max value".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 4096,
+                    dr: 0,
+                    rssi: 0,
+                    snr: 0,
+                    channel: 0,
+                    border_id: 0,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max uplink_id value is 4095".into()),
+            },
+            Test {
+                name: "DR exceeds max value".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 0,
+                    dr: 16,
+                    rssi: 0,
+                    snr: 0,
+                    channel: 0,
+                    border_id: 0,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max dr value is 15".into()),
+            },
+            Test {
+                name: "RSSI exceeds max value".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    rssi: 1,
+                    snr: 0,
+                    channel: 0,
+                    border_id: 0,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max rssi value is 0".into()),
+            },
+            Test {
+                name: "RSSI exceeds min value".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    rssi: -256,
+                    snr: 0,
+                    channel: 0,
+                    border_id: 0,
+                },
+                expected_bytes: None,
+                expected_error: Some("Min rssi value is -255".into()),
+            },
+            Test {
+                name: "SNR exceeds max value".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    rssi: 0,
+                    snr: 32,
+                    channel: 0,
+                    border_id: 0,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max snr value is 31".into()),
+            },
+            Test {
+                name: "SNR exceeds min value".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    rssi: 0,
+                    snr: -33,
+                    channel: 0,
+                    border_id: 0,
+                },
+                expected_bytes: None,
+                expected_error: Some("Min snr value is -32".into()),
+            },
+            Test {
+                name: "Border ID exceeds max value".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    rssi: 0,
+                    snr: 0,
+                    channel: 0,
+                    border_id: 4,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max border_id value is 3".into()),
+            },
+            Test {
+                name: "Uplink id: 1024, dr: 3, rssi: -120, snr: -12, channel: 64".into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    rssi: -120,
+                    snr: -12,
+                    channel: 64,
+                    border_id: 0,
+                },
+                expected_bytes: Some([0x40, 0x03, 0x78, 0x34, 0x40]),
+                expected_error: None,
+            },
+            Test {
+                name: "Uplink id: 1024, dr: 3, rssi: -120, snr: -12, channel: 64, border_id: 2"
+                    .into(),
+                metadata: UplinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    rssi: -120,
+                    snr: -12,
+                    channel: 64,
+                    border_id: 2,
+                },
+                expected_bytes: Some([0x40, 0x03, 0x78, 0xb4, 0x40]),
+                expected_error: None,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let res = tst.metadata.to_bytes();
+
+            if let Some(b) = &tst.expected_bytes {
+                assert_eq!(b, &res.unwrap());
+            } else if let Some(err) = &tst.expected_error {
+                assert_eq!(err.to_string(), res.unwrap_err().to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_uplink_metadata_from_bytes() {
+        struct Test {
+            name: String,
+            bytes: [u8; 5],
+            expected_metadata: UplinkMetadata,
+        }
+
+        let tests = vec![
+            Test {
+                name: "Uplink id: 1024, dr: 3, rssi: -120, snr: -12, channel: 64".into(),
+                bytes: [0x40, 0x03, 0x78, 0x34, 0x40],
+                expected_metadata: UplinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    rssi: -120,
+                    snr: -12,
+                    channel: 64,
+                    border_id: 0,
+                },
+            },
+            Test {
+                name: "Uplink id: 1024, dr: 3, rssi: -120, snr: -12, channel: 64, border_id: 2"
+                    .into(),
+                bytes: [0x40, 0x03, 0x78, 0xb4, 0x40],
+                expected_metadata: UplinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    rssi: -120,
+                    snr: -12,
+                    channel: 64,
+                    border_id: 2,
+                },
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let res = UplinkMetadata::from_bytes(tst.bytes);
+            assert_eq!(res, tst.expected_metadata);
+        }
+    }
+
+    #[test]
+    fn test_uplink_payload_from_vec() {
+        let b = vec![0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x05];
+        let up_pl = UplinkPayload::from_slice(&b, Version::V1).unwrap();
+        assert_eq!(
+            UplinkPayload {
+                metadata: UplinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    rssi: -120,
+                    snr: -12,
+                    channel: 64,
+                    border_id: 0,
+                },
+                relay_id: [0x01, 0x02, 0x03, 0x04],
+                timestamp: None,
+                timestamp_monotonic: false,
+                phy_payload: vec![0x05],
+            },
+            up_pl,
+        );
+
+        let b = vec![
+            0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x80, 0x00, 0x00, 0x01, 0x02,
+            0x03, 0x05,
+        ];
+        let up_pl = UplinkPayload::from_slice(&b, Version::V2).unwrap();
+        assert_eq!(
+            UplinkPayload {
+                metadata: UplinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    rssi: -120,
+                    snr: -12,
+                    channel: 64,
+                    border_id: 0,
+                },
+                relay_id: [0x01, 0x02, 0x03, 0x04],
+                timestamp: Some(0x010203),
+                timestamp_monotonic: true,
+                phy_payload: vec![0x05],
+            },
+            up_pl,
+        );
+
+        assert_eq!(
+            "At least 15 bytes are expected",
+            UplinkPayload::from_slice(&b[..14], Version::V2)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_uplink_payload_to_vec() {
+        let mut up_pl = UplinkPayload {
+            metadata: UplinkMetadata {
+                uplink_id: 1024,
+                dr: 3,
+                rssi: -120,
+                snr: -12,
+                channel: 64,
+                border_id: 0,
+            },
+            relay_id: [0x01, 0x02, 0x03, 0x04],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![0x05],
+        };
+        assert_eq!(Version::V1, up_pl.version());
+        let b = up_pl.to_vec(Version::V1).unwrap();
+        assert_eq!(
+            vec![0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x05],
+            b
+        );
+        // Without timestamp, e.g. when re-signed using a key index other than 0.
+        let b = up_pl.to_vec(Version::V2).unwrap();
+        assert_eq!(
+            vec![
+                0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x05
+            ],
+            b
+        );
+        assert_eq!(up_pl, UplinkPayload::from_slice(&b, Version::V2).unwrap());
+
+        up_pl.timestamp = Some(0x010203);
+        up_pl.timestamp_monotonic = true;
+        assert_eq!(Version::V2, up_pl.version());
+        let b = up_pl.to_vec(Version::V2).unwrap();
+        assert_eq!(
+            vec![
+                0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x80, 0x00, 0x00, 0x01, 0x02,
+                0x03, 0x05
+            ],
+            b
+        );
+        assert_eq!(
+            "Uplink timestamp requires version 2",
+            up_pl.to_vec(Version::V1).unwrap_err().to_string()
+        );
+
+        up_pl.timestamp = Some(0);
+        up_pl.timestamp_monotonic = false;
+        assert_eq!(
+            "Uplink timestamp 0 is reserved",
+            up_pl.to_vec(Version::V2).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_downlink_metadata_from_bytes() {
+        struct Test {
+            name: String,
+            bytes: [u8; 6],
+            expected_metadata: DownlinkMetadata,
+        }
+
+        let tests = vec![Test {
+            name: "Uplink id: 1024, dr: 3, frequency: 868100000, delay: 16".into(),
+            bytes: [0x40, 0x03, 0x84, 0x76, 0x28, 0xff],
+            expected_metadata: DownlinkMetadata {
+                uplink_id: 1024,
+                dr: 3,
+                frequency: 868100000,
+                tx_power: 15,
+                delay: 16,
+            },
+        }];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let res = DownlinkMetadata::from_bytes(tst.bytes);
+            assert_eq!(res, tst.expected_metadata);
+        }
+    }
+
+    #[test]
+    fn test_downlink_metadata_to_bytes() {
+        struct Test {
+            name: String,
+            metadata: DownlinkMetadata,
+            expected_bytes: Option<[u8; 6]>,
+            expected_error: Option<String>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "Uplink ID exceeds max value".into(),
+                metadata: DownlinkMetadata {
+                    uplink_id: 4096,
+                    dr: 0,
+                    frequency: 868100000,
+                    tx_power: 0,
+                    delay: 1,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max uplink_id value is 4095".into()),
+            },
+            Test {
+                name: "DR exceeds max value".into(),
+                metadata: DownlinkMetadata {
+                    uplink_id: 0,
+                    dr: 16,
+                    frequency: 868100000,
+                    tx_power: 0,
+                    delay: 1,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max dr value is 15".into()),
+            },
+            Test {
+                name: "Frequency not multiple of 100".into(),
+                metadata: DownlinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    frequency: 868100001,
+                    tx_power: 0,
+                    delay: 1,
+                },
+                expected_bytes: None,
+                expected_error: Some("Frequency must be multiple of 100".into()),
+            },
+            Test {
+                name: "TX Power exceeds max value".into(),
+                metadata: DownlinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    frequency: 868100000,
+                    tx_power: 16,
+                    delay: 1,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max tx_power value is 15".into()),
+            },
+            Test {
+                name: "Delay exceeds max value".into(),
+                metadata: DownlinkMetadata {
+                    uplink_id: 0,
+                    dr: 0,
+                    frequency: 868100000,
+                    tx_power: 0,
+                    delay: 17,
+                },
+                expected_bytes: None,
+                expected_error: Some("Max delay value is 16".into()),
+            },
+            Test {
+                name: "Uplink id: 1024, dr: 3, frequency: 868100000, tx_power: 15, delay: 16"
+                    .into(),
+                metadata: DownlinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    frequency: 868100000,
+                    tx_power: 15,
+                    delay: 16,
+                },
+                expected_bytes: Some([0x40, 0x03, 0x84, 0x76, 0x28, 0xff]),
+                expected_error: None,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let res = tst.metadata.to_bytes();
+
+            if let Some(b) = &tst.expected_bytes {
+                assert_eq!(b, &res.unwrap());
+            } else if let Some(err) = &tst.expected_error {
+                assert_eq!(err.to_string(), res.unwrap_err().to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_downlink_payload_from_slice() {
+        let b = vec![
+            0x40, 0x03, 0x84, 0x76, 0x28, 0xff, 0x01, 0x02, 0x03, 0x04, 0x05,
+        ];
+        let dn_pl = DownlinkPayload::from_slice(&b).unwrap();
+        assert_eq!(
+            DownlinkPayload {
+                metadata: DownlinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    frequency: 868100000,
+                    tx_power: 15,
+                    delay: 16,
+                },
+                relay_id: [0x01, 0x02, 0x03, 0x04],
+                phy_payload: vec![0x05],
+            },
+            dn_pl,
+        );
+    }
+
+    #[test]
+    fn test_downlink_payload_to_vec() {
+        let dn_pl = DownlinkPayload {
+            metadata: DownlinkMetadata {
+                uplink_id: 1024,
+                dr: 3,
+                frequency: 868100000,
+                tx_power: 15,
+                delay: 16,
+            },
+            relay_id: [0x01, 0x02, 0x03, 0x04],
+            phy_payload: vec![0x05],
+        };
+        let b = dn_pl.to_vec().unwrap();
+        assert_eq!(
+            vec![0x40, 0x03, 0x84, 0x76, 0x28, 0xff, 0x01, 0x02, 0x03, 0x04, 0x05,],
+            b
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_payload_from_slice() {
+        let b = vec![
+            0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 5, 6, 7, 8, 120, 52, 9, 10, 11, 12, 120, 52,
+        ];
+        let heartbeat_pl = HeartbeatPayload::from_slice(&b, Version::V2).unwrap();
+        assert_eq!(
+            HeartbeatPayload {
+                timestamp: 1_000_000_000_000,
+                timestamp_monotonic: false,
+                relay_id: [1, 2, 3, 4],
+                relay_path: vec![
+                    RelayPath {
+                        relay_id: [5, 6, 7, 8],
+                        rssi: -120,
+                        snr: -12,
+                    },
+                    RelayPath {
+                        relay_id: [9, 10, 11, 12],
+                        rssi: -120,
+                        snr: -12,
+                    },
+                ],
+                config_checksum: None,
+            },
+            heartbeat_pl,
+        );
+
+        let b = vec![128, 0, 0, 0, 234, 96, 1, 2, 3, 4];
+        let heartbeat_pl = HeartbeatPayload::from_slice(&b, Version::V2).unwrap();
+        assert_eq!(
+            HeartbeatPayload {
+                timestamp: 60_000,
+                timestamp_monotonic: true,
+                relay_id: [1, 2, 3, 4],
+                relay_path: vec![],
+                config_checksum: None,
+            },
+            heartbeat_pl,
+        );
+
+        // With config checksum.
+        let b = vec![
+            0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 13, 14, 15, 16, 5, 6, 7, 8, 120, 52,
+        ];
+        let heartbeat_pl = HeartbeatPayload::from_slice(&b, Version::V2).unwrap();
+        assert_eq!(
+            HeartbeatPayload {
+                timestamp: 1_000_000_000_000,
+                timestamp_monotonic: false,
+                relay_id: [1, 2, 3, 4],
+                relay_path: vec![RelayPath {
+                    relay_id: [5, 6, 7, 8],
+                    rssi: -120,
+                    snr: -12,
+                }],
+                config_checksum: Some([13, 14, 15, 16]),
+            },
+            heartbeat_pl,
+        );
+
+        // Invalid size.
+        let b = vec![0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 5, 6, 7];
+        assert!(HeartbeatPayload::from_slice(&b, Version::V2).is_err());
+
+        // Version 1.
+        let b = vec![59, 154, 202, 0, 1, 2, 3, 4, 5, 6, 7, 8, 120, 52];
+        let heartbeat_pl = HeartbeatPayload::from_slice(&b, Version::V1).unwrap();
+        assert_eq!(
+            HeartbeatPayload {
+                timestamp: 1_000_000_000_000,
+                timestamp_monotonic: false,
+                relay_id: [1, 2, 3, 4],
+                relay_path: vec![RelayPath {
+                    relay_id: [5, 6, 7, 8],
+                    rssi: -120,
+                    snr: -12,
+                }],
+                config_checksum: None,
+            },
+            heartbeat_pl,
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_payload_to_vec() {
+        let heartbeat_pl = HeartbeatPayload {
+            timestamp: 1_000_000_000_000,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            relay_path: vec![
+                RelayPath {
+                    relay_id: [5, 6, 7, 8],
+                    rssi: -120,
+                    snr: -12,
+                },
+                RelayPath {
+                    relay_id: [9, 10, 11, 12],
+                    rssi: -120,
+                    snr: -12,
+                },
+            ],
+            config_checksum: None,
+        };
+        let b = heartbeat_pl.to_vec(Version::V2).unwrap();
+        assert_eq!(
+            vec![0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 5, 6, 7, 8, 120, 52, 9, 10, 11, 12, 120, 52],
+            b
+        );
+
+        let heartbeat_pl = HeartbeatPayload {
+            timestamp: 60_000,
+            timestamp_monotonic: true,
+            relay_id: [1, 2, 3, 4],
+            relay_path: vec![],
+            config_checksum: None,
+        };
+        let b = heartbeat_pl.to_vec(Version::V2).unwrap();
+        assert_eq!(vec![128, 0, 0, 0, 234, 96, 1, 2, 3, 4], b);
+
+        let heartbeat_pl = HeartbeatPayload {
+            timestamp: 1_000_000_000_000,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            relay_path: vec![RelayPath {
+                relay_id: [5, 6, 7, 8],
+                rssi: -120,
+                snr: -12,
+            }],
+            config_checksum: Some([13, 14, 15, 16]),
+        };
+        let b = heartbeat_pl.to_vec(Version::V2).unwrap();
+        assert_eq!(
+            vec![0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 13, 14, 15, 16, 5, 6, 7, 8, 120, 52],
+            b
+        );
+
+        let heartbeat_pl = HeartbeatPayload {
+            timestamp: TIMESTAMP_MAX + 1,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            relay_path: vec![],
+            config_checksum: None,
+        };
+        assert!(heartbeat_pl.to_vec(Version::V2).is_err());
+
+        // Version 1.
+        let heartbeat_pl = HeartbeatPayload {
+            timestamp: 1_000_000_000_000,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            relay_path: vec![RelayPath {
+                relay_id: [5, 6, 7, 8],
+                rssi: -120,
+                snr: -12,
+            }],
+            config_checksum: None,
+        };
+        assert_eq!(Version::V1, heartbeat_pl.version());
+        let b = heartbeat_pl.to_vec(Version::V1).unwrap();
+        assert_eq!(vec![59, 154, 202, 0, 1, 2, 3, 4, 5, 6, 7, 8, 120, 52], b);
+
+        let heartbeat_pl = HeartbeatPayload {
+            timestamp: 1_000_000_000_001,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            relay_path: vec![],
+            config_checksum: None,
+        };
+        assert_eq!(Version::V2, heartbeat_pl.version());
+        assert_eq!(
+            "Monotonic and millisecond precision timestamps require version 2",
+            heartbeat_pl.to_vec(Version::V1).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_event_payload_from_slice() {
+        let b = vec![
+            0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 1, 10, 1, 2, 3, 4, 3, 3, 52, 46, 48, 120, 2, 4, 0,
+            0, 48, 57, 127, 2, 5, 6,
+        ];
+        let event_pl = EventPayload::from_slice(&b).unwrap();
+        assert_eq!(
+            EventPayload {
+                timestamp: 1_000_000_000_000,
+                timestamp_monotonic: false,
+                relay_id: [1, 2, 3, 4],
+                events: vec![
+                    Event::Attestation(AttestationEvent {
+                        config_checksum: [1, 2, 3, 4],
+                        features: 3,
+                        version: "4.0".into(),
+                        firmware_version: "x".into(),
+                    }),
+                    Event::TxAirtime(TxAirtimeEvent { airtime: 12345 }),
+                    Event::Unknown(127, vec![5, 6]),
+                ],
+            },
+            event_pl,
+        );
+
+        // Invalid TX airtime event size.
+        let b = vec![0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 2, 2, 5, 6];
+        assert_eq!(
+            "4 bytes are expected",
+            EventPayload::from_slice(&b).unwrap_err().to_string()
+        );
+
+        // Event value exceeds the payload.
+        let b = vec![0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 9, 3, 5, 6];
+        assert_eq!(
+            "Not enough bytes to decode event value",
+            EventPayload::from_slice(&b).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_uplink_ack_event() {
+        let event = Event::UplinkAck(UplinkAckEvent {
+            relay_id: [1, 2, 3, 4],
+            uplink_id: 1024,
+        });
+        assert_eq!(0x03, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![1, 2, 3, 4, 4, 0], b);
+        assert_eq!(event, Event::from_slice(0x03, &b).unwrap());
+
+        assert_eq!(
+            "6 bytes are expected",
+            Event::from_slice(0x03, &b[..5]).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_downlink_ack_event() {
+        let event = Event::DownlinkAck(DownlinkAckEvent {
+            uplink_id: 1024,
+            status: 1,
+        });
+        assert_eq!(0x06, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![4, 0, 1], b);
+        assert_eq!(event, Event::from_slice(0x06, &b).unwrap());
+
+        assert_eq!(
+            "3 bytes are expected",
+            Event::from_slice(0x06, &b[..2]).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_shutdown_event() {
+        let event = Event::Shutdown;
+        assert_eq!(0x07, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert!(b.is_empty());
+        assert_eq!(event, Event::from_slice(0x07, &b).unwrap());
+
+        assert_eq!(
+            "0 bytes are expected",
+            Event::from_slice(0x07, &[1]).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_boot_event() {
+        let event = Event::Boot(BootEvent {
+            reason: BootReason::Restart,
+            features: 3,
+            boot_id: [5, 6, 7, 8],
+            version: "4.0.0".into(),
+            crash: None,
+        });
+        assert_eq!(0x08, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![1, 3, 5, 6, 7, 8, 5, 52, 46, 48, 46, 48], b);
+        assert_eq!(event, Event::from_slice(0x08, &b).unwrap());
+
+        let event = Event::Boot(BootEvent {
+            reason: BootReason::CrashRecovery,
+            features: 3,
+            boot_id: [5, 6, 7, 8],
+            version: "4.0.0".into(),
+            crash: Some(CrashSummary {
+                panic_hash: [1, 2, 3, 4],
+                uptime: 3600,
+            }),
+        });
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(
+            vec![2, 3, 5, 6, 7, 8, 5, 52, 46, 48, 46, 48, 1, 2, 3, 4, 0, 0, 14, 16],
+            b
+        );
+        assert_eq!(event, Event::from_slice(0x08, &b).unwrap());
+
+        assert_eq!(
+            "At least 7 bytes are expected",
+            Event::from_slice(0x08, &b[..6]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Invalid crash summary length",
+            Event::from_slice(0x08, &b[..16]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Unexpected BootReason: 3",
+            Event::from_slice(0x08, &[3, 0, 0, 0, 0, 0, 0])
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_location_event() {
+        let event = Event::Location(LocationEvent {
+            latitude: 523_702_000,
+            longitude: -48_952_000,
+            altitude: -5,
+        });
+        assert_eq!(0x09, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![31, 55, 14, 240, 253, 21, 13, 64, 255, 251], b);
+        assert_eq!(event, Event::from_slice(0x09, &b).unwrap());
+
+        assert_eq!(
+            "10 bytes are expected",
+            Event::from_slice(0x09, &b[..9]).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_telemetry_event() {
+        let event = Event::Telemetry(TelemetryEvent {
+            battery_voltage: Some(12600),
+            solar_current: None,
+            temperature: Some(-55),
+        });
+        assert_eq!(0x0a, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![0x05, 49, 56, 255, 201], b);
+        assert_eq!(event, Event::from_slice(0x0a, &b).unwrap());
+
+        let event = Event::Telemetry(TelemetryEvent::default());
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![0x00], b);
+        assert_eq!(event, Event::from_slice(0x0a, &b).unwrap());
+
+        assert_eq!(
+            "5 bytes are expected",
+            Event::from_slice(0x0a, &[0x05, 49, 56, 255])
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Unexpected telemetry flags: 8",
+            Event::from_slice(0x0a, &[0x08]).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_max_hop_count_event() {
+        let event = Event::MaxHopCount(MaxHopCountEvent { max_hop_count: 3 });
+        assert_eq!(0x0b, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![3], b);
+        assert_eq!(event, Event::from_slice(0x0b, &b).unwrap());
+
+        assert_eq!(
+            "1 byte is expected",
+            Event::from_slice(0x0b, &[3, 0]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Max hop count must be 1 - 8",
+            Event::from_slice(0x0b, &[9]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Max hop count must be 1 - 8",
+            Event::MaxHopCount(MaxHopCountEvent { max_hop_count: 0 })
+                .to_vec()
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_challenge_event() {
+        let pl = ChallengeEvent {
+            challenge: [1, 2, 3, 4],
+        };
+
+        let event = Event::Challenge(pl.clone());
+        assert_eq!(0x0c, event.event_type());
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![1, 2, 3, 4], b);
+        assert_eq!(event, Event::from_slice(0x0c, &b).unwrap());
+
+        let event = Event::ChallengeResponse(pl);
+        assert_eq!(0x0d, event.event_type());
+        let b = event.to_vec().unwrap();
+        assert_eq!(event, Event::from_slice(0x0d, &b).unwrap());
+
+        assert_eq!(
+            "4 bytes are expected",
+            Event::from_slice(0x0c, &b[..3]).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_tdma_beacon_event() {
+        let event = Event::TdmaBeacon(TdmaBeaconEvent {
+            slot_duration: 500,
+            slot_count: 3,
+            relay_ids: vec![[1, 2, 3, 4], [5, 6, 7, 8]],
+        });
+        assert_eq!(0x04, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(vec![1, 244, 3, 1, 2, 3, 4, 5, 6, 7, 8], b);
+        assert_eq!(event, Event::from_slice(0x04, &b).unwrap());
+
+        assert_eq!(
+            "3 + n * 4 bytes are expected",
+            Event::from_slice(0x04, &b[..5]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Number of Relay IDs exceeds slot count",
+            Event::from_slice(0x04, &[1, 244, 1, 1, 2, 3, 4, 5, 6, 7, 8])
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_stats_event() {
+        let event = Event::Stats(StatsEvent {
+            gateway_id: [1, 2, 3, 4, 5, 6, 7, 8],
+            rx_packets_received: 10,
+            rx_packets_received_ok: 9,
+            tx_packets_received: 2,
+            tx_packets_emitted: 1,
+            tx_airtime: 1000,
+            uplinks_acked: 5,
+            uplinks_not_acked: 1,
+            rx_packets_per_frequency: vec![FrequencyCount {
+                frequency: 868100000,
+                count: 10,
+            }],
+            tx_packets_per_frequency: vec![FrequencyCount {
+                frequency: 869525000,
+                count: 1,
+            }],
+        });
+        assert_eq!(0x05, event.event_type());
+
+        let b = event.to_vec().unwrap();
+        assert_eq!(
+            vec![
+                1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 10, 0, 0, 0, 9, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 3,
+                232, 0, 5, 0, 1, 1, 51, 190, 39, 160, 0, 10, 51, 211, 230, 8, 0, 1
+            ],
+            b
+        );
+        assert_eq!(event, Event::from_slice(0x05, &b).unwrap());
+
+        assert_eq!(
+            "33 + n * 6 bytes are expected",
+            Event::from_slice(0x05, &b[..34]).unwrap_err().to_string()
+        );
+
+        let mut b = b[..33].to_vec();
+        b[32] = 1;
+        assert_eq!(
+            "RX frequency count exceeds number of frequencies",
+            Event::from_slice(0x05, &b).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_event_payload_to_vec() {
+        let event_pl = EventPayload {
+            timestamp: 1_000_000_000_000,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            events: vec![
+                Event::Attestation(AttestationEvent {
+                    config_checksum: [1, 2, 3, 4],
+                    features: 3,
+                    version: "4.0".into(),
+                    firmware_version: "x".into(),
+                }),
+                Event::TxAirtime(TxAirtimeEvent { airtime: 12345 }),
+                Event::Unknown(127, vec![5, 6]),
+            ],
+        };
+        assert_eq!(
+            vec![
+                0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 1, 10, 1, 2, 3, 4, 3, 3, 52, 46, 48, 120, 2,
+                4, 0, 0, 48, 57, 127, 2, 5, 6
+            ],
+            event_pl.to_vec().unwrap()
+        );
+
+        let event_pl = EventPayload {
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_id: [1, 2, 3, 4],
+            events: vec![Event::Unknown(127, vec![0; EVENT_MAX_SIZE + 1])],
+        };
+        assert!(event_pl.to_vec().is_err());
+    }
+
+    #[test]
+    fn test_mesh_packet_from_slice() {
+        struct Test {
+            name: String,
+            bytes: Vec<u8>,
+            expected_mesh_packet: MeshPacket,
+        }
+
+        let tests = vec![
+            Test {
+                name: "uplink".into(),
+                bytes: vec![
+                    0xe2, 0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x05, 0x01, 0x02,
+                    0x03, 0x04,
+                ],
+                expected_mesh_packet: MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Uplink,
+                        hop_count: 3,
+                        key_index: 0,
+                    },
+                    payload: Payload::Uplink(UplinkPayload {
+                        metadata: UplinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            rssi: -120,
+                            snr: -12,
+                            channel: 64,
+                            border_id: 0,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        timestamp: None,
+                        timestamp_monotonic: false,
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                },
+            },
+            Test {
+                name: "downlink".into(),
+                bytes: vec![
+                    0xef, 0x40, 0x03, 0x84, 0x76, 0x28, 0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0x01,
+                    0x02, 0x03, 0x04,
+                ],
+                expected_mesh_packet: MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Downlink,
+                        hop_count: 8,
+                        key_index: 0,
+                    },
+                    payload: Payload::Downlink(DownlinkPayload {
+                        metadata: DownlinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            frequency: 868100000,
+                            tx_power: 15,
+                            delay: 16,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                },
+            },
+            Test {
+                name: "uplink v2 + key index 1".into(),
+                bytes: vec![
+                    0xfa, 0x01, 0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00,
+                    0x00, 0x01, 0x02, 0x03, 0x05, 0x01, 0x02, 0x03, 0x04,
+                ],
+                expected_mesh_packet: MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Uplink,
+                        hop_count: 3,
+                        key_index: 1,
+                    },
+                    payload: Payload::Uplink(UplinkPayload {
+                        metadata: UplinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            rssi: -120,
+                            snr: -12,
+                            channel: 64,
+                            border_id: 0,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        timestamp: Some(0x010203),
+                        timestamp_monotonic: false,
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                },
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let pl = MeshPacket::from_slice(&tst.bytes).unwrap();
+            assert_eq!(tst.expected_mesh_packet, pl);
+        }
+    }
+
+    #[test]
+    fn test_mesh_packet_to_vec() {
+        struct Test {
+            name: String,
+            mesh_packet: MeshPacket,
+            expected_bytes: Vec<u8>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "uplink".into(),
+                expected_bytes: vec![
+                    0xe2, 0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x05, 0x01, 0x02,
+                    0x03, 0x04,
+                ],
+                mesh_packet: MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Uplink,
+                        hop_count: 3,
+                        key_index: 0,
+                    },
+                    payload: Payload::Uplink(UplinkPayload {
+                        metadata: UplinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            rssi: -120,
+                            snr: -12,
+                            channel: 64,
+                            border_id: 0,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        timestamp: None,
+                        timestamp_monotonic: false,
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                },
+            },
+            Test {
+                name: "downlink".into(),
+                expected_bytes: vec![
+                    0xef, 0x40, 0x03, 0x84, 0x76, 0x28, 0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0x01,
+                    0x02, 0x03, 0x04,
+                ],
+                mesh_packet: MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Downlink,
+                        hop_count: 8,
+                        key_index: 0,
+                    },
+                    payload: Payload::Downlink(DownlinkPayload {
+                        metadata: DownlinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            frequency: 868100000,
+                            tx_power: 15,
+                            delay: 16,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                },
+            },
+            Test {
+                name: "uplink v2 + key index 1".into(),
+                expected_bytes: vec![
+                    0xfa, 0x01, 0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00,
+                    0x00, 0x01, 0x02, 0x03, 0x05, 0x01, 0x02, 0x03, 0x04,
+                ],
+                mesh_packet: MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Uplink,
+                        hop_count: 3,
+                        key_index: 1,
+                    },
+                    payload: Payload::Uplink(UplinkPayload {
+                        metadata: UplinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            rssi: -120,
+                            snr: -12,
+                            channel: 64,
+                            border_id: 0,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        timestamp: Some(0x010203),
+                        timestamp_monotonic: false,
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                },
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let b = tst.mesh_packet.to_vec().unwrap();
+            assert_eq!(tst.expected_bytes, b);
+        }
+    }
+
+    #[test]
+    fn test_packet_from_slice() {
+        struct Test {
+            name: String,
+            bytes: Vec<u8>,
+            expected_packet: Packet,
+        }
+
+        let tests = vec![
+            Test {
+                name: "mesh packet".into(),
+                bytes: vec![
+                    0xe2, 0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x05, 0x01, 0x02,
+                    0x03, 0x04,
+                ],
+                expected_packet: Packet::Mesh(MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Uplink,
+                        hop_count: 3,
+                        key_index: 0,
+                    },
+                    payload: Payload::Uplink(UplinkPayload {
+                        metadata: UplinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            rssi: -120,
+                            snr: -12,
+                            channel: 64,
+                            border_id: 0,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        timestamp: None,
+                        timestamp_monotonic: false,
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                }),
+            },
+            Test {
+                name: "lora packet".into(),
+                bytes: vec![0x01, 0x02, 0x03],
+                expected_packet: Packet::Lora(vec![0x01, 0x02, 0x03]),
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let pkt = Packet::from_slice(&tst.bytes).unwrap();
+            assert_eq!(tst.expected_packet, pkt);
+        }
+    }
+
+    #[test]
+    fn test_packet_to_vec() {
+        struct Test {
+            name: String,
+            expected_bytes: Vec<u8>,
+            packet: Packet,
+        }
+
+        let tests = vec![
+            Test {
+                name: "mesh packet".into(),
+                expected_bytes: vec![
+                    0xe2, 0x40, 0x03, 0x78, 0x34, 0x40, 0x01, 0x02, 0x03, 0x04, 0x05, 0x01, 0x02,
+                    0x03, 0x04,
+                ],
+                packet: Packet::Mesh(MeshPacket {
+                    mhdr: MHDR {
+                        payload_type: PayloadType::Uplink,
+                        hop_count: 3,
+                        key_index: 0,
+                    },
+                    payload: Payload::Uplink(UplinkPayload {
+                        metadata: UplinkMetadata {
+                            uplink_id: 1024,
+                            dr: 3,
+                            rssi: -120,
+                            snr: -12,
+                            channel: 64,
+                            border_id: 0,
+                        },
+                        relay_id: [0x01, 0x02, 0x03, 0x04],
+                        timestamp: None,
+                        timestamp_monotonic: false,
+                        phy_payload: vec![0x05],
+                    }),
+                    mic: Some([0x01, 0x02, 0x03, 0x04]),
+                }),
+            },
+            Test {
+                name: "lora packet".into(),
+                expected_bytes: vec![0x01, 0x02, 0x03],
+                packet: Packet::Lora(vec![0x01, 0x02, 0x03]),
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let b = tst.packet.to_vec().unwrap();
+            assert_eq!(tst.expected_bytes, b);
+        }
+    }
+
+    #[test]
+    fn test_mesh_packet_version() {
+        let mut packet = MeshPacket {
+            mhdr: MHDR {
+                payload_type: PayloadType::Heartbeat,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: Payload::Heartbeat(HeartbeatPayload {
+                timestamp: 1_000_000_000_000,
+                timestamp_monotonic: false,
+                relay_id: [1, 2, 3, 4],
+                relay_path: vec![],
+                config_checksum: None,
+            }),
+            mic: Some([1, 2, 3, 4]),
+        };
+        assert_eq!(Version::V1, packet.version());
+        let b = packet.to_vec().unwrap();
+        assert_eq!(vec![0xf0, 59, 154, 202, 0, 1, 2, 3, 4, 1, 2, 3, 4], b);
+        assert_eq!(packet, MeshPacket::from_slice(&b).unwrap());
+
+        // A version 2 encoding of a packet that can be encoded using version 1
+        // is rejected.
+        let b = vec![0xf8, 0x80, 0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 1, 2, 3, 4];
+        assert_eq!(
+            "Packet must be encoded using version 1",
+            MeshPacket::from_slice(&b).unwrap_err().to_string()
+        );
+
+        // Key index != 0 requires version 2.
+        packet.mhdr.key_index = 1;
+        assert_eq!(Version::V2, packet.version());
+        let b = packet.to_vec().unwrap();
+        assert_eq!(
+            vec![0xf8, 0x81, 0, 232, 212, 165, 16, 0, 1, 2, 3, 4, 1, 2, 3, 4],
+            b
+        );
+        assert_eq!(packet, MeshPacket::from_slice(&b).unwrap());
+    }
+
+    #[test]
+    fn test_mic_key_index() {
+        let key = Aes128Key::from_bytes([1; 16]);
+        let mut packet = MeshPacket {
+            mhdr: MHDR {
+                payload_type: PayloadType::Uplink,
+                hop_count: 1,
+                key_index: 1,
+            },
+            payload: Payload::Uplink(UplinkPayload {
+                metadata: UplinkMetadata {
+                    uplink_id: 1024,
+                    dr: 3,
+                    rssi: -120,
+                    snr: -12,
+                    channel: 64,
+                    border_id: 0,
+                },
+                relay_id: [0x01, 0x02, 0x03, 0x04],
+                timestamp: None,
+                timestamp_monotonic: false,
+                phy_payload: vec![0x05],
+            }),
+            mic: None,
+        };
+        packet.set_mic(key).unwrap();
+        assert!(packet.validate_mic(key).unwrap());
+        assert!(!packet.validate_mic(Aes128Key::null()).unwrap());
+
+        // The key index is covered by the MIC.
+        packet.mhdr.key_index = 2;
+        assert!(!packet.validate_mic(key).unwrap());
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::info;
+use once_cell::sync::Lazy;
+
+use crate::{deadletter, packets};
+
+// Accepted timestamps within this window below the highest accepted timestamp are tracked, such
+// that packets which are received out of order (e.g. over different mesh paths, or delayed by the
+// relay jitter) are still accepted once.
+const WINDOW: Duration = Duration::from_secs(60);
+
+// Max. number of tracked timestamps within the window per key.
+const RECENT_MAX: usize = 64;
+
+// Max. number of tracked boot IDs per Relay ID.
+const BOOT_IDS_MAX: usize = 32;
+
+// Relay ID + payload type + monotonic flag.
+type TimestampKey = ([u8; 4], u8, bool);
+
+struct TimestampState {
+    highest: u64,
+    // Timestamps below floor are rejected.
+    floor: u64,
+    // Accepted timestamps between floor and highest.
+    recent: BTreeSet<u64>,
+}
+
+impl TimestampState {
+    fn new(timestamp: u64) -> Self {
+        TimestampState {
+            highest: timestamp,
+            floor: timestamp.saturating_sub(WINDOW.as_millis() as u64),
+            recent: BTreeSet::from([timestamp]),
+        }
+    }
+
+    fn accept(&mut self, timestamp: u64) {
+        self.highest = self.highest.max(timestamp);
+        self.floor = self
+            .floor
+            .max(self.highest.saturating_sub(WINDOW.as_millis() as u64));
+        self.recent.insert(timestamp);
+        self.recent = self.recent.split_off(&self.floor);
+        while self.recent.len() > RECENT_MAX {
+            if let Some(v) = self.recent.pop_first() {
+                self.floor = v + 1;
+            }
+        }
+    }
+}
+
+// Accepted timestamps per Relay ID, payload type and monotonic flag.
+static TIMESTAMPS: Lazy<Mutex<HashMap<TimestampKey, TimestampState>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Last boot per Relay ID, as announced by its Boot event.
+static BOOTS: Lazy<Mutex<HashMap<[u8; 4], BootState>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct BootState {
+    // Recently announced boot IDs. A Boot event with one of these does not reset the state.
+    boot_ids: VecDeque<[u8; 4]>,
+    // Timestamp of the last Boot event.
+    timestamp: u64,
+    accepted_at: Instant,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rejection {
+    // The timestamp has already been accepted, or is outside the window.
+    Replay,
+    // Version 1 uplinks do not contain a timestamp.
+    MissingTimestamp,
+}
+
+impl Rejection {
+    // Returns the dead-letter reason.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Rejection::Replay => deadletter::REASON_REPLAY,
+            Rejection::MissingTimestamp => deadletter::REASON_MISSING_TIMESTAMP,
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::Replay => write!(f, "Replayed timestamp"),
+            Rejection::MissingTimestamp => write!(f, "Missing timestamp (version 1 uplink)"),
+        }
+    }
+}
+
+// Returns an error if the given packet is a replay, e.g. an uplink, heartbeat or event that was
+// captured and transmitted again later. Each timestamp is accepted once per Relay Gateway and
+// payload type, if it is greater than the highest accepted timestamp or within the window below
+// it. Uplinks without timestamp (version 1 packets) are rejected. Downlinks are not checked, as
+// these originate from the Border Gateway and are only accepted for a pending uplink.
+//
+// Boot-relative (monotonic) timestamps restart on every boot of the Relay Gateway. These are
+// only reset by a (signed) Boot event with a boot ID that has not been announced before, as a
+// replayed Boot event contains a known boot ID. After the Boot event, a monotonic timestamp that
+// is ahead of the time since the Boot event is rejected, as it was sent before the reboot.
+pub fn check(packet: &packets::MeshPacket) -> Result<(), Rejection> {
+    let (relay_id, timestamp, timestamp_monotonic) = match &packet.payload {
+        packets::Payload::Uplink(v) => match v.timestamp {
+            Some(timestamp) => (v.relay_id, timestamp, v.timestamp_monotonic),
+            None => return Err(Rejection::MissingTimestamp),
+        },
+        packets::Payload::Heartbeat(v) => (v.relay_id, v.timestamp, v.timestamp_monotonic),
+        packets::Payload::Event(v) => (v.relay_id, v.timestamp, v.timestamp_monotonic),
+        packets::Payload::Downlink(_) => return Ok(()),
+    };
+
+    let mut timestamps = TIMESTAMPS.lock().unwrap();
+
+    if timestamp_monotonic {
+        let mut boots = BOOTS.lock().unwrap();
+        if let packets::Payload::Event(v) = &packet.payload {
+            for event in &v.events {
+                if let packets::Event::Boot(boot) = event {
+                    record_boot(
+                        &mut boots,
+                        &mut timestamps,
+                        relay_id,
+                        boot.boot_id,
+                        timestamp,
+                    );
+                }
+            }
+        }
+
+        // The clock of the Relay Gateway might run slightly faster, thus 0.1% is added to the
+        // time since the Boot event.
+        if let Some(boot) = boots.get(&relay_id) {
+            let elapsed = boot.accepted_at.elapsed();
+            let max = boot.timestamp + (elapsed + elapsed / 1000 + WINDOW).as_millis() as u64;
+            if timestamp > max {
+                return Err(Rejection::Replay);
+            }
+        }
+    }
+
+    let key = (
+        relay_id,
+        packet.mhdr.payload_type.to_byte(),
+        timestamp_monotonic,
+    );
+    let state = match timestamps.get_mut(&key) {
+        Some(v) => v,
+        None => {
+            timestamps.insert(key, TimestampState::new(timestamp));
+            return Ok(());
+        }
+    };
+
+    if timestamp > state.highest || (timestamp >= state.floor && !state.recent.contains(&timestamp))
+    {
+        state.accept(timestamp);
+        return Ok(());
+    }
+
+    Err(Rejection::Replay)
+}
+
+// Resets the monotonic timestamps of the Relay Gateway, if the given boot ID has not been
+// announced before.
+fn record_boot(
+    boots: &mut HashMap<[u8; 4], BootState>,
+    timestamps: &mut HashMap<TimestampKey, TimestampState>,
+    relay_id: [u8; 4],
+    boot_id: [u8; 4],
+    timestamp: u64,
+) {
+    let boot = boots.entry(relay_id).or_insert_with(|| BootState {
+        boot_ids: VecDeque::new(),
+        timestamp,
+        accepted_at: Instant::now(),
+    });
+    if boot.boot_ids.contains(&boot_id) {
+        return;
+    }
+
+    info!(
+        "Relay Gateway rebooted, resetting its monotonic timestamps, relay_id: {}, boot_id: {}",
+        hex::encode(relay_id),
+        hex::encode(boot_id)
+    );
+
+    boot.boot_ids.push_back(boot_id);
+    if boot.boot_ids.len() > BOOT_IDS_MAX {
+        boot.boot_ids.pop_front();
+    }
+    boot.timestamp = timestamp;
+    boot.accepted_at = Instant::now();
+
+    timestamps.retain(|k, _| !(k.0 == relay_id && k.2));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn heartbeat(
+        relay_id: [u8; 4],
+        timestamp: u64,
+        timestamp_monotonic: bool,
+    ) -> packets::MeshPacket {
+        packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Heartbeat,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+                timestamp,
+                timestamp_monotonic,
+                relay_id,
+                relay_path: vec![],
+                config_checksum: None,
+            }),
+            mic: None,
+        }
+    }
+
+    fn uplink(relay_id: [u8; 4], timestamp: Option<u64>) -> packets::MeshPacket {
+        packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Uplink,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Uplink(packets::UplinkPayload {
+                metadata: packets::UplinkMetadata {
+                    uplink_id: 1,
+                    dr: 0,
+                    rssi: 0,
+                    snr: 0,
+                    channel: 0,
+                    border_id: 0,
+                },
+                relay_id,
+                timestamp,
+                timestamp_monotonic: false,
+                phy_payload: vec![],
+            }),
+            mic: None,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check(&heartbeat([1, 1, 1, 1], 100_000, false)).is_ok());
+        assert!(check(&heartbeat([1, 1, 1, 1], 102_000, false)).is_ok());
+
+        // Out of order, within the window.
+        assert!(check(&heartbeat([1, 1, 1, 1], 101_000, false)).is_ok());
+
+        // Replayed timestamps.
+        assert!(check(&heartbeat([1, 1, 1, 1], 102_000, false)).is_err());
+        assert!(check(&heartbeat([1, 1, 1, 1], 101_000, false)).is_err());
+        assert!(check(&heartbeat([1, 1, 1, 1], 100_000, false)).is_err());
+
+        // Older than the window.
+        assert!(check(&heartbeat([1, 1, 1, 1], 1_000, false)).is_err());
+
+        // Other Relay Gateway.
+        assert!(check(&heartbeat([2, 2, 2, 2], 1_000, false)).is_ok());
+
+        // Other payload type.
+        let mut packet = heartbeat([1, 1, 1, 1], 1_000, false);
+        packet.mhdr.payload_type = packets::PayloadType::Event;
+        packet.payload = packets::Payload::Event(packets::EventPayload {
+            timestamp: 1_000,
+            timestamp_monotonic: false,
+            relay_id: [1, 1, 1, 1],
+            events: vec![],
+        });
+        assert!(check(&packet).is_ok());
+    }
+
+    #[test]
+    fn test_check_window_max_size() {
+        for i in 0..=RECENT_MAX as u64 {
+            assert!(check(&heartbeat([3, 3, 3, 3], 10_000 + i, false)).is_ok());
+        }
+
+        // The oldest timestamp is no longer tracked, thus is rejected.
+        assert!(check(&heartbeat([3, 3, 3, 3], 9_999, false)).is_err());
+        assert!(check(&heartbeat([3, 3, 3, 3], 10_000, false)).is_err());
+    }
+
+    fn boot(relay_id: [u8; 4], timestamp: u64, boot_id: [u8; 4]) -> packets::MeshPacket {
+        packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Event,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Event(packets::EventPayload {
+                timestamp,
+                timestamp_monotonic: true,
+                relay_id,
+                events: vec![packets::Event::Boot(packets::BootEvent {
+                    reason: packets::BootReason::PowerCycle,
+                    features: 0,
+                    boot_id,
+                    version: "4.0.0".into(),
+                    crash: None,
+                })],
+            }),
+            mic: None,
+        }
+    }
+
+    #[test]
+    fn test_check_monotonic() {
+        assert!(check(&boot([4, 4, 4, 4], 590_000, [1, 1, 1, 1])).is_ok());
+        assert!(check(&heartbeat([4, 4, 4, 4], 600_000, true)).is_ok());
+        assert!(check(&heartbeat([4, 4, 4, 4], 600_000, true)).is_err());
+
+        // A timestamp below the window is rejected, also when the Relay Gateway could have
+        // rebooted since the last accepted packet.
+        assert!(check(&heartbeat([4, 4, 4, 4], 60_000, true)).is_err());
+
+        // A replayed Boot event does not reset the state.
+        assert!(check(&boot([4, 4, 4, 4], 590_000, [1, 1, 1, 1])).is_err());
+        assert!(check(&heartbeat([4, 4, 4, 4], 60_000, true)).is_err());
+
+        // The Boot event of the next boot resets the state.
+        assert!(check(&boot([4, 4, 4, 4], 50_000, [2, 2, 2, 2])).is_ok());
+        assert!(check(&heartbeat([4, 4, 4, 4], 60_000, true)).is_ok());
+        assert!(check(&heartbeat([4, 4, 4, 4], 60_000, true)).is_err());
+
+        // A timestamp that is ahead of the time since the Boot event was sent before the reboot.
+        assert!(check(&heartbeat([4, 4, 4, 4], 600_000, true)).is_err());
+
+        // Wall-clock and monotonic timestamps are tracked separately.
+        assert!(check(&heartbeat([4, 4, 4, 4], 1_000, false)).is_ok());
+    }
+
+    #[test]
+    fn test_check_uplink() {
+        assert_eq!(Ok(()), check(&uplink([5, 5, 5, 5], Some(1_000))));
+        assert_eq!(
+            Err(Rejection::Replay),
+            check(&uplink([5, 5, 5, 5], Some(1_000)))
+        );
+
+        // Version 1 uplinks do not contain a timestamp.
+        assert_eq!(
+            Err(Rejection::MissingTimestamp),
+            check(&uplink([5, 5, 5, 5], None))
+        );
+    }
+}
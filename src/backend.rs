@@ -1,22 +1,33 @@
-use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
 use chirpstack_api::prost::Message;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use once_cell::sync::OnceCell;
 use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::time::sleep;
 
-use crate::config::Configuration;
-use crate::{helpers, mesh, proxy};
+use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
+use crate::{
+    deadletter, dutycycle, helpers, logging, mesh, mqtt, packets, proxy, stats, supervisor, tdma,
+};
 use chirpstack_api::gw;
 
 static GATEWAY_ID: OnceCell<Mutex<[u8; 8]>> = OnceCell::new();
 static RELAY_ID: OnceCell<Mutex<[u8; 4]>> = OnceCell::new();
 
+// Backoff of the startup retries, doubled on every retry up to the max.
+const STARTUP_RETRY_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const STARTUP_RETRY_BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+// Endpoint of the mesh Concentratord event socket monitor.
+const MESH_CONCENTRATORD_MONITOR_URL: &str = "inproc://mesh_concentratord_event_monitor";
+
 static CONCENTRATORD_CMD_CHAN: OnceCell<CommandChannel> = OnceCell::new();
 static MESH_CONCENTRATORD_CMD_CHAN: OnceCell<CommandChannel> = OnceCell::new();
 
-type Event = (String, Vec<u8>);
+// Event, payload and the time the event was received.
+type Event = (String, Vec<u8>, Instant);
 type Command = ((String, Vec<u8>), oneshot::Sender<Result<Vec<u8>>>);
 type CommandChannel = mpsc::UnboundedSender<Command>;
 
@@ -34,24 +45,25 @@ async fn setup_concentratord(conf: &Configuration) -> Result<()> {
 
     // Setup ZMQ command.
 
-    // As the zmq::Context can't be shared between threads, we use a channel.
+    // As the zmq::Socket can't be shared between threads, we use a channel.
     let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel::<Command>();
 
     // Spawn the zmq command handler to a dedicated thread.
-    thread::spawn({
-        let command_url = conf.backend.concentratord.command_url.clone();
-
+    let zmq_ctx = zmq::Context::new();
+    let command_url = conf.backend.concentratord.command_url.clone();
+    let sock = new_command_socket(&zmq_ctx, &command_url)?;
+    supervisor::spawn_thread("Concentratord command", {
+        let zmq_ctx = zmq_ctx.clone();
+        let retries = conf.backend.command_retries;
         move || {
-            let zmq_ctx = zmq::Context::new();
-            let mut sock = zmq_ctx.socket(zmq::REQ).unwrap();
-            sock.connect(&command_url).unwrap();
-
-            while let Some(cmd) = cmd_rx.blocking_recv() {
-                let resp = send_zmq_command(&mut sock, &cmd);
-                cmd.1.send(resp).unwrap();
-            }
-
-            error!("Concentratord command loop has been interrupted");
+            zmq_command_loop(
+                &zmq_ctx,
+                sock,
+                &command_url,
+                "concentratord",
+                retries,
+                &mut cmd_rx,
+            )
         }
     });
 
@@ -59,50 +71,34 @@ async fn setup_concentratord(conf: &Configuration) -> Result<()> {
 
     trace!("Reading Gateway ID");
     let mut gateway_id: [u8; 8] = [0; 8];
-    let (gateway_id_tx, gateway_id_rx) = oneshot::channel::<Result<Vec<u8>>>();
-    cmd_tx.send((("gateway_id".to_string(), vec![]), gateway_id_tx))?;
-    let resp = gateway_id_rx.await??;
+    let resp = read_gateway_id(&cmd_tx, conf.backend.startup_timeout).await?;
     gateway_id.copy_from_slice(&resp);
     info!("Retrieved Gateway ID: {}", hex::encode(gateway_id));
     GATEWAY_ID
         .set(Mutex::new(gateway_id))
-        .map_err(|e| anyhow!("OnceCell error: {:?}", e))?;
+        .map_err(|e| Error::Backend(format!("OnceCell error: {:?}", e)))?;
 
     // Set CMD channel.
 
     CONCENTRATORD_CMD_CHAN
         .set(cmd_tx)
-        .map_err(|e| anyhow!("OnceCell error: {:?}", e))?;
+        .map_err(|e| Error::Backend(format!("OnceCell error: {:?}", e)))?;
 
     // Setup ZMQ event.
 
     let (event_tx, event_rx) = mpsc::unbounded_channel::<Event>();
 
     // Spawn the zmq event handler to a dedicated thread.
-    thread::spawn({
-        let event_url = conf.backend.concentratord.event_url.clone();
-
-        move || {
-            let zmq_ctx = zmq::Context::new();
-            let mut sock = zmq_ctx.socket(zmq::SUB).unwrap();
-            sock.connect(&event_url).unwrap();
-            sock.set_subscribe("".as_bytes()).unwrap();
-
-            loop {
-                match receive_zmq_event(&mut sock) {
-                    Ok(v) => event_tx.send(v).unwrap(),
-                    Err(e) => {
-                        error!("Error receiving ZMQ event, error: {}", e);
-                    }
-                }
-            }
-        }
+    let sock = new_event_socket(&zmq_ctx, &conf.backend.concentratord.event_url)?;
+    supervisor::spawn_thread("Concentratord event", move || {
+        zmq_event_loop(sock, event_tx)
     });
 
     // Spawn event handler.
     tokio::spawn({
         let border_gateway = conf.mesh.border_gateway;
         let border_gateway_ignore_direct_uplinks = conf.mesh.border_gateway_ignore_direct_uplinks;
+        let event_max_age = conf.backend.event_max_age;
         let filters = lrwn_filters::Filters {
             dev_addr_prefixes: conf.mesh.filters.dev_addr_prefixes.clone(),
             join_eui_prefixes: conf.mesh.filters.join_eui_prefixes.clone(),
@@ -112,6 +108,7 @@ async fn setup_concentratord(conf: &Configuration) -> Result<()> {
             event_loop(
                 border_gateway,
                 border_gateway_ignore_direct_uplinks,
+                event_max_age,
                 event_rx,
                 filters,
             )
@@ -130,92 +127,139 @@ async fn setup_mesh_conncentratord(conf: &Configuration) -> Result<()> {
 
     // Setup ZMQ command.
 
-    // As the zmq::Context can't be shared between threads, we use a channel.
+    // As the zmq::Socket can't be shared between threads, we use a channel.
     let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel::<Command>();
 
     // Spawn the zmq command handler to a dedicated thread.
-    thread::spawn({
-        let command_url = conf.backend.mesh_concentratord.command_url.clone();
-
+    let zmq_ctx = zmq::Context::new();
+    let command_url = conf.backend.mesh_concentratord.command_url.clone();
+    let sock = new_command_socket(&zmq_ctx, &command_url)?;
+    supervisor::spawn_thread("Mesh Concentratord command", {
+        let zmq_ctx = zmq_ctx.clone();
+        let retries = conf.backend.command_retries;
         move || {
-            let zmq_ctx = zmq::Context::new();
-            let mut sock = zmq_ctx.socket(zmq::REQ).unwrap();
-            sock.connect(&command_url).unwrap();
-
-            while let Some(cmd) = cmd_rx.blocking_recv() {
-                let resp = send_zmq_command(&mut sock, &cmd);
-                cmd.1.send(resp).unwrap();
-            }
-
-            error!("Mesh Concentratord command loop has been interrupted");
+            zmq_command_loop(
+                &zmq_ctx,
+                sock,
+                &command_url,
+                "mesh_concentratord",
+                retries,
+                &mut cmd_rx,
+            )
         }
     });
 
     // Read Relay ID.
     trace!("Reading Gateway ID");
 
-    let (gateway_id_tx, gateway_id_rx) = oneshot::channel::<Result<Vec<u8>>>();
-    cmd_tx.send((("gateway_id".to_string(), vec![]), gateway_id_tx))?;
-    let resp = gateway_id_rx.await??;
+    let resp = read_gateway_id(&cmd_tx, conf.backend.startup_timeout).await?;
     info!("Retrieved Gateway ID: {}", hex::encode(&resp));
 
     let mut relay_id: [u8; 4] = [0; 4];
     relay_id.copy_from_slice(&resp[4..]);
     RELAY_ID
         .set(Mutex::new(relay_id))
-        .map_err(|e| anyhow!("OnceCell error: {:?}", e))?;
+        .map_err(|e| Error::Backend(format!("OnceCell error: {:?}", e)))?;
 
     // set CMD channel.
 
     MESH_CONCENTRATORD_CMD_CHAN
         .set(cmd_tx)
-        .map_err(|e| anyhow!("OnceCell error: {:?}", e))?;
+        .map_err(|e| Error::Backend(format!("OnceCell error: {:?}", e)))?;
 
     // Setup ZMQ event.
 
     let (event_tx, event_rx) = mpsc::unbounded_channel::<Event>();
 
     // Spawn the zmq event handler to a dedicated thread;
-    thread::spawn({
-        let event_url = conf.backend.mesh_concentratord.event_url.clone();
+    let sock = new_event_socket(&zmq_ctx, &conf.backend.mesh_concentratord.event_url)?;
+
+    // Spawn the zmq event socket monitor to a dedicated thread. This reports the (dis)connect of
+    // the event socket as mesh events.
+    let monitor_sock = new_monitor_socket(&zmq_ctx, &sock, MESH_CONCENTRATORD_MONITOR_URL)?;
+    supervisor::spawn_thread("Mesh Concentratord event monitor", {
+        let event_tx = event_tx.clone();
+        move || zmq_monitor_loop(monitor_sock, event_tx)
+    });
 
-        move || {
-            let zmq_ctx = zmq::Context::new();
-            let mut sock = zmq_ctx.socket(zmq::SUB).unwrap();
-            sock.connect(&event_url).unwrap();
-            sock.set_subscribe("".as_bytes()).unwrap();
-
-            loop {
-                match receive_zmq_event(&mut sock) {
-                    Ok(v) => event_tx.send(v).unwrap(),
-                    Err(e) => {
-                        error!("Error receiving ZMQ event, error: {}", e);
-                    }
-                }
-            }
-        }
+    supervisor::spawn_thread("Mesh Concentratord event", move || {
+        zmq_event_loop(sock, event_tx)
     });
 
     // Spawn event handler.
     tokio::spawn({
         let border_gateway = conf.mesh.border_gateway;
+        let event_max_age = conf.backend.event_max_age;
 
         async move {
-            mesh_event_loop(border_gateway, event_rx).await;
+            mesh_event_loop(border_gateway, event_max_age, event_rx).await;
         }
     });
 
     Ok(())
 }
 
+// Reads the Gateway ID. As the Concentratord might not be ready yet (e.g. on boot), this retries
+// with backoff until the startup_timeout has expired (or forever if set to zero).
+async fn read_gateway_id(cmd_tx: &CommandChannel, startup_timeout: Duration) -> Result<Vec<u8>> {
+    let started_at = Instant::now();
+    let mut backoff = STARTUP_RETRY_BACKOFF_MIN;
+
+    loop {
+        let (gateway_id_tx, gateway_id_rx) = oneshot::channel::<Result<Vec<u8>>>();
+        cmd_tx
+            .send((("gateway_id".to_string(), vec![]), gateway_id_tx))
+            .map_err(|_| Error::Backend("Command channel has been closed".into()))?;
+
+        match gateway_id_rx
+            .await
+            .map_err(|_| Error::Backend("Command response channel has been closed".into()))?
+        {
+            Ok(v) if v.len() == 8 => return Ok(v),
+            Ok(v) => {
+                return Err(Error::InvalidMessage(format!(
+                    "Invalid Gateway ID length: {}",
+                    v.len()
+                )))
+            }
+            Err(e) => {
+                let elapsed = started_at.elapsed();
+                if !startup_timeout.is_zero() && elapsed >= startup_timeout {
+                    return Err(Error::Backend(format!(
+                        "Read Gateway ID error, startup_timeout: {:?}, error: {}",
+                        startup_timeout, e
+                    )));
+                }
+
+                // Do not sleep beyond the startup_timeout.
+                if !startup_timeout.is_zero() {
+                    backoff = backoff.min(startup_timeout - elapsed);
+                }
+
+                warn!(
+                    "Read Gateway ID error, Concentratord might not be ready yet, retrying, backoff: {:?}, error: {}",
+                    backoff, e
+                );
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(STARTUP_RETRY_BACKOFF_MAX);
+            }
+        }
+    }
+}
+
 async fn event_loop(
     border_gateway: bool,
     border_gateway_ignore_direct_uplinks: bool,
+    event_max_age: Duration,
     mut event_rx: mpsc::UnboundedReceiver<Event>,
     filters: lrwn_filters::Filters,
 ) {
     trace!("Starting event loop");
     while let Some(event) = event_rx.recv().await {
+        if shed_event("concentratord", &event, event_max_age) {
+            continue;
+        }
+
         if let Err(e) = handle_event_msg(
             border_gateway,
             border_gateway_ignore_direct_uplinks,
@@ -225,21 +269,55 @@ async fn event_loop(
         .await
         {
             error!("Handle event error: {}", e);
+            stats::record_error(&e);
             continue;
         }
     }
 }
 
-async fn mesh_event_loop(border_gateway: bool, mut event_rx: mpsc::UnboundedReceiver<Event>) {
+async fn mesh_event_loop(
+    border_gateway: bool,
+    event_max_age: Duration,
+    mut event_rx: mpsc::UnboundedReceiver<Event>,
+) {
     trace!("Starting mesh event loop");
     while let Some(event) = event_rx.recv().await {
+        if shed_event("mesh_concentratord", &event, event_max_age) {
+            continue;
+        }
+
         if let Err(e) = handle_mesh_event_msg(border_gateway, &event).await {
             error!("Handle mesh event error: {}", e);
+            stats::record_error(&e);
             continue;
         }
     }
 }
 
+// Returns true if the given event must be shed, because the uplink has been received longer than
+// the event_max_age ago. This happens when the event processing lags behind (e.g. a CPU-starved
+// gateway), in which case relaying the uplink is unlikely to be of use.
+fn shed_event(concentratord: &str, event: &Event, event_max_age: Duration) -> bool {
+    if event_max_age.is_zero() || event.0 != "up" {
+        return false;
+    }
+
+    let age = event.2.elapsed();
+    if age <= event_max_age {
+        return false;
+    }
+
+    stats::record_event_shed(concentratord);
+    if let Some(suppressed) = stats::mesh_drop_log_allowed("event_shed") {
+        warn!(
+            "Shedding uplink, event processing lags behind, concentratord: {}, age: {:?}, event_max_age: {:?}, suppressed: {}",
+            concentratord, age, event_max_age, suppressed
+        );
+    }
+
+    true
+}
+
 async fn handle_event_msg(
     border_gateway: bool,
     border_gateway_ignore_direct_uplinks: bool,
@@ -286,18 +364,29 @@ async fn handle_event_msg(
                     debug!(
                         "Discarding uplink because of dev_addr and join_eui filters, uplink_id: {}",
                         rx_info.uplink_id
-                    )
+                    );
+                    return Ok(());
                 }
 
-                info!("Frame received - {}", helpers::format_uplink(&pl)?);
+                if logging::sample(logging::Category::Uplink) {
+                    info!("Frame received - {}", helpers::format_uplink(&pl)?);
+                }
                 mesh::handle_uplink(border_gateway, pl).await?;
             }
         }
         "stats" => {
+            let mut pl = gw::GatewayStats::decode(event.1.as_slice())?;
             if border_gateway {
-                let pl = gw::GatewayStats::decode(event.1.as_slice())?;
                 info!("Gateway stats received, gateway_id: {}", pl.gateway_id);
+                stats::add_mesh_rx_stats(&mut pl);
+                stats::add_mesh_tx_airtime_stats(&mut pl);
+                stats::add_mesh_tx_error_stats(&mut pl);
+                stats::add_mesh_concentratord_stats(&mut pl);
+                mqtt::publish_counters(&pl.metadata);
                 proxy::send_stats(&pl).await?;
+            } else {
+                debug!("Gateway stats received, gateway_id: {}", pl.gateway_id);
+                stats::record_relay_stats(&pl);
             }
         }
         _ => {
@@ -319,6 +408,25 @@ async fn handle_mesh_event_msg(border_gateway: bool, event: &Event) -> Result<()
         "up" => {
             let pl = gw::UplinkFrame::decode(event.1.as_slice())?;
 
+            if let (Some(rx_info), Some(tx_info)) = (&pl.rx_info, &pl.tx_info) {
+                stats::record_mesh_rx(rx_info, tx_info);
+
+                let conf = config::get();
+                if conf.mesh.frequency_selection.mode == config::FrequencySelectionMode::Airtime {
+                    if let Some(dr) = tx_info
+                        .modulation
+                        .as_ref()
+                        .and_then(|v| helpers::gw_modulation_to_data_rate(v).ok())
+                    {
+                        stats::record_mesh_channel_occupancy(
+                            conf.mesh.frequency_selection.window,
+                            tx_info.frequency,
+                            helpers::airtime(&dr, pl.phy_payload.len()),
+                        );
+                    }
+                }
+            }
+
             if let Some(rx_info) = &pl.rx_info {
                 // Filter out frames with invalid CRC.
                 if rx_info.crc_status() != gw::CrcStatus::CrcOk {
@@ -332,10 +440,32 @@ async fn handle_mesh_event_msg(border_gateway: bool, event: &Event) -> Result<()
 
             // The mesh event msg must always be a proprietary payload.
             if pl.phy_payload.first().cloned().unwrap_or_default() & 0xe0 == 0xe0 {
-                info!("Mesh frame received - {}", helpers::format_uplink(&pl)?);
+                if logging::sample(logging::Category::Mesh) {
+                    info!("Mesh frame received - {}", helpers::format_uplink(&pl)?);
+                }
                 mesh::handle_mesh(border_gateway, pl).await?;
             }
         }
+        "stats" => {
+            let pl = gw::GatewayStats::decode(event.1.as_slice())?;
+            debug!("Mesh gateway stats received, gateway_id: {}", pl.gateway_id);
+            stats::record_mesh_concentratord_stats(pl);
+        }
+        // Reported by the event socket monitor.
+        "connected" => {
+            info!(
+                "Mesh Concentratord event socket connected, event_url: {}",
+                String::from_utf8_lossy(&event.1)
+            );
+            stats::record_mesh_concentratord_connected(true);
+        }
+        "disconnected" => {
+            warn!(
+                "Mesh Concentratord event socket disconnected, event_url: {}",
+                String::from_utf8_lossy(&event.1)
+            );
+            stats::record_mesh_concentratord_connected(false);
+        }
         _ => {
             return Ok(());
         }
@@ -353,11 +483,24 @@ async fn send_command(cmd: &str, b: &[u8]) -> Result<Vec<u8>> {
 
     let cmd_chan = CONCENTRATORD_CMD_CHAN
         .get()
-        .ok_or_else(|| anyhow!("CONCENTRATORD_CMD_CHAN is not set"))?;
+        .ok_or_else(|| Error::Backend("CONCENTRATORD_CMD_CHAN is not set".into()))?;
 
     let (cmd_tx, cmd_rx) = oneshot::channel::<Result<Vec<u8>>>();
-    cmd_chan.send(((cmd.to_string(), b.to_vec()), cmd_tx))?;
-    cmd_rx.await?
+    cmd_chan
+        .send(((cmd.to_string(), b.to_vec()), cmd_tx))
+        .map_err(|_| Error::Backend("Command channel has been closed".into()))?;
+    cmd_rx
+        .await
+        .map_err(|_| Error::Backend("Command response channel has been closed".into()))?
+}
+
+// Returns true if the given mesh frame contains a mesh Downlink payload.
+fn is_mesh_downlink(pl: &gw::DownlinkFrame) -> bool {
+    pl.items
+        .first()
+        .and_then(|v| packets::MHDR::from_slice(&v.phy_payload).ok())
+        .map(|v| v.payload_type == packets::PayloadType::Downlink)
+        .unwrap_or_default()
 }
 
 async fn send_mesh_command(cmd: &str, b: &[u8]) -> Result<Vec<u8>> {
@@ -369,28 +512,182 @@ async fn send_mesh_command(cmd: &str, b: &[u8]) -> Result<Vec<u8>> {
 
     let cmd_chan = MESH_CONCENTRATORD_CMD_CHAN
         .get()
-        .ok_or_else(|| anyhow!("MESH_CONCENTRATORD_CMD_CHAN is not set"))?;
+        .ok_or_else(|| Error::Backend("MESH_CONCENTRATORD_CMD_CHAN is not set".into()))?;
 
     let (cmd_tx, cmd_rx) = oneshot::channel::<Result<Vec<u8>>>();
-    cmd_chan.send(((cmd.to_string(), b.to_vec()), cmd_tx))?;
-    cmd_rx.await?
+    cmd_chan
+        .send(((cmd.to_string(), b.to_vec()), cmd_tx))
+        .map_err(|_| Error::Backend("Command channel has been closed".into()))?;
+    cmd_rx
+        .await
+        .map_err(|_| Error::Backend("Command response channel has been closed".into()))?
 }
 
 pub async fn mesh(pl: &gw::DownlinkFrame) -> Result<()> {
-    info!("Sending mesh frame - {}", helpers::format_downlink(pl)?);
+    let conf = config::get();
+
+    if conf.mesh.passive {
+        info!(
+            "Passive mode, not sending mesh frame - {}",
+            helpers::format_downlink(pl)?
+        );
+        return Ok(());
+    }
+
+    // Reserve the airtime, and defer or drop the transmission if it would exceed the duty-cycle
+    // of the band. Downlinks are never deferred, as the delay could exceed the RX delay of the
+    // device. This is handled before the TDMA delay, as the latter depends on the exact time of
+    // transmission.
+    if conf.mesh.duty_cycle.enabled {
+        if let Some((item, frequency, dr)) = pl.items.first().and_then(|item| {
+            item.tx_info.as_ref().and_then(|tx_info| {
+                tx_info
+                    .modulation
+                    .as_ref()
+                    .and_then(|v| helpers::gw_modulation_to_data_rate(v).ok())
+                    .map(|dr| (item, tx_info.frequency, dr))
+            })
+        }) {
+            let airtime = helpers::airtime(&dr, item.phy_payload.len());
+            let max_delay = if is_mesh_downlink(pl) {
+                Duration::ZERO
+            } else {
+                conf.mesh.duty_cycle.max_delay
+            };
+            match dutycycle::reserve_tx(&conf.mesh.duty_cycle, frequency, airtime, max_delay) {
+                Some(delay) => {
+                    if !delay.is_zero() {
+                        debug!(
+                            "Delaying mesh frame for duty-cycle, downlink_id: {}, frequency: {}, delay: {:?}",
+                            pl.downlink_id, frequency, delay
+                        );
+                        sleep(delay).await;
+                    }
+                }
+                None => {
+                    if let Some(suppressed) =
+                        stats::mesh_drop_log_allowed(deadletter::REASON_DUTY_CYCLE_EXCEEDED)
+                    {
+                        warn!(
+                            "Dropping mesh frame, duty-cycle exceeded, downlink_id: {}, frequency: {}, airtime: {:?}, suppressed: {}",
+                            pl.downlink_id, frequency, airtime, suppressed
+                        );
+                    }
+                    deadletter::record(
+                        deadletter::REASON_DUTY_CYCLE_EXCEEDED,
+                        &item.phy_payload,
+                        "Duty-cycle exceeded",
+                    );
+                    return Err(Error::Routing(format!(
+                        "Duty-cycle exceeded, frequency: {}",
+                        frequency
+                    )));
+                }
+            }
+        }
+    }
+
+    // Relay Gateways only transmit within their TDMA slot(s). Downlinks are excluded, as the
+    // delay could exceed the RX delay of the device.
+    if !conf.mesh.border_gateway && conf.mesh.tdma.enabled && !is_mesh_downlink(pl) {
+        if let Some((item, dr)) = pl.items.first().and_then(|item| {
+            item.tx_info
+                .as_ref()
+                .and_then(|v| v.modulation.as_ref())
+                .and_then(|v| helpers::gw_modulation_to_data_rate(v).ok())
+                .map(|dr| (item, dr))
+        }) {
+            let delay = tdma::get_tx_delay(helpers::airtime(&dr, item.phy_payload.len()));
+            if !delay.is_zero() {
+                debug!(
+                    "Delaying mesh frame until TDMA slot, downlink_id: {}, delay: {:?}",
+                    pl.downlink_id, delay
+                );
+                sleep(delay).await;
+            }
+        }
+    }
+
+    if logging::sample(logging::Category::Mesh) {
+        info!("Sending mesh frame - {}", helpers::format_downlink(pl)?);
+    }
 
     let tx_ack = {
         let b = pl.encode_to_vec();
         let resp_b = send_mesh_command("down", &b).await?;
         gw::DownlinkTxAck::decode(resp_b.as_slice())?
     };
-    helpers::tx_ack_to_err(&tx_ack)?;
-    info!("Enqueue acknowledged, downlink_id: {}", pl.downlink_id);
+    let tx_ack_res = helpers::tx_ack_to_err(&tx_ack);
+    if let Some(item) = pl.items.first() {
+        if let Some(tx_info) = &item.tx_info {
+            stats::record_mesh_tx(tx_info.frequency, tx_ack_res.is_ok());
+
+            if tx_ack_res.is_err() {
+                let payload_type = packets::MHDR::from_slice(&item.phy_payload)
+                    .ok()
+                    .map(|v| format!("{:?}", v.payload_type).to_lowercase())
+                    .unwrap_or_default();
+                let status = tx_ack.items.last().map(|v| v.status()).unwrap_or_default();
+                stats::record_mesh_tx_error(&payload_type, tx_info.frequency, status);
+            }
+
+            if tx_ack_res.is_ok() {
+                if let Some(dr) = tx_info
+                    .modulation
+                    .as_ref()
+                    .and_then(|v| helpers::gw_modulation_to_data_rate(v).ok())
+                {
+                    let airtime = helpers::airtime(&dr, item.phy_payload.len());
+                    stats::record_mesh_tx_airtime(airtime);
+                    if conf.mesh.frequency_selection.mode == config::FrequencySelectionMode::Airtime
+                    {
+                        stats::record_mesh_channel_occupancy(
+                            conf.mesh.frequency_selection.window,
+                            tx_info.frequency,
+                            airtime,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    tx_ack_res?;
+    if logging::sample(logging::Category::Downlink) {
+        info!("Enqueue acknowledged, downlink_id: {}", pl.downlink_id);
+    }
     Ok(())
 }
 
 pub async fn send_downlink(pl: &gw::DownlinkFrame) -> Result<gw::DownlinkTxAck> {
-    info!("Sending downlink frame - {}", helpers::format_downlink(pl)?);
+    if config::get().mesh.passive {
+        info!(
+            "Passive mode, not sending downlink frame - {}",
+            helpers::format_downlink(pl)?
+        );
+
+        // Acknowledge the first item, as the Concentratord would.
+        return Ok(gw::DownlinkTxAck {
+            gateway_id: pl.gateway_id.clone(),
+            downlink_id: pl.downlink_id,
+            items: pl
+                .items
+                .iter()
+                .enumerate()
+                .map(|(i, _)| gw::DownlinkTxAckItem {
+                    status: if i == 0 {
+                        gw::TxAckStatus::Ok.into()
+                    } else {
+                        gw::TxAckStatus::Ignored.into()
+                    },
+                })
+                .collect(),
+            ..Default::default()
+        });
+    }
+
+    if logging::sample(logging::Category::Downlink) {
+        info!("Sending downlink frame - {}", helpers::format_downlink(pl)?);
+    }
 
     let b = pl.encode_to_vec();
     let resp_b = send_command("down", &b).await?;
@@ -413,7 +710,7 @@ pub async fn get_relay_id() -> Result<[u8; 4]> {
 
     Ok(*RELAY_ID
         .get()
-        .ok_or_else(|| anyhow!("RELAY_ID is not set"))?
+        .ok_or_else(|| Error::Backend("RELAY_ID is not set".into()))?
         .lock()
         .await)
 }
@@ -423,11 +720,122 @@ pub async fn get_gateway_id() -> Result<[u8; 8]> {
 
     Ok(*GATEWAY_ID
         .get()
-        .ok_or_else(|| anyhow!("GATEWAY_ID is not set"))?
+        .ok_or_else(|| Error::Backend("GATEWAY_ID is not set".into()))?
         .lock()
         .await)
 }
 
+fn new_command_socket(zmq_ctx: &zmq::Context, command_url: &str) -> Result<zmq::Socket> {
+    let sock = zmq_ctx.socket(zmq::REQ)?;
+    sock.connect(command_url)?;
+    Ok(sock)
+}
+
+fn new_event_socket(zmq_ctx: &zmq::Context, event_url: &str) -> Result<zmq::Socket> {
+    let sock = zmq_ctx.socket(zmq::SUB)?;
+    sock.connect(event_url)?;
+    sock.set_subscribe("".as_bytes())?;
+    Ok(sock)
+}
+
+fn new_monitor_socket(
+    zmq_ctx: &zmq::Context,
+    sock: &zmq::Socket,
+    monitor_url: &str,
+) -> Result<zmq::Socket> {
+    sock.monitor(
+        monitor_url,
+        (zmq::SocketEvent::CONNECTED as i32) | (zmq::SocketEvent::DISCONNECTED as i32),
+    )?;
+
+    let monitor_sock = zmq_ctx.socket(zmq::PAIR)?;
+    monitor_sock.connect(monitor_url)?;
+    Ok(monitor_sock)
+}
+
+fn zmq_command_loop(
+    zmq_ctx: &zmq::Context,
+    mut sock: zmq::Socket,
+    command_url: &str,
+    concentratord: &str,
+    retries: usize,
+    cmd_rx: &mut mpsc::UnboundedReceiver<Command>,
+) -> Result<()> {
+    while let Some(cmd) = cmd_rx.blocking_recv() {
+        // Only the downlink enqueue is retried, as this is idempotent for the same downlink_id.
+        let retries = if cmd.0 .0 == "down" { retries } else { 0 };
+        let mut attempt = 0;
+
+        let resp = loop {
+            let resp = send_zmq_command(&mut sock, &cmd);
+            if resp.is_err() {
+                // A REQ socket can't send a new request until it has received the response
+                // of the previous one, thus it must be re-created.
+                sock = new_command_socket(zmq_ctx, command_url)?;
+            }
+
+            match resp {
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    warn!(
+                        "Sending command failed, retrying, concentratord: {}, command: {}, attempt: {}, error: {}",
+                        concentratord, cmd.0 .0, attempt, e
+                    );
+                    stats::record_command_retry(concentratord, &cmd.0 .0);
+                }
+                Err(e) => {
+                    stats::record_command_error(concentratord, &cmd.0 .0);
+                    break Err(e);
+                }
+                Ok(v) => break Ok(v),
+            }
+        };
+
+        // The requester might have gone away (e.g. timeout), in which case the response can be
+        // ignored.
+        if cmd.1.send(resp).is_err() {
+            debug!("Command requester has gone away, command: {}", cmd.0 .0);
+        }
+    }
+
+    Err(Error::Backend("Command channel has been closed".into()))
+}
+
+fn zmq_event_loop(mut sock: zmq::Socket, event_tx: mpsc::UnboundedSender<Event>) -> Result<()> {
+    loop {
+        match receive_zmq_event(&mut sock) {
+            Ok(v) => event_tx
+                .send(v)
+                .map_err(|_| Error::Backend("Event channel has been closed".into()))?,
+            Err(e) => {
+                error!("Error receiving ZMQ event, error: {}", e);
+            }
+        }
+    }
+}
+
+fn zmq_monitor_loop(sock: zmq::Socket, event_tx: mpsc::UnboundedSender<Event>) -> Result<()> {
+    loop {
+        // The first frame contains the event (2 bytes) and value (4 bytes), the second frame
+        // the endpoint.
+        let msg = sock.recv_multipart(0)?;
+        if msg.len() != 2 || msg[0].len() < 2 {
+            error!("Invalid ZMQ monitor event, frames: {}", msg.len());
+            continue;
+        }
+
+        let event = match zmq::SocketEvent::from_raw(u16::from_le_bytes([msg[0][0], msg[0][1]])) {
+            zmq::SocketEvent::CONNECTED => "connected",
+            zmq::SocketEvent::DISCONNECTED => "disconnected",
+            _ => continue,
+        };
+
+        event_tx
+            .send((event.to_string(), msg[1].clone(), Instant::now()))
+            .map_err(|_| Error::Backend("Event channel has been closed".into()))?;
+    }
+}
+
 fn send_zmq_command(sock: &mut zmq::Socket, cmd: &Command) -> Result<Vec<u8>> {
     debug!(
         "Sending command to socket, command: {}, payload: {}",
@@ -442,7 +850,7 @@ fn send_zmq_command(sock: &mut zmq::Socket, cmd: &Command) -> Result<Vec<u8>> {
     let mut items = [sock.as_poll_item(zmq::POLLIN)];
     zmq::poll(&mut items, 100)?;
     if !items[0].is_readable() {
-        return Err(anyhow!("Could not read down response"));
+        return Err(Error::Backend("Could not read down response".into()));
     }
 
     // red tx ack response
@@ -453,11 +861,11 @@ fn send_zmq_command(sock: &mut zmq::Socket, cmd: &Command) -> Result<Vec<u8>> {
 fn receive_zmq_event(sock: &mut zmq::Socket) -> Result<Event> {
     let msg = sock.recv_multipart(0)?;
     if msg.len() != 2 {
-        return Err(anyhow!("Event must have 2 frames"));
+        return Err(Error::InvalidMessage("Event must have 2 frames".into()));
     }
 
     let event = String::from_utf8(msg[0].to_vec())?;
     let b = msg[1].to_vec();
 
-    Ok((event, b))
+    Ok((event, b, Instant::now()))
 }
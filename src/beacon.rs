@@ -0,0 +1,139 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use log::{info, trace, warn};
+use serde::Serialize;
+use tokio::net::UdpSocket;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::config::Configuration;
+use crate::error::Result;
+use crate::{backend, proxy, topology};
+
+// Time of the last uplink forwarded (Border Gateway) or relayed (Relay Gateway).
+static LAST_UPLINK_AT: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct HealthBeacon {
+    pub gateway_id: String,
+    pub border_gateway: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relays_online: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relays_total: Option<usize>,
+    #[serde(with = "humantime_serde")]
+    pub last_uplink_at: Option<SystemTime>,
+    pub event_queue_length: i64,
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    if conf.integrations.health_beacon.server.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up health beacon, server: {}, interval: {:?}",
+        conf.integrations.health_beacon.server, conf.integrations.health_beacon.interval
+    );
+
+    let sock = UdpSocket::bind("0.0.0.0:0").await?;
+
+    tokio::spawn({
+        let server = conf.integrations.health_beacon.server.clone();
+        let beacon_interval = conf.integrations.health_beacon.interval;
+        let border_gateway = conf.mesh.border_gateway;
+
+        async move {
+            beacon_loop(sock, server, beacon_interval, border_gateway).await;
+        }
+    });
+
+    Ok(())
+}
+
+pub fn record_uplink() {
+    *LAST_UPLINK_AT.lock().unwrap() = Some(SystemTime::now());
+}
+
+async fn beacon_loop(sock: UdpSocket, server: String, beacon_interval: Duration, border: bool) {
+    let mut ticker = interval(beacon_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        let beacon = match get_health_beacon(border).await {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Get health beacon error, error: {}", e);
+                continue;
+            }
+        };
+
+        let b = match serde_json::to_vec(&beacon) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Encode health beacon error, error: {}", e);
+                continue;
+            }
+        };
+
+        trace!(
+            "Sending health beacon, server: {}, beacon: {:?}",
+            server,
+            beacon
+        );
+        if let Err(e) = sock.send_to(&b, &server).await {
+            warn!("Send health beacon error, server: {}, error: {}", server, e);
+        }
+    }
+}
+
+async fn get_health_beacon(border: bool) -> Result<HealthBeacon> {
+    let (relays_online, relays_total) = if border {
+        let relays = topology::get_relays();
+        (
+            Some(relays.iter().filter(|v| is_online(v)).count()),
+            Some(relays.len()),
+        )
+    } else {
+        (None, None)
+    };
+
+    Ok(HealthBeacon {
+        gateway_id: hex::encode(backend::get_gateway_id().await?),
+        border_gateway: border,
+        relays_online,
+        relays_total,
+        last_uplink_at: *LAST_UPLINK_AT.lock().unwrap(),
+        event_queue_length: proxy::get_event_queue_length(),
+    })
+}
+
+// Relays loaded from the state file are only considered online after a heartbeat has been
+// received since startup.
+fn is_online(relay: &topology::Relay) -> bool {
+    !relay.stale && !relay.shutdown
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_health_beacon_json() {
+        let beacon = HealthBeacon {
+            gateway_id: "0101010101010101".into(),
+            border_gateway: false,
+            relays_online: None,
+            relays_total: None,
+            last_uplink_at: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1704110400)),
+            event_queue_length: 2,
+        };
+
+        assert_eq!(
+            r#"{"gateway_id":"0101010101010101","border_gateway":false,"last_uplink_at":"2024-01-01T12:00:00Z","event_queue_length":2}"#,
+            serde_json::to_string(&beacon).unwrap()
+        );
+    }
+}
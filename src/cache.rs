@@ -1,5 +1,6 @@
-use std::time::UNIX_EPOCH;
-use std::{collections::VecDeque, usize};
+use std::collections::VecDeque;
+
+use serde::{Serialize, Serializer};
 
 use crate::packets;
 
@@ -32,13 +33,20 @@ impl<T> Cache<T> {
         self.deque.push_back(value);
         true
     }
+
+    // Returns an iterator over the cached values, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.deque.iter()
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
 pub struct PayloadCache {
+    #[serde(rename = "payload_type", serialize_with = "serialize_payload_type")]
     p_type: packets::PayloadType,
     uplink_id: u16,
-    timestamp: u32,
+    timestamp: u64,
+    #[serde(with = "hex")]
     relay_id: [u8; 4],
 }
 
@@ -63,16 +71,30 @@ impl From<&packets::MeshPacket> for PayloadCache {
                 p_type,
                 uplink_id: 0,
                 relay_id: v.relay_id,
-                timestamp: v
-                    .timestamp
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs() as u32,
+                timestamp: v.timestamp,
+            },
+            packets::Payload::Event(v) => PayloadCache {
+                p_type,
+                uplink_id: 0,
+                relay_id: v.relay_id,
+                timestamp: v.timestamp,
             },
         }
     }
 }
 
+fn serialize_payload_type<S>(p_type: &packets::PayloadType, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(match p_type {
+        packets::PayloadType::Uplink => "uplink",
+        packets::PayloadType::Downlink => "downlink",
+        packets::PayloadType::Heartbeat => "heartbeat",
+        packets::PayloadType::Event => "event",
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -93,4 +115,24 @@ mod test {
         assert!(cache.add(6));
         assert_eq!(5, cache.deque.len());
     }
+
+    #[test]
+    fn test_payload_cache_serialize() {
+        let item = PayloadCache {
+            p_type: packets::PayloadType::Heartbeat,
+            uplink_id: 0,
+            timestamp: 1234,
+            relay_id: [1, 2, 3, 4],
+        };
+
+        assert_eq!(
+            serde_json::json!({
+                "payload_type": "heartbeat",
+                "uplink_id": 0,
+                "timestamp": 1234,
+                "relay_id": "01020304",
+            }),
+            serde_json::to_value(item).unwrap()
+        );
+    }
 }
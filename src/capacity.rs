@@ -0,0 +1,398 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::config::Configuration;
+use crate::error::{Error, Result};
+use crate::{helpers, packets};
+
+// ALOHA has a max. throughput of ~18% of the channel capacity, beyond this the mesh saturates.
+const SATURATION_UTILIZATION: f64 = 18.0;
+
+// Above this utilization, the probability of collisions becomes significant.
+const HIGH_UTILIZATION: f64 = 10.0;
+
+// Duty-cycle limit, as used by most sub-bands in e.g. the EU868 region.
+const DUTY_CYCLE_LIMIT: f64 = 1.0;
+
+// Expected traffic, used as input for the capacity report.
+pub struct Traffic {
+    // Number of Relay Gateways.
+    pub relays: usize,
+    // Device uplinks per hour (total, all relays).
+    pub uplinks_per_hour: f64,
+    // Device uplink PHYPayload size.
+    pub uplink_size: usize,
+    // Device downlinks per hour (total, all relays).
+    pub downlinks_per_hour: f64,
+    // Device downlink PHYPayload size.
+    pub downlink_size: usize,
+}
+
+pub struct Report {
+    pub items: Vec<ReportItem>,
+    pub airtime_per_hour: Duration,
+    // Utilization (percentage) of the mesh channels.
+    pub utilization: f64,
+    // Duty-cycle (percentage) of a single Relay Gateway.
+    pub relay_duty_cycle: f64,
+    pub warnings: Vec<String>,
+}
+
+pub struct ReportItem {
+    pub name: String,
+    pub packets_per_hour: f64,
+    pub size: usize,
+    pub airtime: Duration,
+    // Number of transmissions of a single packet within the mesh.
+    pub transmissions: usize,
+    pub airtime_per_hour: Duration,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:>12} {:>6} {:>10} {:>14} {:>18}",
+            "packet", "packets/hour", "size", "airtime", "transmissions", "airtime/hour"
+        )?;
+        for item in &self.items {
+            writeln!(
+                f,
+                "{:<12} {:>12.1} {:>6} {:>10} {:>14} {:>18}",
+                item.name,
+                item.packets_per_hour,
+                item.size,
+                format!("{:?}", item.airtime),
+                item.transmissions,
+                format!("{:.1?}", item.airtime_per_hour),
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Total airtime / hour: {:.1?}", self.airtime_per_hour)?;
+        writeln!(f, "Mesh channel utilization: {:.2}%", self.utilization)?;
+        writeln!(f, "Relay duty-cycle: {:.2}%", self.relay_duty_cycle)?;
+        for warning in &self.warnings {
+            writeln!(f, "WARNING: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+// Returns the expected airtime utilization of the mesh, given the configuration and expected
+// traffic. This assumes that every Relay Gateway relays every packet once (flooding), which
+// is the upper bound.
+pub fn get_report(conf: &Configuration, traffic: &Traffic) -> Result<Report> {
+    if traffic.relays == 0 {
+        return Err(Error::Config("At least one relay is expected".into()));
+    }
+    if conf.mesh.frequencies.is_empty() {
+        return Err(Error::Routing("No mesh frequencies are configured".into()));
+    }
+
+    let transmissions = if conf.mesh.max_hop_count > 1 {
+        traffic.relays
+    } else {
+        1
+    };
+    let per_hour = |interval: Duration| -> f64 {
+        if interval.is_zero() {
+            0.0
+        } else {
+            3600.0 / interval.as_secs_f64() * traffic.relays as f64
+        }
+    };
+
+    let mut items = vec![
+        (
+            "uplink",
+            traffic.uplinks_per_hour,
+            uplink_size(conf, traffic.uplink_size)?,
+        ),
+        (
+            "downlink",
+            traffic.downlinks_per_hour,
+            downlink_size(conf, traffic.downlink_size)?,
+        ),
+        (
+            "heartbeat",
+            per_hour(conf.mesh.heartbeat_interval),
+            heartbeat_size(conf)?,
+        ),
+        (
+            "tx_airtime",
+            per_hour(conf.mesh.tx_airtime_report_interval),
+            event_size(
+                conf,
+                packets::Event::TxAirtime(packets::TxAirtimeEvent { airtime: 0 }),
+            )?,
+        ),
+        (
+            "stats",
+            per_hour(conf.mesh.stats_report_interval),
+            // Assuming 8 RX frequencies and 1 TX frequency.
+            event_size(
+                conf,
+                packets::Event::Stats(packets::StatsEvent {
+                    gateway_id: [0; 8],
+                    rx_packets_received: 0,
+                    rx_packets_received_ok: 0,
+                    tx_packets_received: 0,
+                    tx_packets_emitted: 0,
+                    tx_airtime: 0,
+                    uplinks_acked: 0,
+                    uplinks_not_acked: 0,
+                    rx_packets_per_frequency: vec![
+                        packets::FrequencyCount {
+                            frequency: 0,
+                            count: 0,
+                        };
+                        8
+                    ],
+                    tx_packets_per_frequency: vec![packets::FrequencyCount {
+                        frequency: 0,
+                        count: 0,
+                    }],
+                }),
+            )?,
+        ),
+    ];
+    if conf.mesh.uplink_ack.enabled {
+        items.push((
+            "uplink_ack",
+            traffic.uplinks_per_hour,
+            event_size(
+                conf,
+                packets::Event::UplinkAck(packets::UplinkAckEvent {
+                    relay_id: [0; 4],
+                    uplink_id: 0,
+                }),
+            )?,
+        ));
+    }
+    if conf.mesh.downlink_ack.enabled {
+        items.push((
+            "downlink_ack",
+            traffic.downlinks_per_hour,
+            event_size(
+                conf,
+                packets::Event::DownlinkAck(packets::DownlinkAckEvent {
+                    uplink_id: 0,
+                    status: 0,
+                }),
+            )?,
+        ));
+    }
+
+    let items: Vec<ReportItem> = items
+        .into_iter()
+        .filter(|(_, packets_per_hour, _)| *packets_per_hour > 0.0)
+        .map(|(name, packets_per_hour, size)| {
+            let airtime = helpers::airtime(&conf.mesh.data_rate, size);
+            ReportItem {
+                name: name.to_string(),
+                packets_per_hour,
+                size,
+                airtime,
+                transmissions,
+                airtime_per_hour: airtime.mul_f64(packets_per_hour * transmissions as f64),
+            }
+        })
+        .collect();
+
+    let airtime_per_hour: Duration = items.iter().map(|v| v.airtime_per_hour).sum();
+    let utilization =
+        airtime_per_hour.as_secs_f64() / 3600.0 / conf.mesh.frequencies.len() as f64 * 100.0;
+    let relay_duty_cycle = airtime_per_hour.as_secs_f64() / 3600.0 / traffic.relays as f64 * 100.0;
+
+    let mut warnings = Vec::new();
+    if utilization > SATURATION_UTILIZATION {
+        warnings.push(format!(
+            "Mesh channel utilization exceeds {}%, the mesh will saturate",
+            SATURATION_UTILIZATION
+        ));
+    } else if utilization > HIGH_UTILIZATION {
+        warnings.push(format!(
+            "Mesh channel utilization exceeds {}%, expect a significant number of collisions",
+            HIGH_UTILIZATION
+        ));
+    }
+    if relay_duty_cycle > DUTY_CYCLE_LIMIT {
+        warnings.push(format!(
+            "Relay duty-cycle exceeds {}%, check the regional duty-cycle limits",
+            DUTY_CYCLE_LIMIT
+        ));
+    }
+
+    Ok(Report {
+        items,
+        airtime_per_hour,
+        utilization,
+        relay_duty_cycle,
+        warnings,
+    })
+}
+
+fn uplink_size(conf: &Configuration, phy_payload_size: usize) -> Result<usize> {
+    packet_size(
+        conf,
+        packets::PayloadType::Uplink,
+        packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id: 0,
+                dr: 0,
+                rssi: 0,
+                snr: 0,
+                channel: 0,
+                border_id: 0,
+            },
+            relay_id: [0; 4],
+            // Version 2 uplinks contain a timestamp.
+            timestamp: if conf.mesh.packet_format_version >= 2 {
+                Some(1)
+            } else {
+                None
+            },
+            timestamp_monotonic: false,
+            phy_payload: vec![0; phy_payload_size],
+        }),
+    )
+}
+
+fn downlink_size(conf: &Configuration, phy_payload_size: usize) -> Result<usize> {
+    packet_size(
+        conf,
+        packets::PayloadType::Downlink,
+        packets::Payload::Downlink(packets::DownlinkPayload {
+            metadata: packets::DownlinkMetadata {
+                uplink_id: 0,
+                dr: 0,
+                frequency: 868100000,
+                tx_power: 0,
+                delay: 1,
+            },
+            relay_id: [0; 4],
+            phy_payload: vec![0; phy_payload_size],
+        }),
+    )
+}
+
+// Returns the max. heartbeat size, see also the heartbeat_interval validation.
+fn heartbeat_size(conf: &Configuration) -> Result<usize> {
+    let mut max_relay_path_len = usize::from(conf.mesh.max_hop_count.saturating_sub(1));
+    if conf.mesh.heartbeat_relay_path_max_length != 0 {
+        max_relay_path_len = max_relay_path_len.min(conf.mesh.heartbeat_relay_path_max_length);
+    }
+
+    packet_size(
+        conf,
+        packets::PayloadType::Heartbeat,
+        packets::Payload::Heartbeat(packets::HeartbeatPayload {
+            timestamp: 0,
+            // Version 2 heartbeats use the 6 byte (millisecond / monotonic) timestamp.
+            timestamp_monotonic: conf.mesh.packet_format_version >= 2,
+            relay_id: [0; 4],
+            relay_path: vec![
+                packets::RelayPath {
+                    relay_id: [0; 4],
+                    rssi: 0,
+                    snr: 0,
+                };
+                max_relay_path_len
+            ],
+            config_checksum: if conf.mesh.heartbeat_config_checksum {
+                Some([0; 4])
+            } else {
+                None
+            },
+        }),
+    )
+}
+
+fn event_size(conf: &Configuration, event: packets::Event) -> Result<usize> {
+    packet_size(
+        conf,
+        packets::PayloadType::Event,
+        packets::Payload::Event(packets::EventPayload {
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_id: [0; 4],
+            events: vec![event],
+        }),
+    )
+}
+
+// Returns the packet size, which depends on the packet format version and thus on the configured
+// signing key index.
+fn packet_size(
+    conf: &Configuration,
+    payload_type: packets::PayloadType,
+    payload: packets::Payload,
+) -> Result<usize> {
+    let packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type,
+            hop_count: 1,
+            key_index: conf.mesh.signing_key_index,
+        },
+        payload,
+        mic: Some([0; 4]),
+    };
+    Ok(packet.to_vec().map_err(Error::Packet)?.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_report() {
+        let mut conf = Configuration::default();
+        conf.mesh.max_hop_count = 2;
+        conf.mesh.heartbeat_interval = Duration::from_secs(300);
+
+        let traffic = Traffic {
+            relays: 5,
+            uplinks_per_hour: 300.0,
+            uplink_size: 23,
+            downlinks_per_hour: 0.0,
+            downlink_size: 23,
+        };
+
+        let report = get_report(&conf, &traffic).unwrap();
+        assert_eq!(
+            vec!["uplink", "heartbeat"],
+            report
+                .items
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(5, report.items[0].transmissions);
+        assert_eq!(60.0, report.items[1].packets_per_hour);
+        assert!(report.warnings.is_empty());
+
+        // Many uplinks will saturate the mesh.
+        let traffic = Traffic {
+            uplinks_per_hour: 10000.0,
+            ..traffic
+        };
+        let report = get_report(&conf, &traffic).unwrap();
+        assert!(report.utilization > SATURATION_UTILIZATION);
+        assert_eq!(
+            vec![
+                "Mesh channel utilization exceeds 18%, the mesh will saturate".to_string(),
+                "Relay duty-cycle exceeds 1%, check the regional duty-cycle limits".to_string(),
+            ],
+            report.warnings
+        );
+
+        let traffic = Traffic {
+            relays: 0,
+            ..traffic
+        };
+        assert_eq!(
+            "At least one relay is expected",
+            get_report(&conf, &traffic).err().unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use log::{error, info, warn};
+use rand::random;
+use tokio::time::sleep;
+
+use crate::config::Configuration;
+use crate::error::Result;
+use crate::{events, helpers, packets, stats, topology};
+
+// Last challenge sent (Border Gateway only).
+static CHALLENGE: Mutex<Option<Challenge>> = Mutex::new(None);
+
+// Challenge to echo after the next heartbeat (Relay Gateway only).
+static PENDING: Mutex<Option<[u8; 4]>> = Mutex::new(None);
+
+struct Challenge {
+    challenge: [u8; 4],
+    sent_at: SystemTime,
+    // Relay IDs of the Relay Gateways that have answered the challenge.
+    answered: HashSet<[u8; 4]>,
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    // Only the Border Gateway sends challenges.
+    if !conf.mesh.border_gateway || !conf.mesh.challenge.enabled {
+        return Ok(());
+    }
+
+    info!(
+        "Starting challenge loop, interval: {:?}",
+        conf.mesh.challenge.interval
+    );
+
+    tokio::spawn({
+        let interval = conf.mesh.challenge.interval;
+
+        async move {
+            loop {
+                check_unanswered();
+
+                let challenge: [u8; 4] = random();
+                *CHALLENGE.lock().unwrap() = Some(Challenge {
+                    challenge,
+                    sent_at: helpers::system_time_now(),
+                    answered: HashSet::new(),
+                });
+
+                if let Err(e) = events::send_challenge(packets::ChallengeEvent { challenge }).await
+                {
+                    error!("Send challenge error, error: {}", e);
+                }
+                sleep(interval).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Flags the Relay Gateways that did not answer the last challenge (Border Gateway only).
+fn check_unanswered() {
+    let relay_ids = match CHALLENGE.lock().unwrap().as_ref() {
+        Some(v) => get_unanswered(v, &topology::get_relays()),
+        None => return,
+    };
+
+    for relay_id in relay_ids {
+        warn!(
+            "Relay Gateway did not answer the challenge, its packets might be replayed, relay_id: {}",
+            hex::encode(relay_id)
+        );
+        topology::record_challenge_failed(relay_id);
+        stats::record_relay_challenge_failed(relay_id, true);
+    }
+}
+
+// Returns the Relay IDs of the Relay Gateways that were seen after the challenge was sent, but
+// did not answer it. A Relay Gateway that is alive answers the challenge after its next
+// heartbeat.
+fn get_unanswered(challenge: &Challenge, relays: &[topology::Relay]) -> Vec<[u8; 4]> {
+    relays
+        .iter()
+        .filter(|v| {
+            !v.shutdown
+                && v.last_seen_at >= challenge.sent_at
+                && !challenge.answered.contains(&v.relay_id)
+        })
+        .map(|v| v.relay_id)
+        .collect()
+}
+
+// Records the challenge response of the Relay Gateway (Border Gateway only). A response that does
+// not match the last challenge flags the Relay Gateway, as it might be replayed.
+pub fn record_response(
+    relay_id: [u8; 4],
+    pl: &packets::ChallengeEvent,
+    hop_count: u8,
+    rssi: i32,
+    snr: f32,
+) {
+    let valid = match CHALLENGE.lock().unwrap().as_mut() {
+        Some(v) => answer(v, relay_id, pl.challenge),
+        None => false,
+    };
+
+    if valid {
+        info!(
+            "Relay challenge response received, relay_id: {}",
+            hex::encode(relay_id)
+        );
+    } else {
+        warn!(
+            "Relay challenge response does not match the last challenge, it might be replayed, relay_id: {}, challenge: {}",
+            hex::encode(relay_id),
+            hex::encode(pl.challenge)
+        );
+    }
+
+    topology::record_challenge_response(relay_id, valid, hop_count, rssi, snr);
+    stats::record_relay_challenge_failed(relay_id, !valid);
+}
+
+fn answer(challenge: &mut Challenge, relay_id: [u8; 4], response: [u8; 4]) -> bool {
+    if challenge.challenge != response {
+        return false;
+    }
+
+    challenge.answered.insert(relay_id);
+    true
+}
+
+// Stores the received challenge, which is echoed after the next heartbeat (Relay Gateway only).
+pub fn record_challenge(pl: &packets::ChallengeEvent) {
+    info!(
+        "Challenge received, challenge: {}",
+        hex::encode(pl.challenge)
+    );
+    *PENDING.lock().unwrap() = Some(pl.challenge);
+}
+
+// Sends the challenge response, if a challenge has been received since the previous response
+// (Relay Gateway only).
+pub async fn report_response() -> Result<()> {
+    let challenge = PENDING.lock().unwrap().take();
+    match challenge {
+        Some(challenge) => {
+            events::send_challenge_response(packets::ChallengeEvent { challenge }).await
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn relay(relay_id: [u8; 4], last_seen_at: SystemTime, shutdown: bool) -> topology::Relay {
+        topology::Relay {
+            relay_id,
+            last_seen_at,
+            hop_count: 1,
+            rssi: 0,
+            snr: 0.0,
+            relay_path: vec![],
+            stale: false,
+            attestation: None,
+            tx_airtime: None,
+            uplink_acks: None,
+            asymmetric_link: false,
+            shutdown,
+            boot: None,
+            location: None,
+            telemetry: None,
+            challenge_failed: false,
+            maintenance: false,
+        }
+    }
+
+    #[test]
+    fn test_get_unanswered() {
+        let sent_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut challenge = Challenge {
+            challenge: [1, 2, 3, 4],
+            sent_at,
+            answered: HashSet::new(),
+        };
+
+        assert!(!answer(&mut challenge, [1, 1, 1, 1], [4, 3, 2, 1]));
+        assert!(answer(&mut challenge, [1, 1, 1, 1], [1, 2, 3, 4]));
+
+        let relays = vec![
+            // Answered.
+            relay([1, 1, 1, 1], sent_at + Duration::from_secs(10), false),
+            // Seen, but not answered.
+            relay([2, 2, 2, 2], sent_at + Duration::from_secs(10), false),
+            // Not seen since the challenge.
+            relay([3, 3, 3, 3], sent_at - Duration::from_secs(10), false),
+            // Announced its shutdown.
+            relay([4, 4, 4, 4], sent_at + Duration::from_secs(10), true),
+        ];
+
+        assert_eq!(vec![[2, 2, 2, 2]], get_unanswered(&challenge, &relays));
+    }
+}
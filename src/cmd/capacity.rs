@@ -0,0 +1,9 @@
+use anyhow::Result;
+
+use crate::{capacity, config};
+
+pub fn run(traffic: &capacity::Traffic) -> Result<()> {
+    let conf = config::get();
+    print!("{}", capacity::get_report(&conf, traffic)?);
+    Ok(())
+}
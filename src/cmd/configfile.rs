@@ -22,6 +22,42 @@ pub fn run() {
   # When set to true, log messages are being written to syslog instead of stdout.
   log_to_syslog=false
 
+  # Log to file.
+  #
+  # When set, log messages are being written to this file instead of stdout,
+  # e.g. on gateways without syslog. Ignored when log_to_syslog is set to true.
+  # Example: "/var/log/chirpstack-gateway-mesh.log"
+  file="{{ logging.file }}"
+
+  # Max. log file size (bytes).
+  #
+  # Once the log file exceeds this size, it is rotated (e.g. the .log file is
+  # renamed to .log.1, .log.1 to .log.2, ...). Set this to 0 to disable
+  # rotation.
+  file_max_size={{ logging.file_max_size }}
+
+  # Max. number of rotated log files to retain.
+  #
+  # Older rotated log files are removed.
+  file_max_files={{ logging.file_max_files }}
+
+  # Log sampling.
+  #
+  # The per-packet info log messages can be sampled per category, to reduce
+  # the log volume on busy meshes. A value of N logs 1 out of every N messages,
+  # a value of 0 or 1 logs every message. Warnings and errors are always
+  # logged.
+  [logging.sampling]
+
+    # Uplink frames (LoRaWAN uplinks and relayed uplinks).
+    uplink={{ logging.sampling.uplink }}
+
+    # Downlink frames (LoRaWAN downlinks and relayed downlinks).
+    downlink={{ logging.sampling.downlink }}
+
+    # Mesh frames (received, sent and re-relayed mesh packets).
+    mesh={{ logging.sampling.mesh }}
+
 
 # Mesh configuration.
 [mesh]
@@ -31,6 +67,41 @@ pub fn run() {
   # configured on every Border / Relay gateway equally.
   signing_key="{{ mesh.signing_key }}"
 
+  # Signing key index.
+  #
+  # The index of the signing key that is used to sign the mesh packets sent
+  # by this gateway. Key index 0 refers to signing_key, other indices refer
+  # to the signing_keys. Received mesh packets are validated using the
+  # signing key matching the key index of the packet.
+  #
+  # To rotate the signing key, first add the new key to signing_keys on every
+  # Border / Relay gateway, then update signing_key_index on every gateway,
+  # and finally remove the old key. A key index other than 0 requires
+  # packet_format_version 2.
+  #
+  # Note: only the signing key can be rotated, as this is the only key used by
+  # the mesh. Mesh packets are signed but not encrypted, the relayed LoRaWAN
+  # frames are already encrypted using the LoRaWAN session keys.
+  signing_key_index={{ mesh.signing_key_index }}
+
+  # Packet format version (1 or 2).
+  #
+  # Version 1 is the original packet format, which is understood by all
+  # ChirpStack Gateway Mesh versions. Version 2 packets use an extended
+  # header, which adds the key index (signing_key_index). Version 2
+  # heartbeats contain a millisecond precision timestamp, which falls back
+  # to the time since boot on Relay Gateways without valid wall-clock time.
+  # Version 1 heartbeats contain the wall-clock time in seconds. Version 2
+  # uplinks contain a timestamp, which is required for replay_protection.
+  # Packets that do not use any version 2 feature are always sent using
+  # version 1. Version 2 packets are received independent of this setting.
+  #
+  # Compatibility note: gateways running a ChirpStack Gateway Mesh version that
+  # only implements version 1 drop version 2 packets. Only set this to 2 once
+  # all Border / Relay gateways have been upgraded. Event packets (e.g.
+  # attestation, boot and stats events) are always sent using version 2.
+  packet_format_version={{ mesh.packet_format_version }}
+
   # Border Gateway.
   #
   # If this is set to true, then the ChirpStack Gateway Mesh will consider
@@ -41,14 +112,66 @@ pub fn run() {
   # Heartbeat interval (Relay Gateway only).
   #
   # This defines the interval in which a Relay Gateway (border_gateway=false)
-  # will emit heartbeat messages.
+  # will emit heartbeat messages. Set this to 0s to disable heartbeats. The
+  # interval is validated on startup and must be long enough for heartbeats
+  # to stay within a 1% duty-cycle, given the mesh data-rate, the
+  # max_hop_count and heartbeat_config_checksum (which determine the maximum
+  # heartbeat size).
   heartbeat_interval="{{ mesh.heartbeat_interval }}"
 
+  # Suppress heartbeat if active (Relay Gateway only).
+  #
+  # If set to true, the Relay Gateway will skip a scheduled heartbeat if it
+  # has relayed an uplink within the last heartbeat interval, as the relayed
+  # uplink already proves that the Relay Gateway is alive. Note that in this
+  # case, the relay path of the heartbeat is not reported either.
+  heartbeat_suppress_if_active={{ mesh.heartbeat_suppress_if_active }}
+
+  # Heartbeat relay interval (Relay Gateway only).
+  #
+  # When set, a Relay Gateway will relay at most one heartbeat per originating
+  # Relay Gateway within this interval. Other heartbeats of the same Relay
+  # Gateway received within this interval are dropped. This keeps the
+  # heartbeat overhead in large meshes under control. Set this to 0s to relay
+  # all heartbeats.
+  heartbeat_relay_interval="{{ mesh.heartbeat_relay_interval }}"
+
+  # Heartbeat relay path max length (Relay Gateway only).
+  #
+  # Each Relay Gateway relaying a heartbeat adds a 6 byte entry to its relay
+  # path. In deep meshes, this might exceed the maximum payload size. When set,
+  # a Relay Gateway will only keep the last N entries of the relay path when
+  # relaying a heartbeat. The hop count still reflects the total number of
+  # hops. The kept entries include the RSSI and SNR of each hop, which are used
+  # for routing and the topology. Set this to 0 to keep the full relay path.
+  heartbeat_relay_path_max_length={{ mesh.heartbeat_relay_path_max_length }}
+
+  # Heartbeat config checksum (Relay Gateway only).
+  #
+  # If set to true, the Relay Gateway includes a checksum of its [mappings]
+  # configuration, mesh frequencies and mesh data_rate in its heartbeats. The
+  # Border Gateway compares this against its own checksum and logs a warning
+  # on mismatch, as in this case the channels and data-rates reported by the
+  # Relay Gateway are unwrapped incorrectly. A mismatch is also exposed by the
+  # mesh_relay_config_mismatch metric and published as mesh_config_mismatch
+  # proxy event (see MeshConfigMismatchEvent in the mesh.proto file). Note that
+  # the Border Gateway and all Relay Gateways relaying heartbeats must support
+  # this option.
+  heartbeat_config_checksum={{ mesh.heartbeat_config_checksum }}
+
   # Max hop count.
   #
   # This defines the maximum number of hops a relayed payload will pass.
   max_hop_count={{ mesh.max_hop_count }}
 
+  # Border ID (Border Gateway only).
+  #
+  # In a mesh shared by multiple networks, each Border Gateway can be given
+  # an ID (1 - 3). Relayed uplinks targeted to a different Border ID (see
+  # border_routes) are ignored by this Border Gateway. Set this to 0 to
+  # unwrap all relayed uplinks.
+  border_id={{ mesh.border_id }}
+
   # Ignore direct uplinks (Border Gateway).
   #
   # If this is set to true, then direct uplinks (uplinks that are not relay
@@ -57,10 +180,19 @@ pub fn run() {
   # Gateway.
   border_gateway_ignore_direct_uplinks={{ mesh.border_gateway_ignore_direct_uplinks }}
 
+  # Passive mode (dry-run).
+  #
+  # If set to true, packets are received and processed as usual (MIC
+  # validation, topology, metrics), but all mesh transmissions and downlinks
+  # are suppressed and logged instead. This is useful to safely observe an
+  # existing mesh from a new node. Note that suppressed downlinks are
+  # acknowledged as if they were sent.
+  passive={{ mesh.passive }}
+
   # Mesh frequencies.
   #
-  # The ChirpStack Gateway Mesh will randomly use one of the configured
-  # frequencies when relaying uplink and downlink messages.
+  # The ChirpStack Gateway Mesh will use one of the configured frequencies
+  # when relaying uplink and downlink messages (see frequency_selection).
   frequencies=[
     {{#each mesh.frequencies}}
     {{this}},
@@ -72,6 +204,164 @@ pub fn run() {
   # The TX Power in EIRP used when relaying uplink and downlink messages.
   tx_power={{ mesh.tx_power }}
 
+  # RSSI offset (dB).
+  #
+  # This offset is added to the RSSI values that this Relay Gateway reports
+  # to the Border Gateway (relayed uplink metadata and heartbeat relay path).
+  # As different hardware reports RSSI with different offsets, this can be
+  # used to calibrate the reported values, such that links can be compared
+  # across hardware vendors.
+  rssi_offset={{ mesh.rssi_offset }}
+
+  # Max payload size.
+  #
+  # The max size (in bytes) of a mesh packet, including the mesh overhead.
+  # Mesh packets exceeding this size are dropped (and counted in the
+  # mesh_drop_count metric). The LoRa physical layer limit is 255 bytes.
+  max_payload_size={{ mesh.max_payload_size }}
+
+  # Max airtime.
+  #
+  # If set, a mesh packet does not fit a data-rate if its airtime exceeds
+  # this duration, e.g. to stay within dwell-time limitations. In this case
+  # the fallback_data_rates are tried (in order). If the packet does not fit
+  # any of these, it is dropped. Set this to 0s to disable.
+  max_airtime="{{ mesh.max_airtime }}"
+
+  # TX airtime report interval (Relay Gateway only).
+  #
+  # If set, the Relay Gateway reports its mesh TX airtime utilization of the
+  # past hour to the Border Gateway at this interval (e.g. 1h). The Border
+  # Gateway adds the reported utilization of each Relay Gateway to its gateway
+  # stats (mesh_relay_tx_airtime_pct_RELAY_ID), next to its own utilization
+  # (mesh_tx_airtime_pct). Set this to 0s to disable.
+  tx_airtime_report_interval="{{ mesh.tx_airtime_report_interval }}"
+
+  # Stats report interval (Relay Gateway only).
+  #
+  # If set, the Relay Gateway reports the statistics of its Concentratord
+  # (RX / TX packet counters and per-frequency counts), aggregated since the
+  # previous report, to the Border Gateway at this interval (e.g. 5m). The
+  # Border Gateway forwards these as gateway stats of the Relay Gateway, such
+  # that ChirpStack can show per-relay statistics. Set this to 0s to disable.
+  stats_report_interval="{{ mesh.stats_report_interval }}"
+
+  # Uplink de-duplication window (Relay Gateway only).
+  #
+  # If set, the Relay Gateway relays an end-device uplink only once within
+  # this window, e.g. when the same transmission is received on multiple
+  # channels (repeaters), to reduce the mesh traffic. Uplinks are considered
+  # duplicates when their PHYPayload is equal. Set this to 0s to relay all
+  # uplinks.
+  uplink_dedup_window="{{ mesh.uplink_dedup_window }}"
+
+  # Relay jitter (Relay Gateway only).
+  #
+  # If set, the Relay Gateway delays relaying an uplink, heartbeat or event by
+  # a random duration up to this value, to reduce collisions when multiple
+  # Relay Gateways receive the same packet and would otherwise all retransmit
+  # immediately. Mesh downlinks are not delayed, as these must arrive within
+  # the RX window of the end-device. Note that this adds latency to relayed
+  # uplinks, thus this must be small compared to the RX1 delay. Set this to
+  # 0s to relay immediately.
+  relay_jitter="{{ mesh.relay_jitter }}"
+
+  # Additional signing keys (AES128, HEX encoded), by key index (1 - 15).
+  # Example:
+  #
+  # [[mesh.signing_keys]]
+  #   key_index=1
+  #   key="0102030405060708090a0b0c0d0e0f10"
+  {{#each mesh.signing_keys}}
+  [[mesh.signing_keys]]
+    key_index={{this.key_index}}
+    key="{{this.key}}"
+  {{/each}}
+
+  # Border routes (Relay Gateway only).
+  #
+  # This maps DevAddr prefixes to the Border ID (1 - 3) to which relayed
+  # uplinks of matching devices are targeted. Uplinks not matching any route
+  # (including join-requests) are targeted to any Border Gateway. Example:
+  #
+  # [[mesh.border_routes]]
+  #   dev_addr_prefix="01000000/8"
+  #   border_id=1
+  {{#each mesh.border_routes}}
+  [[mesh.border_routes]]
+    dev_addr_prefix="{{this.dev_addr_prefix}}"
+    border_id={{this.border_id}}
+  {{/each}}
+
+  # Relay groups.
+  #
+  # Relay groups apply policies to Relay Gateways matching one of the
+  # relay_id_prefixes, e.g. to tune solar powered Relay Gateways differently
+  # from mains powered ones. A Relay Gateway belongs to the first matching
+  # group. Policies:
+  #
+  # * payload_types: The payload types (uplink, heartbeat, event) accepted
+  #   from Relay Gateways in the group. All payload types are accepted if
+  #   empty.
+  # * rate_limit_packets / rate_limit_interval: The max. number of packets
+  #   accepted from each Relay Gateway in the group within the interval. Set
+  #   rate_limit_packets to 0 to disable the rate limit.
+  # * tx_power: Mesh TX power used by Relay Gateways in the group, instead of
+  #   mesh.tx_power.
+  # * heartbeat_interval: Heartbeat interval used by Relay Gateways in the
+  #   group, instead of mesh.heartbeat_interval.
+  #
+  # The payload_types and rate limit policies are enforced by the Border
+  # Gateway and by the Relay Gateways relaying the packets. The tx_power and
+  # heartbeat_interval policies are applied by the Relay Gateway itself, thus
+  # the same relay groups must be configured on all gateways. Example:
+  #
+  # [[mesh.relay_groups]]
+  #   name="solar"
+  #   relay_id_prefixes=["01000000/8"]
+  #   payload_types=["uplink", "heartbeat"]
+  #   rate_limit_packets=60
+  #   rate_limit_interval="1h"
+  #   tx_power=14
+  #   heartbeat_interval="1h"
+  {{#each mesh.relay_groups}}
+  [[mesh.relay_groups]]
+    name="{{this.name}}"
+    relay_id_prefixes=[{{#each this.relay_id_prefixes}}"{{this}}",{{/each}}]
+    payload_types=[{{#each this.payload_types}}"{{this}}",{{/each}}]
+    rate_limit_packets={{this.rate_limit_packets}}
+    rate_limit_interval="{{this.rate_limit_interval}}"
+    {{#if this.tx_power includeZero=true}}
+    tx_power={{this.tx_power}}
+    {{/if}}
+    {{#if this.heartbeat_interval}}
+    heartbeat_interval="{{this.heartbeat_interval}}"
+    {{/if}}
+  {{/each}}
+
+  # Maintenance windows (Border Gateway only).
+  #
+  # During a maintenance window (e.g. a planned daily power cycle), the Relay
+  # Gateways matching one of the relay_id_prefixes are expected to be silent.
+  # The Border Gateway then does not report these as offline (webhook
+  # relay_offline event) and does not count downlinks that are not
+  # acknowledged (see mesh.downlink_ack) as failed. The topology (see
+  # [monitoring] and [integrations.mqtt]) marks these with maintenance=true.
+  # The start time is in UTC (HH:MM) and the window repeats daily. Example:
+  #
+  # [[mesh.maintenance_windows]]
+  #   name="solar power cycle"
+  #   relay_id_prefixes=["01000000/8"]
+  #   start="02:00"
+  #   duration="30m"
+  {{#each mesh.maintenance_windows}}
+  [[mesh.maintenance_windows]]
+    name="{{this.name}}"
+    relay_id_prefixes=[{{#each this.relay_id_prefixes}}"{{this}}",{{/each}}]
+    start="{{this.start}}"
+    duration="{{this.duration}}"
+  {{/each}}
+
   # Data-rate properties.
   #
   # The data-rate properties when relaying uplink and downlink messages.
@@ -95,6 +385,599 @@ pub fn run() {
     bitrate={{ mesh.data_rate.bitrate }}
 
 
+  # Fallback data-rates.
+  #
+  # Mesh packets that do not fit the mesh data_rate (see max_airtime) are
+  # transmitted using the first of these data-rates in which they fit. This
+  # is typically a list of faster data-rates, at the cost of a lower range.
+  # Note that all mesh gateways must be able to receive these data-rates.
+  # Example:
+  #
+  # [[mesh.fallback_data_rates]]
+  #   modulation="LORA"
+  #   spreading_factor=7
+  #   bandwidth=125000
+  #   code_rate="4/5"
+  #   bitrate=0
+  {{#each mesh.fallback_data_rates}}
+  [[mesh.fallback_data_rates]]
+    modulation="{{this.modulation}}"
+    spreading_factor={{this.spreading_factor}}
+    bandwidth={{this.bandwidth}}
+    code_rate="{{this.code_rate}}"
+    bitrate={{this.bitrate}}
+  {{/each}}
+
+
+  # Bad-channel avoidance.
+  #
+  # If enabled, the ChirpStack Gateway Mesh keeps track of the failure rate
+  # per mesh frequency. A failure is a mesh frame rejected by the mesh
+  # Concentratord (e.g. because of a collision with another transmission)
+  # or a mesh frame received with CRC error. When the failure rate exceeds
+  # the threshold, the frequency is temporarily removed from the rotation.
+  # After the probe interval, the frequency is used again and re-evaluated.
+  # If all frequencies are excluded, the normal rotation is used.
+  [mesh.bad_channel_avoidance]
+
+    # Enable bad-channel avoidance.
+    enabled={{ mesh.bad_channel_avoidance.enabled }}
+
+    # Failure threshold.
+    #
+    # The ratio of failures (0.0 - 1.0) at which a frequency is excluded.
+    failure_threshold={{ mesh.bad_channel_avoidance.failure_threshold }}
+
+    # Minimum number of samples.
+    #
+    # The failure rate of a frequency is evaluated every N samples (TX
+    # attempts + received frames) on that frequency.
+    min_samples={{ mesh.bad_channel_avoidance.min_samples }}
+
+    # Probe interval.
+    #
+    # The duration for which a frequency is excluded, after which it is
+    # probed again.
+    probe_interval="{{ mesh.bad_channel_avoidance.probe_interval }}"
+
+
+  # Join-request relay policy (Relay Gateway only).
+  #
+  # Join-requests are rare, but high value uplinks. These options make it
+  # possible to relay join-requests differently from other uplinks.
+  [mesh.join_requests]
+
+    # Relay join-requests only.
+    #
+    # If set to true, the Relay Gateway only relays join-requests received
+    # from end-devices. All other uplinks received from end-devices are
+    # dropped. Mesh packets of other Relay Gateways are not affected.
+    relay_only={{ mesh.join_requests.relay_only }}
+
+    # Data-rate properties (optional).
+    #
+    # If set, join-requests are relayed using this data-rate instead of the
+    # mesh data_rate, e.g. to use a more robust data-rate. Note that all mesh
+    # gateways must be able to receive this data-rate.
+    {{#if mesh.join_requests.data_rate}}
+    [mesh.join_requests.data_rate]
+      modulation="{{ mesh.join_requests.data_rate.modulation }}"
+      spreading_factor={{ mesh.join_requests.data_rate.spreading_factor }}
+      bandwidth={{ mesh.join_requests.data_rate.bandwidth }}
+      code_rate="{{ mesh.join_requests.data_rate.code_rate }}"
+      bitrate={{ mesh.join_requests.data_rate.bitrate }}
+    {{else}}
+    # [mesh.join_requests.data_rate]
+    #   modulation="LORA"
+    #   spreading_factor=9
+    #   bandwidth=125000
+    #   code_rate="4/5"
+    #   bitrate=0
+    {{/if}}
+
+
+  # Attestation (Relay Gateway only).
+  #
+  # If enabled, the Relay Gateway reports its Gateway Mesh version, firmware
+  # version, configuration checksum and enabled features to the Border
+  # Gateway, on startup and on receiving the SIGUSR1 signal. The Border
+  # Gateway exposes these in the relay registry (/relays monitoring endpoint),
+  # which can be used to verify the consistency of the fleet.
+  [mesh.attestation]
+
+    # Enable attestation.
+    enabled={{ mesh.attestation.enabled }}
+
+    # Firmware version.
+    #
+    # The firmware version of the gateway, as reported to the Border Gateway.
+    firmware_version="{{ mesh.attestation.firmware_version }}"
+
+
+  # Boot announcement (Relay Gateway only).
+  #
+  # If enabled, the Relay Gateway announces its startup to the Border Gateway,
+  # with its Gateway Mesh version, enabled features, boot ID and the reason of
+  # the (re)start. The Border Gateway exposes this in the relay registry and
+  # publishes it as mesh_relay_boot proxy event (see MeshRelayBootEvent in the
+  # mesh.proto file). The boot ID is also used by the replay protection (see
+  # mesh.replay_protection).
+  [mesh.boot_announcement]
+
+    # Enable the boot announcement.
+    enabled={{ mesh.boot_announcement.enabled }}
+
+    # Crash marker file.
+    #
+    # This file is written on startup and updated on a clean shutdown, to
+    # tell a power cycle, a restart and a crash recovery apart. On a panic, a
+    # crash report (uptime and panic message) is written to this file, of
+    # which a summary (panic message hash and uptime) is included in the boot
+    # announcement on the next start. It must be stored on volatile storage
+    # (e.g. tmpfs), such that it does not survive a power cycle or reboot of
+    # the gateway.
+    crash_marker_file="{{ mesh.boot_announcement.crash_marker_file }}"
+
+
+  # Location (Relay Gateway only).
+  #
+  # If configured, the Relay Gateway reports its location together with its
+  # stats (see stats_report_interval, which must be set). The Border Gateway
+  # adds the reported location to the forwarded gateway stats of the Relay
+  # Gateway, such that ChirpStack can show the Relay Gateway on the map, and
+  # to the relay registry and GeoJSON topology export. The location is also
+  # part of the mesh_packet proxy events (see LocationEvent in the mesh.proto
+  # file).
+  [mesh.location]
+
+    # Location source.
+    #
+    # Options are:
+    #   none   - The location is not reported.
+    #   static - The latitude, longitude and altitude configured below.
+    #   gpsd   - The last location fix reported by gpsd. Nothing is reported
+    #            until gpsd reports a (2D or 3D) fix.
+    source="{{ mesh.location.source }}"
+
+    # Latitude (static source).
+    latitude={{ mesh.location.latitude }}
+
+    # Longitude (static source).
+    longitude={{ mesh.location.longitude }}
+
+    # Altitude in meters (static source).
+    altitude={{ mesh.location.altitude }}
+
+    # gpsd server (gpsd source).
+    gpsd_server="{{ mesh.location.gpsd_server }}"
+
+
+  # Power telemetry (Relay Gateway only).
+  #
+  # If configured, the Relay Gateway reports its battery voltage, solar
+  # current and temperature to the Border Gateway at the report interval. Each
+  # value is read from a file (e.g. sysfs or IIO ADC) or from the output of a
+  # command (executed using sh -c), and is multiplied by the scale to get the
+  # value in V, A and degrees Celsius respectively. Values that are not
+  # configured, or that can not be read, are omitted. The Border Gateway adds
+  # the last reported values to the relay registry and to the forwarded gateway
+  # stats of the Relay Gateway (mesh_battery_voltage, mesh_solar_current and
+  # mesh_temperature). They are also part of the mesh_packet proxy events (see
+  # TelemetryEvent in the mesh.proto file).
+  [mesh.telemetry]
+
+    # Report interval.
+    #
+    # Set this to 0s to disable the telemetry report.
+    report_interval="{{ mesh.telemetry.report_interval }}"
+
+    # Battery voltage.
+    #
+    # Example (sysfs, in uV):
+    #   path="/sys/class/power_supply/battery/voltage_now"
+    #   scale=0.000001
+    [mesh.telemetry.battery_voltage]
+      path="{{ mesh.telemetry.battery_voltage.path }}"
+      command="{{ mesh.telemetry.battery_voltage.command }}"
+      scale={{ mesh.telemetry.battery_voltage.scale }}
+
+    # Solar current.
+    #
+    # Example (IIO ADC, with a 1 ohm shunt and 1 mV per raw unit):
+    #   path="/sys/bus/iio/devices/iio:device0/in_voltage1_raw"
+    #   scale=0.001
+    [mesh.telemetry.solar_current]
+      path="{{ mesh.telemetry.solar_current.path }}"
+      command="{{ mesh.telemetry.solar_current.command }}"
+      scale={{ mesh.telemetry.solar_current.scale }}
+
+    # Temperature.
+    #
+    # Example (sysfs, in millidegrees Celsius):
+    #   path="/sys/class/thermal/thermal_zone0/temp"
+    #   scale=0.001
+    [mesh.telemetry.temperature]
+      path="{{ mesh.telemetry.temperature.path }}"
+      command="{{ mesh.telemetry.temperature.command }}"
+      scale={{ mesh.telemetry.temperature.scale }}
+
+
+  # Uplink acknowledgements.
+  #
+  # If enabled, a Relay Gateway that relays an uplink waits for it to be
+  # acknowledged by the next hop, and retransmits the uplink if it has not
+  # been acknowledged within the timeout. An uplink is acknowledged when the
+  # next Relay Gateway is overheard relaying the uplink (implicit ACK) or when
+  # the Border Gateway acknowledges the uplink (explicit ACK). This must be
+  # enabled on all gateways within the mesh, including the Border Gateway.
+  [mesh.uplink_ack]
+
+    # Enable uplink acknowledgements.
+    enabled={{ mesh.uplink_ack.enabled }}
+
+    # Acknowledgement timeout.
+    #
+    # This timeout is doubled after each retransmission.
+    timeout="{{ mesh.uplink_ack.timeout }}"
+
+    # Max. number of retransmissions.
+    max_retransmissions={{ mesh.uplink_ack.max_retransmissions }}
+
+
+  # Downlink acknowledgements.
+  #
+  # If enabled, the Relay Gateway that unwraps a relayed downlink reports the
+  # TX acknowledgement status of its Concentratord back to the Border Gateway.
+  # The Border Gateway tracks the downlink success rate per Relay Gateway over
+  # the last window downlinks, exposed by the mesh_relay_downlink_success_rate
+  # metric. When it drops below the alert threshold, or recovers, this is
+  # published as mesh_downlink_alert proxy event (see MeshDownlinkAlertEvent
+  # in the mesh.proto file). This must be enabled on all gateways within the
+  # mesh, including the Border Gateway.
+  [mesh.downlink_ack]
+
+    # Enable downlink acknowledgements.
+    enabled={{ mesh.downlink_ack.enabled }}
+
+    # Acknowledgement timeout (Border Gateway only).
+    #
+    # A relayed downlink that has not been acknowledged within this timeout
+    # is counted as failed.
+    timeout="{{ mesh.downlink_ack.timeout }}"
+
+    # Window (Border Gateway only).
+    #
+    # The number of most recent relayed downlinks over which the success rate
+    # is calculated.
+    window={{ mesh.downlink_ack.window }}
+
+    # Alert threshold (Border Gateway only).
+    #
+    # The success rate (0.0 - 1.0) below which an alert is raised. Set this to
+    # 0 to disable alerts.
+    alert_threshold={{ mesh.downlink_ack.alert_threshold }}
+
+
+  # Relay downlink guard.
+  #
+  # If enabled, the Border Gateway spaces consecutive downlinks for devices
+  # behind the same Relay Gateway, such that the mesh transmissions do not
+  # collide at the Relay Gateway. Each downlink reserves the estimated airtime
+  # of the mesh packet + the margin below. Downlinks that would be delayed
+  # beyond their RX delay are rejected with TOO_LATE.
+  [mesh.relay_downlink_guard]
+
+    # Enable the relay downlink guard.
+    enabled={{ mesh.relay_downlink_guard.enabled }}
+
+    # Processing margin.
+    #
+    # The time the Relay Gateway needs to handle a relayed downlink, added to
+    # the airtime of each downlink.
+    margin="{{ mesh.relay_downlink_guard.margin }}"
+
+
+  # Downlink rate limit.
+  #
+  # If enabled, the Border Gateway limits the number of downlinks relayed over
+  # the mesh per end-device (DevAddr), e.g. to protect the mesh from a Network
+  # Server flooding Class-C downlinks. Downlinks exceeding the limit are not
+  # relayed and are rejected with QUEUE_FULL. Join-accepts are not limited.
+  [mesh.downlink_rate_limit]
+
+    # Enable the downlink rate limit.
+    enabled={{ mesh.downlink_rate_limit.enabled }}
+
+    # Max. number of downlinks per DevAddr within the interval.
+    max_downlinks={{ mesh.downlink_rate_limit.max_downlinks }}
+
+    # Interval.
+    interval="{{ mesh.downlink_rate_limit.interval }}"
+
+
+  # Replay protection.
+  #
+  # If enabled, uplink, heartbeat and event packets are dropped when their
+  # timestamp has already been received, or is more than 60s older than the
+  # latest timestamp received from the same Relay Gateway (per packet type).
+  # This rejects captured packets that are transmitted again later (after
+  # these have left the de-duplication cache), while packets received out of
+  # order are still accepted. Uplinks without timestamp are dropped
+  # (dead-letter reason missing_timestamp), thus this requires
+  # packet_format_version 2 on all gateways. Downlink packets are sent by the
+  # Border Gateway and are not checked.
+  #
+  # Note: after a backwards step of the wall-clock time of a Relay Gateway,
+  # its packets are dropped until the previous timestamp has been passed.
+  # Relay Gateways without valid wall-clock time use a boot-relative
+  # timestamp, which restarts on every boot. A lower boot-relative timestamp
+  # is only accepted after the boot announcement of the Relay Gateway, with a
+  # boot ID that was not announced before. Thus, mesh.boot_announcement must
+  # be enabled on such Relay Gateways, else their packets are dropped after a
+  # reboot. Packets sent before the reboot are rejected when their timestamp
+  # is ahead of the time since the boot announcement, but can be replayed once
+  # the new boot-relative timestamp has reached these. Configure NTP or an
+  # RTC on the Relay Gateways to prevent this.
+  [mesh.replay_protection]
+
+    # Enable replay protection.
+    enabled={{ mesh.replay_protection.enabled }}
+
+
+  # Path stickiness.
+  #
+  # If enabled, the Border Gateway remembers per end-device (DevAddr) which
+  # Relay Gateways delivered its recent uplinks. When the Relay Gateway of the
+  # downlink context is offline (it announced its shutdown, or has not been
+  # seen since startup), the downlink is relayed through an other Relay
+  # Gateway that delivered the same uplink (same PHYPayload) within the TTL.
+  # This avoids downlink failures when the Network Server uses the context of
+  # a Relay Gateway that has since gone offline. Join-accepts are not
+  # affected.
+  #
+  # Note: the downlink delay is applied relative to the uplink received by
+  # the preferred Relay Gateway.
+  [mesh.path_stickiness]
+
+    # Enable path stickiness.
+    enabled={{ mesh.path_stickiness.enabled }}
+
+    # TTL.
+    #
+    # An other Relay Gateway that delivered the same uplink is used for
+    # downlinks within this duration after the uplink.
+    ttl="{{ mesh.path_stickiness.ttl }}"
+
+
+  # Routing.
+  #
+  # If enabled, the Relay Gateway builds a table with the link quality between
+  # relays, based on the relay path of the received heartbeats. A packet is
+  # not relayed when an other relay (known to relay packets) has received the
+  # packet from the same transmitter with a better RSSI. This reduces the
+  # mesh airtime in dense deployments. This only applies to packets of which
+  # the transmitter is known, e.g. uplinks that have not yet been relayed and
+  # heartbeats. Downlinks are always relayed.
+  #
+  # Note: this requires heartbeats to be enabled.
+  [mesh.routing]
+
+    # Enable routing.
+    enabled={{ mesh.routing.enabled }}
+
+    # RSSI margin (dB).
+    #
+    # A packet is not relayed if the link of an other relay with the
+    # transmitter is at least this margin better than the own link.
+    rssi_margin={{ mesh.routing.rssi_margin }}
+
+    # Max. age.
+    #
+    # Links that have not been refreshed by a heartbeat within this duration
+    # are ignored.
+    max_age="{{ mesh.routing.max_age }}"
+
+
+  # TDMA.
+  #
+  # If enabled, the Border Gateway periodically sends a beacon, assigning a
+  # transmit slot to each known Relay Gateway (based on the received
+  # heartbeats). Relay Gateways only transmit mesh packets within their slot.
+  # Relay Gateways without assigned slot (e.g. new relays) share the free
+  # slots at the end of each frame. The frame starts when the beacon is
+  # received. This must be enabled on all gateways within the mesh, including
+  # the Border Gateway.
+  #
+  # Note: the slot_duration should be larger than the airtime of the largest
+  # mesh packet, as a transmission is only started when it fits within the
+  # slot.
+  [mesh.tdma]
+
+    # Enable TDMA.
+    enabled={{ mesh.tdma.enabled }}
+
+    # Slot duration (Border Gateway only).
+    slot_duration="{{ mesh.tdma.slot_duration }}"
+
+    # Number of free slots (Border Gateway only).
+    #
+    # These slots are shared by the Relay Gateways without assigned slot.
+    free_slots={{ mesh.tdma.free_slots }}
+
+    # Beacon interval (Border Gateway only).
+    beacon_interval="{{ mesh.tdma.beacon_interval }}"
+
+
+  # Adaptive max hop count.
+  #
+  # If enabled, the Border Gateway periodically advertises a max hop count
+  # to the Relay Gateways, based on the network diameter (the highest hop
+  # count of the non-stale Relay Gateways, learned from the heartbeats) plus
+  # the margin. Relay Gateways use the advertised value, capped by the
+  # max_hop_count setting. This must be enabled on all gateways within the
+  # mesh, including the Border Gateway.
+  #
+  # Note: the margin must be at least 1, such that the heartbeats of Relay
+  # Gateways that are added further away from the Border Gateway are still
+  # relayed, which increases the advertised max hop count.
+  [mesh.adaptive_max_hop_count]
+
+    # Enable adaptive max hop count.
+    enabled={{ mesh.adaptive_max_hop_count.enabled }}
+
+    # Margin (Border Gateway only).
+    #
+    # This is added to the network diameter.
+    margin={{ mesh.adaptive_max_hop_count.margin }}
+
+    # Advertisement interval (Border Gateway only).
+    advertisement_interval="{{ mesh.adaptive_max_hop_count.advertisement_interval }}"
+
+
+  # Challenge.
+  #
+  # If enabled, the Border Gateway periodically sends a random challenge to
+  # the Relay Gateways, which echo it after their next heartbeat. A Relay
+  # Gateway that was seen by the Border Gateway after the challenge was sent,
+  # but that did not answer it before the next challenge, is flagged in the
+  # topology (challenge_failed), as its packets might be replayed (e.g. by a
+  # wormhole). This must be enabled on all gateways within the mesh,
+  # including the Border Gateway.
+  #
+  # Note: the interval must be at least twice the heartbeat_interval of the
+  # Relay Gateways, as the challenge is answered after the next heartbeat.
+  [mesh.challenge]
+
+    # Enable challenge.
+    enabled={{ mesh.challenge.enabled }}
+
+    # Interval (Border Gateway only).
+    interval="{{ mesh.challenge.interval }}"
+
+
+  # Unicast downlinks.
+  #
+  # If enabled, the Relay Gateway only relays downlinks for Relay Gateways
+  # it is on the path to. This is learned from the uplinks, heartbeats and
+  # events relayed by this Relay Gateway, as downlinks follow the reverse
+  # path. This must be enabled on all Relay Gateways within the mesh.
+  #
+  # Note: when no relayed packet of the destination Relay Gateway has been
+  # seen within the path timeout, the downlink is not relayed. Enabling the
+  # heartbeats keeps the paths up-to-date.
+  [mesh.unicast_downlinks]
+
+    # Enable unicast downlinks.
+    enabled={{ mesh.unicast_downlinks.enabled }}
+
+    # Path timeout.
+    path_timeout="{{ mesh.unicast_downlinks.path_timeout }}"
+
+
+  # Frequency selection.
+  #
+  # The mesh frequency is selected for each mesh transmission. Valid modes
+  # are:
+  #
+  # * airtime: Select the frequency with the lowest airtime (mesh
+  #   transmissions and received mesh packets) within the window. Frequencies
+  #   with equal airtime are selected round-robin.
+  # * round_robin: Rotate the frequencies.
+  #
+  # Frequencies excluded by bad_channel_avoidance are skipped in both modes.
+  [mesh.frequency_selection]
+
+    # Mode.
+    mode="{{ mesh.frequency_selection.mode }}"
+
+    # Window.
+    #
+    # The airtime is calculated over this sliding window (airtime mode only).
+    window="{{ mesh.frequency_selection.window }}"
+
+
+  # Duty-cycle.
+  #
+  # If enabled, the airtime of the mesh transmissions is accounted per
+  # frequency band, such that the duty-cycle of each band is not exceeded
+  # within the window (e.g. the 1% sub-bands of EU868). A mesh transmission
+  # that would exceed the duty-cycle is delayed (up to max_delay) until
+  # enough airtime is available, or is dropped otherwise. Mesh downlinks are
+  # never delayed, as this could exceed the RX delay of the device, these are
+  # dropped instead. The airtime is accounted when the transmission is
+  # scheduled. The default bands are the EU868 (ETSI EN 300 220) sub-bands.
+  # Frequencies outside the configured bands are not limited.
+  #
+  # Note: this only accounts the mesh transmissions of this gateway, not the
+  # downlinks transmitted by the (mesh) Concentratord for other purposes.
+  [mesh.duty_cycle]
+
+    # Enable duty-cycle enforcement.
+    enabled={{ mesh.duty_cycle.enabled }}
+
+    # Window.
+    #
+    # The duty-cycle is calculated over this sliding window.
+    window="{{ mesh.duty_cycle.window }}"
+
+    # Max delay.
+    #
+    # The max duration a mesh transmission (other than a downlink) is delayed
+    # to stay within the duty-cycle. Set this to 0s to drop these
+    # transmissions immediately.
+    max_delay="{{ mesh.duty_cycle.max_delay }}"
+
+    # Bands.
+    #
+    # Each band covers the frequencies from min_frequency (inclusive) up to
+    # max_frequency (exclusive). The duty_cycle is a percentage. Example:
+    #
+    # [[mesh.duty_cycle.bands]]
+    #   min_frequency=868000000
+    #   max_frequency=868600000
+    #   duty_cycle=1.0
+    {{#each mesh.duty_cycle.bands}}
+    [[mesh.duty_cycle.bands]]
+      min_frequency={{ this.min_frequency }}
+      max_frequency={{ this.max_frequency }}
+      duty_cycle={{ this.duty_cycle }}
+    {{/each}}
+
+
+  # Filters.
+  #
+  # Uplinks received by the gateway (not by the mesh radio) are only
+  # handled (proxied by the Border Gateway, relayed by the Relay Gateway)
+  # when they match these filters. Join-requests are filtered by JoinEUI
+  # prefix, other uplinks by DevAddr prefix. If no filters are configured,
+  # all uplinks are handled. Example:
+  #
+  # dev_addr_prefixes=["01000000/8"]
+  # join_eui_prefixes=["0102030405060708/64"]
+  [mesh.filters]
+
+    # DevAddr prefixes.
+    dev_addr_prefixes=[
+      {{#each mesh.filters.dev_addr_prefixes}}
+      "{{this}}",
+      {{/each}}
+    ]
+
+    # JoinEUI prefixes.
+    join_eui_prefixes=[
+      {{#each mesh.filters.join_eui_prefixes}}
+      "{{this}}",
+      {{/each}}
+    ]
+
+    # Apply to relayed uplinks (Border Gateway only).
+    #
+    # If set to true, the Border Gateway also applies these filters to the
+    # unwrapped uplinks relayed by Relay Gateways, e.g. when the Border Gateway
+    # only serves a subset of the devices within the mesh.
+    relayed_uplinks={{ mesh.filters.relayed_uplinks }}
+
+
   # Proxy API configuration.
   #
   # If the gateway is configured to operate as Border Gateway. It
@@ -116,10 +999,81 @@ pub fn run() {
     # Command REP socket bind.
     command_bind="{{ mesh.proxy_api.command_bind }}"
 
+    # JSON encoding.
+    #
+    # If set to true, events are published and commands are expected to be
+    # JSON encoded instead of Protobuf encoded. The gateway_id command response
+    # is not affected by this setting.
+    json={{ mesh.proxy_api.json }}
+
+    # Event replay buffer size.
+    #
+    # If set, the last N published events are kept in a buffer. Each event
+    # gets a sequence number (starting at 1), and the get_events command
+    # returns the buffered events with a sequence number greater than the
+    # requested since_seq. This makes it possible for a forwarder that
+    # (re)connects to fetch the events it missed. Set this to 0 to disable.
+    event_replay_buffer_size={{ mesh.proxy_api.event_replay_buffer_size }}
+
+    # Event sequence number frame.
+    #
+    # If set to true, each published event has a third frame containing the
+    # sequence number of the event (8 bytes, big-endian). Consumers can use
+    # this to detect missed events, e.g. because of the PUB/SUB slow-joiner
+    # issue or overload. Only enable this if the consumer of the proxy API
+    # supports this extra frame.
+    event_seq_frame={{ mesh.proxy_api.event_seq_frame }}
+
+    # Mesh packet events.
+    #
+    # If set to true, the Border Gateway publishes every received mesh packet
+    # (including duplicates) as mesh_packet event (see MeshPacketEvent in
+    # proto/mesh.proto). This event contains the decoded mesh packet, the raw
+    # bytes and the RX metadata, which can be used by network analysis tools to
+    # compute mesh KPIs.
+    mesh_packet_events={{ mesh.proxy_api.mesh_packet_events }}
+
+    # Mirror event bind.
+    #
+    # If set, every event published on the event_bind socket is also
+    # published on this (secondary) PUB socket, using the same framing. This
+    # can be used to feed local analytics or debugging tools, without
+    # affecting the packet-forwarder connected to the event_bind socket.
+    # Leave this empty to disable.
+    mirror_event_bind="{{ mesh.proxy_api.mirror_event_bind }}"
+
 
 # Backend configuration.
 [backend]
 
+  # Startup timeout.
+  #
+  # On startup, the Concentratord instances might not be ready yet (e.g. when
+  # these are started at the same time on boot). In this case, connecting is
+  # retried with backoff until this timeout has expired, after which the
+  # ChirpStack Gateway Mesh exits with an error. Set this to 0s to retry
+  # indefinitely.
+  startup_timeout="{{ backend.startup_timeout }}"
+
+  # Command retries.
+  #
+  # The number of times a downlink command is retried immediately when the
+  # Concentratord did not respond in time, before the downlink is considered
+  # failed. As the socket is re-created after each failure, this mitigates
+  # downlinks being dropped because of a single lost request. Set this to 0 to
+  # disable retries.
+  command_retries={{ backend.command_retries }}
+
+  # Event max age.
+  #
+  # If set, received frames (uplink events) that are older than this duration
+  # by the time they are processed are dropped without processing, e.g. on a
+  # CPU-starved gateway of which the event processing lags behind, as the
+  # relayed packets are unlikely to be of use. Dropped frames are counted in
+  # the event_shed_count metric. Set this to 0s to process all frames.
+  event_max_age="{{ backend.event_max_age }}"
+
+
   # ChirpStack Concentratord configuration (end-device communication).
   [backend.concentratord]
 
@@ -143,6 +1097,372 @@ pub fn run() {
 
     # Command API URL.
     command_url="{{ backend.mesh_concentratord.command_url }}"
+
+
+# Monitoring configuration.
+[monitoring]
+
+  # Interface:port.
+  #
+  # If set, this will enable the monitoring endpoints. If not set, the endpoint
+  # will be disabled. Endpoints:
+  #
+  # * /metrics: Exposes Prometheus metrics.
+  # * /health: Returns 200 if the service is running.
+  # * /relays: Returns the last-known Relay Gateway state (Border Gateway only).
+  # * /topology.dot: Returns the mesh topology in Graphviz DOT format (Border
+  #   Gateway only).
+  # * /topology.geojson: Returns the mesh topology in GeoJSON format (Border
+  #   Gateway only).
+  # * /cache: Returns the mesh payloads in the de-duplication cache.
+  # * /duty_cycle: Returns the mesh TX airtime and duty-cycle per frequency
+  #   within the duty-cycle window.
+  # * /link_quality and /link_quality/[relay_id]: Returns the hop count, RSSI
+  #   and SNR of the past 24 hours per Relay Gateway, sampled at most once
+  #   per minute (Border Gateway only). This is kept in memory and is also
+  #   printed by the link-quality subcommand.
+  bind="{{ monitoring.bind }}"
+
+  # Commands.
+  #
+  # If enabled, the following command endpoints are exposed:
+  #
+  # * POST /heartbeat: Sends a heartbeat over the mesh (Relay Gateway only),
+  #   e.g. to verify during commissioning that the Relay Gateway reaches the
+  #   Border Gateway.
+  #
+  # As these endpoints are not authenticated, only enable this when the
+  # monitoring endpoint is bound to a trusted interface (e.g. 127.0.0.1).
+  commands={{ monitoring.commands }}
+
+
+# Watchdog configuration.
+#
+# If enabled, the watchdog monitors the liveness of the event loop and the
+# memory usage of the process. When the event loop stalls for longer than the
+# stall_timeout, or when the memory usage exceeds the memory_limit, the
+# diagnostics are logged and the process exits with a non-zero exit code, such
+# that the service manager (e.g. systemd or procd) restarts it.
+[watchdog]
+
+  # Enable the watchdog.
+  enabled={{ watchdog.enabled }}
+
+  # Interval.
+  #
+  # The interval in which the event loop liveness and the memory usage are
+  # checked.
+  interval="{{ watchdog.interval }}"
+
+  # Stall timeout.
+  #
+  # The max. duration that the event loop may be unresponsive. This must be
+  # greater than the interval.
+  stall_timeout="{{ watchdog.stall_timeout }}"
+
+  # Memory limit (MiB).
+  #
+  # The max. resident memory (RSS) of the process. Set this to 0 to disable
+  # the memory check.
+  memory_limit={{ watchdog.memory_limit }}
+
+
+# Topology configuration (Border Gateway only).
+#
+# The Border Gateway keeps track of the Relay Gateways it receives heartbeats
+# from (last seen, hop count, link metrics and relay path).
+[topology]
+
+  # State file.
+  #
+  # If set, the last-known Relay Gateway state is persisted to this file and
+  # reloaded on startup, so that a restart does not lose visibility until the
+  # next heartbeats are received. Reloaded entries are marked as stale until
+  # refreshed by a heartbeat.
+  state_file="{{ topology.state_file }}"
+
+  # Persist interval.
+  #
+  # The interval in which the state is written to the state_file. The state is
+  # also written on shutdown. Set this to 0s to only write on shutdown.
+  persist_interval="{{ topology.persist_interval }}"
+
+  # Asymmetric link ratio.
+  #
+  # A Relay Gateway is flagged as having an asymmetric link when the ratio of
+  # its relayed uplinks that it reports as not acknowledged is at least this
+  # ratio, while the Border Gateway does receive its uplinks. This is a common
+  # cause of uplinks working while downlinks fail. This requires the uplink
+  # ACKs (mesh.uplink_ack) and the stats report (mesh.stats_report_interval)
+  # to be enabled. Set this to 0 to disable.
+  asymmetric_link_ratio={{ topology.asymmetric_link_ratio }}
+
+  # Relay Gateway locations.
+  #
+  # The locations configured below are used by the GeoJSON topology export,
+  # and take precedence over the locations reported by the Relay Gateways (see
+  # mesh.location). Relay Gateways without location are omitted. Example:
+  #
+  # [[topology.relay_locations]]
+  #   relay_id="01020304"
+  #   latitude=52.3676
+  #   longitude=4.9041
+  #   altitude=0.0
+  {{#each topology.relay_locations}}
+  [[topology.relay_locations]]
+    relay_id="{{this.relay_id}}"
+    latitude={{this.latitude}}
+    longitude={{this.longitude}}
+    altitude={{this.altitude}}
+  {{/each}}
+
+
+# Uplink context configuration (Relay Gateway only).
+#
+# The Relay Gateway stores the context of each relayed uplink, such that the
+# downlink (referring to the uplink by its Uplink ID) can be sent at the
+# correct time.
+[uplink_context]
+
+  # State file.
+  #
+  # If set, the stored uplink contexts are persisted to this file and
+  # reloaded on startup, so that downlinks can still be matched after a
+  # restart.
+  state_file="{{ uplink_context.state_file }}"
+
+  # Persist interval.
+  #
+  # The interval in which the state is written to the state_file. The state is
+  # also written on shutdown. Set this to 0s to only write on shutdown.
+  persist_interval="{{ uplink_context.persist_interval }}"
+
+  # TTL.
+  #
+  # Uplink contexts are removed after this duration. Set this to 0s to only
+  # remove contexts when max_entries is exceeded.
+  ttl="{{ uplink_context.ttl }}"
+
+  # Max entries.
+  #
+  # The max number of uplink contexts to keep. Older entries are removed.
+  max_entries={{ uplink_context.max_entries }}
+
+
+# Dead-letter log configuration.
+#
+# The dead-letter log captures packets that were dropped for operational
+# reasons (e.g. max hop count exceeded, payload too large, no uplink
+# context), together with the reason. The log can be inspected using the
+# dead-letters subcommand.
+[dead_letter]
+
+  # File.
+  #
+  # If set, dropped packets are appended to this file (JSON lines). If not
+  # set, the dead-letter log is disabled.
+  file="{{ dead_letter.file }}"
+
+  # Max entries.
+  #
+  # The max number of entries to keep. Older entries are removed.
+  max_entries={{ dead_letter.max_entries }}
+
+
+# Webhook configuration (Border Gateway only).
+#
+# The Border Gateway can POST selected mesh events as JSON to a HTTP endpoint,
+# such that small deployments can be integrated without MQTT / network server
+# plumbing. Each event contains the event type (event), the Gateway ID of the
+# Border Gateway (gateway_id) and the time of the event (time).
+[webhook]
+
+  # URL.
+  #
+  # If set, events are posted to this URL. Both http:// and https:// URLs are
+  # supported. Use an https:// URL when the headers contain credentials, as
+  # these are sent in plaintext otherwise. If not set, the webhook is disabled.
+  url="{{ webhook.url }}"
+
+  # Events.
+  #
+  # The events to post. Valid options are:
+  #
+  # * heartbeat: A heartbeat was received from a Relay Gateway.
+  # * relay_offline: No heartbeat, event or relayed uplink was received from
+  #   a Relay Gateway for the duration of relay_offline_timeout, or the Relay
+  #   Gateway announced its shutdown.
+  # * topology: A Relay Gateway was added to the topology, or its hop count or
+  #   relay path has changed.
+  events=[
+    {{#each webhook.events}}
+    "{{this}}",
+    {{/each}}
+  ]
+
+  # Timeout.
+  #
+  # The max duration of a webhook request.
+  timeout="{{ webhook.timeout }}"
+
+  # Relay offline timeout.
+  #
+  # A relay_offline event is posted when a Relay Gateway has not been seen for
+  # this duration. This should be a multiple of the Relay Gateway
+  # heartbeat_interval. Set this to 0s to disable relay_offline events.
+  relay_offline_timeout="{{ webhook.relay_offline_timeout }}"
+
+  # Headers.
+  #
+  # Additional HTTP headers to add to each request, e.g. for authentication.
+  # Example:
+  #
+  # [webhook.headers]
+  #   Authorization="Bearer secret"
+  [webhook.headers]
+    {{#each webhook.headers}}
+    {{@key}}="{{this}}"
+    {{/each}}
+
+
+# Integrations configuration (Border Gateway only).
+[integrations]
+
+  # MQTT integration.
+  #
+  # Besides the proxy API, the Border Gateway can publish mesh health topics
+  # (JSON) to a MQTT broker, e.g. for dashboards. Messages are published using
+  # QoS 0. Topics:
+  #
+  # * [topic_prefix]/[gateway_id]/relay/[relay_id]/heartbeat: Relay Gateway
+  #   state, published on every received heartbeat.
+  # * [topic_prefix]/[gateway_id]/topology: Last-known state of all Relay
+  #   Gateways, published every topology_interval (retained).
+  # * [topic_prefix]/[gateway_id]/counters: Mesh counters, published together
+  #   with each Concentratord stats event.
+  [integrations.mqtt]
+
+    # Server.
+    #
+    # If set, this enables the MQTT integration. Use ssl:// to connect using
+    # TLS. Examples: tcp://127.0.0.1:1883, ssl://mqtt.example.com:8883
+    server="{{ integrations.mqtt.server }}"
+
+    # Username (optional).
+    username="{{ integrations.mqtt.username }}"
+
+    # Password (optional).
+    password="{{ integrations.mqtt.password }}"
+
+    # Client ID.
+    #
+    # If not set, chirpstack-gateway-mesh-[gateway_id] is used.
+    client_id="{{ integrations.mqtt.client_id }}"
+
+    # CA certificate file (optional).
+    #
+    # Use this when the server certificate is not signed by one of the system
+    # CA certificates (ssl:// only).
+    ca_cert="{{ integrations.mqtt.ca_cert }}"
+
+    # TLS certificate file (optional).
+    #
+    # Client certificate for TLS client authentication. This requires ca_cert
+    # and tls_key to be set.
+    tls_cert="{{ integrations.mqtt.tls_cert }}"
+
+    # TLS key file (optional).
+    tls_key="{{ integrations.mqtt.tls_key }}"
+
+    # Topic prefix.
+    topic_prefix="{{ integrations.mqtt.topic_prefix }}"
+
+    # Keep alive.
+    #
+    # The MQTT keep alive interval. The connection is re-established when the
+    # broker does not respond to a ping within the keep alive interval.
+    keep_alive="{{ integrations.mqtt.keep_alive }}"
+
+    # Topology interval.
+    #
+    # The interval in which the topology is published. Set this to 0s to
+    # disable publishing the topology.
+    topology_interval="{{ integrations.mqtt.topology_interval }}"
+
+    # Forwarder.
+    #
+    # If enabled, the Border Gateway publishes its events (e.g. the unwrapped
+    # uplinks, gateway stats and mesh events) to the MQTT broker and
+    # subscribes to its commands, using the ChirpStack gateway topic layout,
+    # instead of publishing the events on the proxy API event socket. This
+    # removes the need for a separate ChirpStack MQTT Forwarder process. Events
+    # are published and commands are subscribed using QoS 1. Topics:
+    #
+    # * [topic_prefix]/gateway/[gateway_id]/event/[event]: Event (e.g. up,
+    #   stats, mesh_heartbeat, ack), encoded as configured by
+    #   mesh.proxy_api.json.
+    # * [topic_prefix]/gateway/[gateway_id]/command/[command]: Command, of
+    #   which down (DownlinkFrame) and config (GatewayConfiguration) are
+    #   supported. Each downlink is acknowledged by an ack event.
+    #
+    # Note that the proxy API command socket remains available and that events
+    # are dropped when the MQTT broker is not reachable.
+    [integrations.mqtt.forwarder]
+
+      # Enable the forwarder.
+      enabled={{ integrations.mqtt.forwarder.enabled }}
+
+      # Topic prefix.
+      #
+      # This must match the region topic prefix configured in ChirpStack.
+      topic_prefix="{{ integrations.mqtt.forwarder.topic_prefix }}"
+
+  # Health beacon.
+  #
+  # If enabled, a health summary (JSON) is periodically sent as UDP datagram
+  # to the configured server, e.g. for monitoring by a network management
+  # system (NMS) which does not support Prometheus or MQTT. Example:
+  #
+  # {
+  #   "gateway_id": "0101010101010101",
+  #   "border_gateway": true,
+  #   "relays_online": 3,
+  #   "relays_total": 4,
+  #   "last_uplink_at": "2024-01-01T12:00:00Z",
+  #   "event_queue_length": 0
+  # }
+  #
+  # The relays_online and relays_total fields are only set on the Border
+  # Gateway. A Relay Gateway is considered online when a heartbeat has been
+  # received since startup and it did not announce its shutdown.
+  [integrations.health_beacon]
+
+    # Server.
+    #
+    # If set, this enables the health beacon. Example: 192.168.1.10:9999
+    server="{{ integrations.health_beacon.server }}"
+
+    # Interval.
+    #
+    # The interval in which the health beacon is sent.
+    interval="{{ integrations.health_beacon.interval }}"
+
+
+# Site profiles.
+#
+# Profiles bundle settings (e.g. keys, frequencies, mappings and filters)
+# for a site, and are applied on top of the configuration when started with
+# --profile NAME. A profile can include other profiles, which are applied
+# first. Profiles are not part of the rendered configuration, the configfile
+# subcommand renders the configuration with the profile applied. Example:
+#
+# [profiles.eu868]
+#   mesh.frequencies=[868100000, 868300000, 868500000]
+#
+# [profiles.farm-a]
+#   include=["eu868"]
+#   mesh.signing_key="0102030405060708090a0b0c0d0e0f10"
+#   mesh.filters.dev_addr_prefixes=["01000000/8"]
 "#;
 
     let conf = config::get();
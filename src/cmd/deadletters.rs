@@ -0,0 +1,23 @@
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+
+use crate::{config, deadletter};
+
+pub fn run() -> Result<()> {
+    let conf = config::get();
+    if conf.dead_letter.file.is_empty() {
+        return Err(anyhow!("dead_letter.file is not configured"));
+    }
+
+    for entry in deadletter::read(&conf.dead_letter.file)? {
+        println!(
+            "{} reason: {}, error: {}, phy_payload: {}",
+            humantime::format_rfc3339(entry.time),
+            entry.reason,
+            entry.error,
+            hex::encode(&entry.phy_payload),
+        );
+    }
+
+    Ok(())
+}
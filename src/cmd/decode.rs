@@ -0,0 +1,26 @@
+use anyhow::Result;
+
+use crate::aes128::Aes128Key;
+use crate::config;
+use crate::packets::MeshPacket;
+
+pub fn run(phy_payload: &str, key: Option<Aes128Key>) -> Result<()> {
+    let conf = config::get();
+    let b = hex::decode(phy_payload.trim())?;
+    let packet = MeshPacket::from_slice(&b)?;
+
+    println!("{}", packet);
+    println!("{:#?}", packet);
+
+    // Without key argument, the configured signing key of the key index is used.
+    let mic = match key.or_else(|| conf.mesh.get_signing_key(packet.mhdr.key_index)) {
+        Some(key) => match packet.validate_mic(key)? {
+            true => "valid",
+            false => "invalid",
+        },
+        None => "unknown (no signing key for key index)",
+    };
+    println!("MIC: {}", mic);
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+
+use crate::{config, linkquality};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Prints the link-quality history of the past 24 hours, as recorded by the running Border
+// Gateway. This is retrieved from the monitoring endpoint, as the history is kept in memory.
+pub fn run(relay_id: &Option<String>) -> Result<()> {
+    let conf = config::get();
+    if conf.monitoring.bind.is_empty() {
+        return Err(anyhow!("monitoring.bind is not configured"));
+    }
+
+    let mut addr: SocketAddr = conf.monitoring.bind.parse()?;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(Ipv4Addr::LOCALHOST.into());
+    }
+
+    let path = match relay_id {
+        Some(v) => format!("/link_quality/{}", v),
+        None => "/link_quality".to_string(),
+    };
+
+    let body = get(addr, &path)?;
+    let samples: BTreeMap<String, Vec<linkquality::Sample>> = serde_json::from_slice(&body)?;
+
+    for (relay_id, samples) in &samples {
+        for sample in samples {
+            println!(
+                "{} relay_id: {}, hop_count: {}, rssi: {}, snr: {:.1}",
+                humantime::format_rfc3339(sample.time),
+                relay_id,
+                sample.hop_count,
+                sample.rssi,
+                sample.snr,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn get(addr: SocketAddr, path: &str) -> Result<Vec<u8>> {
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, addr
+    )?;
+
+    let mut resp = Vec::new();
+    stream.read_to_end(&mut resp)?;
+
+    let pos = resp
+        .windows(4)
+        .position(|v| v == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("Invalid HTTP response"))?;
+    let (head, body) = (&resp[..pos], &resp[pos + 4..]);
+
+    let status_line = String::from_utf8_lossy(head.split(|b| *b == b'\n').next().unwrap_or(&[]));
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(anyhow!(
+            "Request failed, status: {}, body: {}",
+            status_line.trim(),
+            String::from_utf8_lossy(body)
+        ));
+    }
+
+    Ok(body.to_vec())
+}
@@ -0,0 +1,19 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+
+use crate::config;
+
+pub fn run(filenames: &[String]) -> Result<()> {
+    if filenames.len() != 1 {
+        return Err(anyhow!("Exactly one configuration file must be given"));
+    }
+
+    let mut doc: toml_edit::DocumentMut = fs::read_to_string(&filenames[0])?.parse()?;
+    for name in config::migrate(&mut doc)? {
+        eprintln!("Migrated {}", name);
+    }
+
+    print!("{}", doc);
+    Ok(())
+}
@@ -1,2 +1,12 @@
+pub mod capacity;
 pub mod configfile;
+pub mod deadletters;
+pub mod decode;
+pub mod linkquality;
+pub mod migrateconfig;
+pub mod printkeys;
 pub mod root;
+pub mod simulate;
+pub mod topology;
+pub mod ucitotoml;
+pub mod vectors;
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::config;
+
+pub fn run() -> Result<()> {
+    let conf = config::get();
+
+    let mut key_indices: Vec<u8> = vec![0];
+    key_indices.extend(conf.mesh.signing_keys.iter().map(|v| v.key_index));
+    key_indices.sort_unstable();
+    key_indices.dedup();
+
+    for key_index in key_indices {
+        let key = match conf.mesh.get_signing_key(key_index) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        // The fingerprint makes it possible to compare keys without sharing them.
+        let fingerprint = Sha256::digest(key.to_bytes());
+
+        println!(
+            "key_index: {}, key: {}, fingerprint: {}{}",
+            key_index,
+            key,
+            hex::encode(&fingerprint[0..4]),
+            if key_index == conf.mesh.signing_key_index {
+                " (active)"
+            } else {
+                ""
+            },
+        );
+    }
+
+    Ok(())
+}
@@ -1,21 +1,64 @@
 use anyhow::Result;
 use futures::stream::StreamExt;
+use log::error;
 use signal_hook::consts::signal::*;
 use signal_hook_tokio::Signals;
 
 use crate::config::Configuration;
-use crate::{backend, heartbeat, proxy};
+use crate::{
+    backend, beacon, challenge, deadletter, events, heartbeat, hopcount, location, monitoring,
+    mqtt, proxy, supervisor, tdma, topology, uplinkcontext, watchdog, webhook,
+};
 
 pub async fn run(conf: &Configuration) -> Result<()> {
+    deadletter::setup(conf)?;
+    topology::setup(conf).await?;
+    uplinkcontext::setup(conf).await?;
+    monitoring::setup(conf).await?;
+    watchdog::setup(conf)?;
+    mqtt::setup(conf).await?;
     proxy::setup(conf).await?;
     backend::setup(conf).await?;
     heartbeat::setup(conf).await?;
+    location::setup(conf).await?;
+    events::setup(conf).await?;
+    tdma::setup(conf).await?;
+    hopcount::setup(conf).await?;
+    challenge::setup(conf).await?;
+    webhook::setup(conf).await?;
+    beacon::setup(conf).await?;
 
-    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGUSR1])?;
     let handle = signals.handle();
 
-    let _ = signals.next().await;
+    let res = loop {
+        tokio::select! {
+            signal = signals.next() => {
+                if signal == Some(SIGUSR1) {
+                    events::request_attestation();
+                    continue;
+                }
+
+                break Ok(());
+            }
+            // Exit on a stopped backend or proxy thread, or on a watchdog error, such that the
+            // service manager can restart the service.
+            e = supervisor::wait() => break Err(e),
+        }
+    };
     handle.close();
 
-    Ok(())
+    if res.is_ok() {
+        if let Err(e) = events::report_shutdown(conf).await {
+            error!("Report shutdown error, error: {}", e);
+        }
+        if let Err(e) = events::mark_clean_shutdown(conf) {
+            error!("Mark clean shutdown error, error: {}", e);
+        }
+    }
+
+    topology::persist()?;
+    uplinkcontext::persist()?;
+
+    res
 }
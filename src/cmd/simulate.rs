@@ -0,0 +1,241 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use rand::random;
+
+use crate::config::{self, Configuration};
+use crate::{heartbeat, helpers, mesh, packets};
+
+// Interval in which the simulation counters are printed.
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+// Gateway ID reported by the simulated Mesh Concentratord.
+const GATEWAY_ID: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+#[derive(Default)]
+struct Counters {
+    uplinks: AtomicUsize,
+    heartbeats: AtomicUsize,
+    downlinks: AtomicUsize,
+}
+
+// Simulates the given number of Relay Gateways, by acting as the Mesh Concentratord of the Border
+// Gateway. The Border Gateway must be started separately, using the same configuration.
+pub fn run(relays: u32, uplink_interval: Duration, heartbeat_interval: Duration) -> Result<()> {
+    let conf = config::get();
+
+    let zmq_ctx = zmq::Context::new();
+    let event_sock = zmq_ctx.socket(zmq::PUB)?;
+    event_sock.bind(&conf.backend.mesh_concentratord.event_url)?;
+    let command_sock = zmq_ctx.socket(zmq::REP)?;
+    command_sock.bind(&conf.backend.mesh_concentratord.command_url)?;
+
+    println!(
+        "Simulating Relay Gateways, relays: {}, uplink_interval: {:?}, heartbeat_interval: {:?}, event_url: {}, command_url: {}",
+        relays,
+        uplink_interval,
+        heartbeat_interval,
+        conf.backend.mesh_concentratord.event_url,
+        conf.backend.mesh_concentratord.command_url
+    );
+
+    let counters = Arc::new(Counters::default());
+
+    thread::spawn({
+        let counters = counters.clone();
+        move || {
+            if let Err(e) = command_loop(command_sock, &counters) {
+                eprintln!("Command loop error, error: {}", e);
+            }
+        }
+    });
+
+    // The uplinks and heartbeats of the relays are spread evenly over the interval.
+    let uplink_interval = uplink_interval / relays.max(1);
+    let heartbeat_interval = heartbeat_interval / relays.max(1);
+    let mut next_uplink = Instant::now();
+    let mut next_heartbeat = Instant::now();
+    let mut next_report = Instant::now() + REPORT_INTERVAL;
+    let mut uplink_relay: u32 = 0;
+    let mut heartbeat_relay: u32 = 0;
+
+    loop {
+        let now = Instant::now();
+
+        if !uplink_interval.is_zero() && now >= next_uplink {
+            let packet = get_uplink_packet(&conf, get_relay_id(uplink_relay))?;
+            send_event(&event_sock, &conf, &packet)?;
+            counters.uplinks.fetch_add(1, Ordering::Relaxed);
+            uplink_relay = (uplink_relay + 1) % relays;
+            next_uplink += uplink_interval;
+        }
+
+        if !heartbeat_interval.is_zero() && now >= next_heartbeat {
+            let packet = get_heartbeat_packet(&conf, get_relay_id(heartbeat_relay))?;
+            send_event(&event_sock, &conf, &packet)?;
+            counters.heartbeats.fetch_add(1, Ordering::Relaxed);
+            heartbeat_relay = (heartbeat_relay + 1) % relays;
+            next_heartbeat += heartbeat_interval;
+        }
+
+        if now >= next_report {
+            println!(
+                "uplinks: {}, heartbeats: {}, downlinks: {}",
+                counters.uplinks.load(Ordering::Relaxed),
+                counters.heartbeats.load(Ordering::Relaxed),
+                counters.downlinks.load(Ordering::Relaxed)
+            );
+            next_report += REPORT_INTERVAL;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// Handles the commands of the Border Gateway. Downlinks are acknowledged as if they were
+// transmitted.
+fn command_loop(sock: zmq::Socket, counters: &Counters) -> Result<()> {
+    loop {
+        let msg = sock.recv_multipart(0)?;
+        let cmd = msg
+            .first()
+            .map(|v| String::from_utf8_lossy(v).to_string())
+            .unwrap_or_default();
+
+        let resp = match cmd.as_str() {
+            "gateway_id" => GATEWAY_ID.to_vec(),
+            "down" => {
+                let pl =
+                    gw::DownlinkFrame::decode(msg.get(1).map(|v| v.as_slice()).unwrap_or(&[]))?;
+                counters.downlinks.fetch_add(1, Ordering::Relaxed);
+
+                gw::DownlinkTxAck {
+                    downlink_id: pl.downlink_id,
+                    items: pl
+                        .items
+                        .iter()
+                        .map(|_| gw::DownlinkTxAckItem {
+                            status: gw::TxAckStatus::Ok.into(),
+                        })
+                        .collect(),
+                    ..Default::default()
+                }
+                .encode_to_vec()
+            }
+            _ => vec![],
+        };
+
+        sock.send(resp, 0)?;
+    }
+}
+
+fn send_event(
+    sock: &zmq::Socket,
+    conf: &Configuration,
+    packet: &packets::MeshPacket,
+) -> Result<()> {
+    let pl = gw::UplinkFrame {
+        phy_payload: packet.to_vec()?,
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: mesh::get_mesh_frequency(conf)?,
+            modulation: Some(helpers::data_rate_to_gw_modulation(
+                &conf.mesh.data_rate,
+                false,
+            )),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: hex::encode(GATEWAY_ID),
+            uplink_id: random(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -120 + (random::<u8>() % 60) as i32,
+            snr: -10.0 + (random::<u8>() % 20) as f32,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    sock.send_multipart([b"up".to_vec(), pl.encode_to_vec()], 0)?;
+    Ok(())
+}
+
+fn get_relay_id(i: u32) -> [u8; 4] {
+    (i + 1).to_be_bytes()
+}
+
+fn get_uplink_packet(conf: &Configuration, relay_id: [u8; 4]) -> Result<packets::MeshPacket> {
+    let mut dev_addr: [u8; 4] = random();
+    // Unconfirmed data up, with an empty FRMPayload.
+    let mut phy_payload = vec![0x40];
+    dev_addr.reverse();
+    phy_payload.extend_from_slice(&dev_addr);
+    phy_payload.extend_from_slice(&[0, 0, 0]);
+    phy_payload.extend_from_slice(&random::<[u8; 4]>());
+    let (timestamp, timestamp_monotonic) = heartbeat::get_uplink_timestamp(conf);
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Uplink,
+            hop_count: 1 + random::<u8>() % conf.mesh.max_hop_count.max(1),
+            key_index: conf.mesh.signing_key_index,
+        },
+        payload: packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id: random::<u16>() & 0x0fff,
+                // The data-rate index is encoded using 4 bits.
+                dr: random_index(conf.mappings.data_rates.len().min(16)),
+                rssi: -120 + (random::<u8>() % 60) as i16,
+                snr: -10 + (random::<u8>() % 20) as i8,
+                channel: random_index(conf.mappings.channels.len()),
+                border_id: 0,
+            },
+            relay_id,
+            timestamp,
+            timestamp_monotonic,
+            phy_payload,
+        }),
+        mic: None,
+    };
+    helpers::set_mic(conf, &mut packet)?;
+
+    Ok(packet)
+}
+
+fn get_heartbeat_packet(conf: &Configuration, relay_id: [u8; 4]) -> Result<packets::MeshPacket> {
+    let (timestamp, timestamp_monotonic) = heartbeat::get_heartbeat_timestamp(conf);
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Heartbeat,
+            hop_count: 1,
+            key_index: conf.mesh.signing_key_index,
+        },
+        payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+            timestamp,
+            timestamp_monotonic,
+            relay_id,
+            relay_path: vec![],
+            config_checksum: if conf.mesh.heartbeat_config_checksum {
+                Some(heartbeat::get_config_checksum(conf)?)
+            } else {
+                None
+            },
+        }),
+        mic: None,
+    };
+    helpers::set_mic(conf, &mut packet)?;
+
+    Ok(packet)
+}
+
+fn random_index(len: usize) -> u8 {
+    if len == 0 {
+        return 0;
+    }
+
+    (random::<usize>() % len.min(256)) as u8
+}
@@ -0,0 +1,27 @@
+use anyhow::{anyhow, Result};
+
+use crate::{config, topology};
+
+pub fn run(format: &str) -> Result<()> {
+    let conf = config::get();
+    if conf.topology.state_file.is_empty() {
+        return Err(anyhow!("topology.state_file is not configured"));
+    }
+
+    let relays = topology::read(&conf.topology.state_file)?;
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&relays)?),
+        "dot" => print!("{}", topology::to_dot(&relays)),
+        "geojson" => println!(
+            "{}",
+            serde_json::to_string_pretty(&topology::to_geojson(
+                &relays,
+                &conf.topology.relay_locations
+            ))?
+        ),
+        _ => return Err(anyhow!("Unsupported format: {}", format)),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,11 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::uci;
+
+pub fn run(filename: &str) -> Result<()> {
+    let content = fs::read_to_string(filename)?;
+    print!("{}", uci::to_toml(&content)?);
+    Ok(())
+}
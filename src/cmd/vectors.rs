@@ -0,0 +1,169 @@
+use anyhow::Result;
+
+use crate::aes128::Aes128Key;
+use crate::config;
+use crate::packets;
+
+// Prints known-answer test vectors, signed with each configured signing key, such that
+// implementations of the mesh protocol can be validated against this implementation.
+pub fn run() -> Result<()> {
+    let conf = config::get();
+
+    let mut key_indices: Vec<u8> = vec![0];
+    key_indices.extend(conf.mesh.signing_keys.iter().map(|v| v.key_index));
+    key_indices.sort_unstable();
+    key_indices.dedup();
+
+    let mut vectors = vec![];
+    for key_index in key_indices {
+        let key = match conf.mesh.get_signing_key(key_index) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        for (name, packet) in get_packets(key_index) {
+            vectors.push(get_vector(name, key_index, key, packet)?);
+        }
+    }
+
+    println!("{}", serde_json::to_string_pretty(&vectors)?);
+
+    Ok(())
+}
+
+fn get_vector(
+    name: &str,
+    key_index: u8,
+    key: Aes128Key,
+    mut packet: packets::MeshPacket,
+) -> Result<serde_json::Value> {
+    packet.set_mic(key)?;
+    let b = packet.to_vec()?;
+
+    Ok(serde_json::json!({
+        "name": name,
+        "key_index": key_index,
+        "key": key.to_string(),
+        "packet": packet.to_string(),
+        "phy_payload": hex::encode(b),
+        "mic": hex::encode(packet.mic.unwrap_or_default()),
+    }))
+}
+
+fn get_packets(key_index: u8) -> Vec<(&'static str, packets::MeshPacket)> {
+    vec![
+        (
+            "uplink",
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Uplink,
+                    hop_count: 1,
+                    key_index,
+                },
+                payload: packets::Payload::Uplink(packets::UplinkPayload {
+                    metadata: packets::UplinkMetadata {
+                        uplink_id: 1024,
+                        dr: 3,
+                        rssi: -120,
+                        snr: -12,
+                        channel: 2,
+                        border_id: 0,
+                    },
+                    relay_id: [1, 2, 3, 4],
+                    timestamp: None,
+                    timestamp_monotonic: false,
+                    phy_payload: vec![0x40, 1, 2, 3, 4, 0, 0, 0],
+                }),
+                mic: None,
+            },
+        ),
+        (
+            "uplink_timestamp",
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Uplink,
+                    hop_count: 1,
+                    key_index,
+                },
+                payload: packets::Payload::Uplink(packets::UplinkPayload {
+                    metadata: packets::UplinkMetadata {
+                        uplink_id: 1024,
+                        dr: 3,
+                        rssi: -120,
+                        snr: -12,
+                        channel: 2,
+                        border_id: 0,
+                    },
+                    relay_id: [1, 2, 3, 4],
+                    timestamp: Some(1_700_000_000_123),
+                    timestamp_monotonic: false,
+                    phy_payload: vec![0x40, 1, 2, 3, 4, 0, 0, 0],
+                }),
+                mic: None,
+            },
+        ),
+        (
+            "downlink",
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Downlink,
+                    hop_count: 1,
+                    key_index,
+                },
+                payload: packets::Payload::Downlink(packets::DownlinkPayload {
+                    metadata: packets::DownlinkMetadata {
+                        uplink_id: 1024,
+                        dr: 3,
+                        frequency: 868100000,
+                        tx_power: 1,
+                        delay: 1,
+                    },
+                    relay_id: [1, 2, 3, 4],
+                    phy_payload: vec![0x60, 1, 2, 3, 4, 0, 0, 0],
+                }),
+                mic: None,
+            },
+        ),
+        (
+            "heartbeat",
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Heartbeat,
+                    hop_count: 2,
+                    key_index,
+                },
+                payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+                    timestamp: 1_700_000_000_000,
+                    timestamp_monotonic: false,
+                    relay_id: [1, 2, 3, 4],
+                    relay_path: vec![packets::RelayPath {
+                        relay_id: [5, 6, 7, 8],
+                        rssi: -100,
+                        snr: 5,
+                    }],
+                    config_checksum: None,
+                }),
+                mic: None,
+            },
+        ),
+        (
+            "event",
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Event,
+                    hop_count: 1,
+                    key_index,
+                },
+                payload: packets::Payload::Event(packets::EventPayload {
+                    timestamp: 1_700_000_000_000,
+                    timestamp_monotonic: false,
+                    relay_id: [1, 2, 3, 4],
+                    events: vec![packets::Event::TxAirtime(packets::TxAirtimeEvent {
+                        airtime: 1000,
+                    })],
+                }),
+                mic: None,
+            },
+        ),
+    ]
+}
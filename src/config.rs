@@ -1,136 +1,1510 @@
+use std::collections::HashMap;
 use std::fs;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
 use once_cell::sync::OnceCell;
-use serde::de::Error;
+use serde::de;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::aes128::Aes128Key;
+use crate::error::{Error, Result};
+use crate::{helpers, mqtt, packets, webhook};
 
 static CONFIG: OnceCell<Mutex<Arc<Configuration>>> = OnceCell::new();
 
+// Legacy names that were migrated when loading the configuration.
+static MIGRATED_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Legacy (ChirpStack Gateway Relay) section and key names, and their current names. Without
+// migration, these would silently deserialize to the defaults.
+const LEGACY_NAMES: [(&str, &str); 2] = [
+    ("relay", "mesh"),
+    ("backend.relay_concentratord", "backend.mesh_concentratord"),
+];
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Configuration {
+    pub logging: Logging,
+    pub mesh: Mesh,
+    pub backend: Backend,
+    pub mappings: Mappings,
+    pub monitoring: Monitoring,
+    pub watchdog: Watchdog,
+    pub topology: Topology,
+    pub uplink_context: UplinkContext,
+    pub dead_letter: DeadLetter,
+    pub webhook: Webhook,
+    pub integrations: Integrations,
+}
+
+impl Configuration {
+    pub fn load(filenames: &[String], profile: Option<&str>) -> Result<()> {
+        let mut content = String::new();
+        for file_name in filenames {
+            content.push_str(&fs::read_to_string(file_name)?);
+        }
+
+        let mut doc: toml_edit::DocumentMut = content.parse()?;
+        *MIGRATED_NAMES.lock().unwrap() = migrate(&mut doc)?;
+
+        let mut table: toml::Table = toml::from_str(&doc.to_string())?;
+        let profiles = match table.remove("profiles") {
+            Some(toml::Value::Table(v)) => v,
+            Some(_) => return Err(Error::Config("profiles must be a table".into())),
+            None => toml::Table::new(),
+        };
+        if let Some(profile) = profile {
+            merge(
+                &mut table,
+                resolve_profile(&profiles, profile, &mut vec![])?,
+            );
+        }
+
+        let conf: Configuration = toml::Value::Table(table).try_into()?;
+        conf.validate()?;
+        set(conf)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.mesh.validate()?;
+        self.watchdog.validate()?;
+        self.topology.validate()?;
+        self.webhook.validate()?;
+        self.integrations.mqtt.validate()?;
+        self.integrations.health_beacon.validate()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Logging {
+    pub level: String,
+    pub log_to_syslog: bool,
+    pub file: String,
+    pub file_max_size: u64,
+    pub file_max_files: usize,
+    pub sampling: LogSampling,
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Logging {
+            level: "info".into(),
+            log_to_syslog: false,
+            file: "".into(),
+            file_max_size: 10 * 1024 * 1024,
+            file_max_files: 5,
+            sampling: LogSampling::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSampling {
+    pub uplink: u64,
+    pub downlink: u64,
+    pub mesh: u64,
+}
+
+impl Default for LogSampling {
+    fn default() -> Self {
+        LogSampling {
+            uplink: 1,
+            downlink: 1,
+            mesh: 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mesh {
+    pub signing_key: Aes128Key,
+    pub signing_key_index: u8,
+    pub signing_keys: Vec<SigningKey>,
+    pub packet_format_version: u8,
+    #[serde(with = "humantime_serde")]
+    pub heartbeat_interval: Duration,
+    pub heartbeat_suppress_if_active: bool,
+    #[serde(with = "humantime_serde")]
+    pub heartbeat_relay_interval: Duration,
+    pub heartbeat_relay_path_max_length: usize,
+    pub heartbeat_config_checksum: bool,
+    pub frequencies: Vec<u32>,
+    pub data_rate: DataRate,
+    pub tx_power: i32,
+    pub rssi_offset: i32,
+    pub proxy_api: ProxyApi,
+    pub filters: Filters,
+    pub border_gateway: bool,
+    pub border_gateway_ignore_direct_uplinks: bool,
+    pub passive: bool,
+    pub max_hop_count: u8,
+    pub bad_channel_avoidance: BadChannelAvoidance,
+    pub join_requests: JoinRequests,
+    pub attestation: Attestation,
+    pub boot_announcement: BootAnnouncement,
+    pub location: Location,
+    pub telemetry: Telemetry,
+    pub uplink_ack: UplinkAck,
+    pub downlink_ack: DownlinkAck,
+    pub relay_downlink_guard: RelayDownlinkGuard,
+    pub downlink_rate_limit: DownlinkRateLimit,
+    pub path_stickiness: PathStickiness,
+    pub replay_protection: ReplayProtection,
+    pub routing: Routing,
+    pub tdma: Tdma,
+    pub adaptive_max_hop_count: AdaptiveMaxHopCount,
+    pub challenge: Challenge,
+    pub unicast_downlinks: UnicastDownlinks,
+    pub duty_cycle: DutyCycle,
+    pub frequency_selection: FrequencySelection,
+    #[serde(with = "humantime_serde")]
+    pub tx_airtime_report_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub stats_report_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub uplink_dedup_window: Duration,
+    #[serde(with = "humantime_serde")]
+    pub relay_jitter: Duration,
+    pub border_id: u8,
+    pub border_routes: Vec<BorderRoute>,
+    pub relay_groups: Vec<RelayGroup>,
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    pub max_payload_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub max_airtime: Duration,
+    pub fallback_data_rates: Vec<DataRate>,
+}
+
+impl Default for Mesh {
+    fn default() -> Self {
+        Mesh {
+            signing_key: Aes128Key::null(),
+            signing_key_index: 0,
+            signing_keys: vec![],
+            packet_format_version: 1,
+            heartbeat_interval: Duration::from_secs(300),
+            heartbeat_suppress_if_active: false,
+            heartbeat_relay_interval: Duration::ZERO,
+            heartbeat_relay_path_max_length: 0,
+            heartbeat_config_checksum: false,
+            frequencies: vec![868100000, 868300000, 868500000],
+            data_rate: DataRate {
+                modulation: Modulation::LORA,
+                spreading_factor: 7,
+                bandwidth: 125000,
+                code_rate: Some(CodeRate::Cr45),
+                bitrate: 0,
+            },
+            tx_power: 16,
+            rssi_offset: 0,
+            proxy_api: ProxyApi::default(),
+            filters: Filters::default(),
+            border_gateway: false,
+            border_gateway_ignore_direct_uplinks: false,
+            passive: false,
+            max_hop_count: 1,
+            bad_channel_avoidance: BadChannelAvoidance::default(),
+            join_requests: JoinRequests::default(),
+            attestation: Attestation::default(),
+            boot_announcement: BootAnnouncement::default(),
+            location: Location::default(),
+            telemetry: Telemetry::default(),
+            uplink_ack: UplinkAck::default(),
+            downlink_ack: DownlinkAck::default(),
+            relay_downlink_guard: RelayDownlinkGuard::default(),
+            downlink_rate_limit: DownlinkRateLimit::default(),
+            path_stickiness: PathStickiness::default(),
+            replay_protection: ReplayProtection::default(),
+            routing: Routing::default(),
+            tdma: Tdma::default(),
+            adaptive_max_hop_count: AdaptiveMaxHopCount::default(),
+            challenge: Challenge::default(),
+            unicast_downlinks: UnicastDownlinks::default(),
+            duty_cycle: DutyCycle::default(),
+            frequency_selection: FrequencySelection::default(),
+            tx_airtime_report_interval: Duration::ZERO,
+            stats_report_interval: Duration::ZERO,
+            uplink_dedup_window: Duration::ZERO,
+            relay_jitter: Duration::ZERO,
+            border_id: 0,
+            border_routes: vec![],
+            relay_groups: vec![],
+            maintenance_windows: vec![],
+            max_payload_size: 255,
+            max_airtime: Duration::ZERO,
+            fallback_data_rates: vec![],
+        }
+    }
+}
+
+impl Mesh {
+    fn validate(&self) -> Result<()> {
+        if self.border_id > 3 {
+            return Err(Error::Config(
+                "mesh.border_id must be between 0 and 3".into(),
+            ));
+        }
+
+        if self.max_payload_size > 255 {
+            return Err(Error::Config(
+                "mesh.max_payload_size must be at most 255".into(),
+            ));
+        }
+
+        self.location.validate()?;
+        if self.location.source != LocationSource::None && self.stats_report_interval.is_zero() {
+            return Err(Error::Config(
+                "mesh.location requires mesh.stats_report_interval to be set".into(),
+            ));
+        }
+        self.telemetry.validate()?;
+
+        if !(1..=2).contains(&self.packet_format_version) {
+            return Err(Error::Config(
+                "mesh.packet_format_version must be 1 or 2".into(),
+            ));
+        }
+
+        for (i, k) in self.signing_keys.iter().enumerate() {
+            if k.key_index == 0 {
+                return Err(Error::Config(
+                    "mesh.signing_keys.key_index 0 is reserved for mesh.signing_key".into(),
+                ));
+            }
+
+            if k.key_index > packets::MAX_KEY_INDEX {
+                return Err(Error::Config(format!(
+                    "mesh.signing_keys.key_index must be at most {}",
+                    packets::MAX_KEY_INDEX
+                )));
+            }
+
+            if self.signing_keys[..i]
+                .iter()
+                .any(|v| v.key_index == k.key_index)
+            {
+                return Err(Error::Config(format!(
+                    "mesh.signing_keys.key_index {} is configured more than once",
+                    k.key_index
+                )));
+            }
+        }
+
+        if self.get_signing_key(self.signing_key_index).is_none() {
+            return Err(Error::Config(format!(
+                "mesh.signing_key_index {} does not refer to a configured signing key",
+                self.signing_key_index
+            )));
+        }
+
+        // The key index is only encoded in version 2 packets.
+        if self.signing_key_index != 0 && self.packet_format_version < 2 {
+            return Err(Error::Config(
+                "mesh.signing_key_index other than 0 requires mesh.packet_format_version 2".into(),
+            ));
+        }
+
+        // Uplinks only contain a timestamp in version 2 packets.
+        if self.replay_protection.enabled && self.packet_format_version < 2 {
+            return Err(Error::Config(
+                "mesh.replay_protection requires mesh.packet_format_version 2".into(),
+            ));
+        }
+
+        if self.downlink_rate_limit.enabled
+            && (self.downlink_rate_limit.max_downlinks == 0
+                || self.downlink_rate_limit.interval.is_zero())
+        {
+            return Err(Error::Config(
+                "mesh.downlink_rate_limit.max_downlinks and interval must be greater than 0".into(),
+            ));
+        }
+
+        if self.downlink_ack.enabled && self.downlink_ack.window == 0 {
+            return Err(Error::Config(
+                "mesh.downlink_ack.window must be greater than 0".into(),
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.downlink_ack.alert_threshold) {
+            return Err(Error::Config(
+                "mesh.downlink_ack.alert_threshold must be between 0 and 1".into(),
+            ));
+        }
+
+        if self
+            .duty_cycle
+            .bands
+            .iter()
+            .any(|v| v.min_frequency >= v.max_frequency)
+        {
+            return Err(Error::Config(
+                "mesh.duty_cycle.bands.min_frequency must be less than max_frequency".into(),
+            ));
+        }
+
+        if self
+            .duty_cycle
+            .bands
+            .iter()
+            .any(|v| v.duty_cycle <= 0.0 || v.duty_cycle > 100.0)
+        {
+            return Err(Error::Config(
+                "mesh.duty_cycle.bands.duty_cycle must be between 0 and 100".into(),
+            ));
+        }
+
+        if self.border_routes.iter().any(|v| v.border_id > 3) {
+            return Err(Error::Config(
+                "mesh.border_routes.border_id must be between 0 and 3".into(),
+            ));
+        }
+
+        for (i, g) in self.relay_groups.iter().enumerate() {
+            if g.name.is_empty() {
+                return Err(Error::Config("mesh.relay_groups.name must be set".into()));
+            }
+
+            if self.relay_groups[..i].iter().any(|v| v.name == g.name) {
+                return Err(Error::Config(format!(
+                    "mesh.relay_groups.name {} is configured more than once",
+                    g.name
+                )));
+            }
+
+            for payload_type in &g.payload_types {
+                if !["uplink", "heartbeat", "event"].contains(&payload_type.as_str()) {
+                    return Err(Error::Config(format!(
+                        "mesh.relay_groups.payload_types contains unknown payload type: {}",
+                        payload_type
+                    )));
+                }
+            }
+
+            if g.rate_limit_packets != 0 && g.rate_limit_interval.is_zero() {
+                return Err(Error::Config(
+                    "mesh.relay_groups.rate_limit_interval must be greater than 0".into(),
+                ));
+            }
+        }
+
+        for w in &self.maintenance_windows {
+            if parse_time_of_day(&w.start).is_none() {
+                return Err(Error::Config(format!(
+                    "mesh.maintenance_windows.start must be formatted as HH:MM, got: {}",
+                    w.start
+                )));
+            }
+
+            if w.duration.is_zero() || w.duration > Duration::from_secs(86400) {
+                return Err(Error::Config(
+                    "mesh.maintenance_windows.duration must be between 1s and 24h".into(),
+                ));
+            }
+        }
+
+        if self.tdma.enabled
+            && (self.tdma.slot_duration.is_zero()
+                || self.tdma.slot_duration > Duration::from_millis(u16::MAX.into()))
+        {
+            return Err(Error::Config(format!(
+                "mesh.tdma.slot_duration must be between 1ms and {:?}",
+                Duration::from_millis(u16::MAX.into())
+            )));
+        }
+
+        if self.adaptive_max_hop_count.enabled {
+            if self.adaptive_max_hop_count.margin == 0 {
+                return Err(Error::Config(
+                    "mesh.adaptive_max_hop_count.margin must be at least 1".into(),
+                ));
+            }
+
+            if self.adaptive_max_hop_count.advertisement_interval.is_zero() {
+                return Err(Error::Config(
+                    "mesh.adaptive_max_hop_count.advertisement_interval must be greater than 0"
+                        .into(),
+                ));
+            }
+        }
+
+        if self.challenge.enabled && self.challenge.interval.is_zero() {
+            return Err(Error::Config(
+                "mesh.challenge.interval must be greater than 0".into(),
+            ));
+        }
+
+        // Border Gateways do not send heartbeats.
+        if !self.border_gateway && !self.heartbeat_interval.is_zero() {
+            let mut max_relay_path_len = usize::from(self.max_hop_count.saturating_sub(1));
+            if self.heartbeat_relay_path_max_length != 0 {
+                max_relay_path_len = max_relay_path_len.min(self.heartbeat_relay_path_max_length);
+            }
+
+            // Version 1: MHDR (1) + timestamp (4) + relay id (4) + config checksum (0 or 4) +
+            // relay path (6 per hop) + MIC (4).
+            // Version 2: MHDR (2) + timestamp (6) + relay id (4) + config checksum (0 or 4) +
+            // relay path (6 per hop) + MIC (4).
+            let mut max_size = match self.packet_format_version {
+                1 => packets::MHDR_SIZE + packets::HEARTBEAT_TIMESTAMP_V1_SIZE,
+                _ => packets::MHDR_SIZE + packets::MHDR_EXTENSION_SIZE + packets::TIMESTAMP_SIZE,
+            } + packets::RELAY_ID_SIZE
+                + packets::RELAY_PATH_SIZE * max_relay_path_len
+                + packets::MIC_SIZE;
+            if self.heartbeat_config_checksum {
+                max_size += packets::HEARTBEAT_CONFIG_CHECKSUM_SIZE;
+            }
+            let airtime = helpers::airtime(&self.data_rate, max_size);
+
+            // Heartbeats may use at most 1% of the airtime.
+            let min_interval = airtime * 100;
+
+            if self.heartbeat_interval < min_interval {
+                return Err(Error::Config(format!(
+                    "mesh.heartbeat_interval ({:?}) is too short, a heartbeat of up to {} bytes (max_hop_count: {}) takes {:?} of airtime using the configured mesh.data_rate, use a heartbeat_interval of at least {:?} (1% duty-cycle) or 0s to disable heartbeats",
+                    self.heartbeat_interval,
+                    max_size,
+                    self.max_hop_count,
+                    airtime,
+                    min_interval,
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the first relay group matching the given Relay ID.
+    pub fn get_relay_group(&self, relay_id: [u8; 4]) -> Option<&RelayGroup> {
+        self.relay_groups
+            .iter()
+            .find(|g| g.relay_id_prefixes.iter().any(|p| p.matches(relay_id)))
+    }
+
+    // Returns the mesh TX power for the given Relay ID, taking the relay group into account.
+    pub fn get_tx_power(&self, relay_id: [u8; 4]) -> i32 {
+        self.get_relay_group(relay_id)
+            .and_then(|g| g.tx_power)
+            .unwrap_or(self.tx_power)
+    }
+
+    // Returns the heartbeat interval for the given Relay ID, taking the relay group into account.
+    pub fn get_heartbeat_interval(&self, relay_id: [u8; 4]) -> Duration {
+        self.get_relay_group(relay_id)
+            .and_then(|g| g.heartbeat_interval)
+            .unwrap_or(self.heartbeat_interval)
+    }
+
+    // Returns true if a maintenance window of the given Relay ID is active at the given time.
+    pub fn in_maintenance(&self, relay_id: [u8; 4], now: SystemTime) -> bool {
+        self.maintenance_windows
+            .iter()
+            .any(|w| w.relay_id_prefixes.iter().any(|p| p.matches(relay_id)) && w.is_active(now))
+    }
+
+    // Returns the signing key for the given key index. Key index 0 refers to the signing_key.
+    pub fn get_signing_key(&self, key_index: u8) -> Option<Aes128Key> {
+        if key_index == 0 {
+            return Some(self.signing_key);
+        }
+
+        self.signing_keys
+            .iter()
+            .find(|v| v.key_index == key_index)
+            .map(|v| v.key)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SigningKey {
+    pub key_index: u8,
+    pub key: Aes128Key,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct BadChannelAvoidance {
+    pub enabled: bool,
+    pub failure_threshold: f32,
+    pub min_samples: u32,
+    #[serde(with = "humantime_serde")]
+    pub probe_interval: Duration,
+}
+
+impl Default for BadChannelAvoidance {
+    fn default() -> Self {
+        BadChannelAvoidance {
+            enabled: false,
+            failure_threshold: 0.5,
+            min_samples: 20,
+            probe_interval: Duration::from_secs(300),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Default)]
 #[serde(default)]
-pub struct Configuration {
-    pub logging: Logging,
-    pub mesh: Mesh,
-    pub backend: Backend,
-    pub mappings: Mappings,
+pub struct JoinRequests {
+    pub relay_only: bool,
+    pub data_rate: Option<DataRate>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Attestation {
+    pub enabled: bool,
+    pub firmware_version: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct BootAnnouncement {
+    pub enabled: bool,
+    pub crash_marker_file: String,
+}
+
+impl Default for BootAnnouncement {
+    fn default() -> Self {
+        BootAnnouncement {
+            enabled: false,
+            crash_marker_file: "/tmp/chirpstack-gateway-mesh.running".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Location {
+    pub source: LocationSource,
+    // Static location (degrees, altitude in meters).
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+    pub gpsd_server: String,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location {
+            source: LocationSource::None,
+            latitude: 0.0,
+            longitude: 0.0,
+            altitude: 0.0,
+            gpsd_server: "127.0.0.1:2947".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationSource {
+    None,
+    Static,
+    Gpsd,
+}
+
+impl Location {
+    fn validate(&self) -> Result<()> {
+        match self.source {
+            LocationSource::None => {}
+            LocationSource::Static => {
+                if !(-90.0..=90.0).contains(&self.latitude)
+                    || !(-180.0..=180.0).contains(&self.longitude)
+                {
+                    return Err(Error::Config(
+                        "mesh.location latitude and longitude must be valid coordinates".into(),
+                    ));
+                }
+
+                if !(f64::from(i16::MIN)..=f64::from(i16::MAX)).contains(&self.altitude) {
+                    return Err(Error::Config(format!(
+                        "mesh.location.altitude must be between {} and {}",
+                        i16::MIN,
+                        i16::MAX
+                    )));
+                }
+            }
+            LocationSource::Gpsd => {
+                if self.gpsd_server.is_empty() {
+                    return Err(Error::Config(
+                        "mesh.location.gpsd_server must be set".into(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Telemetry {
+    #[serde(with = "humantime_serde")]
+    pub report_interval: Duration,
+    // Battery voltage (V).
+    pub battery_voltage: TelemetrySource,
+    // Solar current (A).
+    pub solar_current: TelemetrySource,
+    // Temperature (degrees Celsius).
+    pub temperature: TelemetrySource,
+}
+
+impl Telemetry {
+    fn validate(&self) -> Result<()> {
+        let sources = [
+            ("battery_voltage", &self.battery_voltage),
+            ("solar_current", &self.solar_current),
+            ("temperature", &self.temperature),
+        ];
+
+        for (name, source) in &sources {
+            if !source.path.is_empty() && !source.command.is_empty() {
+                return Err(Error::Config(format!(
+                    "mesh.telemetry.{} path and command can not both be set",
+                    name
+                )));
+            }
+        }
+
+        if !self.report_interval.is_zero() && !sources.iter().any(|(_, v)| v.is_configured()) {
+            return Err(Error::Config(
+                "mesh.telemetry.report_interval is set, but no telemetry source is configured"
+                    .into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+// Telemetry value, read from a file (e.g. sysfs) or the output of a command. The value is
+// multiplied by the scale.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TelemetrySource {
+    pub path: String,
+    pub command: String,
+    pub scale: f64,
+}
+
+impl Default for TelemetrySource {
+    fn default() -> Self {
+        TelemetrySource {
+            path: "".into(),
+            command: "".into(),
+            scale: 1.0,
+        }
+    }
+}
+
+impl TelemetrySource {
+    pub fn is_configured(&self) -> bool {
+        !self.path.is_empty() || !self.command.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct UplinkAck {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    pub max_retransmissions: usize,
+}
+
+impl Default for UplinkAck {
+    fn default() -> Self {
+        UplinkAck {
+            enabled: false,
+            timeout: Duration::from_secs(2),
+            max_retransmissions: 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownlinkAck {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    pub window: usize,
+    pub alert_threshold: f64,
+}
+
+impl Default for DownlinkAck {
+    fn default() -> Self {
+        DownlinkAck {
+            enabled: false,
+            timeout: Duration::from_secs(5),
+            window: 20,
+            alert_threshold: 0.8,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayDownlinkGuard {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub margin: Duration,
+}
+
+impl Default for RelayDownlinkGuard {
+    fn default() -> Self {
+        RelayDownlinkGuard {
+            enabled: false,
+            margin: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownlinkRateLimit {
+    pub enabled: bool,
+    pub max_downlinks: u32,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for DownlinkRateLimit {
+    fn default() -> Self {
+        DownlinkRateLimit {
+            enabled: false,
+            max_downlinks: 10,
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ReplayProtection {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathStickiness {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+}
+
+impl Default for PathStickiness {
+    fn default() -> Self {
+        PathStickiness {
+            enabled: false,
+            ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Routing {
+    pub enabled: bool,
+    pub rssi_margin: i16,
+    #[serde(with = "humantime_serde")]
+    pub max_age: Duration,
+}
+
+impl Default for Routing {
+    fn default() -> Self {
+        Routing {
+            enabled: false,
+            rssi_margin: 6,
+            max_age: Duration::from_secs(900),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tdma {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub slot_duration: Duration,
+    pub free_slots: u8,
+    #[serde(with = "humantime_serde")]
+    pub beacon_interval: Duration,
+}
+
+impl Default for Tdma {
+    fn default() -> Self {
+        Tdma {
+            enabled: false,
+            slot_duration: Duration::from_secs(1),
+            free_slots: 1,
+            beacon_interval: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveMaxHopCount {
+    pub enabled: bool,
+    pub margin: u8,
+    #[serde(with = "humantime_serde")]
+    pub advertisement_interval: Duration,
+}
+
+impl Default for AdaptiveMaxHopCount {
+    fn default() -> Self {
+        AdaptiveMaxHopCount {
+            enabled: false,
+            margin: 1,
+            advertisement_interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Challenge {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for Challenge {
+    fn default() -> Self {
+        Challenge {
+            enabled: false,
+            interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct DutyCycle {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    #[serde(with = "humantime_serde")]
+    pub max_delay: Duration,
+    pub bands: Vec<DutyCycleBand>,
+}
+
+impl Default for DutyCycle {
+    fn default() -> Self {
+        // EU868 (ETSI EN 300 220) sub-bands.
+        let band = |min_frequency, max_frequency, duty_cycle| DutyCycleBand {
+            min_frequency,
+            max_frequency,
+            duty_cycle,
+        };
+
+        DutyCycle {
+            enabled: false,
+            window: Duration::from_secs(3600),
+            max_delay: Duration::ZERO,
+            bands: vec![
+                band(863000000, 865000000, 0.1),
+                band(865000000, 868000000, 1.0),
+                band(868000000, 868600000, 1.0),
+                band(868700000, 869200000, 0.1),
+                band(869400000, 869650000, 10.0),
+                band(869700000, 870000000, 1.0),
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DutyCycleBand {
+    pub min_frequency: u32,
+    pub max_frequency: u32,
+    // Duty-cycle (percentage).
+    pub duty_cycle: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrequencySelection {
+    pub mode: FrequencySelectionMode,
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+}
+
+impl Default for FrequencySelection {
+    fn default() -> Self {
+        FrequencySelection {
+            mode: FrequencySelectionMode::Airtime,
+            window: Duration::from_secs(600),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FrequencySelectionMode {
+    Airtime,
+    RoundRobin,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnicastDownlinks {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub path_timeout: Duration,
+}
+
+impl Default for UnicastDownlinks {
+    fn default() -> Self {
+        UnicastDownlinks {
+            enabled: false,
+            path_timeout: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BorderRoute {
+    pub dev_addr_prefix: lrwn_filters::DevAddrPrefix,
+    pub border_id: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RelayGroup {
+    pub name: String,
+    pub relay_id_prefixes: Vec<RelayIdPrefix>,
+    pub payload_types: Vec<String>,
+    pub rate_limit_packets: u32,
+    #[serde(with = "humantime_serde")]
+    pub rate_limit_interval: Duration,
+    pub tx_power: Option<i32>,
+    #[serde(with = "humantime_serde")]
+    pub heartbeat_interval: Option<Duration>,
+}
+
+impl Default for RelayGroup {
+    fn default() -> Self {
+        RelayGroup {
+            name: "".into(),
+            relay_id_prefixes: vec![],
+            payload_types: vec![],
+            rate_limit_packets: 0,
+            rate_limit_interval: Duration::from_secs(60),
+            tx_power: None,
+            heartbeat_interval: None,
+        }
+    }
+}
+
+impl RelayGroup {
+    // Returns true if the given payload type (e.g. heartbeat) is allowed. All payload types are
+    // allowed if payload_types is empty.
+    pub fn payload_type_allowed(&self, payload_type: &str) -> bool {
+        self.payload_types.is_empty() || self.payload_types.iter().any(|v| v == payload_type)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaintenanceWindow {
+    pub name: String,
+    pub relay_id_prefixes: Vec<RelayIdPrefix>,
+    // Daily start time (UTC), formatted as HH:MM.
+    pub start: String,
+    #[serde(with = "humantime_serde")]
+    pub duration: Duration,
+}
+
+impl Default for MaintenanceWindow {
+    fn default() -> Self {
+        MaintenanceWindow {
+            name: "".into(),
+            relay_id_prefixes: vec![],
+            start: "00:00".into(),
+            duration: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl MaintenanceWindow {
+    // Returns true if the window is active at the given time. A window may extend past midnight.
+    pub fn is_active(&self, now: SystemTime) -> bool {
+        let start = match parse_time_of_day(&self.start) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_secs() % 86400)
+            .unwrap_or_default();
+        let elapsed = (now + 86400 - start) % 86400;
+        elapsed < self.duration.as_secs()
+    }
+}
+
+// Returns the seconds since midnight of the given HH:MM time.
+fn parse_time_of_day(s: &str) -> Option<u64> {
+    let (h, m) = s.split_once(':')?;
+    let (h, m): (u64, u64) = (h.parse().ok()?, m.parse().ok()?);
+    if h > 23 || m > 59 {
+        return None;
+    }
+
+    Some(h * 3600 + m * 60)
+}
+
+// Relay ID prefix, formatted as [RELAY_ID]/[BITS], e.g. 01020000/16.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelayIdPrefix([u8; 4], u32);
+
+impl RelayIdPrefix {
+    pub fn matches(&self, relay_id: [u8; 4]) -> bool {
+        if self.1 == 0 {
+            return true;
+        }
+
+        let shift = 32 - self.1;
+        u32::from_be_bytes(relay_id) >> shift == u32::from_be_bytes(self.0) >> shift
+    }
+}
+
+impl std::fmt::Display for RelayIdPrefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", hex::encode(self.0), self.1)
+    }
+}
+
+impl std::str::FromStr for RelayIdPrefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (relay_id, size) = s.split_once('/').unwrap_or((s, "32"));
+
+        let mut prefix: [u8; 4] = [0; 4];
+        hex::decode_to_slice(relay_id, &mut prefix)
+            .map_err(|e| format!("Invalid relay_id prefix: {}, error: {}", s, e))?;
+        let size: u32 = size
+            .parse()
+            .map_err(|e| format!("Invalid relay_id prefix: {}, error: {}", s, e))?;
+        if size > 32 {
+            return Err(format!(
+                "Invalid relay_id prefix: {}, size must be <= 32",
+                s
+            ));
+        }
+
+        Ok(RelayIdPrefix(prefix, size))
+    }
+}
+
+impl Serialize for RelayIdPrefix {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for RelayIdPrefix {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backend {
+    pub concentratord: Concentratord,
+    pub mesh_concentratord: Concentratord,
+    #[serde(with = "humantime_serde")]
+    pub startup_timeout: Duration,
+    pub command_retries: usize,
+    #[serde(with = "humantime_serde")]
+    pub event_max_age: Duration,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend {
+            concentratord: Concentratord::default(),
+            mesh_concentratord: Concentratord::default(),
+            startup_timeout: Duration::from_secs(60),
+            command_retries: 2,
+            event_max_age: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Concentratord {
+    pub event_url: String,
+    pub command_url: String,
+}
+
+impl Default for Concentratord {
+    fn default() -> Self {
+        Concentratord {
+            event_url: "ipc:///tmp/concentratord_event".into(),
+            command_url: "ipc:///tmp/concentratord_command".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyApi {
+    pub event_bind: String,
+    pub command_bind: String,
+    pub json: bool,
+    pub event_replay_buffer_size: usize,
+    pub event_seq_frame: bool,
+    pub mesh_packet_events: bool,
+    pub mirror_event_bind: String,
+}
+
+impl Default for ProxyApi {
+    fn default() -> Self {
+        ProxyApi {
+            event_bind: "ipc:///tmp/gateway_relay_event".into(),
+            command_bind: "ipc:///tmp/gateway_relay_command".into(),
+            json: false,
+            event_replay_buffer_size: 0,
+            event_seq_frame: false,
+            mesh_packet_events: false,
+            mirror_event_bind: "".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Filters {
+    pub dev_addr_prefixes: Vec<lrwn_filters::DevAddrPrefix>,
+    pub join_eui_prefixes: Vec<lrwn_filters::EuiPrefix>,
+    pub relayed_uplinks: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Monitoring {
+    pub bind: String,
+    pub commands: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watchdog {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub stall_timeout: Duration,
+    pub memory_limit: u64,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Watchdog {
+            enabled: false,
+            interval: Duration::from_secs(5),
+            stall_timeout: Duration::from_secs(60),
+            memory_limit: 0,
+        }
+    }
+}
+
+impl Watchdog {
+    fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if self.interval.is_zero() {
+            return Err(Error::Config(
+                "watchdog.interval must be greater than 0s".into(),
+            ));
+        }
+
+        if self.stall_timeout <= self.interval {
+            return Err(Error::Config(
+                "watchdog.stall_timeout must be greater than watchdog.interval".into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Topology {
+    pub state_file: String,
+    #[serde(with = "humantime_serde")]
+    pub persist_interval: Duration,
+    pub relay_locations: Vec<RelayLocation>,
+    pub asymmetric_link_ratio: f64,
 }
 
-impl Configuration {
-    pub fn load(filenames: &[String]) -> Result<()> {
-        let mut content = String::new();
-        for file_name in filenames {
-            content.push_str(&fs::read_to_string(file_name)?);
+impl Default for Topology {
+    fn default() -> Self {
+        Topology {
+            state_file: "".into(),
+            persist_interval: Duration::from_secs(60),
+            relay_locations: vec![],
+            asymmetric_link_ratio: 0.5,
+        }
+    }
+}
+
+impl Topology {
+    fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.asymmetric_link_ratio) {
+            return Err(Error::Config(
+                "topology.asymmetric_link_ratio must be between 0 and 1".into(),
+            ));
         }
 
-        let conf: Configuration = toml::from_str(&content)?;
-        set(conf)
+        Ok(())
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RelayLocation {
+    #[serde(with = "hex")]
+    pub relay_id: [u8; 4],
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
-pub struct Logging {
-    pub level: String,
-    pub log_to_syslog: bool,
+pub struct UplinkContext {
+    pub state_file: String,
+    #[serde(with = "humantime_serde")]
+    pub persist_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+    pub max_entries: usize,
 }
 
-impl Default for Logging {
+impl Default for UplinkContext {
     fn default() -> Self {
-        Logging {
-            level: "info".into(),
-            log_to_syslog: false,
+        UplinkContext {
+            state_file: "".into(),
+            persist_interval: Duration::from_secs(10),
+            ttl: Duration::from_secs(60),
+            max_entries: 4096,
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
-pub struct Mesh {
-    pub signing_key: Aes128Key,
+pub struct DeadLetter {
+    pub file: String,
+    pub max_entries: usize,
+}
+
+impl Default for DeadLetter {
+    fn default() -> Self {
+        DeadLetter {
+            file: "".into(),
+            max_entries: 1000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Webhook {
+    pub url: String,
+    pub events: Vec<String>,
+    pub headers: HashMap<String, String>,
     #[serde(with = "humantime_serde")]
-    pub heartbeat_interval: Duration,
-    pub frequencies: Vec<u32>,
-    pub data_rate: DataRate,
-    pub tx_power: i32,
-    pub proxy_api: ProxyApi,
-    pub filters: Filters,
-    pub border_gateway: bool,
-    pub border_gateway_ignore_direct_uplinks: bool,
-    pub max_hop_count: u8,
+    pub timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    pub relay_offline_timeout: Duration,
 }
 
-impl Default for Mesh {
+impl Default for Webhook {
     fn default() -> Self {
-        Mesh {
-            signing_key: Aes128Key::null(),
-            heartbeat_interval: Duration::from_secs(300),
-            frequencies: vec![868100000, 868300000, 868500000],
-            data_rate: DataRate {
-                modulation: Modulation::LORA,
-                spreading_factor: 7,
-                bandwidth: 125000,
-                code_rate: Some(CodeRate::Cr45),
-                bitrate: 0,
-            },
-            tx_power: 16,
-            proxy_api: ProxyApi::default(),
-            filters: Filters::default(),
-            border_gateway: false,
-            border_gateway_ignore_direct_uplinks: false,
-            max_hop_count: 1,
+        Webhook {
+            url: "".into(),
+            events: vec![
+                "heartbeat".into(),
+                "relay_offline".into(),
+                "topology".into(),
+            ],
+            headers: HashMap::new(),
+            timeout: Duration::from_secs(5),
+            relay_offline_timeout: Duration::from_secs(900),
+        }
+    }
+}
+
+impl Webhook {
+    fn validate(&self) -> Result<()> {
+        if !self.url.is_empty() {
+            webhook::parse_url(&self.url)?;
+        }
+        webhook::parse_headers(&self.headers)?;
+
+        for event in &self.events {
+            if !["heartbeat", "relay_offline", "topology"].contains(&event.as_str()) {
+                return Err(Error::Config(format!(
+                    "webhook.events contains unknown event: {}",
+                    event
+                )));
+            }
         }
+
+        Ok(())
     }
 }
 
 #[derive(Serialize, Deserialize, Default)]
 #[serde(default)]
-pub struct Backend {
-    pub concentratord: Concentratord,
-    pub mesh_concentratord: Concentratord,
+pub struct Integrations {
+    pub mqtt: Mqtt,
+    pub health_beacon: HealthBeacon,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
-pub struct Concentratord {
-    pub event_url: String,
-    pub command_url: String,
+pub struct Mqtt {
+    pub server: String,
+    pub username: String,
+    pub password: String,
+    pub client_id: String,
+    pub ca_cert: String,
+    pub tls_cert: String,
+    pub tls_key: String,
+    pub topic_prefix: String,
+    #[serde(with = "humantime_serde")]
+    pub keep_alive: Duration,
+    #[serde(with = "humantime_serde")]
+    pub topology_interval: Duration,
+    pub forwarder: MqttForwarder,
 }
 
-impl Default for Concentratord {
+impl Default for Mqtt {
     fn default() -> Self {
-        Concentratord {
-            event_url: "ipc:///tmp/concentratord_event".into(),
-            command_url: "ipc:///tmp/concentratord_command".into(),
+        Mqtt {
+            server: "".into(),
+            username: "".into(),
+            password: "".into(),
+            client_id: "".into(),
+            ca_cert: "".into(),
+            tls_cert: "".into(),
+            tls_key: "".into(),
+            topic_prefix: "mesh".into(),
+            keep_alive: Duration::from_secs(30),
+            topology_interval: Duration::from_secs(60),
+            forwarder: MqttForwarder::default(),
+        }
+    }
+}
+
+impl Mqtt {
+    fn validate(&self) -> Result<()> {
+        if self.server.is_empty() {
+            if self.forwarder.enabled {
+                return Err(Error::Config(
+                    "integrations.mqtt.forwarder requires integrations.mqtt.server to be set"
+                        .into(),
+                ));
+            }
+
+            return Ok(());
+        }
+
+        mqtt::parse_server(&self.server)?;
+
+        if self.tls_cert.is_empty() != self.tls_key.is_empty() {
+            return Err(Error::Config(
+                "integrations.mqtt.tls_cert and integrations.mqtt.tls_key must be set together"
+                    .into(),
+            ));
+        }
+
+        if !self.tls_cert.is_empty() && self.ca_cert.is_empty() {
+            return Err(Error::Config(
+                "integrations.mqtt.tls_cert requires integrations.mqtt.ca_cert to be set".into(),
+            ));
+        }
+
+        if self.keep_alive < Duration::from_secs(2)
+            || self.keep_alive > Duration::from_secs(u16::MAX.into())
+        {
+            return Err(Error::Config(
+                "integrations.mqtt.keep_alive must be between 2s and 65535s".into(),
+            ));
         }
+
+        Ok(())
     }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
-pub struct ProxyApi {
-    pub event_bind: String,
-    pub command_bind: String,
+pub struct MqttForwarder {
+    pub enabled: bool,
+    pub topic_prefix: String,
 }
 
-impl Default for ProxyApi {
+impl Default for MqttForwarder {
     fn default() -> Self {
-        ProxyApi {
-            event_bind: "ipc:///tmp/gateway_relay_event".into(),
-            command_bind: "ipc:///tmp/gateway_relay_command".into(),
+        MqttForwarder {
+            enabled: false,
+            topic_prefix: "eu868".into(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize)]
 #[serde(default)]
-pub struct Filters {
-    pub dev_addr_prefixes: Vec<lrwn_filters::DevAddrPrefix>,
-    pub join_eui_prefixes: Vec<lrwn_filters::EuiPrefix>,
+pub struct HealthBeacon {
+    pub server: String,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for HealthBeacon {
+    fn default() -> Self {
+        HealthBeacon {
+            server: "".into(),
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl HealthBeacon {
+    fn validate(&self) -> Result<()> {
+        if !self.server.is_empty() && self.interval.is_zero() {
+            return Err(Error::Config(
+                "integrations.health_beacon.interval must be greater than 0s".into(),
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, PartialEq, Eq)]
@@ -217,22 +1591,903 @@ impl<'de> Deserialize<'de> for CodeRate {
             "4/5LI" => CodeRate::CrLi45,
             "4/6LI" => CodeRate::CrLi46,
             "4/8LI" => CodeRate::CrLi48,
-            _ => return Err(Error::custom(format!("Unexpected code_rate: {}", s))),
+            _ => return Err(de::Error::custom(format!("Unexpected code_rate: {}", s))),
         })
     }
 }
 
+// Renames the legacy section and key names in the given configuration document. Returns the
+// renamed names, formatted as "old -> new".
+pub fn migrate(doc: &mut toml_edit::DocumentMut) -> Result<Vec<String>> {
+    let mut out = Vec::new();
+
+    for (old, new) in LEGACY_NAMES {
+        let (parent, old_key) = old.rsplit_once('.').unwrap_or(("", old));
+        let new_key = new.rsplit('.').next().unwrap_or(new);
+
+        let table = match get_table_mut(doc.as_table_mut(), parent) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let item = match table.remove(old_key) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if table.contains_key(new_key) {
+            return Err(Error::Config(format!(
+                "Both {} and {} are configured",
+                old, new
+            )));
+        }
+
+        table.insert(new_key, item);
+        out.push(format!("{} -> {}", old, new));
+    }
+
+    Ok(out)
+}
+
+// Returns the given profile, with its included profiles merged in (in order). The profile
+// itself takes precedence over the included profiles.
+fn resolve_profile(
+    profiles: &toml::Table,
+    name: &str,
+    stack: &mut Vec<String>,
+) -> Result<toml::Table> {
+    if stack.iter().any(|v| v == name) {
+        return Err(Error::Config(format!(
+            "Profile include cycle: {} -> {}",
+            stack.join(" -> "),
+            name
+        )));
+    }
+
+    let mut profile = match profiles.get(name) {
+        Some(toml::Value::Table(v)) => v.clone(),
+        Some(_) => return Err(Error::Config(format!("Profile {} must be a table", name))),
+        None => return Err(Error::Config(format!("Profile {} does not exist", name))),
+    };
+
+    let includes = match profile.remove("include") {
+        Some(toml::Value::Array(v)) => v,
+        Some(_) => {
+            return Err(Error::Config(format!(
+                "Profile {} include must be an array",
+                name
+            )))
+        }
+        None => vec![],
+    };
+
+    stack.push(name.to_string());
+    let mut out = toml::Table::new();
+    for include in includes {
+        let include = include.as_str().ok_or_else(|| {
+            Error::Config(format!(
+                "Profile {} include must contain profile names",
+                name
+            ))
+        })?;
+        merge(&mut out, resolve_profile(profiles, include, stack)?);
+    }
+    stack.pop();
+
+    merge(&mut out, profile);
+    Ok(out)
+}
+
+// Merges the other table into the given table. Tables are merged recursively, other values
+// (including arrays) are replaced.
+fn merge(table: &mut toml::Table, other: toml::Table) {
+    for (k, v) in other {
+        match (table.get_mut(&k), v) {
+            (Some(toml::Value::Table(a)), toml::Value::Table(b)) => merge(a, b),
+            (_, v) => {
+                table.insert(k, v);
+            }
+        }
+    }
+}
+
+fn get_table_mut<'a>(
+    table: &'a mut dyn toml_edit::TableLike,
+    path: &str,
+) -> Option<&'a mut dyn toml_edit::TableLike> {
+    if path.is_empty() {
+        return Some(table);
+    }
+
+    let (key, path) = path.split_once('.').unwrap_or((path, ""));
+    get_table_mut(table.get_mut(key)?.as_table_like_mut()?, path)
+}
+
+// Returns the legacy names that were migrated when loading the configuration.
+pub fn get_migrated_names() -> Vec<String> {
+    MIGRATED_NAMES.lock().unwrap().clone()
+}
+
 pub fn set(c: Configuration) -> Result<()> {
     CONFIG
         .set(Mutex::new(Arc::new(c)))
-        .map_err(|_| anyhow!("Set OnceCell error"))
+        .map_err(|_| Error::Config("Set OnceCell error".into()))
 }
 
 pub fn get() -> Arc<Configuration> {
     let conf = CONFIG
         .get()
-        .ok_or_else(|| anyhow!("OnceCell is not set"))
+        .ok_or_else(|| Error::Config("OnceCell is not set".into()))
         .unwrap();
 
     conf.lock().unwrap().clone()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_migrate() {
+        let mut doc: toml_edit::DocumentMut = r#"
+# Relay configuration.
+[relay]
+  border_gateway=true
+
+  [relay.proxy_api]
+    event_bind="ipc:///tmp/gateway_relay_event"
+
+[backend]
+  [backend.relay_concentratord]
+    event_url="ipc:///tmp/concentratord_relay_event"
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                "relay -> mesh".to_string(),
+                "backend.relay_concentratord -> backend.mesh_concentratord".to_string(),
+            ],
+            migrate(&mut doc).unwrap()
+        );
+
+        let conf: Configuration = toml::from_str(&doc.to_string()).unwrap();
+        assert!(conf.mesh.border_gateway);
+        assert_eq!(
+            "ipc:///tmp/gateway_relay_event",
+            conf.mesh.proxy_api.event_bind
+        );
+        assert_eq!(
+            "ipc:///tmp/concentratord_relay_event",
+            conf.backend.mesh_concentratord.event_url
+        );
+        assert!(doc.to_string().contains("# Relay configuration."));
+
+        // Nothing to migrate.
+        assert!(migrate(&mut doc).unwrap().is_empty());
+
+        // Both the legacy and current name are configured.
+        let mut doc: toml_edit::DocumentMut = "[relay]\n[mesh]\n".parse().unwrap();
+        assert_eq!(
+            "Both relay and mesh are configured",
+            migrate(&mut doc).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_resolve_profile() {
+        let profiles: toml::Table = toml::from_str(
+            r#"
+            [base]
+            mesh.signing_key = "01020304050607080102030405060708"
+            mesh.frequencies = [868100000]
+            mesh.data_rate.spreading_factor = 9
+
+            [eu868]
+            include = ["base"]
+            mesh.frequencies = [868100000, 868300000]
+
+            [farm-a]
+            include = ["eu868"]
+            mesh.border_gateway = true
+            mesh.data_rate.bandwidth = 250000
+
+            [cycle-a]
+            include = ["cycle-b"]
+
+            [cycle-b]
+            include = ["cycle-a"]
+        "#,
+        )
+        .unwrap();
+
+        let mut table: toml::Table = toml::from_str(
+            r#"
+            [mesh]
+            max_hop_count = 3
+        "#,
+        )
+        .unwrap();
+        merge(
+            &mut table,
+            resolve_profile(&profiles, "farm-a", &mut vec![]).unwrap(),
+        );
+        let conf: Configuration = toml::Value::Table(table).try_into().unwrap();
+
+        assert!(conf.mesh.border_gateway);
+        assert_eq!(3, conf.mesh.max_hop_count);
+        assert_eq!(vec![868100000, 868300000], conf.mesh.frequencies);
+        assert_eq!(9, conf.mesh.data_rate.spreading_factor);
+        assert_eq!(250000, conf.mesh.data_rate.bandwidth);
+        assert_eq!(
+            "01020304050607080102030405060708",
+            conf.mesh.signing_key.to_string()
+        );
+
+        assert_eq!(
+            "Profile farm-b does not exist",
+            resolve_profile(&profiles, "farm-b", &mut vec![])
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Profile include cycle: cycle-a -> cycle-b -> cycle-a",
+            resolve_profile(&profiles, "cycle-a", &mut vec![])
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_mesh_validate() {
+        struct Test {
+            name: String,
+            mesh: Mesh,
+            expected_error: Option<String>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "default".into(),
+                mesh: Mesh::default(),
+                expected_error: None,
+            },
+            Test {
+                name: "heartbeats disabled".into(),
+                mesh: Mesh {
+                    heartbeat_interval: Duration::ZERO,
+                    ..Default::default()
+                },
+                expected_error: None,
+            },
+            Test {
+                name: "heartbeat interval too short".into(),
+                mesh: Mesh {
+                    heartbeat_interval: Duration::from_secs(1),
+                    ..Default::default()
+                },
+                expected_error: Some("mesh.heartbeat_interval (1s) is too short, a heartbeat of up to 13 bytes (max_hop_count: 1) takes 46.336ms of airtime using the configured mesh.data_rate, use a heartbeat_interval of at least 4.6336s (1% duty-cycle) or 0s to disable heartbeats".into()),
+            },
+            Test {
+                name: "heartbeat interval too short, config checksum".into(),
+                mesh: Mesh {
+                    heartbeat_interval: Duration::from_secs(5),
+                    heartbeat_config_checksum: true,
+                    ..Default::default()
+                },
+                expected_error: Some("mesh.heartbeat_interval (5s) is too short, a heartbeat of up to 17 bytes (max_hop_count: 1) takes 51.456ms of airtime using the configured mesh.data_rate, use a heartbeat_interval of at least 5.1456s (1% duty-cycle) or 0s to disable heartbeats".into()),
+            },
+            Test {
+                name: "heartbeat interval too short, relay path max length, version 2".into(),
+                mesh: Mesh {
+                    heartbeat_interval: Duration::from_secs(5),
+                    packet_format_version: 2,
+                    max_hop_count: 8,
+                    heartbeat_relay_path_max_length: 2,
+                    ..Default::default()
+                },
+                expected_error: Some("mesh.heartbeat_interval (5s) is too short, a heartbeat of up to 28 bytes (max_hop_count: 8) takes 66.816ms of airtime using the configured mesh.data_rate, use a heartbeat_interval of at least 6.6816s (1% duty-cycle) or 0s to disable heartbeats".into()),
+            },
+            Test {
+                name: "adaptive max hop count margin is 0".into(),
+                mesh: Mesh {
+                    adaptive_max_hop_count: AdaptiveMaxHopCount {
+                        enabled: true,
+                        margin: 0,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.adaptive_max_hop_count.margin must be at least 1".into(),
+                ),
+            },
+            Test {
+                name: "signing key index not configured".into(),
+                mesh: Mesh {
+                    signing_key_index: 1,
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.signing_key_index 1 does not refer to a configured signing key".into(),
+                ),
+            },
+            Test {
+                name: "signing key index configured".into(),
+                mesh: Mesh {
+                    signing_key_index: 1,
+                    signing_keys: vec![SigningKey {
+                        key_index: 1,
+                        key: Aes128Key::from_bytes([1; 16]),
+                    }],
+                    packet_format_version: 2,
+                    ..Default::default()
+                },
+                expected_error: None,
+            },
+            Test {
+                name: "signing key index requires packet format version 2".into(),
+                mesh: Mesh {
+                    signing_key_index: 1,
+                    signing_keys: vec![SigningKey {
+                        key_index: 1,
+                        key: Aes128Key::from_bytes([1; 16]),
+                    }],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.signing_key_index other than 0 requires mesh.packet_format_version 2"
+                        .into(),
+                ),
+            },
+            Test {
+                name: "signing key index exceeds max value".into(),
+                mesh: Mesh {
+                    signing_keys: vec![SigningKey {
+                        key_index: 16,
+                        key: Aes128Key::from_bytes([1; 16]),
+                    }],
+                    ..Default::default()
+                },
+                expected_error: Some("mesh.signing_keys.key_index must be at most 15".into()),
+            },
+            Test {
+                name: "replay protection requires packet format version 2".into(),
+                mesh: Mesh {
+                    replay_protection: ReplayProtection { enabled: true },
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.replay_protection requires mesh.packet_format_version 2".into(),
+                ),
+            },
+            Test {
+                name: "invalid packet format version".into(),
+                mesh: Mesh {
+                    packet_format_version: 3,
+                    ..Default::default()
+                },
+                expected_error: Some("mesh.packet_format_version must be 1 or 2".into()),
+            },
+            Test {
+                name: "signing key index 0 is reserved".into(),
+                mesh: Mesh {
+                    signing_keys: vec![SigningKey {
+                        key_index: 0,
+                        key: Aes128Key::from_bytes([1; 16]),
+                    }],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.signing_keys.key_index 0 is reserved for mesh.signing_key".into(),
+                ),
+            },
+            Test {
+                name: "signing key index configured more than once".into(),
+                mesh: Mesh {
+                    signing_keys: vec![
+                        SigningKey {
+                            key_index: 1,
+                            key: Aes128Key::from_bytes([1; 16]),
+                        },
+                        SigningKey {
+                            key_index: 1,
+                            key: Aes128Key::from_bytes([2; 16]),
+                        },
+                    ],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.signing_keys.key_index 1 is configured more than once".into(),
+                ),
+            },
+            Test {
+                name: "duty-cycle band min frequency exceeds max frequency".into(),
+                mesh: Mesh {
+                    duty_cycle: DutyCycle {
+                        bands: vec![DutyCycleBand {
+                            min_frequency: 868600000,
+                            max_frequency: 868000000,
+                            duty_cycle: 1.0,
+                        }],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.duty_cycle.bands.min_frequency must be less than max_frequency".into(),
+                ),
+            },
+            Test {
+                name: "duty-cycle band invalid duty-cycle".into(),
+                mesh: Mesh {
+                    duty_cycle: DutyCycle {
+                        bands: vec![DutyCycleBand {
+                            min_frequency: 868000000,
+                            max_frequency: 868600000,
+                            duty_cycle: 0.0,
+                        }],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.duty_cycle.bands.duty_cycle must be between 0 and 100".into(),
+                ),
+            },
+            Test {
+                name: "relay group without name".into(),
+                mesh: Mesh {
+                    relay_groups: vec![RelayGroup::default()],
+                    ..Default::default()
+                },
+                expected_error: Some("mesh.relay_groups.name must be set".into()),
+            },
+            Test {
+                name: "relay group configured more than once".into(),
+                mesh: Mesh {
+                    relay_groups: vec![
+                        RelayGroup {
+                            name: "solar".into(),
+                            ..Default::default()
+                        },
+                        RelayGroup {
+                            name: "solar".into(),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.relay_groups.name solar is configured more than once".into(),
+                ),
+            },
+            Test {
+                name: "relay group unknown payload type".into(),
+                mesh: Mesh {
+                    relay_groups: vec![RelayGroup {
+                        name: "solar".into(),
+                        payload_types: vec!["downlink".into()],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.relay_groups.payload_types contains unknown payload type: downlink"
+                        .into(),
+                ),
+            },
+            Test {
+                name: "relay group rate limit without interval".into(),
+                mesh: Mesh {
+                    relay_groups: vec![RelayGroup {
+                        name: "solar".into(),
+                        rate_limit_packets: 10,
+                        rate_limit_interval: Duration::ZERO,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.relay_groups.rate_limit_interval must be greater than 0".into(),
+                ),
+            },
+            Test {
+                name: "maintenance window invalid start".into(),
+                mesh: Mesh {
+                    maintenance_windows: vec![MaintenanceWindow {
+                        start: "24:00".into(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.maintenance_windows.start must be formatted as HH:MM, got: 24:00".into(),
+                ),
+            },
+            Test {
+                name: "maintenance window invalid duration".into(),
+                mesh: Mesh {
+                    maintenance_windows: vec![MaintenanceWindow {
+                        duration: Duration::from_secs(86401),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.maintenance_windows.duration must be between 1s and 24h".into(),
+                ),
+            },
+            Test {
+                name: "static location".into(),
+                mesh: Mesh {
+                    location: Location {
+                        source: LocationSource::Static,
+                        latitude: 52.3702,
+                        longitude: 4.8952,
+                        altitude: -5.0,
+                        ..Default::default()
+                    },
+                    stats_report_interval: Duration::from_secs(300),
+                    ..Default::default()
+                },
+                expected_error: None,
+            },
+            Test {
+                name: "static location invalid latitude".into(),
+                mesh: Mesh {
+                    location: Location {
+                        source: LocationSource::Static,
+                        latitude: 91.0,
+                        ..Default::default()
+                    },
+                    stats_report_interval: Duration::from_secs(300),
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.location latitude and longitude must be valid coordinates".into(),
+                ),
+            },
+            Test {
+                name: "static location invalid altitude".into(),
+                mesh: Mesh {
+                    location: Location {
+                        source: LocationSource::Static,
+                        altitude: 40000.0,
+                        ..Default::default()
+                    },
+                    stats_report_interval: Duration::from_secs(300),
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.location.altitude must be between -32768 and 32767".into(),
+                ),
+            },
+            Test {
+                name: "gpsd location without server".into(),
+                mesh: Mesh {
+                    location: Location {
+                        source: LocationSource::Gpsd,
+                        gpsd_server: "".into(),
+                        ..Default::default()
+                    },
+                    stats_report_interval: Duration::from_secs(300),
+                    ..Default::default()
+                },
+                expected_error: Some("mesh.location.gpsd_server must be set".into()),
+            },
+            Test {
+                name: "telemetry".into(),
+                mesh: Mesh {
+                    telemetry: Telemetry {
+                        report_interval: Duration::from_secs(3600),
+                        temperature: TelemetrySource {
+                            path: "/sys/class/thermal/thermal_zone0/temp".into(),
+                            scale: 0.001,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_error: None,
+            },
+            Test {
+                name: "telemetry path and command".into(),
+                mesh: Mesh {
+                    telemetry: Telemetry {
+                        battery_voltage: TelemetrySource {
+                            path: "/sys/class/power_supply/battery/voltage_now".into(),
+                            command: "read-battery".into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.telemetry.battery_voltage path and command can not both be set".into(),
+                ),
+            },
+            Test {
+                name: "telemetry without source".into(),
+                mesh: Mesh {
+                    telemetry: Telemetry {
+                        report_interval: Duration::from_secs(3600),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.telemetry.report_interval is set, but no telemetry source is configured"
+                        .into(),
+                ),
+            },
+            Test {
+                name: "location without stats report".into(),
+                mesh: Mesh {
+                    location: Location {
+                        source: LocationSource::Gpsd,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_error: Some(
+                    "mesh.location requires mesh.stats_report_interval to be set".into(),
+                ),
+            },
+            Test {
+                name: "heartbeat interval too short, border gateway".into(),
+                mesh: Mesh {
+                    heartbeat_interval: Duration::from_secs(1),
+                    border_gateway: true,
+                    ..Default::default()
+                },
+                expected_error: None,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let res = tst.mesh.validate();
+            if let Some(e) = &tst.expected_error {
+                assert_eq!(e, &res.err().unwrap().to_string());
+            } else {
+                assert!(res.is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_relay_id_prefix() {
+        let p: RelayIdPrefix = "01020000/16".parse().unwrap();
+        assert_eq!("01020000/16", p.to_string());
+        assert!(p.matches([1, 2, 3, 4]));
+        assert!(!p.matches([1, 3, 3, 4]));
+
+        let p: RelayIdPrefix = "01020304".parse().unwrap();
+        assert_eq!("01020304/32", p.to_string());
+        assert!(p.matches([1, 2, 3, 4]));
+        assert!(!p.matches([1, 2, 3, 5]));
+
+        let p: RelayIdPrefix = "00000000/0".parse().unwrap();
+        assert!(p.matches([1, 2, 3, 4]));
+
+        assert!("0102/16".parse::<RelayIdPrefix>().is_err());
+        assert!("01020000/33".parse::<RelayIdPrefix>().is_err());
+    }
+
+    #[test]
+    fn test_get_relay_group() {
+        let mesh = Mesh {
+            relay_groups: vec![
+                RelayGroup {
+                    name: "solar".into(),
+                    relay_id_prefixes: vec!["01000000/8".parse().unwrap()],
+                    tx_power: Some(10),
+                    heartbeat_interval: Some(Duration::from_secs(3600)),
+                    ..Default::default()
+                },
+                RelayGroup {
+                    name: "mains".into(),
+                    relay_id_prefixes: vec!["00000000/0".parse().unwrap()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!("solar", mesh.get_relay_group([1, 2, 3, 4]).unwrap().name);
+        assert_eq!("mains", mesh.get_relay_group([2, 2, 3, 4]).unwrap().name);
+
+        assert_eq!(10, mesh.get_tx_power([1, 2, 3, 4]));
+        assert_eq!(16, mesh.get_tx_power([2, 2, 3, 4]));
+        assert_eq!(
+            Duration::from_secs(3600),
+            mesh.get_heartbeat_interval([1, 2, 3, 4])
+        );
+        assert_eq!(
+            Duration::from_secs(300),
+            mesh.get_heartbeat_interval([2, 2, 3, 4])
+        );
+        assert!(Mesh::default().get_relay_group([1, 2, 3, 4]).is_none());
+    }
+
+    #[test]
+    fn test_maintenance_window() {
+        let day = UNIX_EPOCH + Duration::from_secs(19723 * 86400);
+        let at = |h: u64, m: u64| day + Duration::from_secs(h * 3600 + m * 60);
+
+        let mesh = Mesh {
+            maintenance_windows: vec![MaintenanceWindow {
+                name: "power cycle".into(),
+                relay_id_prefixes: vec!["01000000/8".parse().unwrap()],
+                start: "23:30".into(),
+                duration: Duration::from_secs(3600),
+            }],
+            ..Default::default()
+        };
+
+        assert!(!mesh.in_maintenance([1, 2, 3, 4], at(23, 29)));
+        assert!(mesh.in_maintenance([1, 2, 3, 4], at(23, 30)));
+        // The window extends past midnight.
+        assert!(mesh.in_maintenance([1, 2, 3, 4], at(0, 29)));
+        assert!(!mesh.in_maintenance([1, 2, 3, 4], at(0, 30)));
+        // Other relays are not affected.
+        assert!(!mesh.in_maintenance([2, 2, 3, 4], at(23, 30)));
+
+        assert_eq!(Some(0), parse_time_of_day("00:00"));
+        assert_eq!(Some(84600), parse_time_of_day("23:30"));
+        assert_eq!(None, parse_time_of_day("23:60"));
+        assert_eq!(None, parse_time_of_day("2330"));
+    }
+
+    #[test]
+    fn test_webhook_validate() {
+        assert!(Webhook::default().validate().is_ok());
+        assert!(Webhook {
+            url: "http://localhost:8080/events".into(),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+        assert!(Webhook {
+            url: "https://localhost/events".into(),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+        assert_eq!(
+            "webhook.url must be an http:// or https:// URL",
+            Webhook {
+                url: "ftp://localhost".into(),
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+        assert_eq!(
+            "webhook.events contains unknown event: uplink",
+            Webhook {
+                events: vec!["uplink".into()],
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_watchdog_validate() {
+        assert!(Watchdog::default().validate().is_ok());
+        assert!(Watchdog {
+            enabled: true,
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+        assert_eq!(
+            "watchdog.interval must be greater than 0s",
+            Watchdog {
+                enabled: true,
+                interval: Duration::ZERO,
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+        assert_eq!(
+            "watchdog.stall_timeout must be greater than watchdog.interval",
+            Watchdog {
+                enabled: true,
+                stall_timeout: Duration::from_secs(5),
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_mqtt_validate() {
+        assert!(Mqtt::default().validate().is_ok());
+        assert!(Mqtt {
+            server: "tcp://localhost:1883".into(),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+        assert!(Mqtt {
+            server: "ssl://localhost:8883".into(),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+        assert_eq!(
+            "integrations.mqtt.server must be a tcp:// or ssl:// URL",
+            Mqtt {
+                server: "mqtt://localhost:1883".into(),
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+        assert_eq!(
+            "integrations.mqtt.tls_cert requires integrations.mqtt.ca_cert to be set",
+            Mqtt {
+                server: "ssl://localhost:8883".into(),
+                tls_cert: "/etc/cert.pem".into(),
+                tls_key: "/etc/key.pem".into(),
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+        assert_eq!(
+            "integrations.mqtt.keep_alive must be between 2s and 65535s",
+            Mqtt {
+                server: "tcp://localhost:1883".into(),
+                keep_alive: Duration::ZERO,
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+        assert_eq!(
+            "integrations.mqtt.forwarder requires integrations.mqtt.server to be set",
+            Mqtt {
+                forwarder: MqttForwarder {
+                    enabled: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_health_beacon_validate() {
+        assert!(HealthBeacon::default().validate().is_ok());
+        assert_eq!(
+            "integrations.health_beacon.interval must be greater than 0s",
+            HealthBeacon {
+                server: "127.0.0.1:9999".into(),
+                interval: Duration::ZERO,
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+    }
+}
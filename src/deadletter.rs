@@ -0,0 +1,208 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use log::{error, info, trace};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Configuration;
+use crate::error::{Error, Result};
+use crate::{helpers, stats};
+
+static DEAD_LETTERS: OnceCell<Mutex<DeadLetterFile>> = OnceCell::new();
+
+// Reasons for dropping a packet.
+pub const REASON_INVALID_MIC: &str = "invalid_mic";
+pub const REASON_MAX_HOP_COUNT_EXCEEDED: &str = "max_hop_count_exceeded";
+pub const REASON_PAYLOAD_TOO_LARGE: &str = "payload_too_large";
+pub const REASON_NO_UPLINK_CONTEXT: &str = "no_uplink_context";
+pub const REASON_DOWNLINK_TX_FAILED: &str = "downlink_tx_failed";
+pub const REASON_UPLINK_NOT_ACKNOWLEDGED: &str = "uplink_not_acknowledged";
+pub const REASON_DUTY_CYCLE_EXCEEDED: &str = "duty_cycle_exceeded";
+pub const REASON_DOWNLINK_RATE_LIMITED: &str = "downlink_rate_limited";
+pub const REASON_REPLAY: &str = "replay";
+pub const REASON_MISSING_TIMESTAMP: &str = "missing_timestamp";
+pub const REASON_RELAY_GROUP_POLICY: &str = "relay_group_policy";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    #[serde(with = "humantime_serde")]
+    pub time: SystemTime,
+    pub reason: String,
+    pub error: String,
+    #[serde(with = "hex")]
+    pub phy_payload: Vec<u8>,
+}
+
+struct DeadLetterFile {
+    file: String,
+    max_entries: usize,
+    entry_count: usize,
+}
+
+pub fn setup(conf: &Configuration) -> Result<()> {
+    if conf.dead_letter.file.is_empty() || conf.dead_letter.max_entries == 0 {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up dead-letter log, file: {}, max_entries: {}",
+        conf.dead_letter.file, conf.dead_letter.max_entries
+    );
+
+    let entry_count = compact(&conf.dead_letter.file, conf.dead_letter.max_entries)?;
+
+    DEAD_LETTERS
+        .set(Mutex::new(DeadLetterFile {
+            file: conf.dead_letter.file.clone(),
+            max_entries: conf.dead_letter.max_entries,
+            entry_count,
+        }))
+        .map_err(|_| Error::Config("OnceCell already set".into()))?;
+
+    Ok(())
+}
+
+// Records a packet that was dropped for operational reasons. Besides the dead-letter log (if
+// configured), this increments the mesh_drop_count metric.
+pub fn record(reason: &str, phy_payload: &[u8], error: &str) {
+    stats::record_mesh_drop(reason);
+
+    let dead_letters = match DEAD_LETTERS.get() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let entry = Entry {
+        time: helpers::system_time_now(),
+        reason: reason.to_string(),
+        error: error.to_string(),
+        phy_payload: phy_payload.to_vec(),
+    };
+
+    let mut dead_letters = dead_letters.lock().unwrap();
+    if let Err(e) = append(&mut dead_letters, &entry) {
+        error!("Write dead-letter log error, error: {}", e);
+    }
+}
+
+// Reads the entries from the given dead-letter log file.
+pub fn read(file: &str) -> Result<Vec<Entry>> {
+    if !Path::new(file).exists() {
+        return Ok(vec![]);
+    }
+
+    fs::read_to_string(file)?
+        .lines()
+        .filter(|v| !v.is_empty())
+        .map(|v| Ok(serde_json::from_str(v)?))
+        .collect()
+}
+
+fn append(dead_letters: &mut DeadLetterFile, entry: &Entry) -> Result<()> {
+    trace!(
+        "Writing dead-letter entry, file: {}, reason: {}",
+        dead_letters.file,
+        entry.reason
+    );
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&dead_letters.file)?;
+    f.write_all(line.as_bytes())?;
+    dead_letters.entry_count += 1;
+
+    // The file is allowed to grow to twice the max entries, such that it does not need to be
+    // re-written on every append.
+    if dead_letters.entry_count >= dead_letters.max_entries * 2 {
+        dead_letters.entry_count = compact(&dead_letters.file, dead_letters.max_entries)?;
+    }
+
+    Ok(())
+}
+
+// Truncates the dead-letter log file to the last max_entries entries and returns the number of
+// entries.
+fn compact(file: &str, max_entries: usize) -> Result<usize> {
+    let mut entries = read(file)?;
+    if entries.len() > max_entries {
+        entries.drain(..entries.len() - max_entries);
+    }
+
+    let mut b = Vec::new();
+    for entry in &entries {
+        b.extend_from_slice(serde_json::to_string(entry)?.as_bytes());
+        b.push(b'\n');
+    }
+
+    // Write to a temporary file first, so that a crash while writing does not
+    // corrupt the log.
+    let tmp_file = format!("{}.tmp", file);
+    fs::write(&tmp_file, b)?;
+    fs::rename(&tmp_file, file)?;
+
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_append_compact() {
+        let file = std::env::temp_dir().join("chirpstack-gateway-mesh-deadletter-test.jsonl");
+        let file = file.to_str().unwrap();
+        let _ = fs::remove_file(file);
+
+        let mut dead_letters = DeadLetterFile {
+            file: file.to_string(),
+            max_entries: 2,
+            entry_count: 0,
+        };
+
+        for i in 0..3 {
+            append(
+                &mut dead_letters,
+                &Entry {
+                    time: SystemTime::UNIX_EPOCH,
+                    reason: REASON_PAYLOAD_TOO_LARGE.to_string(),
+                    error: format!("error {}", i),
+                    phy_payload: vec![i],
+                },
+            )
+            .unwrap();
+        }
+
+        // The 4th entry triggers the compaction.
+        assert_eq!(3, read(file).unwrap().len());
+        append(
+            &mut dead_letters,
+            &Entry {
+                time: SystemTime::UNIX_EPOCH,
+                reason: REASON_INVALID_MIC.to_string(),
+                error: "error 3".into(),
+                phy_payload: vec![3],
+            },
+        )
+        .unwrap();
+
+        let entries = read(file).unwrap();
+        fs::remove_file(file).unwrap();
+
+        assert_eq!(2, dead_letters.entry_count);
+        assert_eq!(
+            vec![vec![2], vec![3]],
+            entries
+                .iter()
+                .map(|v| v.phy_payload.clone())
+                .collect::<Vec<Vec<u8>>>()
+        );
+    }
+}
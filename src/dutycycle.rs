@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::config;
+
+// Airtime of the mesh transmissions within the duty-cycle window, per frequency.
+type TxAirtime = HashMap<u32, VecDeque<(Instant, Duration)>>;
+
+static MESH_TX_AIRTIME: Lazy<Mutex<TxAirtime>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TxAirtimeUsage {
+    pub frequency: u32,
+    // Mesh TX airtime within the duty-cycle window.
+    #[serde(with = "humantime_serde")]
+    pub airtime: Duration,
+    // Duty-cycle (percentage) of the frequency within the duty-cycle window.
+    pub duty_cycle: f64,
+}
+
+// Reserves the given airtime on the given frequency and returns the delay after which it can be
+// transmitted without exceeding the duty-cycle of its band. The airtime is recorded at the time
+// of transmission (now + delay), within the same lock, such that concurrent transmissions can not
+// both claim the remaining budget. It returns None (without reserving) if the delay would exceed
+// the given max delay, or if the airtime exceeds the budget of the band. Frequencies outside the
+// configured bands are not limited.
+pub fn reserve_tx(
+    conf: &config::DutyCycle,
+    frequency: u32,
+    airtime: Duration,
+    max_delay: Duration,
+) -> Option<Duration> {
+    let now = Instant::now();
+    let mut tx_airtime = MESH_TX_AIRTIME.lock().unwrap();
+    prune(&mut tx_airtime, now, conf.window);
+
+    let delay = match get_band(conf, frequency) {
+        Some(band) => {
+            // The airtime of all frequencies within the band, sorted by time.
+            let mut band_airtime: Vec<(Instant, Duration)> = tx_airtime
+                .iter()
+                .filter(|(f, _)| band_contains(band, **f))
+                .flat_map(|(_, v)| v.iter().cloned())
+                .collect();
+            band_airtime.sort_by_key(|(ts, _)| *ts);
+
+            get_delay(
+                &band_airtime,
+                now,
+                conf.window,
+                get_budget(conf.window, band),
+                airtime,
+            )
+            .filter(|v| *v <= max_delay)?
+        }
+        None => Duration::ZERO,
+    };
+
+    let queue = tx_airtime.entry(frequency).or_default();
+    let pos = queue.partition_point(|(ts, _)| *ts <= now + delay);
+    queue.insert(pos, (now + delay, airtime));
+
+    Some(delay)
+}
+
+// Returns the mesh TX airtime usage within the duty-cycle window, per frequency.
+pub fn get_tx_airtime_usage(conf: &config::DutyCycle) -> Vec<TxAirtimeUsage> {
+    let now = Instant::now();
+    let mut tx_airtime = MESH_TX_AIRTIME.lock().unwrap();
+    prune(&mut tx_airtime, now, conf.window);
+
+    let airtime: BTreeMap<u32, Duration> = tx_airtime
+        .iter()
+        .map(|(f, v)| (*f, v.iter().map(|(_, d)| *d).sum()))
+        .collect();
+
+    airtime
+        .into_iter()
+        .map(|(frequency, airtime)| TxAirtimeUsage {
+            frequency,
+            airtime,
+            duty_cycle: airtime.as_secs_f64() / conf.window.as_secs_f64() * 100.0,
+        })
+        .collect()
+}
+
+fn get_band(conf: &config::DutyCycle, frequency: u32) -> Option<&config::DutyCycleBand> {
+    conf.bands.iter().find(|v| band_contains(v, frequency))
+}
+
+fn band_contains(band: &config::DutyCycleBand, frequency: u32) -> bool {
+    frequency >= band.min_frequency && frequency < band.max_frequency
+}
+
+// Returns the airtime budget of the band within the window.
+fn get_budget(window: Duration, band: &config::DutyCycleBand) -> Duration {
+    window.mul_f64(band.duty_cycle / 100.0)
+}
+
+// Returns the delay after which the given airtime fits the budget, given the (sorted) airtime
+// usage within the window. The delay is the time until enough of the usage has left the window.
+fn get_delay(
+    usage: &[(Instant, Duration)],
+    now: Instant,
+    window: Duration,
+    budget: Duration,
+    airtime: Duration,
+) -> Option<Duration> {
+    if airtime > budget {
+        return None;
+    }
+
+    let mut used: Duration = usage.iter().map(|(_, v)| *v).sum();
+    if used + airtime <= budget {
+        return Some(Duration::ZERO);
+    }
+
+    for (ts, v) in usage {
+        used = used.saturating_sub(*v);
+        if used + airtime <= budget {
+            return Some((*ts + window).saturating_duration_since(now));
+        }
+    }
+
+    Some(window)
+}
+
+fn prune(
+    tx_airtime: &mut HashMap<u32, VecDeque<(Instant, Duration)>>,
+    now: Instant,
+    window: Duration,
+) {
+    for v in tx_airtime.values_mut() {
+        while v
+            .front()
+            .map(|(ts, _)| now.saturating_duration_since(*ts) > window)
+            .unwrap_or_default()
+        {
+            v.pop_front();
+        }
+    }
+    tx_airtime.retain(|_, v| !v.is_empty());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Records the airtime of a mesh transmission on the given frequency.
+    fn record_tx(conf: &config::DutyCycle, frequency: u32, airtime: Duration) {
+        let now = Instant::now();
+        let mut tx_airtime = MESH_TX_AIRTIME.lock().unwrap();
+        tx_airtime
+            .entry(frequency)
+            .or_default()
+            .push_back((now, airtime));
+        prune(&mut tx_airtime, now, conf.window);
+    }
+
+    #[test]
+    fn test_get_delay() {
+        let start = Instant::now();
+        let now = start + Duration::from_secs(3600);
+        let window = Duration::from_secs(3600);
+        let budget = Duration::from_secs(36);
+
+        // No usage.
+        assert_eq!(
+            Some(Duration::ZERO),
+            get_delay(&[], now, window, budget, Duration::from_secs(1))
+        );
+
+        // Airtime exceeds the budget.
+        assert_eq!(
+            None,
+            get_delay(&[], now, window, budget, Duration::from_secs(37))
+        );
+
+        let usage = vec![
+            (start + Duration::from_secs(600), Duration::from_secs(20)),
+            (start + Duration::from_secs(2600), Duration::from_secs(15)),
+        ];
+
+        // Fits the remaining budget.
+        assert_eq!(
+            Some(Duration::ZERO),
+            get_delay(&usage, now, window, budget, Duration::from_secs(1))
+        );
+
+        // Fits once the first transmission has left the window.
+        assert_eq!(
+            Some(Duration::from_secs(600)),
+            get_delay(&usage, now, window, budget, Duration::from_secs(2))
+        );
+
+        // Fits once both transmissions have left the window.
+        assert_eq!(
+            Some(Duration::from_secs(2600)),
+            get_delay(&usage, now, window, budget, Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_reserve_tx() {
+        let conf = config::DutyCycle {
+            enabled: true,
+            bands: vec![config::DutyCycleBand {
+                min_frequency: 869400000,
+                max_frequency: 869650000,
+                duty_cycle: 1.0,
+            }],
+            ..Default::default()
+        };
+        let max_delay = Duration::from_secs(3600);
+
+        // Outside the configured bands.
+        record_tx(&conf, 868100000, Duration::from_secs(100));
+        assert_eq!(
+            Some(Duration::ZERO),
+            reserve_tx(&conf, 868100000, Duration::from_secs(1), Duration::ZERO)
+        );
+
+        // The usage of all frequencies within the band is accounted, including the airtime
+        // reserved by reserve_tx.
+        record_tx(&conf, 869425000, Duration::from_secs(20));
+        record_tx(&conf, 869525000, Duration::from_secs(14));
+        assert_eq!(
+            Some(Duration::ZERO),
+            reserve_tx(&conf, 869525000, Duration::from_secs(1), Duration::ZERO)
+        );
+        assert_eq!(
+            Some(Duration::ZERO),
+            reserve_tx(&conf, 869525000, Duration::from_secs(1), Duration::ZERO)
+        );
+
+        // The budget has been used, thus this is not reserved without delay.
+        assert_eq!(
+            None,
+            reserve_tx(&conf, 869525000, Duration::from_secs(1), Duration::ZERO)
+        );
+        assert!(
+            reserve_tx(&conf, 869525000, Duration::from_secs(1), max_delay).unwrap()
+                > Duration::ZERO
+        );
+
+        // Exceeds the budget of the band.
+        assert_eq!(
+            None,
+            reserve_tx(&conf, 869525000, Duration::from_secs(37), max_delay)
+        );
+
+        // The reserved (delayed) transmission is accounted at its time of transmission.
+        let usage = get_tx_airtime_usage(&conf);
+        assert_eq!(
+            Some(Duration::from_secs(17)),
+            usage
+                .iter()
+                .find(|v| v.frequency == 869525000)
+                .map(|v| v.airtime)
+        );
+    }
+
+    #[test]
+    fn test_get_tx_airtime_usage() {
+        let conf = config::DutyCycle {
+            window: Duration::from_secs(3600),
+            ..Default::default()
+        };
+
+        record_tx(&conf, 867100000, Duration::from_secs(18));
+        record_tx(&conf, 867100000, Duration::from_secs(18));
+
+        let usage = get_tx_airtime_usage(&conf);
+        assert_eq!(
+            Some(&TxAirtimeUsage {
+                frequency: 867100000,
+                airtime: Duration::from_secs(36),
+                duty_cycle: 1.0,
+            }),
+            usage.iter().find(|v| v.frequency == 867100000)
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    // Mesh packet encoding or decoding error (returned by the packets crate).
+    #[error("{0}")]
+    Packet(anyhow::Error),
+
+    // Mesh packet MIC validation failed.
+    #[error("Invalid MIC")]
+    InvalidMic,
+
+    // Missing or invalid field in a message received from the Concentratord or forwarder.
+    #[error("{0}")]
+    InvalidMessage(String),
+
+    // Error related to the communication with the Concentratord(s) or forwarder.
+    #[error("{0}")]
+    Backend(String),
+
+    #[error("{0}")]
+    Config(String),
+
+    // The packet can't be routed, e.g. because its frequency or data-rate is not part of the
+    // configured mappings, or because there is no uplink context for it.
+    #[error("{0}")]
+    Routing(String),
+
+    #[error(transparent)]
+    TomlDe(#[from] toml::de::Error),
+
+    #[error(transparent)]
+    TomlSer(#[from] toml::ser::Error),
+
+    #[error(transparent)]
+    TomlEdit(#[from] toml_edit::TomlError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Fmt(#[from] std::fmt::Error),
+
+    #[error(transparent)]
+    Zmq(#[from] zmq::Error),
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    ProtobufDecode(#[from] prost::DecodeError),
+
+    #[error(transparent)]
+    ProtobufEncode(#[from] prost::EncodeError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    TryFromInt(#[from] std::num::TryFromIntError),
+
+    #[error(transparent)]
+    TryFromSlice(#[from] std::array::TryFromSliceError),
+
+    #[error(transparent)]
+    FromUtf8(#[from] std::string::FromUtf8Error),
+}
+
+impl Error {
+    // Returns the error kind, e.g. to be used as metric label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Packet(_) => "packet",
+            Error::InvalidMic => "invalid_mic",
+            Error::InvalidMessage(_)
+            | Error::ProtobufDecode(_)
+            | Error::ProtobufEncode(_)
+            | Error::Json(_)
+            | Error::TryFromInt(_)
+            | Error::TryFromSlice(_)
+            | Error::FromUtf8(_) => "invalid_message",
+            Error::Backend(_) | Error::Zmq(_) | Error::Http(_) => "backend",
+            Error::Config(_) | Error::TomlDe(_) | Error::TomlSer(_) | Error::TomlEdit(_) => {
+                "config"
+            }
+            Error::Routing(_) => "routing",
+            Error::Io(_) | Error::Fmt(_) => "io",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        assert_eq!("invalid_mic", Error::InvalidMic.kind());
+        assert_eq!(
+            "packet",
+            Error::Packet(anyhow::anyhow!("Input is empty")).kind()
+        );
+        assert_eq!(
+            "invalid_message",
+            Error::from(u8::try_from(256u16).unwrap_err()).kind()
+        );
+        assert_eq!(
+            "routing",
+            Error::Routing("No mesh frequencies are configured".into()).kind()
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            "tx_info is None",
+            Error::InvalidMessage("tx_info is None".into()).to_string()
+        );
+    }
+}
@@ -0,0 +1,604 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::panic;
+use std::time::{Duration, Instant};
+
+use chirpstack_api::gw;
+use log::{error, info, warn};
+use rand::random;
+use sha2::{Digest, Sha256};
+use tokio::task;
+use tokio::time::{sleep, timeout};
+
+use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
+use crate::mesh::{get_mesh_frequency, get_tx_power};
+use crate::{backend, heartbeat, helpers, location, packets, stats, telemetry};
+
+// Delay of the attestation on startup, such that it does not collide with the first heartbeat.
+const ATTESTATION_STARTUP_DELAY: Duration = Duration::from_secs(10);
+
+// Max. time to wait for the shutdown event to be sent, e.g. when it is delayed by the duty-cycle.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Content of the crash marker file while running and after a clean shutdown.
+const CRASH_MARKER_RUNNING: &str = "running";
+const CRASH_MARKER_STOPPED: &str = "stopped";
+// First line of the crash marker file after a panic, followed by the crash report.
+const CRASH_MARKER_PANIC: &str = "panic";
+
+// Max. number of RX and TX frequencies each in the stats event, such that it fits the max. event
+// size. The frequencies with the most packets are reported.
+const STATS_MAX_FREQUENCIES: usize = 16;
+
+// Feature flags of the attestation event.
+const FEATURES: [(u8, &str); 8] = [
+    (0x01, "join_requests_relay_only"),
+    (0x02, "bad_channel_avoidance"),
+    (0x04, "fallback_data_rates"),
+    (0x08, "max_airtime"),
+    (0x10, "border_routes"),
+    (0x20, "heartbeat_suppress_if_active"),
+    (0x40, "tdma"),
+    (0x80, "duty_cycle"),
+];
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    // Only Relay Gateways report events to the Border Gateway.
+    if conf.mesh.border_gateway {
+        return Ok(());
+    }
+
+    if !conf.mesh.tx_airtime_report_interval.is_zero() {
+        info!(
+            "Starting TX airtime report loop, tx_airtime_report_interval: {:?}",
+            conf.mesh.tx_airtime_report_interval
+        );
+
+        tokio::spawn({
+            let tx_airtime_report_interval = conf.mesh.tx_airtime_report_interval;
+
+            async move {
+                loop {
+                    sleep(tx_airtime_report_interval).await;
+                    if let Err(e) = report_tx_airtime().await {
+                        error!("Report TX airtime error, error: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    if !conf.mesh.stats_report_interval.is_zero() {
+        info!(
+            "Starting stats report loop, stats_report_interval: {:?}",
+            conf.mesh.stats_report_interval
+        );
+
+        tokio::spawn({
+            let stats_report_interval = conf.mesh.stats_report_interval;
+
+            async move {
+                loop {
+                    sleep(stats_report_interval).await;
+                    if let Err(e) = report_stats().await {
+                        error!("Report stats error, error: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    if !conf.mesh.telemetry.report_interval.is_zero() {
+        info!(
+            "Starting telemetry report loop, report_interval: {:?}",
+            conf.mesh.telemetry.report_interval
+        );
+
+        tokio::spawn({
+            let report_interval = conf.mesh.telemetry.report_interval;
+
+            async move {
+                loop {
+                    sleep(report_interval).await;
+                    if let Err(e) = report_telemetry().await {
+                        error!("Report telemetry error, error: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    let boot = if conf.mesh.boot_announcement.enabled {
+        let (reason, crash) = init_crash_marker(&conf.mesh.boot_announcement.crash_marker_file)?;
+        if reason == packets::BootReason::CrashRecovery {
+            warn!("Previous process did not shut down cleanly, reporting crash recovery");
+        }
+        setup_panic_hook(conf.mesh.boot_announcement.crash_marker_file.clone());
+
+        Some(packets::BootEvent {
+            reason,
+            features: get_features(conf),
+            boot_id: heartbeat::get_boot_id(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            crash,
+        })
+    } else {
+        None
+    };
+
+    if !conf.mesh.attestation.enabled && boot.is_none() {
+        return Ok(());
+    }
+
+    info!(
+        "Scheduling startup events, delay: {:?}",
+        ATTESTATION_STARTUP_DELAY
+    );
+
+    tokio::spawn(async move {
+        sleep(ATTESTATION_STARTUP_DELAY).await;
+        if let Err(e) = report_startup(boot).await {
+            error!("Report startup events error, error: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+// Reports the attestation on demand (e.g. on SIGUSR1), if enabled.
+pub fn request_attestation() {
+    let conf = config::get();
+    if conf.mesh.border_gateway || !conf.mesh.attestation.enabled {
+        return;
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = report_attestation().await {
+            error!("Report attestation error, error: {}", e);
+        }
+    });
+}
+
+pub async fn report_attestation() -> Result<()> {
+    let conf = config::get();
+    let attestation = get_attestation(&conf)?;
+    send_events(
+        "attestation",
+        vec![packets::Event::Attestation(attestation)],
+    )
+    .await
+}
+
+// Reports the attestation (if enabled) and the given boot event in a single mesh packet.
+async fn report_startup(boot: Option<packets::BootEvent>) -> Result<()> {
+    let conf = config::get();
+    let mut events = vec![];
+    if conf.mesh.attestation.enabled {
+        events.push(packets::Event::Attestation(get_attestation(&conf)?));
+    }
+    if let Some(boot) = boot {
+        events.push(packets::Event::Boot(boot));
+    }
+
+    send_events("startup", events).await
+}
+
+pub async fn report_tx_airtime() -> Result<()> {
+    let airtime = stats::get_mesh_tx_airtime();
+    send_events(
+        "TX airtime",
+        vec![packets::Event::TxAirtime(packets::TxAirtimeEvent {
+            airtime: airtime.as_millis().try_into().unwrap_or(u32::MAX),
+        })],
+    )
+    .await
+}
+
+pub async fn report_stats() -> Result<()> {
+    let pl = stats::take_relay_stats();
+    let uplink_acks = stats::take_relay_uplink_acks();
+    let airtime = stats::get_mesh_tx_airtime();
+
+    // The location precedes the stats, such that the Border Gateway can add it to the forwarded
+    // gateway stats.
+    let mut events = vec![];
+    if let Some(location) = location::get(&config::get()) {
+        events.push(packets::Event::Location(location));
+    }
+    events.push(packets::Event::Stats(get_stats_event(
+        &pl,
+        backend::get_gateway_id().await?,
+        airtime.as_millis().try_into().unwrap_or(u32::MAX),
+        uplink_acks,
+    )));
+
+    send_events("stats", events).await
+}
+
+pub async fn report_telemetry() -> Result<()> {
+    let conf = config::get();
+    // The values are read from files or commands, which might block.
+    let telemetry = task::spawn_blocking(move || telemetry::get(&conf))
+        .await
+        .map_err(|e| Error::Backend(e.to_string()))?;
+
+    send_events("telemetry", vec![packets::Event::Telemetry(telemetry)]).await
+}
+
+// Announces the shutdown of the Relay Gateway, such that the Border Gateway does not need to wait
+// for missed heartbeats.
+pub async fn report_shutdown(conf: &Configuration) -> Result<()> {
+    if conf.mesh.border_gateway {
+        return Ok(());
+    }
+
+    timeout(
+        SHUTDOWN_TIMEOUT,
+        send_events("shutdown", vec![packets::Event::Shutdown]),
+    )
+    .await
+    .map_err(|_| Error::Backend("Sending shutdown event timeout".into()))?
+}
+
+// Marks the clean shutdown in the crash marker file (Relay Gateway only).
+pub fn mark_clean_shutdown(conf: &Configuration) -> Result<()> {
+    if conf.mesh.border_gateway || !conf.mesh.boot_announcement.enabled {
+        return Ok(());
+    }
+
+    fs::write(
+        &conf.mesh.boot_announcement.crash_marker_file,
+        CRASH_MARKER_STOPPED,
+    )?;
+    Ok(())
+}
+
+// Acknowledges the relayed uplink (Border Gateway only).
+pub async fn send_uplink_ack(relay_id: [u8; 4], uplink_id: u16) -> Result<()> {
+    send_events(
+        "uplink ACK",
+        vec![packets::Event::UplinkAck(packets::UplinkAckEvent {
+            relay_id,
+            uplink_id,
+        })],
+    )
+    .await
+}
+
+// Acknowledges the relayed downlink, with the TX acknowledgement status of the Concentratord
+// (Relay Gateway only).
+pub async fn send_downlink_ack(uplink_id: u16, status: gw::TxAckStatus) -> Result<()> {
+    send_events(
+        "downlink ACK",
+        vec![packets::Event::DownlinkAck(packets::DownlinkAckEvent {
+            uplink_id,
+            status: u8::try_from(i32::from(status)).unwrap_or(u8::MAX),
+        })],
+    )
+    .await
+}
+
+// Sends the TDMA schedule (Border Gateway only).
+pub async fn send_tdma_beacon(beacon: packets::TdmaBeaconEvent) -> Result<()> {
+    send_events("TDMA beacon", vec![packets::Event::TdmaBeacon(beacon)]).await
+}
+
+// Sends the max hop count (Border Gateway only).
+pub async fn send_max_hop_count(pl: packets::MaxHopCountEvent) -> Result<()> {
+    send_events("max hop count", vec![packets::Event::MaxHopCount(pl)]).await
+}
+
+// Sends a challenge (Border Gateway only).
+pub async fn send_challenge(pl: packets::ChallengeEvent) -> Result<()> {
+    send_events("challenge", vec![packets::Event::Challenge(pl)]).await
+}
+
+// Sends the response to a challenge (Relay Gateway only).
+pub async fn send_challenge_response(pl: packets::ChallengeEvent) -> Result<()> {
+    send_events(
+        "challenge response",
+        vec![packets::Event::ChallengeResponse(pl)],
+    )
+    .await
+}
+
+async fn send_events(name: &str, events: Vec<packets::Event>) -> Result<()> {
+    let conf = config::get();
+    let (timestamp, timestamp_monotonic) = heartbeat::get_timestamp();
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 1,
+            key_index: conf.mesh.signing_key_index,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            timestamp,
+            timestamp_monotonic,
+            relay_id: backend::get_relay_id().await.unwrap_or_default(),
+            events,
+        }),
+        mic: None,
+    };
+    helpers::set_mic(&conf, &mut packet)?;
+
+    let pl = gw::DownlinkFrame {
+        downlink_id: random(),
+        items: vec![gw::DownlinkFrameItem {
+            phy_payload: packet.to_vec().map_err(Error::Packet)?,
+            tx_info: Some(gw::DownlinkTxInfo {
+                frequency: get_mesh_frequency(&conf)?,
+                modulation: Some(helpers::data_rate_to_gw_modulation(
+                    &conf.mesh.data_rate,
+                    false,
+                )),
+                power: get_tx_power(&conf).await,
+                timing: Some(gw::Timing {
+                    parameters: Some(gw::timing::Parameters::Immediately(
+                        gw::ImmediatelyTimingInfo {},
+                    )),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    info!(
+        "Sending {} event packet, downlink_id: {}, mesh_packet: {}",
+        name, pl.downlink_id, packet
+    );
+    backend::mesh(&pl).await
+}
+
+// Returns the names of the features set in the given bitmask.
+pub fn get_feature_names(features: u8) -> Vec<String> {
+    FEATURES
+        .iter()
+        .filter(|(flag, _)| features & flag != 0)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+fn get_stats_event(
+    pl: &gw::GatewayStats,
+    gateway_id: [u8; 8],
+    tx_airtime: u32,
+    uplink_acks: (u32, u32),
+) -> packets::StatsEvent {
+    packets::StatsEvent {
+        gateway_id,
+        rx_packets_received: pl.rx_packets_received,
+        rx_packets_received_ok: pl.rx_packets_received_ok,
+        tx_packets_received: pl.tx_packets_received,
+        tx_packets_emitted: pl.tx_packets_emitted,
+        tx_airtime,
+        uplinks_acked: uplink_acks.0.try_into().unwrap_or(u16::MAX),
+        uplinks_not_acked: uplink_acks.1.try_into().unwrap_or(u16::MAX),
+        rx_packets_per_frequency: get_frequency_counts(&pl.rx_packets_per_frequency),
+        tx_packets_per_frequency: get_frequency_counts(&pl.tx_packets_per_frequency),
+    }
+}
+
+// Returns the (at most STATS_MAX_FREQUENCIES) frequencies with the most packets, sorted by
+// frequency.
+fn get_frequency_counts(m: &HashMap<u32, u32>) -> Vec<packets::FrequencyCount> {
+    let mut counts: Vec<packets::FrequencyCount> = m
+        .iter()
+        .map(|(k, v)| packets::FrequencyCount {
+            frequency: *k,
+            count: (*v).try_into().unwrap_or(u16::MAX),
+        })
+        .collect();
+    counts.sort_by_key(|v| (std::cmp::Reverse(v.count), v.frequency));
+    counts.truncate(STATS_MAX_FREQUENCIES);
+    counts.sort_by_key(|v| v.frequency);
+    counts
+}
+
+// Returns the boot reason based on the content of the crash marker file, after which the file is
+// marked as running. A missing file means that the gateway was power cycled (or rebooted), as the
+// file is expected to be stored on volatile storage.
+fn init_crash_marker(path: &str) -> Result<(packets::BootReason, Option<packets::CrashSummary>)> {
+    let out = match fs::read_to_string(path) {
+        Ok(v) if v == CRASH_MARKER_STOPPED => (packets::BootReason::Restart, None),
+        Ok(v) => (packets::BootReason::CrashRecovery, get_crash_summary(&v)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (packets::BootReason::PowerCycle, None),
+        Err(e) => return Err(e.into()),
+    };
+
+    fs::write(path, CRASH_MARKER_RUNNING)?;
+    Ok(out)
+}
+
+// Writes a crash report to the crash marker file on panic, which is summarized in the boot event
+// on the next start.
+fn setup_panic_hook(path: String) {
+    let started_at = Instant::now();
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let report = format!(
+            "{}\n{}\n{}",
+            CRASH_MARKER_PANIC,
+            started_at.elapsed().as_secs(),
+            info
+        );
+        if let Err(e) = fs::write(&path, report) {
+            eprintln!("Write crash report error, error: {}", e);
+        }
+        default_hook(info);
+    }));
+}
+
+// Returns the crash summary of the given crash marker file content, if it contains a crash report
+// (panic marker, uptime in seconds and panic message, separated by newlines).
+fn get_crash_summary(report: &str) -> Option<packets::CrashSummary> {
+    let mut lines = report.splitn(3, '\n');
+    if lines.next()? != CRASH_MARKER_PANIC {
+        return None;
+    }
+    let uptime: u64 = lines.next()?.parse().ok()?;
+    let message = lines.next().unwrap_or_default();
+
+    warn!(
+        "Previous process panicked, uptime: {:?}, message: {}",
+        Duration::from_secs(uptime),
+        message
+    );
+
+    let hash = Sha256::digest(message.as_bytes());
+    let mut panic_hash: [u8; 4] = [0; 4];
+    panic_hash.copy_from_slice(&hash[0..4]);
+
+    Some(packets::CrashSummary {
+        panic_hash,
+        uptime: uptime.try_into().unwrap_or(u32::MAX),
+    })
+}
+
+fn get_attestation(conf: &Configuration) -> Result<packets::AttestationEvent> {
+    Ok(packets::AttestationEvent {
+        config_checksum: get_config_checksum(conf)?,
+        features: get_features(conf),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        firmware_version: conf.mesh.attestation.firmware_version.clone(),
+    })
+}
+
+// Returns the first 4 bytes of the SHA256 hash of the mesh and mappings configuration. Other
+// sections (e.g. logging) are excluded as these do not affect the mesh behavior.
+fn get_config_checksum(conf: &Configuration) -> Result<[u8; 4]> {
+    let b = serde_json::to_vec(&(&conf.mesh, &conf.mappings))?;
+    let hash = Sha256::digest(b);
+
+    let mut checksum: [u8; 4] = [0; 4];
+    checksum.copy_from_slice(&hash[0..4]);
+    Ok(checksum)
+}
+
+fn get_features(conf: &Configuration) -> u8 {
+    let enabled = [
+        conf.mesh.join_requests.relay_only,
+        conf.mesh.bad_channel_avoidance.enabled,
+        !conf.mesh.fallback_data_rates.is_empty(),
+        !conf.mesh.max_airtime.is_zero(),
+        !conf.mesh.border_routes.is_empty(),
+        conf.mesh.heartbeat_suppress_if_active,
+        conf.mesh.tdma.enabled,
+        conf.mesh.duty_cycle.enabled,
+    ];
+
+    FEATURES
+        .iter()
+        .zip(enabled)
+        .filter(|(_, enabled)| *enabled)
+        .fold(0, |features, ((flag, _), _)| features | flag)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_features() {
+        let mut conf = Configuration::default();
+        assert_eq!(0, get_features(&conf));
+
+        conf.mesh.join_requests.relay_only = true;
+        conf.mesh.max_airtime = Duration::from_millis(400);
+        let features = get_features(&conf);
+        assert_eq!(0x09, features);
+        assert_eq!(
+            vec!["join_requests_relay_only", "max_airtime"],
+            get_feature_names(features)
+        );
+    }
+
+    #[test]
+    fn test_init_crash_marker() {
+        let file = std::env::temp_dir().join("chirpstack-gateway-mesh-crash-marker-test");
+        let file = file.to_str().unwrap();
+        let _ = fs::remove_file(file);
+
+        assert_eq!(
+            (packets::BootReason::PowerCycle, None),
+            init_crash_marker(file).unwrap()
+        );
+        assert_eq!(
+            (packets::BootReason::CrashRecovery, None),
+            init_crash_marker(file).unwrap()
+        );
+
+        fs::write(file, CRASH_MARKER_STOPPED).unwrap();
+        assert_eq!(
+            (packets::BootReason::Restart, None),
+            init_crash_marker(file).unwrap()
+        );
+        assert_eq!(CRASH_MARKER_RUNNING, fs::read_to_string(file).unwrap());
+
+        fs::write(file, "panic\n3600\npanicked at src/main.rs:1:1:\nboom").unwrap();
+        let (reason, crash) = init_crash_marker(file).unwrap();
+        assert_eq!(packets::BootReason::CrashRecovery, reason);
+        assert_eq!(3600, crash.unwrap().uptime);
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_get_crash_summary() {
+        assert_eq!(None, get_crash_summary(CRASH_MARKER_RUNNING));
+        assert_eq!(None, get_crash_summary("panic\ninvalid\nboom"));
+
+        let a = get_crash_summary("panic\n10\nboom").unwrap();
+        let b = get_crash_summary("panic\n20\nboom").unwrap();
+        let c = get_crash_summary("panic\n10\nbang").unwrap();
+        assert_eq!(10, a.uptime);
+        assert_eq!(a.panic_hash, b.panic_hash);
+        assert_ne!(a.panic_hash, c.panic_hash);
+    }
+
+    #[test]
+    fn test_get_stats_event() {
+        let pl = gw::GatewayStats {
+            rx_packets_received: 100000,
+            rx_packets_received_ok: 90000,
+            tx_packets_received: 2,
+            tx_packets_emitted: 1,
+            rx_packets_per_frequency: (0..20).map(|i| (868000000 + i * 100000, i)).collect(),
+            tx_packets_per_frequency: [(869525000, 100000)].into(),
+            ..Default::default()
+        };
+
+        let event = get_stats_event(&pl, [1, 2, 3, 4, 5, 6, 7, 8], 1000, (70000, 1));
+        assert_eq!(100000, event.rx_packets_received);
+        assert_eq!(1000, event.tx_airtime);
+        assert_eq!(u16::MAX, event.uplinks_acked);
+        assert_eq!(1, event.uplinks_not_acked);
+
+        // Only the frequencies with the most packets are included.
+        assert_eq!(STATS_MAX_FREQUENCIES, event.rx_packets_per_frequency.len());
+        assert_eq!(
+            packets::FrequencyCount {
+                frequency: 868400000,
+                count: 4,
+            },
+            event.rx_packets_per_frequency[0]
+        );
+
+        // Counts are capped.
+        assert_eq!(
+            vec![packets::FrequencyCount {
+                frequency: 869525000,
+                count: u16::MAX,
+            }],
+            event.tx_packets_per_frequency
+        );
+
+        // The event fits the max. event size.
+        assert!(packets::Event::Stats(event).to_vec().unwrap().len() <= packets::EVENT_MAX_SIZE);
+    }
+}
@@ -1,37 +1,74 @@
-use std::time::SystemTime;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
 use chirpstack_api::gw;
-use log::{error, info};
+use log::{debug, error, info, warn};
+use once_cell::sync::Lazy;
 use rand::random;
+use sha2::{Digest, Sha256};
 use tokio::time::sleep;
 
 use crate::backend;
+use crate::challenge;
 use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
 use crate::helpers;
-use crate::mesh::get_mesh_frequency;
+use crate::mesh::{self, get_mesh_frequency, get_tx_power};
 use crate::packets;
 
+// Wall-clock times before 2020-01-01 are considered invalid, e.g. a Relay Gateway without RTC
+// that did not (yet) sync its time using NTP.
+const MIN_VALID_TIME: Duration = Duration::from_secs(1_577_836_800);
+
+static STARTED_AT: Lazy<Instant> = Lazy::new(Instant::now);
+// Identifies the time base of the monotonic timestamps (see get_uptime), which is announced in the
+// boot event. In case the kernel boot ID is not available, the uptime falls back to the time since
+// start, thus a random ID is used.
+static BOOT_ID: Lazy<[u8; 4]> = Lazy::new(|| {
+    fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .ok()
+        .and_then(|v| hex::decode(v.get(0..8)?).ok())
+        .and_then(|v| v.try_into().ok())
+        .unwrap_or_else(random)
+});
+static LAST_TIMESTAMP: Mutex<(u64, bool)> = Mutex::new((0, false));
+
 pub async fn setup(conf: &Configuration) -> Result<()> {
     // Only Relay gatewways need to report heartbeat as the Border Gateway is already internet
     // connected and reports status through the Concentratord.
-    if conf.mesh.border_gateway || conf.mesh.heartbeat_interval.is_zero() {
+    let heartbeat_interval = conf
+        .mesh
+        .get_heartbeat_interval(backend::get_relay_id().await.unwrap_or_default());
+    if conf.mesh.border_gateway || heartbeat_interval.is_zero() {
         return Ok(());
     }
 
+    Lazy::force(&STARTED_AT);
+
     info!(
         "Starting heartbeat loop, heartbeat_interval: {:?}",
-        conf.mesh.heartbeat_interval
+        heartbeat_interval
     );
 
     tokio::spawn({
-        let heartbeat_interval = conf.mesh.heartbeat_interval;
+        let heartbeat_suppress_if_active = conf.mesh.heartbeat_suppress_if_active;
 
         async move {
             loop {
-                if let Err(e) = report_heartbeat().await {
+                if heartbeat_suppress_if_active && mesh::uplink_relayed_within(heartbeat_interval) {
+                    debug!(
+                        "Skipping heartbeat, an uplink was relayed within the heartbeat interval"
+                    );
+                } else if let Err(e) = report_heartbeat().await {
                     error!("Report heartbeat error, error: {}", e);
                 }
+
+                // The challenge is also answered when the heartbeat is suppressed, as the relayed
+                // uplinks do not contain the challenge.
+                if let Err(e) = challenge::report_response().await {
+                    error!("Report challenge response error, error: {}", e);
+                }
                 sleep(heartbeat_interval).await;
             }
         }
@@ -42,32 +79,40 @@ pub async fn setup(conf: &Configuration) -> Result<()> {
 
 pub async fn report_heartbeat() -> Result<()> {
     let conf = config::get();
+    let (timestamp, timestamp_monotonic) = get_heartbeat_timestamp(&conf);
 
     let mut packet = packets::MeshPacket {
         mhdr: packets::MHDR {
             payload_type: packets::PayloadType::Heartbeat,
             hop_count: 1,
+            key_index: conf.mesh.signing_key_index,
         },
         payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
-            timestamp: SystemTime::now(),
+            timestamp,
+            timestamp_monotonic,
             relay_id: backend::get_relay_id().await.unwrap_or_default(),
             relay_path: vec![],
+            config_checksum: if conf.mesh.heartbeat_config_checksum {
+                Some(get_config_checksum(&conf)?)
+            } else {
+                None
+            },
         }),
         mic: None,
     };
-    packet.set_mic(conf.mesh.signing_key)?;
+    helpers::set_mic(&conf, &mut packet)?;
 
     let pl = gw::DownlinkFrame {
         downlink_id: random(),
         items: vec![gw::DownlinkFrameItem {
-            phy_payload: packet.to_vec()?,
+            phy_payload: packet.to_vec().map_err(Error::Packet)?,
             tx_info: Some(gw::DownlinkTxInfo {
                 frequency: get_mesh_frequency(&conf)?,
                 modulation: Some(helpers::data_rate_to_gw_modulation(
                     &conf.mesh.data_rate,
                     false,
                 )),
-                power: conf.mesh.tx_power,
+                power: get_tx_power(&conf).await,
                 timing: Some(gw::Timing {
                     parameters: Some(gw::timing::Parameters::Immediately(
                         gw::ImmediatelyTimingInfo {},
@@ -86,3 +131,76 @@ pub async fn report_heartbeat() -> Result<()> {
     );
     backend::mesh(&pl).await
 }
+
+// Returns the first 4 bytes of the SHA256 hash of the mappings and the mesh parameters that must
+// be equal on the Relay and Border Gateways. The relayed uplinks refer to the channels and
+// data-rates by index and all gateways must use the same mesh frequencies and data-rate.
+pub fn get_config_checksum(conf: &Configuration) -> Result<[u8; 4]> {
+    let b = serde_json::to_vec(&(&conf.mappings, &conf.mesh.frequencies, &conf.mesh.data_rate))?;
+    let hash = Sha256::digest(b);
+
+    let mut checksum: [u8; 4] = [0; 4];
+    checksum.copy_from_slice(&hash[0..4]);
+    Ok(checksum)
+}
+
+// Returns the heartbeat timestamp. Version 1 heartbeats only contain the wall-clock time in
+// seconds, thus the millisecond precision and monotonic timestamps (see get_timestamp) are only
+// used when packet_format_version 2 is configured.
+pub fn get_heartbeat_timestamp(conf: &Configuration) -> (u64, bool) {
+    if conf.mesh.packet_format_version >= 2 {
+        return get_timestamp();
+    }
+
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    (secs * 1000, false)
+}
+
+// Returns the uplink timestamp, which is only encoded in version 2 packets (see get_timestamp).
+pub fn get_uplink_timestamp(conf: &Configuration) -> (Option<u64>, bool) {
+    if conf.mesh.packet_format_version < 2 {
+        return (None, false);
+    }
+
+    let (timestamp, timestamp_monotonic) = get_timestamp();
+    (Some(timestamp), timestamp_monotonic)
+}
+
+// Returns the heartbeat timestamp in milliseconds and whether it is monotonic. In case the
+// wall-clock time is not valid, this falls back to the time since boot so that the ordering of
+// heartbeats remains correct. This is also used for events and version 2 uplinks. The returned
+// timestamps are strictly increasing, as replay protection rejects a repeated timestamp.
+pub fn get_timestamp() -> (u64, bool) {
+    let (timestamp, timestamp_monotonic) = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) if d >= MIN_VALID_TIME => (d.as_millis() as u64, false),
+        _ => {
+            warn!("System time is not valid, using monotonic heartbeat timestamp");
+            (get_uptime().as_millis() as u64, true)
+        }
+    };
+
+    let mut last = LAST_TIMESTAMP.lock().unwrap();
+    *last = if last.1 == timestamp_monotonic {
+        (timestamp.max(last.0 + 1), timestamp_monotonic)
+    } else {
+        (timestamp, timestamp_monotonic)
+    };
+    *last
+}
+
+// Returns the boot ID, which changes when the monotonic timestamps restart.
+pub fn get_boot_id() -> [u8; 4] {
+    *BOOT_ID
+}
+
+// Returns the time since boot, or the time since start if this is not available.
+fn get_uptime() -> Duration {
+    fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|v| v.split_whitespace().next()?.parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+        .unwrap_or_else(|| STARTED_AT.elapsed())
+}
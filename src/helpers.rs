@@ -1,6 +1,10 @@
-use anyhow::Result;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::config;
+use log::warn;
+
+use crate::error::{Error, Result};
+use crate::{config, packets, proto};
 use chirpstack_api::gw;
 
 pub fn frequency_to_chan(freq: u32) -> Result<u8> {
@@ -11,7 +15,10 @@ pub fn frequency_to_chan(freq: u32) -> Result<u8> {
         }
     }
 
-    Err(anyhow!("Frequency {} does not map to a channel", freq))
+    Err(Error::Routing(format!(
+        "Frequency {} does not map to a channel",
+        freq
+    )))
 }
 
 pub fn chan_to_frequency(chan: u8) -> Result<u32> {
@@ -20,16 +27,32 @@ pub fn chan_to_frequency(chan: u8) -> Result<u32> {
         .channels
         .get(chan as usize)
         .cloned()
-        .ok_or_else(|| anyhow!("Channel {} does not map to a frequency", chan))
+        .ok_or_else(|| Error::Routing(format!("Channel {} does not map to a frequency", chan)))
 }
 
 pub fn modulation_to_dr(modulation: &gw::Modulation) -> Result<u8> {
+    let dr = gw_modulation_to_data_rate(modulation)?;
+
+    let conf = config::get();
+    for (i, d) in conf.mappings.data_rates.iter().enumerate() {
+        if dr == *d {
+            return Ok(i as u8);
+        }
+    }
+
+    Err(Error::Routing(format!(
+        "Modulation: {:?} does not map to a data-rate",
+        modulation
+    )))
+}
+
+pub fn gw_modulation_to_data_rate(modulation: &gw::Modulation) -> Result<config::DataRate> {
     let mod_params = modulation
         .parameters
         .as_ref()
-        .ok_or_else(|| anyhow!("parameters must not be None"))?;
+        .ok_or_else(|| Error::InvalidMessage("parameters must not be None".into()))?;
 
-    let dr = match mod_params {
+    Ok(match mod_params {
         gw::modulation::Parameters::Lora(v) => config::DataRate {
             modulation: config::Modulation::LORA,
             bandwidth: v.bandwidth,
@@ -47,7 +70,7 @@ pub fn modulation_to_dr(modulation: &gw::Modulation) -> Result<u8> {
                 gw::CodeRate::CrLi46 => config::CodeRate::CrLi46,
                 gw::CodeRate::CrLi48 => config::CodeRate::CrLi48,
                 gw::CodeRate::CrUndefined => {
-                    return Err(anyhow!("code_rate is CrUndefined"));
+                    return Err(Error::InvalidMessage("code_rate is CrUndefined".into()));
                 }
             }),
             spreading_factor: v.spreading_factor as u8,
@@ -59,34 +82,29 @@ pub fn modulation_to_dr(modulation: &gw::Modulation) -> Result<u8> {
             ..Default::default()
         },
         gw::modulation::Parameters::LrFhss(_) => {
-            return Err(anyhow!("LR-FHSS is not supported"));
+            return Err(Error::InvalidMessage("LR-FHSS is not supported".into()));
         }
-    };
-
-    let conf = config::get();
-    for (i, d) in conf.mappings.data_rates.iter().enumerate() {
-        if dr == *d {
-            return Ok(i as u8);
-        }
-    }
-
-    Err(anyhow!(
-        "Modulation: {:?} does not map to a data-rate",
-        modulation
-    ))
+    })
 }
 
 pub fn dr_to_modulation(dr: u8, ipol: bool) -> Result<gw::Modulation> {
     let conf = config::get();
-    let dr = conf
-        .mappings
-        .data_rates
-        .get(dr as usize)
-        .ok_or_else(|| anyhow!("Data-rate {} does not map to a modulation", dr))?;
+    let dr =
+        conf.mappings.data_rates.get(dr as usize).ok_or_else(|| {
+            Error::Routing(format!("Data-rate {} does not map to a modulation", dr))
+        })?;
 
     Ok(data_rate_to_gw_modulation(dr, ipol))
 }
 
+// Returns true if the given PHYPayload is a LoRaWAN join-request.
+pub fn is_join_request(phy_payload: &[u8]) -> bool {
+    phy_payload
+        .first()
+        .map(|v| v >> 5 == 0x00)
+        .unwrap_or_default()
+}
+
 pub fn data_rate_to_gw_modulation(dr: &config::DataRate, ipol: bool) -> gw::Modulation {
     match dr.modulation {
         config::Modulation::LORA => gw::Modulation {
@@ -143,7 +161,7 @@ pub fn tx_power_to_index(tx_power: i32) -> Result<u8> {
         }
     }
 
-    out.ok_or_else(|| anyhow!("No TX Power equal or lower than: {}", tx_power))
+    out.ok_or_else(|| Error::Routing(format!("No TX Power equal or lower than: {}", tx_power)))
 }
 
 pub fn index_to_tx_power(tx_power: u8) -> Result<i32> {
@@ -152,7 +170,112 @@ pub fn index_to_tx_power(tx_power: u8) -> Result<i32> {
         .tx_power
         .get(tx_power as usize)
         .cloned()
-        .ok_or_else(|| anyhow!("TX Power index {} does not exist", tx_power))
+        .ok_or_else(|| Error::Routing(format!("TX Power index {} does not exist", tx_power)))
+}
+
+// Returns the time-on-air of a frame with the given payload size, using the given data-rate.
+// This assumes an explicit header, CRC enabled and the default preamble length (LoRa: 8 symbols,
+// FSK: 5 bytes).
+pub fn airtime(dr: &config::DataRate, payload_size: usize) -> Duration {
+    let payload_size = payload_size as f64;
+
+    match dr.modulation {
+        config::Modulation::LORA => {
+            if dr.bandwidth == 0 {
+                return Duration::ZERO;
+            }
+
+            let sf = dr.spreading_factor as f64;
+            let t_sym = 2f64.powf(sf) / dr.bandwidth as f64;
+
+            // Low data-rate optimization is enabled when the symbol time exceeds 16ms.
+            let de = if t_sym > 0.016 { 1.0 } else { 0.0 };
+
+            // The number of coded bits per 4 information bits (CR + 4).
+            let cr = match dr.code_rate {
+                Some(config::CodeRate::Cr45) | Some(config::CodeRate::CrLi45) | None => 5.0,
+                Some(config::CodeRate::Cr46) | Some(config::CodeRate::CrLi46) => 6.0,
+                Some(config::CodeRate::Cr47) => 7.0,
+                Some(config::CodeRate::Cr48) | Some(config::CodeRate::CrLi48) => 8.0,
+                Some(config::CodeRate::Cr38) => 4.0 * 8.0 / 3.0,
+                Some(config::CodeRate::Cr26) => 4.0 * 6.0 / 2.0,
+                Some(config::CodeRate::Cr14) => 4.0 * 4.0,
+                Some(config::CodeRate::Cr16) => 4.0 * 6.0,
+                Some(config::CodeRate::Cr56) => 4.0 * 6.0 / 5.0,
+            };
+
+            let t_preamble = (8.0 + 4.25) * t_sym;
+            let payload_symbols = 8.0
+                + ((8.0 * payload_size - 4.0 * sf + 28.0 + 16.0) / (4.0 * (sf - 2.0 * de)))
+                    .ceil()
+                    .max(0.0)
+                    * cr;
+
+            Duration::from_secs_f64(t_preamble + payload_symbols * t_sym)
+        }
+        config::Modulation::FSK => {
+            if dr.bitrate == 0 {
+                return Duration::ZERO;
+            }
+
+            // Preamble (5) + sync-word (3) + length (1) + payload + CRC (2).
+            Duration::from_secs_f64((11.0 + payload_size) * 8.0 / dr.bitrate as f64)
+        }
+    }
+}
+
+// Returns the RSSI with the given offset applied, limited to the range that can be encoded in
+// the mesh packets (-255 - 0).
+pub fn apply_rssi_offset(rssi: i32, offset: i32) -> i16 {
+    rssi.saturating_add(offset).clamp(-255, 0) as i16
+}
+
+// Returns the current system time. In case the system time is before the UNIX epoch (e.g. a
+// gateway of which the RTC was reset), this returns the UNIX epoch as such a time can't be
+// encoded.
+pub fn system_time_now() -> SystemTime {
+    let now = SystemTime::now();
+    if now < UNIX_EPOCH {
+        warn!("System time is before the UNIX epoch, using the UNIX epoch instead");
+        return UNIX_EPOCH;
+    }
+    now
+}
+
+pub fn system_time_to_timestamp(t: SystemTime) -> pbjson_types::Timestamp {
+    let d = t.duration_since(UNIX_EPOCH).unwrap_or_else(|_| {
+        warn!("Time is before the UNIX epoch, using the UNIX epoch instead");
+        Duration::ZERO
+    });
+    pbjson_types::Timestamp {
+        seconds: d.as_secs() as i64,
+        nanos: d.subsec_nanos() as i32,
+    }
+}
+
+// Converts a timestamp in milliseconds (e.g. of a heartbeat) to a Protobuf Timestamp.
+pub fn millis_to_timestamp(ms: u64) -> pbjson_types::Timestamp {
+    pbjson_types::Timestamp {
+        seconds: (ms / 1000) as i64,
+        nanos: ((ms % 1000) * 1_000_000) as i32,
+    }
+}
+
+// Sets the key index and MIC of the given mesh packet, using the signing key selected by
+// mesh.signing_key_index.
+pub fn set_mic(conf: &config::Configuration, packet: &mut packets::MeshPacket) -> Result<()> {
+    let key = conf
+        .mesh
+        .get_signing_key(conf.mesh.signing_key_index)
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "mesh.signing_key_index {} does not refer to a configured signing key",
+                conf.mesh.signing_key_index
+            ))
+        })?;
+
+    packet.mhdr.key_index = conf.mesh.signing_key_index;
+    packet.set_mic(key).map_err(Error::Packet)
 }
 
 pub fn tx_ack_to_err(tx_ack: &gw::DownlinkTxAck) -> Result<()> {
@@ -164,7 +287,7 @@ pub fn tx_ack_to_err(tx_ack: &gw::DownlinkTxAck) -> Result<()> {
         .collect();
 
     if tx_ack_ok.is_empty() {
-        Err(anyhow!(
+        Err(Error::Backend(format!(
             "Tx Ack error: {}",
             tx_ack
                 .items
@@ -173,7 +296,7 @@ pub fn tx_ack_to_err(tx_ack: &gw::DownlinkTxAck) -> Result<()> {
                 .unwrap_or_default()
                 .status()
                 .as_str_name()
-        ))
+        )))
     } else {
         Ok(())
     }
@@ -183,17 +306,17 @@ pub fn format_uplink(pl: &gw::UplinkFrame) -> Result<String> {
     let tx_info = pl
         .tx_info
         .as_ref()
-        .ok_or_else(|| anyhow!("tx_info is None"))?;
+        .ok_or_else(|| Error::InvalidMessage("tx_info is None".into()))?;
 
     let rx_info = pl
         .rx_info
         .as_ref()
-        .ok_or_else(|| anyhow!("rx_info is None"))?;
+        .ok_or_else(|| Error::InvalidMessage("rx_info is None".into()))?;
 
     let modulation = tx_info
         .modulation
         .as_ref()
-        .ok_or_else(|| anyhow!("modulation is None"))?;
+        .ok_or_else(|| Error::InvalidMessage("modulation is None".into()))?;
 
     Ok(format!(
         "[uplink_id: {}, freq: {}, rssi: {}, snr: {}, mod: {}]",
@@ -212,17 +335,17 @@ pub fn format_downlink(pl: &gw::DownlinkFrame) -> Result<String> {
         let tx_info = i
             .tx_info
             .as_ref()
-            .ok_or_else(|| anyhow!("tx_info is None"))?;
+            .ok_or_else(|| Error::InvalidMessage("tx_info is None".into()))?;
 
         let modulation = tx_info
             .modulation
             .as_ref()
-            .ok_or_else(|| anyhow!("modulation is None"))?;
+            .ok_or_else(|| Error::InvalidMessage("modulation is None".into()))?;
 
         let timing = tx_info
             .timing
             .as_ref()
-            .ok_or_else(|| anyhow!("timing is None"))?;
+            .ok_or_else(|| Error::InvalidMessage("timing is None".into()))?;
 
         out.push(format!(
             "[freq: {}, power: {}, mod: {}, timing: {}]",
@@ -265,3 +388,750 @@ fn format_timing(pl: &gw::Timing) -> String {
         _ => "".to_string(),
     }
 }
+
+pub fn mesh_packet_to_proto(p: &packets::MeshPacket) -> proto::MeshPacket {
+    proto::MeshPacket {
+        payload_type: match p.mhdr.payload_type {
+            packets::PayloadType::Uplink => proto::PayloadType::Uplink,
+            packets::PayloadType::Downlink => proto::PayloadType::Downlink,
+            packets::PayloadType::Heartbeat => proto::PayloadType::Heartbeat,
+            packets::PayloadType::Event => proto::PayloadType::Event,
+        }
+        .into(),
+        hop_count: p.mhdr.hop_count.into(),
+        key_index: p.mhdr.key_index.into(),
+        payload: Some(match &p.payload {
+            packets::Payload::Uplink(v) => {
+                proto::mesh_packet::Payload::Uplink(proto::UplinkPayload {
+                    metadata: Some(proto::UplinkMetadata {
+                        uplink_id: v.metadata.uplink_id.into(),
+                        dr: v.metadata.dr.into(),
+                        rssi: v.metadata.rssi.into(),
+                        snr: v.metadata.snr.into(),
+                        channel: v.metadata.channel.into(),
+                        border_id: v.metadata.border_id.into(),
+                    }),
+                    relay_id: v.relay_id.to_vec(),
+                    phy_payload: v.phy_payload.clone(),
+                    timestamp: v.timestamp.map(millis_to_timestamp),
+                    timestamp_monotonic: v.timestamp_monotonic,
+                })
+            }
+            packets::Payload::Downlink(v) => {
+                proto::mesh_packet::Payload::Downlink(proto::DownlinkPayload {
+                    metadata: Some(proto::DownlinkMetadata {
+                        uplink_id: v.metadata.uplink_id.into(),
+                        dr: v.metadata.dr.into(),
+                        frequency: v.metadata.frequency,
+                        tx_power: v.metadata.tx_power.into(),
+                        delay: v.metadata.delay.into(),
+                    }),
+                    relay_id: v.relay_id.to_vec(),
+                    phy_payload: v.phy_payload.clone(),
+                })
+            }
+            packets::Payload::Heartbeat(v) => {
+                proto::mesh_packet::Payload::Heartbeat(proto::HeartbeatPayload {
+                    timestamp: Some(millis_to_timestamp(v.timestamp)),
+                    timestamp_monotonic: v.timestamp_monotonic,
+                    relay_id: v.relay_id.to_vec(),
+                    relay_path: v
+                        .relay_path
+                        .iter()
+                        .map(|v| proto::RelayPath {
+                            relay_id: v.relay_id.to_vec(),
+                            rssi: v.rssi.into(),
+                            snr: v.snr.into(),
+                        })
+                        .collect(),
+                    config_checksum: v.config_checksum.map(|v| v.to_vec()).unwrap_or_default(),
+                })
+            }
+            packets::Payload::Event(v) => proto::mesh_packet::Payload::Event(proto::EventPayload {
+                timestamp: Some(millis_to_timestamp(v.timestamp)),
+                timestamp_monotonic: v.timestamp_monotonic,
+                relay_id: v.relay_id.to_vec(),
+                events: v
+                    .events
+                    .iter()
+                    .map(|v| proto::Event {
+                        event: Some(match v {
+                            packets::Event::Attestation(v) => {
+                                proto::event::Event::Attestation(proto::AttestationEvent {
+                                    config_checksum: v.config_checksum.to_vec(),
+                                    features: v.features.into(),
+                                    version: v.version.clone(),
+                                    firmware_version: v.firmware_version.clone(),
+                                })
+                            }
+                            packets::Event::TxAirtime(v) => {
+                                proto::event::Event::TxAirtime(proto::TxAirtimeEvent {
+                                    airtime: v.airtime,
+                                })
+                            }
+                            packets::Event::UplinkAck(v) => {
+                                proto::event::Event::UplinkAck(proto::UplinkAckEvent {
+                                    relay_id: v.relay_id.to_vec(),
+                                    uplink_id: v.uplink_id.into(),
+                                })
+                            }
+                            packets::Event::TdmaBeacon(v) => {
+                                proto::event::Event::TdmaBeacon(proto::TdmaBeaconEvent {
+                                    slot_duration: v.slot_duration.into(),
+                                    slot_count: v.slot_count.into(),
+                                    relay_ids: v.relay_ids.iter().map(|v| v.to_vec()).collect(),
+                                })
+                            }
+                            packets::Event::Stats(v) => {
+                                proto::event::Event::Stats(proto::StatsEvent {
+                                    gateway_id: v.gateway_id.to_vec(),
+                                    rx_packets_received: v.rx_packets_received,
+                                    rx_packets_received_ok: v.rx_packets_received_ok,
+                                    tx_packets_received: v.tx_packets_received,
+                                    tx_packets_emitted: v.tx_packets_emitted,
+                                    tx_airtime: v.tx_airtime,
+                                    uplinks_acked: v.uplinks_acked.into(),
+                                    uplinks_not_acked: v.uplinks_not_acked.into(),
+                                    rx_packets_per_frequency: frequency_counts_to_map(
+                                        &v.rx_packets_per_frequency,
+                                    ),
+                                    tx_packets_per_frequency: frequency_counts_to_map(
+                                        &v.tx_packets_per_frequency,
+                                    ),
+                                })
+                            }
+                            packets::Event::DownlinkAck(v) => {
+                                proto::event::Event::DownlinkAck(proto::DownlinkAckEvent {
+                                    uplink_id: v.uplink_id.into(),
+                                    status: v.status.into(),
+                                })
+                            }
+                            packets::Event::Shutdown => {
+                                proto::event::Event::Shutdown(proto::ShutdownEvent {})
+                            }
+                            packets::Event::Boot(v) => {
+                                proto::event::Event::Boot(boot_event_to_proto(v))
+                            }
+                            packets::Event::Location(v) => {
+                                proto::event::Event::Location(proto::LocationEvent {
+                                    latitude: v.latitude,
+                                    longitude: v.longitude,
+                                    altitude: v.altitude.into(),
+                                })
+                            }
+                            packets::Event::Telemetry(v) => {
+                                proto::event::Event::Telemetry(proto::TelemetryEvent {
+                                    battery_voltage: v.battery_voltage.map(|v| v.into()),
+                                    solar_current: v.solar_current.map(|v| v.into()),
+                                    temperature: v.temperature.map(|v| v.into()),
+                                })
+                            }
+                            packets::Event::MaxHopCount(v) => {
+                                proto::event::Event::MaxHopCount(proto::MaxHopCountEvent {
+                                    max_hop_count: v.max_hop_count.into(),
+                                })
+                            }
+                            packets::Event::Challenge(v) => {
+                                proto::event::Event::Challenge(proto::ChallengeEvent {
+                                    challenge: v.challenge.to_vec(),
+                                })
+                            }
+                            packets::Event::ChallengeResponse(v) => {
+                                proto::event::Event::ChallengeResponse(proto::ChallengeEvent {
+                                    challenge: v.challenge.to_vec(),
+                                })
+                            }
+                            packets::Event::Unknown(t, v) => {
+                                proto::event::Event::Unknown(proto::UnknownEvent {
+                                    event_type: (*t).into(),
+                                    value: v.clone(),
+                                })
+                            }
+                        }),
+                    })
+                    .collect(),
+            }),
+        }),
+        mic: p.mic.map(|v| v.to_vec()).unwrap_or_default(),
+    }
+}
+
+pub fn proto_to_mesh_packet(p: &proto::MeshPacket) -> Result<packets::MeshPacket> {
+    let payload = p
+        .payload
+        .as_ref()
+        .ok_or_else(|| Error::InvalidMessage("payload is None".into()))?;
+
+    Ok(packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: match p.payload_type() {
+                proto::PayloadType::Uplink => packets::PayloadType::Uplink,
+                proto::PayloadType::Downlink => packets::PayloadType::Downlink,
+                proto::PayloadType::Heartbeat => packets::PayloadType::Heartbeat,
+                proto::PayloadType::Event => packets::PayloadType::Event,
+            },
+            hop_count: p.hop_count.try_into()?,
+            key_index: p.key_index.try_into()?,
+        },
+        payload: match payload {
+            proto::mesh_packet::Payload::Uplink(v) => {
+                let metadata = v
+                    .metadata
+                    .as_ref()
+                    .ok_or_else(|| Error::InvalidMessage("metadata is None".into()))?;
+
+                packets::Payload::Uplink(packets::UplinkPayload {
+                    metadata: packets::UplinkMetadata {
+                        uplink_id: metadata.uplink_id.try_into()?,
+                        dr: metadata.dr.try_into()?,
+                        rssi: metadata.rssi.try_into()?,
+                        snr: metadata.snr.try_into()?,
+                        channel: metadata.channel.try_into()?,
+                        border_id: metadata.border_id.try_into()?,
+                    },
+                    relay_id: v.relay_id.as_slice().try_into()?,
+                    timestamp: v
+                        .timestamp
+                        .as_ref()
+                        .map(|v| -> Result<u64> {
+                            Ok(u64::try_from(v.seconds)? * 1000
+                                + u64::try_from(v.nanos)? / 1_000_000)
+                        })
+                        .transpose()?,
+                    timestamp_monotonic: v.timestamp_monotonic,
+                    phy_payload: v.phy_payload.clone(),
+                })
+            }
+            proto::mesh_packet::Payload::Downlink(v) => {
+                let metadata = v
+                    .metadata
+                    .as_ref()
+                    .ok_or_else(|| Error::InvalidMessage("metadata is None".into()))?;
+
+                packets::Payload::Downlink(packets::DownlinkPayload {
+                    metadata: packets::DownlinkMetadata {
+                        uplink_id: metadata.uplink_id.try_into()?,
+                        dr: metadata.dr.try_into()?,
+                        frequency: metadata.frequency,
+                        tx_power: metadata.tx_power.try_into()?,
+                        delay: metadata.delay.try_into()?,
+                    },
+                    relay_id: v.relay_id.as_slice().try_into()?,
+                    phy_payload: v.phy_payload.clone(),
+                })
+            }
+            proto::mesh_packet::Payload::Heartbeat(v) => {
+                let timestamp = v
+                    .timestamp
+                    .as_ref()
+                    .ok_or_else(|| Error::InvalidMessage("timestamp is None".into()))?;
+
+                packets::Payload::Heartbeat(packets::HeartbeatPayload {
+                    timestamp: u64::try_from(timestamp.seconds)? * 1000
+                        + u64::try_from(timestamp.nanos)? / 1_000_000,
+                    timestamp_monotonic: v.timestamp_monotonic,
+                    relay_id: v.relay_id.as_slice().try_into()?,
+                    relay_path: v
+                        .relay_path
+                        .iter()
+                        .map(|v| {
+                            Ok(packets::RelayPath {
+                                relay_id: v.relay_id.as_slice().try_into()?,
+                                rssi: v.rssi.try_into()?,
+                                snr: v.snr.try_into()?,
+                            })
+                        })
+                        .collect::<Result<Vec<packets::RelayPath>>>()?,
+                    config_checksum: if v.config_checksum.is_empty() {
+                        None
+                    } else {
+                        Some(v.config_checksum.as_slice().try_into()?)
+                    },
+                })
+            }
+            proto::mesh_packet::Payload::Event(v) => {
+                let timestamp = v
+                    .timestamp
+                    .as_ref()
+                    .ok_or_else(|| Error::InvalidMessage("timestamp is None".into()))?;
+
+                packets::Payload::Event(packets::EventPayload {
+                    timestamp: u64::try_from(timestamp.seconds)? * 1000
+                        + u64::try_from(timestamp.nanos)? / 1_000_000,
+                    timestamp_monotonic: v.timestamp_monotonic,
+                    relay_id: v.relay_id.as_slice().try_into()?,
+                    events: v
+                        .events
+                        .iter()
+                        .map(|v| {
+                            Ok(
+                                match v
+                                    .event
+                                    .as_ref()
+                                    .ok_or_else(|| Error::InvalidMessage("event is None".into()))?
+                                {
+                                    proto::event::Event::Attestation(v) => {
+                                        packets::Event::Attestation(packets::AttestationEvent {
+                                            config_checksum: v
+                                                .config_checksum
+                                                .as_slice()
+                                                .try_into()?,
+                                            features: v.features.try_into()?,
+                                            version: v.version.clone(),
+                                            firmware_version: v.firmware_version.clone(),
+                                        })
+                                    }
+                                    proto::event::Event::TxAirtime(v) => {
+                                        packets::Event::TxAirtime(packets::TxAirtimeEvent {
+                                            airtime: v.airtime,
+                                        })
+                                    }
+                                    proto::event::Event::UplinkAck(v) => {
+                                        packets::Event::UplinkAck(packets::UplinkAckEvent {
+                                            relay_id: v.relay_id.as_slice().try_into()?,
+                                            uplink_id: v.uplink_id.try_into()?,
+                                        })
+                                    }
+                                    proto::event::Event::TdmaBeacon(v) => {
+                                        packets::Event::TdmaBeacon(packets::TdmaBeaconEvent {
+                                            slot_duration: v.slot_duration.try_into()?,
+                                            slot_count: v.slot_count.try_into()?,
+                                            relay_ids: v
+                                                .relay_ids
+                                                .iter()
+                                                .map(|v| v.as_slice().try_into())
+                                                .collect::<Result<Vec<[u8; 4]>, _>>()?,
+                                        })
+                                    }
+                                    proto::event::Event::Stats(v) => {
+                                        packets::Event::Stats(packets::StatsEvent {
+                                            gateway_id: v.gateway_id.as_slice().try_into()?,
+                                            rx_packets_received: v.rx_packets_received,
+                                            rx_packets_received_ok: v.rx_packets_received_ok,
+                                            tx_packets_received: v.tx_packets_received,
+                                            tx_packets_emitted: v.tx_packets_emitted,
+                                            tx_airtime: v.tx_airtime,
+                                            uplinks_acked: v.uplinks_acked.try_into()?,
+                                            uplinks_not_acked: v.uplinks_not_acked.try_into()?,
+                                            rx_packets_per_frequency: map_to_frequency_counts(
+                                                &v.rx_packets_per_frequency,
+                                            )?,
+                                            tx_packets_per_frequency: map_to_frequency_counts(
+                                                &v.tx_packets_per_frequency,
+                                            )?,
+                                        })
+                                    }
+                                    proto::event::Event::DownlinkAck(v) => {
+                                        packets::Event::DownlinkAck(packets::DownlinkAckEvent {
+                                            uplink_id: v.uplink_id.try_into()?,
+                                            status: v.status.try_into()?,
+                                        })
+                                    }
+                                    proto::event::Event::Shutdown(_) => packets::Event::Shutdown,
+                                    proto::event::Event::Boot(v) => {
+                                        packets::Event::Boot(packets::BootEvent {
+                                            reason: match v.reason() {
+                                                proto::BootReason::PowerCycle => {
+                                                    packets::BootReason::PowerCycle
+                                                }
+                                                proto::BootReason::Restart => {
+                                                    packets::BootReason::Restart
+                                                }
+                                                proto::BootReason::CrashRecovery => {
+                                                    packets::BootReason::CrashRecovery
+                                                }
+                                            },
+                                            features: v.features.try_into()?,
+                                            boot_id: v.boot_id.as_slice().try_into()?,
+                                            version: v.version.clone(),
+                                            crash: match &v.crash {
+                                                Some(v) => Some(packets::CrashSummary {
+                                                    panic_hash: v
+                                                        .panic_hash
+                                                        .as_slice()
+                                                        .try_into()?,
+                                                    uptime: v.uptime,
+                                                }),
+                                                None => None,
+                                            },
+                                        })
+                                    }
+                                    proto::event::Event::Location(v) => {
+                                        packets::Event::Location(packets::LocationEvent {
+                                            latitude: v.latitude,
+                                            longitude: v.longitude,
+                                            altitude: v.altitude.try_into()?,
+                                        })
+                                    }
+                                    proto::event::Event::Telemetry(v) => {
+                                        packets::Event::Telemetry(packets::TelemetryEvent {
+                                            battery_voltage: v
+                                                .battery_voltage
+                                                .map(|v| v.try_into())
+                                                .transpose()?,
+                                            solar_current: v
+                                                .solar_current
+                                                .map(|v| v.try_into())
+                                                .transpose()?,
+                                            temperature: v
+                                                .temperature
+                                                .map(|v| v.try_into())
+                                                .transpose()?,
+                                        })
+                                    }
+                                    proto::event::Event::MaxHopCount(v) => {
+                                        packets::Event::MaxHopCount(packets::MaxHopCountEvent {
+                                            max_hop_count: v.max_hop_count.try_into()?,
+                                        })
+                                    }
+                                    proto::event::Event::Challenge(v) => {
+                                        packets::Event::Challenge(packets::ChallengeEvent {
+                                            challenge: v.challenge.as_slice().try_into()?,
+                                        })
+                                    }
+                                    proto::event::Event::ChallengeResponse(v) => {
+                                        packets::Event::ChallengeResponse(packets::ChallengeEvent {
+                                            challenge: v.challenge.as_slice().try_into()?,
+                                        })
+                                    }
+                                    proto::event::Event::Unknown(v) => packets::Event::Unknown(
+                                        v.event_type.try_into()?,
+                                        v.value.clone(),
+                                    ),
+                                },
+                            )
+                        })
+                        .collect::<Result<Vec<packets::Event>>>()?,
+                })
+            }
+        },
+        mic: if p.mic.is_empty() {
+            None
+        } else {
+            Some(p.mic.as_slice().try_into()?)
+        },
+    })
+}
+
+pub fn boot_event_to_proto(v: &packets::BootEvent) -> proto::BootEvent {
+    proto::BootEvent {
+        reason: match v.reason {
+            packets::BootReason::PowerCycle => proto::BootReason::PowerCycle,
+            packets::BootReason::Restart => proto::BootReason::Restart,
+            packets::BootReason::CrashRecovery => proto::BootReason::CrashRecovery,
+        }
+        .into(),
+        features: v.features.into(),
+        version: v.version.clone(),
+        crash: v.crash.as_ref().map(|v| proto::CrashSummary {
+            panic_hash: v.panic_hash.to_vec(),
+            uptime: v.uptime,
+        }),
+        boot_id: v.boot_id.to_vec(),
+    }
+}
+
+pub fn frequency_counts_to_map(counts: &[packets::FrequencyCount]) -> HashMap<u32, u32> {
+    counts
+        .iter()
+        .map(|v| (v.frequency, v.count.into()))
+        .collect()
+}
+
+// Returns the frequency counts, sorted by frequency.
+pub fn map_to_frequency_counts(m: &HashMap<u32, u32>) -> Result<Vec<packets::FrequencyCount>> {
+    let mut out = m
+        .iter()
+        .map(|(k, v)| {
+            Ok(packets::FrequencyCount {
+                frequency: *k,
+                count: (*v).try_into()?,
+            })
+        })
+        .collect::<Result<Vec<packets::FrequencyCount>>>()?;
+    out.sort_by_key(|v| v.frequency);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_system_time_to_timestamp() {
+        assert_eq!(
+            pbjson_types::Timestamp {
+                seconds: 1,
+                nanos: 500_000_000,
+            },
+            system_time_to_timestamp(UNIX_EPOCH + Duration::from_millis(1500))
+        );
+
+        // Before the UNIX epoch.
+        assert_eq!(
+            pbjson_types::Timestamp {
+                seconds: 0,
+                nanos: 0,
+            },
+            system_time_to_timestamp(UNIX_EPOCH - Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn test_airtime() {
+        struct Test {
+            name: String,
+            dr: config::DataRate,
+            payload_size: usize,
+            expected_airtime: Duration,
+        }
+
+        let tests = vec![
+            Test {
+                name: "LoRa SF7 BW125 CR4/5 13 bytes".into(),
+                dr: config::DataRate {
+                    modulation: config::Modulation::LORA,
+                    spreading_factor: 7,
+                    bandwidth: 125000,
+                    code_rate: Some(config::CodeRate::Cr45),
+                    ..Default::default()
+                },
+                payload_size: 13,
+                expected_airtime: Duration::from_micros(46336),
+            },
+            Test {
+                name: "LoRa SF12 BW125 CR4/5 13 bytes".into(),
+                dr: config::DataRate {
+                    modulation: config::Modulation::LORA,
+                    spreading_factor: 12,
+                    bandwidth: 125000,
+                    code_rate: Some(config::CodeRate::Cr45),
+                    ..Default::default()
+                },
+                payload_size: 13,
+                expected_airtime: Duration::from_micros(1155072),
+            },
+            Test {
+                name: "FSK 50kbps 13 bytes".into(),
+                dr: config::DataRate {
+                    modulation: config::Modulation::FSK,
+                    bitrate: 50000,
+                    ..Default::default()
+                },
+                payload_size: 13,
+                expected_airtime: Duration::from_micros(3840),
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let airtime = airtime(&tst.dr, tst.payload_size);
+            assert_eq!(
+                tst.expected_airtime.as_micros(),
+                airtime.as_micros(),
+                "{:?}",
+                airtime
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_rssi_offset() {
+        struct Test {
+            rssi: i32,
+            offset: i32,
+            expected_rssi: i16,
+        }
+
+        let tests = vec![
+            Test {
+                rssi: -80,
+                offset: 0,
+                expected_rssi: -80,
+            },
+            Test {
+                rssi: -80,
+                offset: 5,
+                expected_rssi: -75,
+            },
+            Test {
+                rssi: -80,
+                offset: -10,
+                expected_rssi: -90,
+            },
+            Test {
+                rssi: -2,
+                offset: 5,
+                expected_rssi: 0,
+            },
+            Test {
+                rssi: -250,
+                offset: -10,
+                expected_rssi: -255,
+            },
+        ];
+
+        for tst in &tests {
+            assert_eq!(tst.expected_rssi, apply_rssi_offset(tst.rssi, tst.offset));
+        }
+    }
+
+    #[test]
+    fn test_proto() {
+        let packets = vec![
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Uplink,
+                    hop_count: 3,
+                    key_index: 0,
+                },
+                payload: packets::Payload::Uplink(packets::UplinkPayload {
+                    metadata: packets::UplinkMetadata {
+                        uplink_id: 1024,
+                        dr: 3,
+                        rssi: -120,
+                        snr: -12,
+                        channel: 64,
+                        border_id: 0,
+                    },
+                    relay_id: [0x01, 0x02, 0x03, 0x04],
+                    timestamp: Some(1_000_000_000_123),
+                    timestamp_monotonic: false,
+                    phy_payload: vec![0x05],
+                }),
+                mic: Some([0x01, 0x02, 0x03, 0x04]),
+            },
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Downlink,
+                    hop_count: 8,
+                    key_index: 0,
+                },
+                payload: packets::Payload::Downlink(packets::DownlinkPayload {
+                    metadata: packets::DownlinkMetadata {
+                        uplink_id: 1024,
+                        dr: 3,
+                        frequency: 868100000,
+                        tx_power: 15,
+                        delay: 16,
+                    },
+                    relay_id: [0x01, 0x02, 0x03, 0x04],
+                    phy_payload: vec![0x05],
+                }),
+                mic: None,
+            },
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Heartbeat,
+                    hop_count: 2,
+                    key_index: 0,
+                },
+                payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+                    timestamp: 1_000_000_000_123,
+                    timestamp_monotonic: true,
+                    relay_id: [1, 2, 3, 4],
+                    relay_path: vec![packets::RelayPath {
+                        relay_id: [5, 6, 7, 8],
+                        rssi: -120,
+                        snr: -12,
+                    }],
+                    config_checksum: Some([9, 10, 11, 12]),
+                }),
+                mic: Some([0x01, 0x02, 0x03, 0x04]),
+            },
+            packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Event,
+                    hop_count: 1,
+                    key_index: 0,
+                },
+                payload: packets::Payload::Event(packets::EventPayload {
+                    timestamp: 1_000_000_000_123,
+                    timestamp_monotonic: false,
+                    relay_id: [1, 2, 3, 4],
+                    events: vec![
+                        packets::Event::Attestation(packets::AttestationEvent {
+                            config_checksum: [1, 2, 3, 4],
+                            features: 3,
+                            version: "4.0.0".into(),
+                            firmware_version: "1.2.3".into(),
+                        }),
+                        packets::Event::TxAirtime(packets::TxAirtimeEvent { airtime: 12345 }),
+                        packets::Event::UplinkAck(packets::UplinkAckEvent {
+                            relay_id: [5, 6, 7, 8],
+                            uplink_id: 1024,
+                        }),
+                        packets::Event::TdmaBeacon(packets::TdmaBeaconEvent {
+                            slot_duration: 500,
+                            slot_count: 3,
+                            relay_ids: vec![[1, 2, 3, 4], [5, 6, 7, 8]],
+                        }),
+                        packets::Event::Stats(packets::StatsEvent {
+                            gateway_id: [1, 2, 3, 4, 5, 6, 7, 8],
+                            rx_packets_received: 10,
+                            rx_packets_received_ok: 9,
+                            tx_packets_received: 2,
+                            tx_packets_emitted: 1,
+                            tx_airtime: 1000,
+                            uplinks_acked: 5,
+                            uplinks_not_acked: 1,
+                            rx_packets_per_frequency: vec![
+                                packets::FrequencyCount {
+                                    frequency: 868100000,
+                                    count: 6,
+                                },
+                                packets::FrequencyCount {
+                                    frequency: 868300000,
+                                    count: 4,
+                                },
+                            ],
+                            tx_packets_per_frequency: vec![packets::FrequencyCount {
+                                frequency: 869525000,
+                                count: 1,
+                            }],
+                        }),
+                        packets::Event::DownlinkAck(packets::DownlinkAckEvent {
+                            uplink_id: 1024,
+                            status: 1,
+                        }),
+                        packets::Event::Shutdown,
+                        packets::Event::Boot(packets::BootEvent {
+                            reason: packets::BootReason::CrashRecovery,
+                            features: 3,
+                            boot_id: [5, 6, 7, 8],
+                            version: "4.0.0".into(),
+                            crash: Some(packets::CrashSummary {
+                                panic_hash: [1, 2, 3, 4],
+                                uptime: 3600,
+                            }),
+                        }),
+                        packets::Event::Location(packets::LocationEvent {
+                            latitude: 523_702_000,
+                            longitude: -48_952_000,
+                            altitude: -5,
+                        }),
+                        packets::Event::Telemetry(packets::TelemetryEvent {
+                            battery_voltage: Some(12600),
+                            solar_current: None,
+                            temperature: Some(-55),
+                        }),
+                        packets::Event::MaxHopCount(packets::MaxHopCountEvent { max_hop_count: 3 }),
+                        packets::Event::Challenge(packets::ChallengeEvent {
+                            challenge: [1, 2, 3, 4],
+                        }),
+                        packets::Event::ChallengeResponse(packets::ChallengeEvent {
+                            challenge: [5, 6, 7, 8],
+                        }),
+                        packets::Event::Unknown(127, vec![5, 6]),
+                    ],
+                }),
+                mic: Some([0x01, 0x02, 0x03, 0x04]),
+            },
+        ];
+
+        for pkt in &packets {
+            let pb = mesh_packet_to_proto(pkt);
+            assert_eq!(pkt, &proto_to_mesh_packet(&pb).unwrap());
+        }
+    }
+}
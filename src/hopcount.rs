@@ -0,0 +1,171 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+use tokio::time::sleep;
+
+use crate::config::Configuration;
+use crate::error::Result;
+use crate::{events, packets, topology};
+
+// The advertised max hop count expires after this number of missed advertisements, after which
+// the configured max hop count is used again.
+const ADVERTISEMENT_EXPIRY_FACTOR: u32 = 3;
+
+static ADVERTISED: Mutex<Option<(u8, Instant)>> = Mutex::new(None);
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    // Only the Border Gateway advertises the max hop count.
+    if !conf.mesh.border_gateway || !conf.mesh.adaptive_max_hop_count.enabled {
+        return Ok(());
+    }
+
+    info!(
+        "Starting max hop count advertisement loop, advertisement_interval: {:?}, margin: {}",
+        conf.mesh.adaptive_max_hop_count.advertisement_interval,
+        conf.mesh.adaptive_max_hop_count.margin
+    );
+
+    tokio::spawn({
+        let advertisement_interval = conf.mesh.adaptive_max_hop_count.advertisement_interval;
+        let margin = conf.mesh.adaptive_max_hop_count.margin;
+        let max_hop_count = conf.mesh.max_hop_count;
+
+        async move {
+            loop {
+                // Nothing is advertised until the diameter is known from the heartbeats.
+                if let Some(event) = get_event(&topology::get_relays(), margin, max_hop_count) {
+                    if let Err(e) = events::send_max_hop_count(event).await {
+                        error!("Send max hop count error, error: {}", e);
+                    }
+                }
+                sleep(advertisement_interval).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Returns the max hop count event, based on the network diameter (the highest hop count of the
+// Relay Gateways known by the Border Gateway) plus the margin, capped by the configured max hop
+// count. This returns None when no Relay Gateway is known.
+fn get_event(
+    relays: &[topology::Relay],
+    margin: u8,
+    max_hop_count: u8,
+) -> Option<packets::MaxHopCountEvent> {
+    let diameter = relays.iter().map(|v| v.hop_count).max()?;
+
+    Some(packets::MaxHopCountEvent {
+        max_hop_count: diameter
+            .saturating_add(margin)
+            .min(max_hop_count)
+            .clamp(1, packets::MAX_HOP_COUNT),
+    })
+}
+
+// Updates the max hop count from the received advertisement (Relay Gateway only).
+pub fn record_advertisement(pl: &packets::MaxHopCountEvent) {
+    info!("Max hop count updated, max_hop_count: {}", pl.max_hop_count);
+    *ADVERTISED.lock().unwrap() = Some((pl.max_hop_count, Instant::now()));
+}
+
+// Returns the max hop count to enforce. This is the advertised max hop count, capped by the
+// configured max hop count, or the configured max hop count when no (valid) advertisement has
+// been received.
+pub fn get_max_hop_count(conf: &Configuration) -> u8 {
+    if !conf.mesh.adaptive_max_hop_count.enabled {
+        return conf.mesh.max_hop_count;
+    }
+
+    get_max_hop_count_for_advertisement(
+        conf.mesh.max_hop_count,
+        *ADVERTISED.lock().unwrap(),
+        conf.mesh.adaptive_max_hop_count.advertisement_interval * ADVERTISEMENT_EXPIRY_FACTOR,
+        Instant::now(),
+    )
+}
+
+fn get_max_hop_count_for_advertisement(
+    max_hop_count: u8,
+    advertised: Option<(u8, Instant)>,
+    expiry: Duration,
+    now: Instant,
+) -> u8 {
+    match advertised {
+        Some((v, received_at)) if now.duration_since(received_at) < expiry => v.min(max_hop_count),
+        _ => max_hop_count,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn relay(hop_count: u8) -> topology::Relay {
+        topology::Relay {
+            relay_id: [hop_count; 4],
+            last_seen_at: SystemTime::now(),
+            hop_count,
+            rssi: 0,
+            snr: 0.0,
+            relay_path: vec![],
+            stale: false,
+            attestation: None,
+            tx_airtime: None,
+            uplink_acks: None,
+            asymmetric_link: false,
+            shutdown: false,
+            boot: None,
+            location: None,
+            telemetry: None,
+            challenge_failed: false,
+            maintenance: false,
+        }
+    }
+
+    #[test]
+    fn test_get_event() {
+        assert_eq!(None, get_event(&[], 1, 8));
+        assert_eq!(
+            Some(packets::MaxHopCountEvent { max_hop_count: 4 }),
+            get_event(&[relay(1), relay(3), relay(2)], 1, 8)
+        );
+        assert_eq!(
+            Some(packets::MaxHopCountEvent { max_hop_count: 5 }),
+            get_event(&[relay(1), relay(3)], 2, 5)
+        );
+        assert_eq!(
+            Some(packets::MaxHopCountEvent { max_hop_count: 8 }),
+            get_event(&[relay(8)], 255, 255)
+        );
+    }
+
+    #[test]
+    fn test_get_max_hop_count_for_advertisement() {
+        let now = Instant::now();
+        let expiry = Duration::from_secs(60);
+
+        assert_eq!(5, get_max_hop_count_for_advertisement(5, None, expiry, now));
+        assert_eq!(
+            3,
+            get_max_hop_count_for_advertisement(5, Some((3, now)), expiry, now)
+        );
+        assert_eq!(
+            5,
+            get_max_hop_count_for_advertisement(5, Some((7, now)), expiry, now)
+        );
+        assert_eq!(
+            5,
+            get_max_hop_count_for_advertisement(
+                5,
+                Some((3, now - Duration::from_secs(60))),
+                expiry,
+                now
+            )
+        );
+    }
+}
@@ -1,14 +1,36 @@
-#[macro_use]
-extern crate anyhow;
-
-pub mod aes128;
+pub mod antireplay;
 pub mod backend;
+pub mod beacon;
 pub mod cache;
+pub mod capacity;
+pub mod challenge;
 pub mod cmd;
 pub mod config;
+pub mod deadletter;
+pub mod dutycycle;
+pub mod error;
+pub mod events;
 pub mod heartbeat;
 pub mod helpers;
+pub mod hopcount;
+pub mod linkquality;
+pub mod location;
 pub mod logging;
 pub mod mesh;
-pub mod packets;
+pub mod metrics;
+pub mod monitoring;
+pub mod mqtt;
+pub mod proto;
 pub mod proxy;
+pub mod routing;
+pub mod stats;
+pub mod supervisor;
+pub mod tdma;
+pub mod telemetry;
+pub mod topology;
+pub mod uci;
+pub mod uplinkcontext;
+pub mod watchdog;
+pub mod webhook;
+
+pub use chirpstack_gateway_mesh_packets::{self as packets, aes128};
@@ -0,0 +1,128 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers;
+
+// Samples older than this are removed.
+const MAX_AGE: Duration = Duration::from_secs(86400);
+
+// Min. time between two samples of the same relay. Together with MAX_AGE, this bounds the
+// number of samples per relay.
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+// Max. number of samples per relay (one per MIN_INTERVAL, for MAX_AGE).
+const MAX_SAMPLES: usize = (MAX_AGE.as_secs() / MIN_INTERVAL.as_secs()) as usize;
+
+static SAMPLES: Lazy<Mutex<HashMap<[u8; 4], VecDeque<Sample>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Sample {
+    #[serde(with = "humantime_serde")]
+    pub time: SystemTime,
+    pub hop_count: u8,
+    // RSSI and SNR of the last hop, as received by the Border Gateway.
+    pub rssi: i32,
+    pub snr: f32,
+}
+
+// Records the link quality of a packet received from the given relay. Packets received within
+// MIN_INTERVAL of the previous sample are not recorded.
+pub fn record(relay_id: [u8; 4], hop_count: u8, rssi: i32, snr: f32) {
+    record_sample(
+        relay_id,
+        Sample {
+            time: helpers::system_time_now(),
+            hop_count,
+            rssi,
+            snr,
+        },
+    );
+}
+
+// Returns the samples of the past 24 hours by relay ID (HEX encoded), from oldest to newest.
+pub fn get(relay_id: Option<[u8; 4]>) -> BTreeMap<String, Vec<Sample>> {
+    let now = helpers::system_time_now();
+    let mut samples = SAMPLES.lock().unwrap();
+    prune(&mut samples, now);
+
+    samples
+        .iter()
+        .filter(|(k, _)| relay_id.map(|v| v == **k).unwrap_or(true))
+        .map(|(k, v)| (hex::encode(k), v.iter().cloned().collect()))
+        .collect()
+}
+
+fn record_sample(relay_id: [u8; 4], sample: Sample) {
+    let mut samples = SAMPLES.lock().unwrap();
+    prune(&mut samples, sample.time);
+
+    let relay_samples = samples.entry(relay_id).or_default();
+    if let Some(last) = relay_samples.back() {
+        if sample
+            .time
+            .duration_since(last.time)
+            .map(|v| v < MIN_INTERVAL)
+            .unwrap_or_default()
+        {
+            return;
+        }
+    }
+
+    if relay_samples.len() == MAX_SAMPLES {
+        relay_samples.pop_front();
+    }
+    relay_samples.push_back(sample);
+}
+
+fn prune(samples: &mut HashMap<[u8; 4], VecDeque<Sample>>, now: SystemTime) {
+    for v in samples.values_mut() {
+        while v
+            .front()
+            .map(|s| now.duration_since(s.time).unwrap_or_default() > MAX_AGE)
+            .unwrap_or_default()
+        {
+            v.pop_front();
+        }
+    }
+    samples.retain(|_, v| !v.is_empty());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_sample() {
+        let relay_id = [9, 9, 9, 9];
+        let start = SystemTime::now() - MAX_AGE - Duration::from_secs(600);
+        let sample = |offset: u64, rssi: i32| Sample {
+            time: start + Duration::from_secs(offset),
+            hop_count: 1,
+            rssi,
+            snr: 5.0,
+        };
+
+        record_sample(relay_id, sample(0, -80));
+        // Within MIN_INTERVAL of the previous sample.
+        record_sample(relay_id, sample(30, -81));
+        record_sample(relay_id, sample(60, -82));
+
+        // Both samples are older than MAX_AGE.
+        assert!(get(Some(relay_id)).is_empty());
+
+        record_sample(relay_id, sample(900, -83));
+        record_sample(relay_id, sample(960, -84));
+
+        let samples = get(Some(relay_id));
+        assert_eq!(
+            vec![sample(900, -83), sample(960, -84)],
+            samples["09090909"]
+        );
+        assert!(!get(Some([8, 8, 8, 8])).contains_key("09090909"));
+    }
+}
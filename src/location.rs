@@ -0,0 +1,162 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::sleep;
+
+use crate::config::{Configuration, LocationSource};
+use crate::error::Result;
+use crate::packets;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+// Last location fix received from gpsd.
+static GPSD_FIX: Mutex<Option<packets::LocationEvent>> = Mutex::new(None);
+
+// gpsd TPV (time-position-velocity) report. Other report classes are ignored.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(default)]
+struct Report {
+    class: String,
+    // 0 / 1 = no fix, 2 = 2D fix, 3 = 3D fix.
+    mode: u8,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    // Altitude (MSL), alt is reported by gpsd versions before 3.20.
+    #[serde(rename = "altMSL")]
+    alt_msl: Option<f64>,
+    alt: Option<f64>,
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    if conf.mesh.border_gateway || conf.mesh.location.source != LocationSource::Gpsd {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up gpsd location, server: {}",
+        conf.mesh.location.gpsd_server
+    );
+
+    tokio::spawn({
+        let server = conf.mesh.location.gpsd_server.clone();
+
+        async move {
+            loop {
+                // The connection is only closed by error, in which case we reconnect.
+                if let Err(e) = gpsd_loop(&server).await {
+                    error!("gpsd connection error, server: {}, error: {}", server, e);
+                }
+
+                sleep(RECONNECT_DELAY).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Returns the location to report, or None if no location is configured or gpsd did not report
+// a fix yet.
+pub fn get(conf: &Configuration) -> Option<packets::LocationEvent> {
+    match conf.mesh.location.source {
+        LocationSource::None => None,
+        LocationSource::Static => Some(to_location_event(
+            conf.mesh.location.latitude,
+            conf.mesh.location.longitude,
+            conf.mesh.location.altitude,
+        )),
+        LocationSource::Gpsd => GPSD_FIX.lock().unwrap().clone(),
+    }
+}
+
+async fn gpsd_loop(server: &str) -> Result<()> {
+    let mut stream = TcpStream::connect(server).await?;
+    stream
+        .write_all(b"?WATCH={\"enable\":true,\"json\":true};\n")
+        .await?;
+    info!("Connected to gpsd, server: {}", server);
+
+    let mut lines = BufReader::new(stream).lines();
+    while let Some(line) = lines.next_line().await? {
+        match parse_report(&line) {
+            Ok(Some(location)) => {
+                debug!("gpsd location fix received, location: {:?}", location);
+                *GPSD_FIX.lock().unwrap() = Some(location);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Decode gpsd report error, error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the location of a TPV report with a (2D or 3D) fix. A 2D fix has no altitude.
+fn parse_report(line: &str) -> Result<Option<packets::LocationEvent>> {
+    let report: Report = serde_json::from_str(line)?;
+    if report.class != "TPV" || report.mode < 2 {
+        return Ok(None);
+    }
+
+    Ok(match (report.lat, report.lon) {
+        (Some(lat), Some(lon)) => Some(to_location_event(
+            lat,
+            lon,
+            report.alt_msl.or(report.alt).unwrap_or_default(),
+        )),
+        _ => None,
+    })
+}
+
+fn to_location_event(latitude: f64, longitude: f64, altitude: f64) -> packets::LocationEvent {
+    packets::LocationEvent {
+        latitude: (latitude * 1e7).round() as i32,
+        longitude: (longitude * 1e7).round() as i32,
+        altitude: altitude.round() as i16,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_report() {
+        assert_eq!(
+            Some(packets::LocationEvent {
+                latitude: 523_702_000,
+                longitude: -48_952_000,
+                altitude: 12,
+            }),
+            parse_report(
+                r#"{"class":"TPV","device":"/dev/ttyS0","mode":3,"lat":52.3702,"lon":-4.8952,"altHAE":58.1,"altMSL":11.6}"#
+            )
+            .unwrap()
+        );
+
+        // gpsd < 3.20, 2D fix.
+        assert_eq!(
+            Some(packets::LocationEvent {
+                latitude: 523_702_000,
+                longitude: -48_952_000,
+                altitude: 0,
+            }),
+            parse_report(r#"{"class":"TPV","mode":2,"lat":52.3702,"lon":-4.8952}"#).unwrap()
+        );
+
+        // No fix.
+        assert_eq!(None, parse_report(r#"{"class":"TPV","mode":1}"#).unwrap());
+
+        // Other report class.
+        assert_eq!(
+            None,
+            parse_report(r#"{"class":"VERSION","release":"3.22"}"#).unwrap()
+        );
+
+        assert!(parse_report("invalid").is_err());
+    }
+}
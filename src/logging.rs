@@ -1,22 +1,221 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
-use anyhow::Result;
 use syslog::{BasicLogger, Facility, Formatter3164};
 
-pub fn setup(name: &str, level: log::Level, syslog: bool) -> Result<()> {
-    if syslog {
+use crate::config;
+use crate::error::{Error, Result};
+
+#[derive(Clone, Copy)]
+pub enum Category {
+    Uplink,
+    Downlink,
+    Mesh,
+}
+
+static SAMPLE_COUNTERS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+
+pub fn setup(name: &str, level: log::Level, conf: &config::Logging) -> Result<()> {
+    if conf.log_to_syslog {
         let formatter = Formatter3164 {
             facility: Facility::LOG_USER,
             hostname: None,
             process: name.to_string(),
             pid: process::id(),
         };
-        let logger = syslog::unix(formatter).map_err(|e| anyhow!("{}", e))?;
+        let logger =
+            syslog::unix(formatter).map_err(|e| Error::Io(std::io::Error::other(e.to_string())))?;
         log::set_boxed_logger(Box::new(BasicLogger::new(logger)))
-            .map(|()| log::set_max_level(level.to_level_filter()))?;
+            .map(|()| log::set_max_level(level.to_level_filter()))
+            .map_err(|e| Error::Config(e.to_string()))?;
+    } else if !conf.file.is_empty() {
+        let logger = FileLogger::new(level, &conf.file, conf.file_max_size, conf.file_max_files)?;
+        log::set_boxed_logger(Box::new(logger))
+            .map(|()| log::set_max_level(level.to_level_filter()))
+            .map_err(|e| Error::Config(e.to_string()))?;
     } else {
-        simple_logger::init_with_level(level)?;
+        simple_logger::init_with_level(level).map_err(|e| Error::Config(e.to_string()))?;
     }
 
     Ok(())
 }
+
+struct FileLogger {
+    level: log::Level,
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: Mutex<(File, u64)>,
+}
+
+impl FileLogger {
+    fn new(level: log::Level, path: &str, max_size: u64, max_files: usize) -> Result<Self> {
+        let path = PathBuf::from(path);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(FileLogger {
+            level,
+            path,
+            max_size,
+            max_files,
+            file: Mutex::new((file, size)),
+        })
+    }
+}
+
+impl log::Log for FileLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            humantime::format_rfc3339_millis(SystemTime::now()),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let mut file = self.file.lock().unwrap();
+        if file.0.write_all(line.as_bytes()).is_err() {
+            return;
+        }
+        file.1 += line.len() as u64;
+
+        if self.max_size != 0 && file.1 >= self.max_size {
+            if let Err(e) = rotate(&self.path, self.max_files) {
+                eprintln!("Rotate log file error, error: {}", e);
+            }
+
+            // Re-open the log file, also when the rotation failed as the log file might have
+            // been removed.
+            match OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)
+            {
+                Ok(v) => *file = (v, 0),
+                Err(e) => eprintln!("Open log file error, error: {}", e),
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().0.flush();
+    }
+}
+
+// Rotates the log files, e.g. .log.1 is renamed to .log.2 and .log to .log.1. Rotated log files
+// exceeding max_files are removed.
+fn rotate(path: &Path, max_files: usize) -> Result<()> {
+    let rotated_path = |i: usize| {
+        let mut p = path.as_os_str().to_owned();
+        p.push(format!(".{}", i));
+        PathBuf::from(p)
+    };
+
+    if max_files == 0 {
+        return Ok(fs::remove_file(path)?);
+    }
+
+    let _ = fs::remove_file(rotated_path(max_files));
+    for i in (1..max_files).rev() {
+        let p = rotated_path(i);
+        if p.exists() {
+            fs::rename(p, rotated_path(i + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(1))?;
+
+    Ok(())
+}
+
+// Returns true if the per-packet info log message of the given category must be logged, based
+// on the configured 1-in-N sampling. This must not be used for warnings and errors.
+pub fn sample(category: Category) -> bool {
+    let conf = config::get();
+    let n = match category {
+        Category::Uplink => conf.logging.sampling.uplink,
+        Category::Downlink => conf.logging.sampling.downlink,
+        Category::Mesh => conf.logging.sampling.mesh,
+    };
+
+    sample_n(&SAMPLE_COUNTERS[category as usize], n)
+}
+
+fn sample_n(counter: &AtomicU64, n: u64) -> bool {
+    if n <= 1 {
+        return true;
+    }
+
+    counter.fetch_add(1, Ordering::Relaxed) % n == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample_n() {
+        struct Test {
+            n: u64,
+            expected: Vec<bool>,
+        }
+
+        let tests = vec![
+            Test {
+                n: 0,
+                expected: vec![true, true, true],
+            },
+            Test {
+                n: 1,
+                expected: vec![true, true, true],
+            },
+            Test {
+                n: 3,
+                expected: vec![true, false, false, true, false, false, true],
+            },
+        ];
+
+        for tst in &tests {
+            let counter = AtomicU64::new(0);
+            let out: Vec<bool> = tst
+                .expected
+                .iter()
+                .map(|_| sample_n(&counter, tst.n))
+                .collect();
+            assert_eq!(tst.expected, out);
+        }
+    }
+
+    #[test]
+    fn test_rotate() {
+        let dir = std::env::temp_dir().join(format!("gateway-mesh-log-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.log");
+
+        for i in 0..4 {
+            fs::write(&path, format!("{}", i)).unwrap();
+            rotate(&path, 2).unwrap();
+        }
+
+        assert!(!path.exists());
+        assert_eq!("3", fs::read_to_string(dir.join("test.log.1")).unwrap());
+        assert_eq!("2", fs::read_to_string(dir.join("test.log.2")).unwrap());
+        assert!(!dir.join("test.log.3").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
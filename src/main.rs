@@ -3,9 +3,9 @@ use std::time::Duration;
 use std::{process, str::FromStr};
 
 use clap::{Parser, Subcommand};
-use log::info;
+use log::{error, info, warn};
 
-use chirpstack_gateway_mesh::{cmd, config, logging};
+use chirpstack_gateway_mesh::{aes128::Aes128Key, capacity, cmd, config, logging};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -13,6 +13,10 @@ struct Cli {
     #[arg(short, long, value_name = "FILE")]
     config: Vec<String>,
 
+    /// Site profile to apply on top of the configuration
+    #[arg(short, long, value_name = "NAME")]
+    profile: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -21,27 +25,174 @@ struct Cli {
 enum Commands {
     /// Print the configuration template
     Configfile {},
+
+    /// Print the expected mesh airtime utilization for the configuration
+    Capacity {
+        /// Number of Relay Gateways
+        #[arg(long, default_value_t = 1)]
+        relays: usize,
+
+        /// Device uplinks per hour (total)
+        #[arg(long, default_value_t = 0.0)]
+        uplinks_per_hour: f64,
+
+        /// Device uplink PHYPayload size
+        #[arg(long, default_value_t = 23)]
+        uplink_size: usize,
+
+        /// Device downlinks per hour (total)
+        #[arg(long, default_value_t = 0.0)]
+        downlinks_per_hour: f64,
+
+        /// Device downlink PHYPayload size
+        #[arg(long, default_value_t = 23)]
+        downlink_size: usize,
+    },
+
+    /// Print the dead-letter log entries
+    DeadLetters {},
+
+    /// Decode a mesh PHYPayload and validate its MIC
+    Decode {
+        /// HEX encoded mesh PHYPayload
+        #[arg(value_name = "PHY_PAYLOAD")]
+        phy_payload: String,
+
+        /// Signing key (HEX encoded), the configured signing key of the key index is used if not set
+        #[arg(long)]
+        key: Option<Aes128Key>,
+    },
+
+    /// Print the link-quality history of the past 24 hours (from the running Border Gateway)
+    LinkQuality {
+        /// Relay ID (HEX encoded), all relays are printed if not set
+        relay_id: Option<String>,
+    },
+
+    /// Print the configuration file with legacy section and key names migrated
+    MigrateConfig {},
+
+    /// Print the configured signing keys by key index, with their fingerprint
+    PrintKeys {},
+
+    /// Simulate Relay Gateways, by acting as the Mesh Concentratord of the Border Gateway
+    Simulate {
+        /// Number of Relay Gateways
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        relays: u32,
+
+        /// Uplink interval (per Relay Gateway, 0s to disable)
+        #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+        uplink_interval: Duration,
+
+        /// Heartbeat interval (per Relay Gateway, 0s to disable)
+        #[arg(long, default_value = "300s", value_parser = humantime::parse_duration)]
+        heartbeat_interval: Duration,
+    },
+
+    /// Print the topology from the topology state file
+    Topology {
+        /// Output format
+        #[arg(long, default_value = "json", value_parser = ["json", "dot", "geojson"])]
+        format: String,
+    },
+
+    /// Convert an UCI configuration file (OpenWrt) to the TOML configuration format
+    UciToToml {
+        /// UCI configuration file (e.g. as exported by uci export)
+        #[arg(value_name = "FILE")]
+        file: String,
+    },
+
+    /// Print known-answer test vectors (mesh packets and MICs) for the configured signing keys
+    Vectors {},
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    config::Configuration::load(&cli.config).expect("Read configuration error");
+    config::Configuration::load(&cli.config, cli.profile.as_deref())
+        .expect("Read configuration error");
 
     if let Some(Commands::Configfile {}) = &cli.command {
         cmd::configfile::run();
         process::exit(0);
     }
 
+    if let Some(Commands::Capacity {
+        relays,
+        uplinks_per_hour,
+        uplink_size,
+        downlinks_per_hour,
+        downlink_size,
+    }) = &cli.command
+    {
+        cmd::capacity::run(&capacity::Traffic {
+            relays: *relays,
+            uplinks_per_hour: *uplinks_per_hour,
+            uplink_size: *uplink_size,
+            downlinks_per_hour: *downlinks_per_hour,
+            downlink_size: *downlink_size,
+        })
+        .expect("Capacity report error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::DeadLetters {}) = &cli.command {
+        cmd::deadletters::run().expect("Print dead-letters error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::Decode { phy_payload, key }) = &cli.command {
+        cmd::decode::run(phy_payload, *key).expect("Decode error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::LinkQuality { relay_id }) = &cli.command {
+        cmd::linkquality::run(relay_id).expect("Link quality error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::MigrateConfig {}) = &cli.command {
+        cmd::migrateconfig::run(&cli.config).expect("Migrate configuration error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::PrintKeys {}) = &cli.command {
+        cmd::printkeys::run().expect("Print keys error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::Simulate {
+        relays,
+        uplink_interval,
+        heartbeat_interval,
+    }) = &cli.command
+    {
+        cmd::simulate::run(*relays, *uplink_interval, *heartbeat_interval).expect("Simulate error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::Topology { format }) = &cli.command {
+        cmd::topology::run(format).expect("Print topology error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::UciToToml { file }) = &cli.command {
+        cmd::ucitotoml::run(file).expect("Convert UCI configuration error");
+        process::exit(0);
+    }
+
+    if let Some(Commands::Vectors {}) = &cli.command {
+        cmd::vectors::run().expect("Print test vectors error");
+        process::exit(0);
+    }
+
     let conf = config::get();
     let log_level = log::Level::from_str(&conf.logging.level).expect("Parse log_level error");
 
     // Loop until success, as this will fail when syslog hasn't been fully started.
-    while let Err(e) = logging::setup(
-        env!("CARGO_PKG_NAME"),
-        log_level,
-        conf.logging.log_to_syslog,
-    ) {
+    while let Err(e) = logging::setup(env!("CARGO_PKG_NAME"), log_level, &conf.logging) {
         println!("Setup log error: {}", e);
         sleep(Duration::from_secs(1))
     }
@@ -54,5 +205,15 @@ async fn main() {
         env!("CARGO_PKG_HOMEPAGE"),
     );
 
-    cmd::root::run(&conf).await.unwrap();
+    for name in config::get_migrated_names() {
+        warn!(
+            "Legacy configuration name has been migrated, run the migrate-config subcommand to update the configuration file, migrated: {}",
+            name
+        );
+    }
+
+    if let Err(e) = cmd::root::run(&conf).await {
+        error!("{:#}", e);
+        process::exit(1);
+    }
 }
@@ -1,30 +1,75 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::iter;
 use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::Result;
-use chirpstack_api::gw;
-use log::{info, trace, warn};
+use chirpstack_api::{common, gw};
+use log::{debug, error, info, trace, warn};
 use once_cell::sync::Lazy;
 use rand::random;
+use tokio::time::sleep;
 
+use crate::error::{Error, Result};
 use crate::{
-    backend,
+    antireplay, backend, beacon,
     cache::{Cache, PayloadCache},
+    challenge,
     config::{self, Configuration},
-    helpers,
+    deadletter, events, heartbeat, helpers, hopcount, logging,
     packets::{
         self, DownlinkMetadata, MeshPacket, Payload, PayloadType, UplinkMetadata, UplinkPayload,
         MHDR,
     },
-    proxy,
+    proto, proxy, routing, stats, tdma, topology, uplinkcontext, webhook,
 };
 
+// Relay ID + Uplink ID.
+type UplinkKey = ([u8; 4], u16);
+
+// Max. number of relayed uplinks remembered per DevAddr for path stickiness.
+const DEVICE_PATHS_MAX: usize = 8;
+
 static CTX_PREFIX: [u8; 3] = [1, 2, 3];
 static MESH_CHANNEL: Mutex<usize> = Mutex::new(0);
-static UPLINK_ID: Mutex<u16> = Mutex::new(0);
-static UPLINK_CONTEXT: Lazy<Mutex<HashMap<u16, Vec<u8>>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
 static PAYLOAD_CACHE: Lazy<Mutex<Cache<PayloadCache>>> = Lazy::new(|| Mutex::new(Cache::new(64)));
+static UPLINK_RELAYED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+static HEARTBEAT_RELAYED_AT: Lazy<Mutex<HashMap<[u8; 4], Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static UPLINK_PAYLOAD_RELAYED_AT: Lazy<Mutex<HashMap<Vec<u8>, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static DOWNLINK_PATHS: Lazy<Mutex<HashMap<[u8; 4], Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static RELAY_DOWNLINK_BUSY_UNTIL: Lazy<Mutex<HashMap<[u8; 4], Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+// Relayed downlinks per DevAddr within the downlink_rate_limit interval.
+static DOWNLINK_RELAYED_AT: Lazy<Mutex<HashMap<[u8; 4], VecDeque<Instant>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+// Received packets per Relay ID within the rate_limit_interval of its relay group.
+static RELAY_GROUP_RECEIVED_AT: Lazy<Mutex<HashMap<[u8; 4], VecDeque<Instant>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+// Relay ID + Uplink ID and PHYPayload of the recent relayed uplinks per DevAddr (oldest first),
+// used for path stickiness.
+type DevicePaths = HashMap<[u8; 4], VecDeque<(UplinkKey, Vec<u8>, Instant)>>;
+
+static DEVICE_PATHS: Lazy<Mutex<DevicePaths>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// Relayed uplinks pending acknowledgement, with the hop count of the transmitted mesh packet.
+static UPLINK_ACK_PENDING: Lazy<Mutex<HashMap<UplinkKey, u8>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+// Relayed uplinks acknowledged by the Border Gateway, such that the retransmissions of the Relay
+// Gateway (e.g. when the ACK was lost) are acknowledged again.
+static UPLINK_ACKED: Lazy<Mutex<Cache<UplinkKey>>> = Lazy::new(|| Mutex::new(Cache::new(64)));
+// Relayed downlinks pending acknowledgement by the Relay Gateway.
+static DOWNLINK_ACK_PENDING: Lazy<Mutex<HashSet<UplinkKey>>> =
+    Lazy::new(|| Mutex::new(HashSet::new()));
+// Delivery results of the most recent relayed downlinks per Relay Gateway.
+static DOWNLINK_ACK_RESULTS: Lazy<Mutex<HashMap<[u8; 4], DownlinkAckResults>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Default)]
+struct DownlinkAckResults {
+    results: VecDeque<bool>,
+    alert: bool,
+}
 
 // Handle LoRaWAN payload (non-proprietary).
 pub async fn handle_uplink(border_gateway: bool, pl: gw::UplinkFrame) -> Result<()> {
@@ -37,12 +82,53 @@ pub async fn handle_uplink(border_gateway: bool, pl: gw::UplinkFrame) -> Result<
 // Handle Proprietary LoRaWAN payload (mesh encapsulated).
 pub async fn handle_mesh(border_gateway: bool, pl: gw::UplinkFrame) -> Result<()> {
     let conf = config::get();
-    let packet = MeshPacket::from_slice(&pl.phy_payload)?;
-    if !packet.validate_mic(conf.mesh.signing_key)? {
-        warn!("Dropping packet, invalid MIC, mesh_packet: {}", packet);
+    let packet = MeshPacket::from_slice(&pl.phy_payload).map_err(Error::Packet)?;
+    // Packets signed with an unknown key index are handled as packets with an invalid MIC.
+    let mic_valid = match conf.mesh.get_signing_key(packet.mhdr.key_index) {
+        Some(key) => packet.validate_mic(key).map_err(Error::Packet)?,
+        None => false,
+    };
+    if !mic_valid {
+        if let Some(suppressed) = stats::mesh_drop_log_allowed(deadletter::REASON_INVALID_MIC) {
+            warn!(
+                "Dropping packet, invalid MIC, suppressed: {}, mesh_packet: {}",
+                suppressed, packet
+            );
+        }
+        deadletter::record(
+            deadletter::REASON_INVALID_MIC,
+            &pl.phy_payload,
+            &Error::InvalidMic.to_string(),
+        );
         return Ok(());
     }
 
+    // Publish the mesh packet before de-duplication, such that analysis tools can see every
+    // reception of the packet.
+    if border_gateway && conf.mesh.proxy_api.mesh_packet_events {
+        proxy::send_mesh_packet(&proto::MeshPacketEvent {
+            gateway_id: hex::encode(backend::get_gateway_id().await?),
+            mesh_packet: Some(helpers::mesh_packet_to_proto(&packet)),
+            phy_payload: pl.phy_payload.clone(),
+            frequency: pl.tx_info.as_ref().map(|v| v.frequency).unwrap_or_default(),
+            rssi: pl.rx_info.as_ref().map(|v| v.rssi).unwrap_or_default(),
+            snr: pl.rx_info.as_ref().map(|v| v.snr).unwrap_or_default(),
+            time: Some(
+                pl.rx_info
+                    .as_ref()
+                    .and_then(|v| v.gw_time.clone())
+                    .unwrap_or_else(|| helpers::system_time_to_timestamp(SystemTime::now())),
+            ),
+        })
+        .await?;
+    }
+
+    // This must be handled before de-duplication, as the re-relayed uplink of the next hop
+    // (implicit acknowledgement) is a duplicate of the uplink that was relayed.
+    if !border_gateway && conf.mesh.uplink_ack.enabled {
+        handle_uplink_ack(&packet);
+    }
+
     // If we can't add the packet to the cache, it means we have already seen the packet and we can
     // drop it.
     if !PAYLOAD_CACHE.lock().unwrap().add((&packet).into()) {
@@ -50,14 +136,61 @@ pub async fn handle_mesh(border_gateway: bool, pl: gw::UplinkFrame) -> Result<()
             "Dropping packet as it has already been seen, mesh_packet: {}",
             packet
         );
+
+        // The duplicate might be a retransmission, as the Relay Gateway did not receive the ACK.
+        if border_gateway && conf.mesh.uplink_ack.enabled {
+            if let Payload::Uplink(v) = &packet.payload {
+                let key = (v.relay_id, v.metadata.uplink_id);
+                if UPLINK_ACKED.lock().unwrap().iter().any(|v| *v == key) {
+                    debug!(
+                        "Re-sending uplink ACK for duplicate uplink, relay_id: {}, uplink_id: {}",
+                        hex::encode(key.0),
+                        key.1
+                    );
+                    events::send_uplink_ack(key.0, key.1).await?;
+                }
+            }
+        }
+
         return Ok(());
     };
 
+    if conf.mesh.replay_protection.enabled {
+        if let Err(e) = antireplay::check(&packet) {
+            if let Some(suppressed) = stats::mesh_drop_log_allowed(e.reason()) {
+                warn!(
+                    "Dropping packet, {}, suppressed: {}, mesh_packet: {}",
+                    e.to_string().to_lowercase(),
+                    suppressed,
+                    packet
+                );
+            }
+            deadletter::record(e.reason(), &pl.phy_payload, &e.to_string());
+            return Ok(());
+        }
+    }
+
+    if let Some(msg) = check_relay_group_policy(&conf, &packet) {
+        if let Some(suppressed) =
+            stats::mesh_drop_log_allowed(deadletter::REASON_RELAY_GROUP_POLICY)
+        {
+            warn!(
+                "Dropping packet, {}, suppressed: {}, mesh_packet: {}",
+                msg.to_lowercase(),
+                suppressed,
+                packet
+            );
+        }
+        deadletter::record(deadletter::REASON_RELAY_GROUP_POLICY, &pl.phy_payload, msg);
+        return Ok(());
+    }
+
     match border_gateway {
         // Proxy relayed uplink
         true => match packet.mhdr.payload_type {
             PayloadType::Uplink => proxy_uplink_mesh_packet(&pl, packet).await,
             PayloadType::Heartbeat => proxy_heartbeat_mesh_packet(&pl, packet).await,
+            PayloadType::Event => proxy_event_mesh_packet(&pl, packet).await,
             _ => Ok(()),
         },
         false => relay_mesh_packet(&pl, packet).await,
@@ -69,7 +202,7 @@ pub async fn handle_downlink(pl: gw::DownlinkFrame) -> Result<gw::DownlinkTxAck>
         let tx_info = first_item
             .tx_info
             .as_ref()
-            .ok_or_else(|| anyhow!("tx_info is None"))?;
+            .ok_or_else(|| Error::InvalidMessage("tx_info is None".into()))?;
 
         // Check if context has the CTX_PREFIX, if not we just proxy the downlink payload.
         if tx_info.context.len() != CTX_PREFIX.len() + 6
@@ -83,18 +216,22 @@ pub async fn handle_downlink(pl: gw::DownlinkFrame) -> Result<gw::DownlinkTxAck>
 }
 
 async fn proxy_downlink_lora_packet(pl: &gw::DownlinkFrame) -> Result<gw::DownlinkTxAck> {
-    info!(
-        "Proxying LoRaWAN downlink, downlink: {}",
-        helpers::format_downlink(pl)?
-    );
+    if logging::sample(logging::Category::Downlink) {
+        info!(
+            "Proxying LoRaWAN downlink, downlink: {}",
+            helpers::format_downlink(pl)?
+        );
+    }
     backend::send_downlink(pl).await
 }
 
 async fn proxy_uplink_lora_packet(pl: &gw::UplinkFrame) -> Result<()> {
-    info!(
-        "Proxying LoRaWAN uplink, uplink: {}",
-        helpers::format_uplink(pl)?
-    );
+    if logging::sample(logging::Category::Uplink) {
+        info!(
+            "Proxying LoRaWAN uplink, uplink: {}",
+            helpers::format_uplink(pl)?
+        );
+    }
     proxy::send_uplink(pl).await
 }
 
@@ -102,15 +239,53 @@ async fn proxy_uplink_mesh_packet(pl: &gw::UplinkFrame, packet: MeshPacket) -> R
     let mesh_pl = match &packet.payload {
         Payload::Uplink(v) => v,
         _ => {
-            return Err(anyhow!("Expected Uplink payload"));
+            return Err(Error::InvalidMessage("Expected Uplink payload".into()));
         }
     };
 
-    info!(
-        "Unwrapping relayed uplink, uplink_id: {}, mesh_packet: {}",
-        pl.rx_info.as_ref().map(|v| v.uplink_id).unwrap_or_default(),
-        packet
-    );
+    if let Some(rx_info) = &pl.rx_info {
+        topology::record_uplink(
+            mesh_pl.relay_id,
+            packet.mhdr.hop_count,
+            rx_info.rssi,
+            rx_info.snr,
+        );
+    }
+
+    let conf = config::get();
+    let border_id = conf.mesh.border_id;
+    if border_id != 0 && mesh_pl.metadata.border_id != 0 && mesh_pl.metadata.border_id != border_id
+    {
+        debug!(
+            "Ignoring relayed uplink targeted to other Border Gateway, border_id: {}, mesh_packet: {}",
+            mesh_pl.metadata.border_id, packet
+        );
+        return Ok(());
+    }
+
+    if conf.mesh.filters.relayed_uplinks
+        && !lrwn_filters::matches(
+            &mesh_pl.phy_payload,
+            &lrwn_filters::Filters {
+                dev_addr_prefixes: conf.mesh.filters.dev_addr_prefixes.clone(),
+                join_eui_prefixes: conf.mesh.filters.join_eui_prefixes.clone(),
+            },
+        )
+    {
+        debug!(
+            "Discarding relayed uplink because of dev_addr and join_eui filters, mesh_packet: {}",
+            packet
+        );
+        return Ok(());
+    }
+
+    if logging::sample(logging::Category::Uplink) {
+        info!(
+            "Unwrapping relayed uplink, uplink_id: {}, mesh_packet: {}",
+            pl.rx_info.as_ref().map(|v| v.uplink_id).unwrap_or_default(),
+            packet
+        );
+    }
 
     let mut pl = pl.clone();
 
@@ -126,7 +301,16 @@ async fn proxy_uplink_mesh_packet(pl: &gw::UplinkFrame, packet: MeshPacket) -> R
             .metadata
             .insert("relay_id".to_string(), hex::encode(mesh_pl.relay_id));
 
-        // Set RSSI and SNR.
+        // Set the RSSI and SNR of the last hop (as received by the Border Gateway), before
+        // these are overwritten by the first hop values.
+        rx_info
+            .metadata
+            .insert("last_hop_rssi".to_string(), rx_info.rssi.to_string());
+        rx_info
+            .metadata
+            .insert("last_hop_snr".to_string(), rx_info.snr.to_string());
+
+        // Set RSSI and SNR (of the first hop).
         rx_info.snr = mesh_pl.metadata.snr.into();
         rx_info.rssi = mesh_pl.metadata.rssi.into();
 
@@ -149,22 +333,71 @@ async fn proxy_uplink_mesh_packet(pl: &gw::UplinkFrame, packet: MeshPacket) -> R
     // Set original PHYPayload.
     pl.phy_payload.clone_from(&mesh_pl.phy_payload);
 
-    proxy::send_uplink(&pl).await
+    if conf.mesh.path_stickiness.enabled {
+        if let Some(dev_addr) = get_dev_addr(&mesh_pl.phy_payload) {
+            record_device_path(
+                dev_addr,
+                (mesh_pl.relay_id, mesh_pl.metadata.uplink_id),
+                &mesh_pl.phy_payload,
+            );
+        }
+    }
+
+    proxy::send_uplink(&pl).await?;
+
+    if conf.mesh.uplink_ack.enabled {
+        UPLINK_ACKED
+            .lock()
+            .unwrap()
+            .add((mesh_pl.relay_id, mesh_pl.metadata.uplink_id));
+        events::send_uplink_ack(mesh_pl.relay_id, mesh_pl.metadata.uplink_id).await?;
+    }
+
+    Ok(())
 }
 
 async fn proxy_heartbeat_mesh_packet(pl: &gw::UplinkFrame, packet: MeshPacket) -> Result<()> {
     let mesh_pl = match &packet.payload {
         Payload::Heartbeat(v) => v,
         _ => {
-            return Err(anyhow!("Expected Heartbeat payload"));
+            return Err(Error::InvalidMessage("Expected Heartbeat payload".into()));
         }
     };
 
-    info!(
-        "Unwrapping relay heartbeat packet, uplink_id: {}, mesh_packet: {}",
-        pl.rx_info.as_ref().map(|v| v.uplink_id).unwrap_or_default(),
-        packet
-    );
+    if logging::sample(logging::Category::Mesh) {
+        info!(
+            "Unwrapping relay heartbeat packet, uplink_id: {}, mesh_packet: {}",
+            pl.rx_info.as_ref().map(|v| v.uplink_id).unwrap_or_default(),
+            packet
+        );
+    }
+
+    if let Some(config_checksum) = mesh_pl.config_checksum {
+        let expected_config_checksum = heartbeat::get_config_checksum(&config::get())?;
+        let mismatch = config_checksum != expected_config_checksum;
+        if mismatch {
+            warn!(
+                "Relay config checksum mismatch, the [mappings] or mesh frequencies / data_rate configuration of the Relay Gateway differs from the Border Gateway, relay_id: {}, config_checksum: {}, expected_config_checksum: {}",
+                hex::encode(mesh_pl.relay_id),
+                hex::encode(config_checksum),
+                hex::encode(expected_config_checksum)
+            );
+        }
+
+        if stats::record_relay_config_mismatch(mesh_pl.relay_id, mismatch) && mismatch {
+            proxy::send_mesh_config_mismatch(&proto::MeshConfigMismatchEvent {
+                gateway_id: hex::encode(backend::get_gateway_id().await?),
+                relay_id: mesh_pl.relay_id.to_vec(),
+                config_checksum: config_checksum.to_vec(),
+                expected_config_checksum: expected_config_checksum.to_vec(),
+            })
+            .await?;
+        }
+    }
+
+    if let Some(rx_info) = &pl.rx_info {
+        topology::record_heartbeat(mesh_pl, packet.mhdr.hop_count, rx_info.rssi, rx_info.snr);
+    }
 
     let heartbeat_pl = gw::MeshHeartbeat {
         gateway_id: hex::encode(backend::get_gateway_id().await?),
@@ -173,24 +406,240 @@ async fn proxy_heartbeat_mesh_packet(pl: &gw::UplinkFrame, packet: MeshPacket) -
             .relay_path
             .iter()
             .map(|v| gw::MeshHeartbeatRelayPath {
-                relay_id: hex::encode(&v.relay_id),
+                relay_id: hex::encode(v.relay_id),
                 rssi: v.rssi.into(),
                 snr: v.snr.into(),
             })
             .collect(),
-        time: Some(mesh_pl.timestamp.into()),
+        // A monotonic timestamp is only meaningful to the Relay Gateway itself, in which case
+        // the time of reception is used.
+        time: Some(if mesh_pl.timestamp_monotonic {
+            helpers::system_time_to_timestamp(SystemTime::now())
+        } else {
+            helpers::millis_to_timestamp(mesh_pl.timestamp)
+        }),
     };
 
     proxy::send_mesh_heartbeat(&heartbeat_pl).await
 }
 
+async fn proxy_event_mesh_packet(pl: &gw::UplinkFrame, packet: MeshPacket) -> Result<()> {
+    let mesh_pl = match &packet.payload {
+        Payload::Event(v) => v,
+        _ => {
+            return Err(Error::InvalidMessage("Expected Event payload".into()));
+        }
+    };
+
+    if logging::sample(logging::Category::Mesh) {
+        info!(
+            "Unwrapping relay event packet, uplink_id: {}, mesh_packet: {}",
+            pl.rx_info.as_ref().map(|v| v.uplink_id).unwrap_or_default(),
+            packet
+        );
+    }
+
+    let (rssi, snr) = pl
+        .rx_info
+        .as_ref()
+        .map(|v| (v.rssi, v.snr))
+        .unwrap_or_default();
+
+    for event in &mesh_pl.events {
+        match event {
+            packets::Event::Attestation(v) => {
+                info!(
+                    "Relay attestation received, relay_id: {}, version: {}, firmware_version: {}, config_checksum: {}, features: {:?}",
+                    hex::encode(mesh_pl.relay_id),
+                    v.version,
+                    v.firmware_version,
+                    hex::encode(v.config_checksum),
+                    events::get_feature_names(v.features),
+                );
+                topology::record_attestation(mesh_pl.relay_id, v, packet.mhdr.hop_count, rssi, snr);
+            }
+            packets::Event::TxAirtime(v) => {
+                info!(
+                    "Relay TX airtime received, relay_id: {}, airtime: {:?}",
+                    hex::encode(mesh_pl.relay_id),
+                    Duration::from_millis(v.airtime.into()),
+                );
+                topology::record_tx_airtime(mesh_pl.relay_id, v, packet.mhdr.hop_count, rssi, snr);
+            }
+            packets::Event::Stats(v) => {
+                info!(
+                    "Relay stats received, relay_id: {}, gateway_id: {}",
+                    hex::encode(mesh_pl.relay_id),
+                    hex::encode(v.gateway_id),
+                );
+                topology::record_tx_airtime(
+                    mesh_pl.relay_id,
+                    &packets::TxAirtimeEvent {
+                        airtime: v.tx_airtime,
+                    },
+                    packet.mhdr.hop_count,
+                    rssi,
+                    snr,
+                );
+                if topology::record_uplink_acks(
+                    mesh_pl.relay_id,
+                    v,
+                    packet.mhdr.hop_count,
+                    rssi,
+                    snr,
+                ) {
+                    let asymmetric_link = topology::get_relays()
+                        .iter()
+                        .any(|r| r.relay_id == mesh_pl.relay_id && r.asymmetric_link);
+                    if asymmetric_link {
+                        warn!(
+                            "Relay Gateway has an asymmetric link, it does not receive the uplink ACKs, relay_id: {}, uplinks_acked: {}, uplinks_not_acked: {}",
+                            hex::encode(mesh_pl.relay_id),
+                            v.uplinks_acked,
+                            v.uplinks_not_acked
+                        );
+                    } else {
+                        info!(
+                            "Relay Gateway asymmetric link resolved, relay_id: {}",
+                            hex::encode(mesh_pl.relay_id)
+                        );
+                    }
+                    stats::record_relay_asymmetric_link(mesh_pl.relay_id, asymmetric_link);
+                }
+                proxy::send_stats(&get_relay_stats(mesh_pl.relay_id, v)).await?;
+            }
+            packets::Event::Telemetry(v) => {
+                debug!(
+                    "Relay telemetry received, relay_id: {}",
+                    hex::encode(mesh_pl.relay_id)
+                );
+                topology::record_telemetry(mesh_pl.relay_id, v, packet.mhdr.hop_count, rssi, snr);
+            }
+            packets::Event::DownlinkAck(v) => {
+                let key = (mesh_pl.relay_id, v.uplink_id);
+                if !DOWNLINK_ACK_PENDING.lock().unwrap().remove(&key) {
+                    debug!(
+                        "Ignoring downlink ACK event, downlink is not pending, relay_id: {}, uplink_id: {}",
+                        hex::encode(mesh_pl.relay_id),
+                        v.uplink_id
+                    );
+                    continue;
+                }
+
+                let status = gw::TxAckStatus::try_from(i32::from(v.status))
+                    .unwrap_or(gw::TxAckStatus::Ignored);
+                debug!(
+                    "Downlink acknowledged, relay_id: {}, uplink_id: {}, status: {}",
+                    hex::encode(mesh_pl.relay_id),
+                    v.uplink_id,
+                    status.as_str_name()
+                );
+                record_downlink_ack(mesh_pl.relay_id, status == gw::TxAckStatus::Ok).await?;
+            }
+            packets::Event::Boot(v) => {
+                if v.reason == packets::BootReason::CrashRecovery {
+                    warn!(
+                        "Relay Gateway recovered from a crash, relay_id: {}, version: {}",
+                        hex::encode(mesh_pl.relay_id),
+                        v.version
+                    );
+                } else {
+                    info!(
+                        "Relay Gateway started, relay_id: {}, reason: {:?}, version: {}",
+                        hex::encode(mesh_pl.relay_id),
+                        v.reason,
+                        v.version
+                    );
+                }
+                topology::record_boot(mesh_pl.relay_id, v, packet.mhdr.hop_count, rssi, snr);
+                proxy::send_mesh_relay_boot(&proto::MeshRelayBootEvent {
+                    gateway_id: hex::encode(backend::get_gateway_id().await?),
+                    relay_id: mesh_pl.relay_id.to_vec(),
+                    boot: Some(helpers::boot_event_to_proto(v)),
+                })
+                .await?;
+            }
+            packets::Event::Shutdown => {
+                warn!(
+                    "Relay Gateway announced its shutdown, relay_id: {}",
+                    hex::encode(mesh_pl.relay_id)
+                );
+                let relay =
+                    topology::record_shutdown(mesh_pl.relay_id, packet.mhdr.hop_count, rssi, snr);
+                webhook::send(webhook::Event::RelayOffline {
+                    relay_id: relay.relay_id,
+                    last_seen_at: relay.last_seen_at,
+                    shutdown: true,
+                });
+            }
+            packets::Event::Location(v) => {
+                debug!(
+                    "Relay location received, relay_id: {}, latitude: {}, longitude: {}, altitude: {}",
+                    hex::encode(mesh_pl.relay_id),
+                    f64::from(v.latitude) / 1e7,
+                    f64::from(v.longitude) / 1e7,
+                    v.altitude
+                );
+                topology::record_location(mesh_pl.relay_id, v, packet.mhdr.hop_count, rssi, snr);
+            }
+            packets::Event::UplinkAck(_) => {
+                trace!(
+                    "Ignoring uplink ACK event, relay_id: {}",
+                    hex::encode(mesh_pl.relay_id)
+                );
+            }
+            packets::Event::TdmaBeacon(_) => {
+                trace!(
+                    "Ignoring TDMA beacon event, relay_id: {}",
+                    hex::encode(mesh_pl.relay_id)
+                );
+            }
+            packets::Event::MaxHopCount(_) => {
+                trace!(
+                    "Ignoring max hop count event, relay_id: {}",
+                    hex::encode(mesh_pl.relay_id)
+                );
+            }
+            packets::Event::Challenge(_) => {
+                trace!(
+                    "Ignoring challenge event, relay_id: {}",
+                    hex::encode(mesh_pl.relay_id)
+                );
+            }
+            packets::Event::ChallengeResponse(v) => {
+                if config::get().mesh.challenge.enabled {
+                    challenge::record_response(
+                        mesh_pl.relay_id,
+                        v,
+                        packet.mhdr.hop_count,
+                        rssi,
+                        snr,
+                    );
+                }
+            }
+            packets::Event::Unknown(t, _) => {
+                debug!(
+                    "Ignoring unknown relay event, relay_id: {}, event_type: {}",
+                    hex::encode(mesh_pl.relay_id),
+                    t
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
 async fn relay_mesh_packet(pl: &gw::UplinkFrame, mut packet: MeshPacket) -> Result<()> {
     let conf = config::get();
     let relay_id = backend::get_relay_id().await?;
     let rx_info = pl
         .rx_info
         .as_ref()
-        .ok_or_else(|| anyhow!("rx_info is None"))?;
+        .ok_or_else(|| Error::InvalidMessage("rx_info is None".into()))?;
+
+    // This must be retrieved before the relay path of a heartbeat is updated.
+    let transmitter = routing::get_transmitter(&packet);
 
     match &mut packet.payload {
         packets::Payload::Uplink(pl) => {
@@ -202,10 +651,22 @@ async fn relay_mesh_packet(pl: &gw::UplinkFrame, mut packet: MeshPacket) -> Resu
             }
         }
         packets::Payload::Downlink(pl) => {
+            if pl.relay_id != relay_id
+                && conf.mesh.unicast_downlinks.enabled
+                && !on_downlink_path(pl.relay_id, conf.mesh.unicast_downlinks.path_timeout)
+            {
+                debug!(
+                    "Dropping downlink, this relay is not on the path to the destination relay, relay_id: {}",
+                    hex::encode(pl.relay_id)
+                );
+                return Ok(());
+            }
+
             if pl.relay_id == relay_id {
                 // We must unwrap the mesh encapsulated packet and send it to the
                 // End Device.
 
+                let uplink_id = pl.metadata.uplink_id;
                 let pl = gw::DownlinkFrame {
                     downlink_id: random(),
                     items: vec![gw::DownlinkFrameItem {
@@ -216,7 +677,7 @@ async fn relay_mesh_packet(pl: &gw::UplinkFrame, mut packet: MeshPacket) -> Resu
                             timing: Some(gw::Timing {
                                 parameters: Some(gw::timing::Parameters::Delay(
                                     gw::DelayTimingInfo {
-                                        delay: Some(prost_types::Duration {
+                                        delay: Some(pbjson_types::Duration {
                                             seconds: pl.metadata.delay.into(),
                                             ..Default::default()
                                         }),
@@ -224,7 +685,14 @@ async fn relay_mesh_packet(pl: &gw::UplinkFrame, mut packet: MeshPacket) -> Resu
                                 )),
                             }),
                             modulation: Some(helpers::dr_to_modulation(pl.metadata.dr, true)?),
-                            context: get_uplink_context(pl.metadata.uplink_id)?,
+                            context: get_uplink_context(pl.metadata.uplink_id).map_err(|e| {
+                                deadletter::record(
+                                    deadletter::REASON_NO_UPLINK_CONTEXT,
+                                    &pl.phy_payload,
+                                    &e.to_string(),
+                                );
+                                e
+                            })?,
                             ..Default::default()
                         }),
                         ..Default::default()
@@ -233,11 +701,37 @@ async fn relay_mesh_packet(pl: &gw::UplinkFrame, mut packet: MeshPacket) -> Resu
                     ..Default::default()
                 };
 
-                info!(
-                    "Unwrapping relayed downlink, downlink_id: {}, mesh_packet: {}",
-                    pl.downlink_id, packet
-                );
-                return helpers::tx_ack_to_err(&backend::send_downlink(&pl).await?);
+                if logging::sample(logging::Category::Downlink) {
+                    info!(
+                        "Unwrapping relayed downlink, downlink_id: {}, mesh_packet: {}",
+                        pl.downlink_id, packet
+                    );
+                }
+                let tx_ack = backend::send_downlink(&pl).await?;
+                if conf.mesh.downlink_ack.enabled {
+                    let status = tx_ack
+                        .items
+                        .iter()
+                        .map(|v| v.status())
+                        .find(|v| *v != gw::TxAckStatus::Ignored)
+                        .unwrap_or(gw::TxAckStatus::Ignored);
+                    if let Err(e) = events::send_downlink_ack(uplink_id, status).await {
+                        warn!("Sending downlink ACK failed, error: {}", e);
+                    }
+                }
+
+                let res = helpers::tx_ack_to_err(&tx_ack);
+                if let Err(e) = &res {
+                    deadletter::record(
+                        deadletter::REASON_DOWNLINK_TX_FAILED,
+                        pl.items
+                            .first()
+                            .map(|v| v.phy_payload.as_slice())
+                            .unwrap_or_default(),
+                        &e.to_string(),
+                    );
+                }
+                return res;
             }
         }
         packets::Payload::Heartbeat(pl) => {
@@ -248,40 +742,161 @@ async fn relay_mesh_packet(pl: &gw::UplinkFrame, mut packet: MeshPacket) -> Resu
                 return Ok(());
             }
 
+            routing::record_heartbeat(
+                pl,
+                packet.mhdr.hop_count,
+                relay_id,
+                helpers::apply_rssi_offset(rx_info.rssi, conf.mesh.rssi_offset),
+                rx_info.snr as i8,
+            );
+
+            if !heartbeat_relay_allowed(pl.relay_id, conf.mesh.heartbeat_relay_interval) {
+                debug!(
+                    "Dropping heartbeat as a heartbeat of this relay was relayed within heartbeat_relay_interval, relay_id: {}",
+                    hex::encode(pl.relay_id)
+                );
+                return Ok(());
+            }
+
             // Add our Relay ID to the path.
             pl.relay_path.push(packets::RelayPath {
                 relay_id,
-                rssi: rx_info.rssi as i16,
+                rssi: helpers::apply_rssi_offset(rx_info.rssi, conf.mesh.rssi_offset),
                 snr: rx_info.snr as i8,
             });
+
+            // Only keep the last N hops of the relay path. The hop count in the MHDR
+            // still reflects the total number of hops.
+            let max_len = conf.mesh.heartbeat_relay_path_max_length;
+            if max_len != 0 && pl.relay_path.len() > max_len {
+                pl.relay_path.drain(..pl.relay_path.len() - max_len);
+            }
+        }
+        packets::Payload::Event(pl) => {
+            if pl.relay_id == relay_id {
+                trace!("Dropping packet as this relay was the sender");
+
+                // Drop the packet, as we are the sender.
+                return Ok(());
+            }
+
+            if conf.mesh.tdma.enabled {
+                for event in &pl.events {
+                    if let packets::Event::TdmaBeacon(v) = event {
+                        tdma::record_beacon(v, relay_id);
+                    }
+                }
+            }
+
+            if conf.mesh.adaptive_max_hop_count.enabled {
+                for event in &pl.events {
+                    if let packets::Event::MaxHopCount(v) = event {
+                        hopcount::record_advertisement(v);
+                    }
+                }
+            }
+
+            if conf.mesh.challenge.enabled {
+                for event in &pl.events {
+                    if let packets::Event::Challenge(v) = event {
+                        challenge::record_challenge(v);
+                    }
+                }
+            }
+
+            // Drop the packet, as it only contains uplink ACKs for this relay.
+            if !pl.events.is_empty()
+                && pl
+                    .events
+                    .iter()
+                    .all(|v| matches!(v, packets::Event::UplinkAck(v) if v.relay_id == relay_id))
+            {
+                trace!("Dropping packet as this relay was the uplink ACK recipient");
+                return Ok(());
+            }
+        }
+    }
+
+    // Stay silent if an other relay has a better link with the transmitter, as that relay
+    // is expected to relay the packet.
+    if conf.mesh.routing.enabled {
+        if let Some(transmitter) = transmitter {
+            if !routing::should_relay(
+                transmitter,
+                relay_id,
+                conf.mesh.routing.rssi_margin,
+                conf.mesh.routing.max_age,
+            ) {
+                debug!(
+                    "Dropping mesh packet, an other relay has a better link with the transmitter, transmitter: {}, mesh_packet: {}",
+                    hex::encode(transmitter),
+                    packet
+                );
+                return Ok(());
+            }
         }
     }
 
     // In any other case, we increment the hop_count and re-transmit the mesh encapsulated
     // packet.
 
+    let data_rate = match &packet.payload {
+        packets::Payload::Uplink(pl) => get_uplink_data_rate(&conf, &pl.phy_payload),
+        _ => &conf.mesh.data_rate,
+    };
+
     // Increment hop count.
     packet.mhdr.hop_count += 1;
 
+    // This is expected at the edge of the mesh, thus this is not an error.
+    let max_hop_count = hopcount::get_max_hop_count(&conf);
+    if packet.mhdr.hop_count > max_hop_count {
+        if let Some(suppressed) =
+            stats::mesh_drop_log_allowed(deadletter::REASON_MAX_HOP_COUNT_EXCEEDED)
+        {
+            info!(
+                "Dropping mesh packet, max hop count exceeded, max_hop_count: {}, suppressed: {}, mesh_packet: {}",
+                max_hop_count, suppressed, packet
+            );
+        }
+        deadletter::record(
+            deadletter::REASON_MAX_HOP_COUNT_EXCEEDED,
+            &pl.phy_payload,
+            "Max hop count exceeded",
+        );
+        return Ok(());
+    }
+
     // We need to re-set the MIC as we have changed the payload by incrementing
     // the hop count (and in casee of heartbeat, we have modified the Relay path).
-    packet.set_mic(conf.mesh.signing_key)?;
+    helpers::set_mic(&conf, &mut packet)?;
 
-    if packet.mhdr.hop_count > conf.mesh.max_hop_count {
-        return Err(anyhow!("Max hop count exceeded"));
-    }
+    let phy_payload = packet.to_vec().map_err(Error::Packet)?;
+    let data_rate = match get_tx_data_rate(&conf, data_rate, phy_payload.len()) {
+        Some(v) => v,
+        None => {
+            warn!(
+                "Dropping mesh packet, size does not fit mesh data-rate, size: {}, mesh_packet: {}",
+                phy_payload.len(),
+                packet
+            );
+            deadletter::record(
+                deadletter::REASON_PAYLOAD_TOO_LARGE,
+                &phy_payload,
+                "Size does not fit mesh data-rate",
+            );
+            return Ok(());
+        }
+    };
 
     let pl = gw::DownlinkFrame {
         downlink_id: random(),
         items: vec![gw::DownlinkFrameItem {
-            phy_payload: packet.to_vec()?,
+            phy_payload,
             tx_info: Some(gw::DownlinkTxInfo {
                 frequency: get_mesh_frequency(&conf)?,
-                modulation: Some(helpers::data_rate_to_gw_modulation(
-                    &conf.mesh.data_rate,
-                    false,
-                )),
-                power: conf.mesh.tx_power,
+                modulation: Some(helpers::data_rate_to_gw_modulation(data_rate, false)),
+                power: get_tx_power(&conf).await,
                 timing: Some(gw::Timing {
                     parameters: Some(gw::timing::Parameters::Immediately(
                         gw::ImmediatelyTimingInfo {},
@@ -294,10 +909,34 @@ async fn relay_mesh_packet(pl: &gw::UplinkFrame, mut packet: MeshPacket) -> Resu
         ..Default::default()
     };
 
-    info!(
-        "Re-relaying mesh packet, downlink_id: {}, mesh_packet: {}",
-        pl.downlink_id, packet
-    );
+    if logging::sample(logging::Category::Mesh) {
+        info!(
+            "Re-relaying mesh packet, downlink_id: {}, mesh_packet: {}",
+            pl.downlink_id, packet
+        );
+    }
+
+    // As this relay forwards packets of the originating relay towards the Border Gateway,
+    // it is on the path for downlinks to this relay.
+    if conf.mesh.unicast_downlinks.enabled {
+        match &packet.payload {
+            Payload::Uplink(v) => record_downlink_path(v.relay_id),
+            Payload::Heartbeat(v) => record_downlink_path(v.relay_id),
+            Payload::Event(v) => record_downlink_path(v.relay_id),
+            Payload::Downlink(_) => {}
+        }
+    }
+
+    // This is scheduled before the transmission, as a failed transmission must be
+    // retransmitted too.
+    if conf.mesh.uplink_ack.enabled && packet.mhdr.payload_type == PayloadType::Uplink {
+        schedule_uplink_retransmissions(&packet, pl.clone());
+    }
+
+    if packet.mhdr.payload_type != PayloadType::Downlink {
+        relay_jitter(&conf, pl.downlink_id).await;
+    }
+
     backend::mesh(&pl).await
 }
 
@@ -307,47 +946,88 @@ async fn relay_uplink_lora_packet(pl: &gw::UplinkFrame) -> Result<()> {
     let rx_info = pl
         .rx_info
         .as_ref()
-        .ok_or_else(|| anyhow!("rx_info is None"))?;
+        .ok_or_else(|| Error::InvalidMessage("rx_info is None".into()))?;
     let tx_info = pl
         .tx_info
         .as_ref()
-        .ok_or_else(|| anyhow!("tx_info is None"))?;
+        .ok_or_else(|| Error::InvalidMessage("tx_info is None".into()))?;
     let modulation = tx_info
         .modulation
         .as_ref()
-        .ok_or_else(|| anyhow!("modulation is None"))?;
+        .ok_or_else(|| Error::InvalidMessage("modulation is None".into()))?;
+
+    if conf.mesh.join_requests.relay_only && !helpers::is_join_request(&pl.phy_payload) {
+        debug!(
+            "Dropping uplink, only join-requests are relayed, uplink_id: {}",
+            rx_info.uplink_id
+        );
+        return Ok(());
+    }
+
+    if !uplink_relay_allowed(&pl.phy_payload, conf.mesh.uplink_dedup_window) {
+        debug!(
+            "Dropping uplink, duplicate within uplink_dedup_window, uplink_id: {}",
+            rx_info.uplink_id
+        );
+        return Ok(());
+    }
+
+    let (timestamp, timestamp_monotonic) = heartbeat::get_uplink_timestamp(&conf);
 
     let mut packet = MeshPacket {
         mhdr: MHDR {
             payload_type: PayloadType::Uplink,
             hop_count: 1,
+            key_index: conf.mesh.signing_key_index,
         },
         payload: Payload::Uplink(UplinkPayload {
             metadata: UplinkMetadata {
                 uplink_id: store_uplink_context(&rx_info.context),
                 dr: helpers::modulation_to_dr(modulation)?,
                 channel: helpers::frequency_to_chan(tx_info.frequency)?,
-                rssi: rx_info.rssi as i16,
+                border_id: get_border_id(&conf, &pl.phy_payload),
+                rssi: helpers::apply_rssi_offset(rx_info.rssi, conf.mesh.rssi_offset),
                 snr: rx_info.snr as i8,
             },
             relay_id: backend::get_relay_id().await?,
+            timestamp,
+            timestamp_monotonic,
             phy_payload: pl.phy_payload.clone(),
         }),
         mic: None,
     };
-    packet.set_mic(conf.mesh.signing_key)?;
+    helpers::set_mic(&conf, &mut packet)?;
+
+    let phy_payload = packet.to_vec().map_err(Error::Packet)?;
+    let data_rate = match get_tx_data_rate(
+        &conf,
+        get_uplink_data_rate(&conf, &pl.phy_payload),
+        phy_payload.len(),
+    ) {
+        Some(v) => v,
+        None => {
+            warn!(
+                "Dropping uplink, mesh packet size does not fit mesh data-rate, uplink_id: {}, size: {}",
+                rx_info.uplink_id,
+                phy_payload.len()
+            );
+            deadletter::record(
+                deadletter::REASON_PAYLOAD_TOO_LARGE,
+                &phy_payload,
+                "Size does not fit mesh data-rate",
+            );
+            return Ok(());
+        }
+    };
 
     let pl = gw::DownlinkFrame {
         downlink_id: random(),
         items: vec![gw::DownlinkFrameItem {
-            phy_payload: packet.to_vec()?,
+            phy_payload,
             tx_info: Some(gw::DownlinkTxInfo {
                 frequency: get_mesh_frequency(&conf)?,
-                power: conf.mesh.tx_power,
-                modulation: Some(helpers::data_rate_to_gw_modulation(
-                    &conf.mesh.data_rate,
-                    false,
-                )),
+                power: get_tx_power(&conf).await,
+                modulation: Some(helpers::data_rate_to_gw_modulation(data_rate, false)),
                 timing: Some(gw::Timing {
                     parameters: Some(gw::timing::Parameters::Immediately(
                         gw::ImmediatelyTimingInfo {},
@@ -360,12 +1040,25 @@ async fn relay_uplink_lora_packet(pl: &gw::UplinkFrame) -> Result<()> {
         ..Default::default()
     };
 
-    info!(
-        "Relaying uplink LoRa frame, uplink_id: {}, downlink_id: {}, mesh_packet: {}",
-        rx_info.uplink_id, pl.downlink_id, packet,
-    );
+    if logging::sample(logging::Category::Uplink) {
+        info!(
+            "Relaying uplink LoRa frame, uplink_id: {}, downlink_id: {}, mesh_packet: {}",
+            rx_info.uplink_id, pl.downlink_id, packet,
+        );
+    }
 
-    backend::mesh(&pl).await
+    // This is scheduled before the transmission, as a failed transmission must be
+    // retransmitted too.
+    if conf.mesh.uplink_ack.enabled {
+        schedule_uplink_retransmissions(&packet, pl.clone());
+    }
+
+    relay_jitter(&conf, pl.downlink_id).await;
+    backend::mesh(&pl).await?;
+    *UPLINK_RELAYED_AT.lock().unwrap() = Some(Instant::now());
+    beacon::record_uplink();
+
+    Ok(())
 }
 
 async fn relay_downlink_lora_packet(pl: &gw::DownlinkFrame) -> Result<gw::DownlinkTxAck> {
@@ -379,19 +1072,57 @@ async fn relay_downlink_lora_packet(pl: &gw::DownlinkFrame) -> Result<gw::Downli
         })
         .collect();
 
+    if conf.mesh.downlink_rate_limit.enabled {
+        let phy_payload = pl
+            .items
+            .first()
+            .map(|v| v.phy_payload.as_slice())
+            .unwrap_or_default();
+
+        if let Some(dev_addr) = get_dev_addr(phy_payload) {
+            if !downlink_relay_allowed(
+                dev_addr,
+                conf.mesh.downlink_rate_limit.max_downlinks,
+                conf.mesh.downlink_rate_limit.interval,
+            ) {
+                warn!(
+                    "Relay downlink failed, downlink rate limit exceeded, downlink_id: {}, dev_addr: {}",
+                    pl.downlink_id,
+                    hex::encode(dev_addr)
+                );
+                deadletter::record(
+                    deadletter::REASON_DOWNLINK_RATE_LIMITED,
+                    phy_payload,
+                    "Downlink rate limit exceeded",
+                );
+
+                for item in tx_ack_items.iter_mut() {
+                    item.status = gw::TxAckStatus::QueueFull.into();
+                }
+
+                return Ok(gw::DownlinkTxAck {
+                    gateway_id: pl.gateway_id.clone(),
+                    downlink_id: pl.downlink_id,
+                    items: tx_ack_items,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
     for (i, downlink_item) in pl.items.iter().enumerate() {
         let tx_info = downlink_item
             .tx_info
             .as_ref()
-            .ok_or_else(|| anyhow!("tx_info is None"))?;
+            .ok_or_else(|| Error::InvalidMessage("tx_info is None".into()))?;
         let modulation = tx_info
             .modulation
             .as_ref()
-            .ok_or_else(|| anyhow!("modulation is None"))?;
+            .ok_or_else(|| Error::InvalidMessage("modulation is None".into()))?;
         let timing = tx_info
             .timing
             .as_ref()
-            .ok_or_else(|| anyhow!("timing is None"))?;
+            .ok_or_else(|| Error::InvalidMessage("timing is None".into()))?;
         let delay = match &timing.parameters {
             Some(gw::timing::Parameters::Delay(v)) => v
                 .delay
@@ -399,33 +1130,55 @@ async fn relay_downlink_lora_packet(pl: &gw::DownlinkFrame) -> Result<gw::Downli
                 .map(|v| v.seconds as u8)
                 .unwrap_or_default(),
             _ => {
-                return Err(anyhow!("Only Delay timing is supported"));
+                return Err(Error::InvalidMessage(
+                    "Only Delay timing is supported".into(),
+                ));
             }
         };
 
         let ctx = tx_info
             .context
             .get(CTX_PREFIX.len()..CTX_PREFIX.len() + 6)
-            .ok_or_else(|| anyhow!("context does not contain enough bytes"))?;
+            .ok_or_else(|| Error::InvalidMessage("context does not contain enough bytes".into()))?;
+
+        let mut relay_id = {
+            let mut b: [u8; 4] = [0; 4];
+            b.copy_from_slice(&ctx[0..4]);
+            b
+        };
+        let mut uplink_id = {
+            let mut b: [u8; 2] = [0; 2];
+            b.copy_from_slice(&ctx[4..6]);
+            u16::from_be_bytes(b)
+        };
+
+        // The context is only overridden when its Relay Gateway is offline, by an other Relay
+        // Gateway that delivered the same uplink frame.
+        if conf.mesh.path_stickiness.enabled && topology::is_offline(relay_id) {
+            if let Some(key) = get_dev_addr(&downlink_item.phy_payload).and_then(|v| {
+                get_device_path(v, (relay_id, uplink_id), conf.mesh.path_stickiness.ttl)
+            }) {
+                debug!(
+                    "Context relay is offline, preferring relay that delivered the same uplink, downlink_id: {}, relay_id: {}, context_relay_id: {}",
+                    pl.downlink_id,
+                    hex::encode(key.0),
+                    hex::encode(relay_id)
+                );
+                (relay_id, uplink_id) = key;
+            }
+        }
 
         let mut packet = packets::MeshPacket {
             mhdr: packets::MHDR {
                 payload_type: packets::PayloadType::Downlink,
                 hop_count: 1,
+                key_index: conf.mesh.signing_key_index,
             },
             payload: packets::Payload::Downlink(packets::DownlinkPayload {
                 phy_payload: downlink_item.phy_payload.clone(),
-                relay_id: {
-                    let mut b: [u8; 4] = [0; 4];
-                    b.copy_from_slice(&ctx[0..4]);
-                    b
-                },
+                relay_id,
                 metadata: DownlinkMetadata {
-                    uplink_id: {
-                        let mut b: [u8; 2] = [0; 2];
-                        b.copy_from_slice(&ctx[4..6]);
-                        u16::from_be_bytes(b)
-                    },
+                    uplink_id,
                     dr: helpers::modulation_to_dr(modulation)?,
                     frequency: tx_info.frequency,
                     tx_power: helpers::tx_power_to_index(tx_info.power)?,
@@ -434,19 +1187,65 @@ async fn relay_downlink_lora_packet(pl: &gw::DownlinkFrame) -> Result<gw::Downli
             }),
             mic: None,
         };
-        packet.set_mic(conf.mesh.signing_key)?;
+        helpers::set_mic(&conf, &mut packet)?;
+
+        let phy_payload = packet.to_vec().map_err(Error::Packet)?;
+        let data_rate = match get_tx_data_rate(&conf, &conf.mesh.data_rate, phy_payload.len()) {
+            Some(v) => v,
+            None => {
+                warn!(
+                    "Relay downlink failed, mesh packet size does not fit mesh data-rate, downlink_id: {}, size: {}",
+                    pl.downlink_id,
+                    phy_payload.len()
+                );
+                deadletter::record(
+                    deadletter::REASON_PAYLOAD_TOO_LARGE,
+                    &phy_payload,
+                    "Size does not fit mesh data-rate",
+                );
+                tx_ack_items[i].status = gw::TxAckStatus::InternalError.into();
+                continue;
+            }
+        };
+
+        if conf.mesh.relay_downlink_guard.enabled {
+            let airtime = helpers::airtime(data_rate, phy_payload.len());
+            let wait = match reserve_relay_downlink(
+                relay_id,
+                airtime + conf.mesh.relay_downlink_guard.margin,
+                Duration::from_secs(delay.into()),
+            ) {
+                Some(v) => v,
+                None => {
+                    warn!(
+                        "Relay downlink failed, relay is busy with previous downlinks, downlink_id: {}, relay_id: {}",
+                        pl.downlink_id,
+                        hex::encode(relay_id)
+                    );
+                    tx_ack_items[i].status = gw::TxAckStatus::TooLate.into();
+                    continue;
+                }
+            };
+
+            if !wait.is_zero() {
+                debug!(
+                    "Delaying relay downlink, relay is busy with previous downlink, downlink_id: {}, relay_id: {}, delay: {:?}",
+                    pl.downlink_id,
+                    hex::encode(relay_id),
+                    wait
+                );
+                sleep(wait).await;
+            }
+        }
 
         let pl = gw::DownlinkFrame {
             downlink_id: pl.downlink_id,
             items: vec![gw::DownlinkFrameItem {
-                phy_payload: packet.to_vec()?,
+                phy_payload,
                 tx_info: Some(gw::DownlinkTxInfo {
                     frequency: get_mesh_frequency(&conf)?,
-                    power: conf.mesh.tx_power,
-                    modulation: Some(helpers::data_rate_to_gw_modulation(
-                        &conf.mesh.data_rate,
-                        false,
-                    )),
+                    power: get_tx_power(&conf).await,
+                    modulation: Some(helpers::data_rate_to_gw_modulation(data_rate, false)),
                     timing: Some(gw::Timing {
                         parameters: Some(gw::timing::Parameters::Immediately(
                             gw::ImmediatelyTimingInfo {},
@@ -459,14 +1258,21 @@ async fn relay_downlink_lora_packet(pl: &gw::DownlinkFrame) -> Result<gw::Downli
             ..Default::default()
         };
 
-        info!(
-            "Sending downlink frame as relayed downlink, downlink_id: {}, mesh_packet: {}",
-            pl.downlink_id, packet
-        );
+        if logging::sample(logging::Category::Downlink) {
+            info!(
+                "Sending downlink frame as relayed downlink, downlink_id: {}, mesh_packet: {}",
+                pl.downlink_id, packet
+            );
+        }
 
         match backend::mesh(&pl).await {
             Ok(_) => {
                 tx_ack_items[i].status = gw::TxAckStatus::Ok.into();
+                if conf.mesh.downlink_ack.enabled {
+                    if let Payload::Downlink(v) = &packet.payload {
+                        schedule_downlink_ack_timeout((relay_id, v.metadata.uplink_id));
+                    }
+                }
                 break;
             }
             Err(e) => {
@@ -484,43 +1290,848 @@ async fn relay_downlink_lora_packet(pl: &gw::DownlinkFrame) -> Result<gw::Downli
     })
 }
 
+// Delays relaying by a random duration up to the relay_jitter, such that Relay Gateways receiving
+// the same packet do not all retransmit at the same time.
+async fn relay_jitter(conf: &Configuration, downlink_id: u32) {
+    if conf.mesh.relay_jitter.is_zero() {
+        return;
+    }
+
+    let delay = conf.mesh.relay_jitter.mul_f64(random::<f64>());
+    debug!(
+        "Delaying relay by jitter, downlink_id: {}, delay: {:?}",
+        downlink_id, delay
+    );
+    sleep(delay).await;
+}
+
+// Reserves the given duration for a downlink to the given relay and returns how long the
+// transmission must wait for the previous downlinks to this relay. It returns None (and
+// does not reserve anything) when the downlink would have to wait longer than max_wait.
+fn reserve_relay_downlink(
+    relay_id: [u8; 4],
+    duration: Duration,
+    max_wait: Duration,
+) -> Option<Duration> {
+    let mut busy_until = RELAY_DOWNLINK_BUSY_UNTIL.lock().unwrap();
+    let now = Instant::now();
+    busy_until.retain(|_, v| *v > now);
+
+    let start = busy_until.get(&relay_id).cloned().unwrap_or(now).max(now);
+    let wait = start - now;
+    if !wait.is_zero() && wait >= max_wait {
+        return None;
+    }
+
+    busy_until.insert(relay_id, start + duration);
+    Some(wait)
+}
+
+// Returns the mesh TX power of this gateway, which can be overridden by its relay group.
+pub async fn get_tx_power(conf: &Configuration) -> i32 {
+    conf.mesh
+        .get_tx_power(backend::get_relay_id().await.unwrap_or_default())
+}
+
+// Returns the mesh frequency to use for the next transmission. Using the airtime frequency
+// selection, this is the frequency with the lowest recent airtime (transmissions and receptions),
+// using the round-robin order for frequencies with equal airtime.
 pub fn get_mesh_frequency(conf: &Configuration) -> Result<u32> {
     if conf.mesh.frequencies.is_empty() {
-        return Err(anyhow!("No mesh frequencies are configured"));
+        return Err(Error::Routing("No mesh frequencies are configured".into()));
     }
 
     let mut mesh_channel = MESH_CHANNEL.lock().unwrap();
-    *mesh_channel += 1;
+    *mesh_channel = select_mesh_channel(
+        conf,
+        *mesh_channel,
+        |frequency| stats::is_mesh_channel_excluded(&conf.mesh.bad_channel_avoidance, frequency),
+        |frequency| {
+            stats::get_mesh_channel_occupancy(conf.mesh.frequency_selection.window, frequency)
+        },
+    );
+    Ok(conf.mesh.frequencies[*mesh_channel])
+}
+
+// Returns the mesh channel following the given (previously used) channel, given the excluded
+// channels and the recent airtime per frequency.
+fn select_mesh_channel<E, O>(
+    conf: &Configuration,
+    mesh_channel: usize,
+    is_excluded: E,
+    get_occupancy: O,
+) -> usize
+where
+    E: Fn(u32) -> bool,
+    O: Fn(u32) -> Duration,
+{
+    let mut selected: Option<(usize, Duration)> = None;
+    let mut fallback = None;
+
+    for i in 1..=conf.mesh.frequencies.len() {
+        let channel = (mesh_channel + i) % conf.mesh.frequencies.len();
+        let frequency = conf.mesh.frequencies[channel];
+
+        fallback.get_or_insert(channel);
+        if is_excluded(frequency) {
+            continue;
+        }
+
+        if conf.mesh.frequency_selection.mode == config::FrequencySelectionMode::RoundRobin {
+            selected = Some((channel, Duration::ZERO));
+            break;
+        }
 
-    if *mesh_channel >= conf.mesh.frequencies.len() {
-        *mesh_channel = 0;
+        let occupancy = get_occupancy(frequency);
+        if selected.map(|(_, v)| occupancy < v).unwrap_or(true) {
+            selected = Some((channel, occupancy));
+        }
     }
 
-    Ok(conf.mesh.frequencies[*mesh_channel])
+    // If all frequencies are excluded, fallback to the normal rotation.
+    selected.map(|(v, _)| v).or(fallback).unwrap_or_default()
+}
+
+// Returns the Border ID to which the given uplink PHYPayload must be routed, based on the
+// DevAddr of the uplink. This returns 0 (any Border Gateway) if no route matches.
+fn get_border_id(conf: &Configuration, phy_payload: &[u8]) -> u8 {
+    // Only data uplinks contain a DevAddr.
+    let m_type = phy_payload.first().map(|v| v >> 5);
+    if !matches!(m_type, Some(0x02 | 0x04)) {
+        return 0;
+    }
+
+    conf.mesh
+        .border_routes
+        .iter()
+        .find(|v| {
+            lrwn_filters::matches(
+                phy_payload,
+                &lrwn_filters::Filters {
+                    dev_addr_prefixes: vec![v.dev_addr_prefix],
+                    ..Default::default()
+                },
+            )
+        })
+        .map(|v| v.border_id)
+        .unwrap_or_default()
+}
+
+// Returns the data-rate for transmitting a mesh packet of the given size. This returns the given
+// data-rate if the packet fits, else the first of the fallback_data_rates in which it fits. In case
+// the packet does not fit any of these data-rates, this returns None and the packet must be
+// dropped.
+fn get_tx_data_rate<'a>(
+    conf: &'a Configuration,
+    data_rate: &'a config::DataRate,
+    size: usize,
+) -> Option<&'a config::DataRate> {
+    if size > conf.mesh.max_payload_size {
+        return None;
+    }
+
+    iter::once(data_rate)
+        .chain(conf.mesh.fallback_data_rates.iter())
+        .find(|dr| {
+            conf.mesh.max_airtime.is_zero() || helpers::airtime(dr, size) <= conf.mesh.max_airtime
+        })
+}
+
+// Returns the mesh data-rate for relaying the given uplink PHYPayload.
+fn get_uplink_data_rate<'a>(conf: &'a Configuration, phy_payload: &[u8]) -> &'a config::DataRate {
+    if helpers::is_join_request(phy_payload) {
+        if let Some(dr) = &conf.mesh.join_requests.data_rate {
+            return dr;
+        }
+    }
+
+    &conf.mesh.data_rate
+}
+
+// Handles the acknowledgement of relayed uplinks. An uplink is implicitly acknowledged when the
+// next hop is overheard relaying the uplink (with a higher hop count), and explicitly by the
+// uplink ACK event of the Border Gateway.
+fn handle_uplink_ack(packet: &MeshPacket) {
+    let mut pending = UPLINK_ACK_PENDING.lock().unwrap();
+
+    match &packet.payload {
+        Payload::Uplink(v) => {
+            let key = (v.relay_id, v.metadata.uplink_id);
+            if pending
+                .get(&key)
+                .map(|hop_count| packet.mhdr.hop_count > *hop_count)
+                .unwrap_or_default()
+            {
+                pending.remove(&key);
+                debug!(
+                    "Uplink implicitly acknowledged, relay_id: {}, uplink_id: {}",
+                    hex::encode(key.0),
+                    key.1
+                );
+            }
+        }
+        Payload::Event(v) => {
+            for event in &v.events {
+                if let packets::Event::UplinkAck(v) = event {
+                    if pending.remove(&(v.relay_id, v.uplink_id)).is_some() {
+                        stats::record_relay_uplink_ack(true);
+                        debug!(
+                            "Uplink acknowledged, relay_id: {}, uplink_id: {}",
+                            hex::encode(v.relay_id),
+                            v.uplink_id
+                        );
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// Schedules the retransmissions of the relayed uplink, until it has been acknowledged.
+fn schedule_uplink_retransmissions(packet: &MeshPacket, pl: gw::DownlinkFrame) {
+    let key = match &packet.payload {
+        Payload::Uplink(v) => (v.relay_id, v.metadata.uplink_id),
+        _ => return,
+    };
+    UPLINK_ACK_PENDING
+        .lock()
+        .unwrap()
+        .insert(key, packet.mhdr.hop_count);
+
+    tokio::spawn(async move {
+        if let Err(e) = retransmit_uplink(key, pl).await {
+            UPLINK_ACK_PENDING.lock().unwrap().remove(&key);
+            error!("Retransmit uplink error, error: {}", e);
+        }
+    });
+}
+
+async fn retransmit_uplink(key: UplinkKey, pl: gw::DownlinkFrame) -> Result<()> {
+    let conf = config::get();
+    let mut timeout = conf.mesh.uplink_ack.timeout;
+
+    for attempt in 1..=conf.mesh.uplink_ack.max_retransmissions {
+        sleep(timeout).await;
+        if !UPLINK_ACK_PENDING.lock().unwrap().contains_key(&key) {
+            return Ok(());
+        }
+
+        let mut pl = pl.clone();
+        pl.downlink_id = random();
+        if let Some(tx_info) = pl.items.first_mut().and_then(|v| v.tx_info.as_mut()) {
+            tx_info.frequency = get_mesh_frequency(&conf)?;
+        }
+
+        info!(
+            "Retransmitting unacknowledged uplink, relay_id: {}, uplink_id: {}, attempt: {}, downlink_id: {}",
+            hex::encode(key.0),
+            key.1,
+            attempt,
+            pl.downlink_id
+        );
+        if let Err(e) = backend::mesh(&pl).await {
+            warn!("Retransmitting uplink failed, error: {}", e);
+        }
+
+        timeout *= 2;
+    }
+
+    sleep(timeout).await;
+    if UPLINK_ACK_PENDING.lock().unwrap().remove(&key).is_some() {
+        stats::record_relay_uplink_ack(false);
+        warn!(
+            "Uplink has not been acknowledged, relay_id: {}, uplink_id: {}, max_retransmissions: {}",
+            hex::encode(key.0),
+            key.1,
+            conf.mesh.uplink_ack.max_retransmissions
+        );
+        deadletter::record(
+            deadletter::REASON_UPLINK_NOT_ACKNOWLEDGED,
+            pl.items
+                .first()
+                .map(|v| v.phy_payload.as_slice())
+                .unwrap_or_default(),
+            "Uplink has not been acknowledged",
+        );
+    }
+
+    Ok(())
+}
+
+// Counts the relayed downlink as failed if it has not been acknowledged within the timeout.
+fn schedule_downlink_ack_timeout(key: UplinkKey) {
+    DOWNLINK_ACK_PENDING.lock().unwrap().insert(key);
+
+    tokio::spawn(async move {
+        let conf = config::get();
+        sleep(conf.mesh.downlink_ack.timeout).await;
+        if DOWNLINK_ACK_PENDING.lock().unwrap().remove(&key) {
+            // The Relay Gateway is expected to be silent during its maintenance window.
+            if conf.mesh.in_maintenance(key.0, helpers::system_time_now()) {
+                debug!(
+                    "Downlink has not been acknowledged, ignoring as relay is in maintenance, relay_id: {}, uplink_id: {}",
+                    hex::encode(key.0),
+                    key.1
+                );
+                return;
+            }
+
+            warn!(
+                "Downlink has not been acknowledged, relay_id: {}, uplink_id: {}",
+                hex::encode(key.0),
+                key.1
+            );
+            if let Err(e) = record_downlink_ack(key.0, false).await {
+                error!("Record downlink ACK error, error: {}", e);
+            }
+        }
+    });
+}
+
+// Records the delivery result of a relayed downlink and publishes an alert when the success rate
+// of the Relay Gateway crosses the alert threshold.
+async fn record_downlink_ack(relay_id: [u8; 4], ok: bool) -> Result<()> {
+    let conf = config::get();
+    let (success_rate, alert) = match update_downlink_ack_results(
+        relay_id,
+        ok,
+        conf.mesh.downlink_ack.window,
+        conf.mesh.downlink_ack.alert_threshold,
+    ) {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    stats::record_relay_downlink_success_rate(relay_id, success_rate);
+
+    let alert = match alert {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    if alert {
+        warn!(
+            "Relay downlink success rate dropped below threshold, relay_id: {}, success_rate: {:.2}, alert_threshold: {:.2}",
+            hex::encode(relay_id),
+            success_rate,
+            conf.mesh.downlink_ack.alert_threshold
+        );
+    } else {
+        info!(
+            "Relay downlink success rate recovered, relay_id: {}, success_rate: {:.2}, alert_threshold: {:.2}",
+            hex::encode(relay_id),
+            success_rate,
+            conf.mesh.downlink_ack.alert_threshold
+        );
+    }
+
+    proxy::send_mesh_downlink_alert(&proto::MeshDownlinkAlertEvent {
+        gateway_id: hex::encode(backend::get_gateway_id().await?),
+        relay_id: relay_id.to_vec(),
+        success_rate: success_rate as f32,
+        alert_threshold: conf.mesh.downlink_ack.alert_threshold as f32,
+        alert,
+    })
+    .await
+}
+
+// Adds the delivery result to the window of the given Relay Gateway. Once the window is filled,
+// this returns the success rate and the new alert state if it changed.
+fn update_downlink_ack_results(
+    relay_id: [u8; 4],
+    ok: bool,
+    window: usize,
+    alert_threshold: f64,
+) -> Option<(f64, Option<bool>)> {
+    let mut results = DOWNLINK_ACK_RESULTS.lock().unwrap();
+    let results = results.entry(relay_id).or_default();
+
+    results.results.push_back(ok);
+    while results.results.len() > window {
+        results.results.pop_front();
+    }
+    if results.results.len() < window {
+        return None;
+    }
+
+    let success_rate =
+        results.results.iter().filter(|v| **v).count() as f64 / results.results.len() as f64;
+    let alert = success_rate < alert_threshold;
+    if alert == results.alert {
+        return Some((success_rate, None));
+    }
+
+    results.alert = alert;
+    Some((success_rate, Some(alert)))
+}
+
+// Returns the cached mesh payloads used for de-duplication, from oldest to newest.
+pub fn get_payload_cache() -> Vec<PayloadCache> {
+    PAYLOAD_CACHE.lock().unwrap().iter().cloned().collect()
+}
+
+// Returns true if this relay has relayed an uplink within the given duration.
+pub fn uplink_relayed_within(d: Duration) -> bool {
+    UPLINK_RELAYED_AT
+        .lock()
+        .unwrap()
+        .map(|v| v.elapsed() < d)
+        .unwrap_or_default()
+}
+
+fn record_downlink_path(relay_id: [u8; 4]) {
+    DOWNLINK_PATHS
+        .lock()
+        .unwrap()
+        .insert(relay_id, Instant::now());
 }
 
-fn get_uplink_id() -> u16 {
-    let mut uplink_id = UPLINK_ID.lock().unwrap();
-    *uplink_id += 1;
+// Returns true if this relay has relayed a packet of the given relay within the given timeout,
+// in which case this relay is on the path for downlinks to the given relay.
+fn on_downlink_path(relay_id: [u8; 4], timeout: Duration) -> bool {
+    let mut paths = DOWNLINK_PATHS.lock().unwrap();
+    paths.retain(|_, v| v.elapsed() < timeout);
+    paths.contains_key(&relay_id)
+}
+
+// Returns true if the heartbeat of the given relay can be relayed. This is the case when no
+// heartbeat of this relay has been relayed within the given interval.
+fn heartbeat_relay_allowed(relay_id: [u8; 4], interval: Duration) -> bool {
+    if interval.is_zero() {
+        return true;
+    }
+
+    let mut relayed_at = HEARTBEAT_RELAYED_AT.lock().unwrap();
+    if let Some(ts) = relayed_at.get(&relay_id) {
+        if ts.elapsed() < interval {
+            return false;
+        }
+    }
+
+    relayed_at.insert(relay_id, Instant::now());
+    true
+}
+
+// Returns true if the given end-device uplink can be relayed. This is the case when the same
+// PHYPayload has not been relayed within the given window.
+fn uplink_relay_allowed(phy_payload: &[u8], window: Duration) -> bool {
+    if window.is_zero() {
+        return true;
+    }
+
+    let mut relayed_at = UPLINK_PAYLOAD_RELAYED_AT.lock().unwrap();
+    relayed_at.retain(|_, ts| ts.elapsed() < window);
+
+    if relayed_at.contains_key(phy_payload) {
+        return false;
+    }
+
+    relayed_at.insert(phy_payload.to_vec(), Instant::now());
+    true
+}
+
+// Returns the gateway stats of the Relay Gateway, for the given stats event.
+fn get_relay_stats(relay_id: [u8; 4], event: &packets::StatsEvent) -> gw::GatewayStats {
+    let mut metadata: HashMap<String, String> = [
+        ("relay_id".to_string(), hex::encode(relay_id)),
+        (
+            "mesh_uplinks_acked".to_string(),
+            event.uplinks_acked.to_string(),
+        ),
+        (
+            "mesh_uplinks_not_acked".to_string(),
+            event.uplinks_not_acked.to_string(),
+        ),
+        (
+            "mesh_tx_airtime_pct".to_string(),
+            format!(
+                "{:.2}",
+                stats::get_airtime_pct(Duration::from_millis(event.tx_airtime.into()))
+            ),
+        ),
+    ]
+    .into();
+
+    // Last reported power telemetry, which is reported at its own interval.
+    if let Some(telemetry) = topology::get_telemetry(relay_id) {
+        for (k, v) in [
+            ("mesh_battery_voltage", telemetry.battery_voltage),
+            ("mesh_solar_current", telemetry.solar_current),
+            ("mesh_temperature", telemetry.temperature),
+        ] {
+            if let Some(v) = v {
+                metadata.insert(k.to_string(), format!("{:.3}", v));
+            }
+        }
+    }
+
+    gw::GatewayStats {
+        gateway_id: hex::encode(event.gateway_id),
+        time: Some(helpers::system_time_to_timestamp(helpers::system_time_now())),
+        rx_packets_received: event.rx_packets_received,
+        rx_packets_received_ok: event.rx_packets_received_ok,
+        tx_packets_received: event.tx_packets_received,
+        tx_packets_emitted: event.tx_packets_emitted,
+        rx_packets_per_frequency: helpers::frequency_counts_to_map(&event.rx_packets_per_frequency),
+        tx_packets_per_frequency: helpers::frequency_counts_to_map(&event.tx_packets_per_frequency),
+        metadata,
+        // The Relay Gateway sends its location before its stats, within the same packet.
+        location: topology::get_location(relay_id).map(|v| common::Location {
+            latitude: v.latitude,
+            longitude: v.longitude,
+            altitude: v.altitude,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn record_device_path(dev_addr: [u8; 4], key: UplinkKey, phy_payload: &[u8]) {
+    let mut device_paths = DEVICE_PATHS.lock().unwrap();
+    let paths = device_paths.entry(dev_addr).or_default();
+    paths.push_back((key, phy_payload.to_vec(), Instant::now()));
+    if paths.len() > DEVICE_PATHS_MAX {
+        paths.pop_front();
+    }
+}
+
+// Returns the Relay ID + Uplink ID of the most recent uplink of the given DevAddr, relayed within
+// the given TTL by an other Relay Gateway than the one of the given context, with the same
+// PHYPayload as the uplink of the context. It returns None if the uplink of the context is
+// unknown (or expired).
+fn get_device_path(dev_addr: [u8; 4], context: UplinkKey, ttl: Duration) -> Option<UplinkKey> {
+    let mut device_paths = DEVICE_PATHS.lock().unwrap();
+    for paths in device_paths.values_mut() {
+        paths.retain(|(_, _, relayed_at)| relayed_at.elapsed() < ttl);
+    }
+    device_paths.retain(|_, paths| !paths.is_empty());
 
-    if *uplink_id > 4095 {
-        *uplink_id = 0;
+    let paths = device_paths.get(&dev_addr)?;
+    let (_, phy_payload, _) = paths.iter().find(|(key, _, _)| *key == context)?;
+    paths
+        .iter()
+        .rev()
+        .find(|(key, v, _)| key.0 != context.0 && v == phy_payload)
+        .map(|(key, _, _)| *key)
+}
+
+// Returns the DevAddr of the given PHYPayload. This returns None if the PHYPayload is not a data
+// uplink or downlink, e.g. a join-request or join-accept.
+fn get_dev_addr(phy_payload: &[u8]) -> Option<[u8; 4]> {
+    let m_type = phy_payload.first().map(|v| v >> 5);
+    if !matches!(m_type, Some(0x02..=0x05)) {
+        return None;
+    }
+
+    // The DevAddr is encoded as little-endian.
+    let mut dev_addr: [u8; 4] = phy_payload.get(1..5)?.try_into().ok()?;
+    dev_addr.reverse();
+    Some(dev_addr)
+}
+
+// Returns true if a downlink for the given DevAddr can be relayed. This is the case when less
+// than max_downlinks have been relayed for this DevAddr within the given interval.
+fn downlink_relay_allowed(dev_addr: [u8; 4], max_downlinks: u32, interval: Duration) -> bool {
+    rate_limit_allowed(&DOWNLINK_RELAYED_AT, dev_addr, max_downlinks, interval)
+}
+
+// Returns the dead-letter message in case the packet must be dropped because of the relay group
+// policy of the Relay Gateway that originated the packet.
+fn check_relay_group_policy(conf: &Configuration, packet: &MeshPacket) -> Option<&'static str> {
+    let (relay_id, payload_type) = match &packet.payload {
+        Payload::Uplink(v) => (v.relay_id, "uplink"),
+        Payload::Heartbeat(v) => (v.relay_id, "heartbeat"),
+        Payload::Event(v) => (v.relay_id, "event"),
+        _ => return None,
+    };
+
+    let group = conf.mesh.get_relay_group(relay_id)?;
+    if !group.payload_type_allowed(payload_type) {
+        return Some("Payload type not allowed by relay group");
+    }
+
+    if group.rate_limit_packets != 0
+        && !rate_limit_allowed(
+            &RELAY_GROUP_RECEIVED_AT,
+            relay_id,
+            group.rate_limit_packets,
+            group.rate_limit_interval,
+        )
+    {
+        return Some("Relay group rate limit exceeded");
     }
 
-    *uplink_id
+    None
+}
+
+// Returns true and records the event if less than max events were recorded for the given key
+// within the interval.
+fn rate_limit_allowed(
+    events: &Mutex<HashMap<[u8; 4], VecDeque<Instant>>>,
+    key: [u8; 4],
+    max: u32,
+    interval: Duration,
+) -> bool {
+    let mut events = events.lock().unwrap();
+    for v in events.values_mut() {
+        while v
+            .front()
+            .map(|ts| ts.elapsed() >= interval)
+            .unwrap_or_default()
+        {
+            v.pop_front();
+        }
+    }
+    events.retain(|_, v| !v.is_empty());
+
+    let timestamps = events.entry(key).or_default();
+    if timestamps.len() >= max as usize {
+        return false;
+    }
+
+    timestamps.push_back(Instant::now());
+    true
 }
 
 pub fn store_uplink_context(ctx: &[u8]) -> u16 {
-    let uplink_id = get_uplink_id();
-    let mut uplink_ctx = UPLINK_CONTEXT.lock().unwrap();
-    uplink_ctx.insert(uplink_id, ctx.to_vec());
-    uplink_id
+    uplinkcontext::store(ctx)
 }
 
 fn get_uplink_context(uplink_id: u16) -> Result<Vec<u8>> {
-    let uplink_ctx = UPLINK_CONTEXT.lock().unwrap();
-    uplink_ctx
-        .get(&uplink_id)
-        .cloned()
-        .ok_or_else(|| anyhow!("No uplink context for uplink_id: {}", uplink_id))
+    uplinkcontext::get(uplink_id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select_mesh_channel() {
+        let mut conf = Configuration::default();
+        conf.mesh.frequencies = vec![869100000, 869300000, 869500000];
+
+        let no_airtime = |_| Duration::ZERO;
+        let airtime = |frequency| match frequency {
+            869100000 => Duration::from_secs(2),
+            869300000 => Duration::from_secs(3),
+            _ => Duration::from_secs(1),
+        };
+
+        // Without airtime, the frequencies are rotated.
+        assert_eq!(1, select_mesh_channel(&conf, 0, |_| false, no_airtime));
+        assert_eq!(2, select_mesh_channel(&conf, 1, |_| false, no_airtime));
+        assert_eq!(0, select_mesh_channel(&conf, 2, |_| false, no_airtime));
+
+        // The frequency with the lowest airtime is selected.
+        assert_eq!(2, select_mesh_channel(&conf, 0, |_| false, airtime));
+        assert_eq!(2, select_mesh_channel(&conf, 2, |_| false, airtime));
+
+        // Excluded frequencies are skipped.
+        assert_eq!(
+            0,
+            select_mesh_channel(&conf, 0, |frequency| frequency == 869500000, airtime)
+        );
+
+        // If all frequencies are excluded, the frequencies are rotated.
+        assert_eq!(1, select_mesh_channel(&conf, 0, |_| true, airtime));
+
+        // Round-robin ignores the airtime.
+        conf.mesh.frequency_selection.mode = config::FrequencySelectionMode::RoundRobin;
+        assert_eq!(1, select_mesh_channel(&conf, 0, |_| false, airtime));
+        assert_eq!(2, select_mesh_channel(&conf, 1, |_| false, airtime));
+    }
+
+    #[test]
+    fn test_get_dev_addr() {
+        // Unconfirmed data up.
+        assert_eq!(
+            Some([4, 3, 2, 1]),
+            get_dev_addr(&[0x40, 1, 2, 3, 4, 0, 0, 0])
+        );
+        // Unconfirmed data down.
+        assert_eq!(
+            Some([4, 3, 2, 1]),
+            get_dev_addr(&[0x60, 1, 2, 3, 4, 0, 0, 0])
+        );
+        // Confirmed data down.
+        assert_eq!(
+            Some([4, 3, 2, 1]),
+            get_dev_addr(&[0xa0, 1, 2, 3, 4, 0, 0, 0])
+        );
+        // Join-request.
+        assert_eq!(None, get_dev_addr(&[0x00, 1, 2, 3, 4, 0, 0, 0]));
+        // Join-accept.
+        assert_eq!(None, get_dev_addr(&[0x20, 1, 2, 3, 4, 0, 0, 0]));
+        // Too short.
+        assert_eq!(None, get_dev_addr(&[0x60, 1, 2]));
+    }
+
+    #[test]
+    fn test_device_path() {
+        let ttl = Duration::from_millis(100);
+
+        let ctx = ([1, 1, 1, 1], 10);
+
+        assert_eq!(None, get_device_path([5, 5, 5, 5], ctx, ttl));
+
+        // The same uplink, relayed by two Relay Gateways.
+        record_device_path([5, 5, 5, 5], ctx, &[0x40, 5, 5, 5, 5, 0, 1, 0]);
+        assert_eq!(None, get_device_path([5, 5, 5, 5], ctx, ttl));
+        record_device_path(
+            [5, 5, 5, 5],
+            ([2, 2, 2, 2], 20),
+            &[0x40, 5, 5, 5, 5, 0, 1, 0],
+        );
+        assert_eq!(
+            Some(([2, 2, 2, 2], 20)),
+            get_device_path([5, 5, 5, 5], ctx, ttl)
+        );
+
+        // A more recent uplink (other FCnt) does not match the uplink of the context.
+        record_device_path(
+            [5, 5, 5, 5],
+            ([3, 3, 3, 3], 30),
+            &[0x40, 5, 5, 5, 5, 0, 2, 0],
+        );
+        assert_eq!(
+            Some(([2, 2, 2, 2], 20)),
+            get_device_path([5, 5, 5, 5], ctx, ttl)
+        );
+
+        // Unknown context.
+        assert_eq!(None, get_device_path([5, 5, 5, 5], ([4, 4, 4, 4], 40), ttl));
+
+        // The path expires after the TTL.
+        std::thread::sleep(ttl);
+        assert_eq!(None, get_device_path([5, 5, 5, 5], ctx, ttl));
+    }
+
+    #[test]
+    fn test_downlink_relay_allowed() {
+        let interval = Duration::from_millis(100);
+
+        assert!(downlink_relay_allowed([1, 1, 1, 1], 2, interval));
+        assert!(downlink_relay_allowed([1, 1, 1, 1], 2, interval));
+        assert!(!downlink_relay_allowed([1, 1, 1, 1], 2, interval));
+
+        // The limit is per DevAddr.
+        assert!(downlink_relay_allowed([2, 2, 2, 2], 2, interval));
+
+        // Downlinks are allowed again after the interval.
+        std::thread::sleep(interval);
+        assert!(downlink_relay_allowed([1, 1, 1, 1], 2, interval));
+    }
+
+    #[test]
+    fn test_update_downlink_ack_results() {
+        let relay_id = [3, 3, 3, 3];
+
+        // No success rate until the window is filled.
+        for _ in 0..3 {
+            assert_eq!(None, update_downlink_ack_results(relay_id, true, 4, 0.5));
+        }
+        assert_eq!(
+            Some((1.0, None)),
+            update_downlink_ack_results(relay_id, true, 4, 0.5)
+        );
+
+        // The oldest results are dropped from the window.
+        assert_eq!(
+            Some((0.75, None)),
+            update_downlink_ack_results(relay_id, false, 4, 0.5)
+        );
+        assert_eq!(
+            Some((0.5, None)),
+            update_downlink_ack_results(relay_id, false, 4, 0.5)
+        );
+        assert_eq!(
+            Some((0.25, Some(true))),
+            update_downlink_ack_results(relay_id, false, 4, 0.5)
+        );
+        assert_eq!(
+            Some((0.25, None)),
+            update_downlink_ack_results(relay_id, true, 4, 0.5)
+        );
+        assert_eq!(
+            Some((0.5, Some(false))),
+            update_downlink_ack_results(relay_id, true, 4, 0.5)
+        );
+
+        // The results are per relay.
+        assert_eq!(
+            None,
+            update_downlink_ack_results([4, 4, 4, 4], false, 4, 0.5)
+        );
+    }
+
+    #[test]
+    fn test_check_relay_group_policy() {
+        let mut conf = Configuration::default();
+        conf.mesh.relay_groups = vec![config::RelayGroup {
+            name: "solar".into(),
+            relay_id_prefixes: vec!["05050000/16".parse().unwrap()],
+            payload_types: vec!["uplink".into(), "heartbeat".into()],
+            rate_limit_packets: 1,
+            rate_limit_interval: Duration::from_secs(60),
+            ..Default::default()
+        }];
+
+        let heartbeat = |relay_id: [u8; 4]| MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: PayloadType::Heartbeat,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: Payload::Heartbeat(packets::HeartbeatPayload {
+                timestamp: 0,
+                timestamp_monotonic: false,
+                relay_id,
+                relay_path: vec![],
+                config_checksum: None,
+            }),
+            mic: None,
+        };
+        let event = MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: PayloadType::Event,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: Payload::Event(packets::EventPayload {
+                timestamp: 0,
+                timestamp_monotonic: false,
+                relay_id: [5, 5, 1, 1],
+                events: vec![],
+            }),
+            mic: None,
+        };
+
+        assert_eq!(
+            Some("Payload type not allowed by relay group"),
+            check_relay_group_policy(&conf, &event)
+        );
+        assert_eq!(
+            None,
+            check_relay_group_policy(&conf, &heartbeat([5, 5, 1, 1]))
+        );
+        assert_eq!(
+            Some("Relay group rate limit exceeded"),
+            check_relay_group_policy(&conf, &heartbeat([5, 5, 1, 1]))
+        );
+
+        // The rate limit is per Relay ID.
+        assert_eq!(
+            None,
+            check_relay_group_policy(&conf, &heartbeat([5, 5, 1, 2]))
+        );
+
+        // Relays not in a group are not affected.
+        for _ in 0..2 {
+            assert_eq!(
+                None,
+                check_relay_group_policy(&conf, &heartbeat([6, 6, 1, 1]))
+            );
+        }
+    }
 }
@@ -0,0 +1,37 @@
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::{Metric, Registry};
+
+use crate::error::Result;
+
+static REGISTRY: Lazy<RwLock<Registry>> = Lazy::new(|| RwLock::new(<Registry>::default()));
+
+pub fn register(name: &str, help: &str, metric: impl Metric) {
+    let mut registry_w = REGISTRY.write().unwrap();
+    registry_w.register(name, help, metric)
+}
+
+pub fn encode_to_string() -> Result<String> {
+    let registry_r = REGISTRY.read().unwrap();
+    let mut buffer = String::new();
+    encode(&mut buffer, &registry_r)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prometheus_client::metrics::counter::Counter;
+
+    #[test]
+    fn test_encode_to_string() {
+        let counter: Counter = Counter::default();
+        register("test_counter", "Test counter", counter.clone());
+        counter.inc();
+
+        let out = encode_to_string().unwrap();
+        assert!(out.contains("test_counter_total 1"));
+    }
+}
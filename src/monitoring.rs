@@ -0,0 +1,147 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use log::{error, info};
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
+use crate::{dutycycle, heartbeat, linkquality, mesh, metrics, topology};
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    if conf.monitoring.bind.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up monitoring endpoint, bind: {}",
+        conf.monitoring.bind
+    );
+
+    let addr: SocketAddr = conf
+        .monitoring
+        .bind
+        .parse()
+        .map_err(|e| Error::Config(format!("Invalid monitoring.bind: {}", e)))?;
+    let listener = TcpListener::bind(addr).await?;
+    let mut app = Router::new()
+        .route("/metrics", get(prometheus_handler))
+        .route("/health", get(health_handler))
+        .route("/relays", get(relays_handler))
+        .route("/topology.dot", get(topology_dot_handler))
+        .route("/topology.geojson", get(topology_geojson_handler))
+        .route("/cache", get(cache_handler))
+        .route("/duty_cycle", get(duty_cycle_handler))
+        .route("/link_quality", get(link_quality_handler))
+        .route("/link_quality/:relay_id", get(relay_link_quality_handler));
+
+    if conf.monitoring.commands {
+        app = app.route("/heartbeat", post(heartbeat_handler));
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Monitoring endpoint error, error: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+async fn prometheus_handler() -> impl IntoResponse {
+    match metrics::encode_to_string() {
+        Ok(v) => (StatusCode::OK, v),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn health_handler() -> impl IntoResponse {
+    StatusCode::OK
+}
+
+async fn relays_handler() -> impl IntoResponse {
+    json_response(&topology::get_relays_with_maintenance(&config::get()))
+}
+
+async fn cache_handler() -> impl IntoResponse {
+    json_response(&mesh::get_payload_cache())
+}
+
+async fn duty_cycle_handler() -> impl IntoResponse {
+    let conf = config::get();
+    json_response(&dutycycle::get_tx_airtime_usage(&conf.mesh.duty_cycle))
+}
+
+async fn link_quality_handler() -> impl IntoResponse {
+    json_response(&linkquality::get(None))
+}
+
+async fn relay_link_quality_handler(Path(relay_id): Path<String>) -> Response {
+    let mut b: [u8; 4] = [0; 4];
+    if let Err(e) = hex::decode_to_slice(&relay_id, &mut b) {
+        return (StatusCode::BAD_REQUEST, format!("Invalid relay_id: {}", e)).into_response();
+    }
+
+    json_response(&linkquality::get(Some(b))).into_response()
+}
+
+// Sends a heartbeat over the mesh (Relay Gateway only), e.g. to verify that the Border Gateway
+// can be reached.
+async fn heartbeat_handler() -> impl IntoResponse {
+    if config::get().mesh.border_gateway {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Heartbeats are only sent by Relay Gateways".to_string(),
+        );
+    }
+
+    info!("Heartbeat requested through monitoring endpoint");
+    match heartbeat::report_heartbeat().await {
+        Ok(_) => (StatusCode::OK, "".to_string()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+fn json_response<T: Serialize>(v: &T) -> impl IntoResponse {
+    match serde_json::to_string(v) {
+        Ok(v) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/json")],
+            v,
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, "text/plain")],
+            e.to_string(),
+        ),
+    }
+}
+
+async fn topology_dot_handler() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/vnd.graphviz")],
+        topology::to_dot(&topology::get_relays()),
+    )
+}
+
+async fn topology_geojson_handler() -> impl IntoResponse {
+    let conf = config::get();
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        topology::to_geojson(
+            &topology::get_relays_with_maintenance(&conf),
+            &conf.topology.relay_locations,
+        )
+        .to_string(),
+    )
+}
@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
+
+use log::{error, info, trace, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::gauge::Gauge;
+use rumqttc::{AsyncClient, Event, EventLoop, Incoming, MqttOptions, Publish, QoS, Transport};
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep, MissedTickBehavior};
+
+use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
+use crate::{backend, metrics, proxy, topology};
+
+// Max number of messages waiting to be published. Messages are dropped when the queue is full,
+// e.g. when the MQTT broker is unreachable.
+const QUEUE_SIZE: usize = 100;
+
+// Delay before reconnecting to the MQTT broker.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+static MESSAGE_CHAN: OnceCell<mpsc::Sender<Message>> = OnceCell::new();
+
+static CONNECTED: Lazy<Gauge> = Lazy::new(|| {
+    let gauge = Gauge::default();
+    metrics::register(
+        "mqtt_connected",
+        "Connection state to the MQTT broker (1 = connected)",
+        gauge.clone(),
+    );
+    gauge
+});
+static PUBLISH_COUNT: Lazy<Counter> = Lazy::new(|| {
+    let counter = Counter::default();
+    metrics::register(
+        "mqtt_publish_count",
+        "Number of messages published to the MQTT broker",
+        counter.clone(),
+    );
+    counter
+});
+static DROP_COUNT: Lazy<Counter> = Lazy::new(|| {
+    let counter = Counter::default();
+    metrics::register(
+        "mqtt_drop_count",
+        "Number of messages that were dropped as the queue was full",
+        counter.clone(),
+    );
+    counter
+});
+
+#[derive(Debug, PartialEq)]
+struct Message {
+    // Topic, relative to the topic_prefix / Gateway ID. For forwarded events, this is the event
+    // type, relative to the forwarder topic_prefix / Gateway ID.
+    topic: String,
+    payload: Vec<u8>,
+    retain: bool,
+    event: bool,
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    // Only the Border Gateway receives the heartbeats of all Relay Gateways.
+    if !conf.mesh.border_gateway || conf.integrations.mqtt.server.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up MQTT integration, server: {}, topic_prefix: {}, forwarder: {}",
+        conf.integrations.mqtt.server,
+        conf.integrations.mqtt.topic_prefix,
+        conf.integrations.mqtt.forwarder.enabled
+    );
+
+    let (message_tx, message_rx) = mpsc::channel::<Message>(QUEUE_SIZE);
+    MESSAGE_CHAN
+        .set(message_tx)
+        .map_err(|e| Error::Backend(format!("OnceCell error: {:?}", e)))?;
+
+    tokio::spawn(async move {
+        publish_loop(message_rx).await;
+    });
+
+    if !conf.integrations.mqtt.topology_interval.is_zero() {
+        tokio::spawn({
+            let topology_interval = conf.integrations.mqtt.topology_interval;
+
+            async move {
+                topology_loop(topology_interval).await;
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// Publishes the given Relay Gateway heartbeat (as recorded in the topology).
+pub fn publish_heartbeat(relay: &topology::Relay) {
+    if MESSAGE_CHAN.get().is_none() {
+        return;
+    }
+
+    match serde_json::to_vec(relay) {
+        Ok(payload) => publish(Message {
+            topic: format!("relay/{}/heartbeat", hex::encode(relay.relay_id)),
+            payload,
+            retain: false,
+            event: false,
+        }),
+        Err(e) => error!("Encode heartbeat error, error: {}", e),
+    }
+}
+
+// Publishes the mesh counters of the given gateway stats metadata. Numeric values are published
+// as JSON numbers.
+pub fn publish_counters(metadata: &HashMap<String, String>) {
+    if MESSAGE_CHAN.get().is_none() {
+        return;
+    }
+
+    match encode_counters(metadata) {
+        Ok(payload) => publish(Message {
+            topic: "counters".into(),
+            payload,
+            retain: false,
+            event: false,
+        }),
+        Err(e) => error!("Encode counters error, error: {}", e),
+    }
+}
+
+// Publishes the given (encoded) proxy API event, using the ChirpStack gateway topic layout.
+pub fn forward_event(event: &str, payload: Vec<u8>) {
+    publish(Message {
+        topic: event.to_string(),
+        payload,
+        retain: false,
+        event: true,
+    });
+}
+
+fn publish(msg: Message) {
+    let message_tx = match MESSAGE_CHAN.get() {
+        Some(v) => v,
+        None => return,
+    };
+
+    trace!("Queueing MQTT message, topic: {}", msg.topic);
+
+    if let Err(e) = message_tx.try_send(msg) {
+        warn!("Dropping MQTT message, error: {}", e);
+        DROP_COUNT.inc();
+    }
+}
+
+fn encode_counters(metadata: &HashMap<String, String>) -> Result<Vec<u8>> {
+    let counters: serde_json::Map<String, serde_json::Value> = metadata
+        .iter()
+        .filter(|(k, _)| k.starts_with("mesh_"))
+        .map(|(k, v)| {
+            let v = match v.parse::<f64>() {
+                Ok(f) => serde_json::Value::from(f),
+                Err(_) => serde_json::Value::from(v.as_str()),
+            };
+            (k.clone(), v)
+        })
+        .collect();
+
+    Ok(serde_json::to_vec(&counters)?)
+}
+
+async fn topology_loop(topology_interval: Duration) {
+    let mut ticker = interval(topology_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        match serde_json::to_vec(&topology::get_relays_with_maintenance(&config::get())) {
+            Ok(payload) => publish(Message {
+                topic: "topology".into(),
+                payload,
+                retain: true,
+                event: false,
+            }),
+            Err(e) => error!("Encode topology error, error: {}", e),
+        }
+    }
+}
+
+async fn publish_loop(mut message_rx: mpsc::Receiver<Message>) {
+    trace!("Starting MQTT publish loop");
+
+    let conf = config::get();
+    let mqtt_conf = &conf.integrations.mqtt;
+    let (gateway_id, options) = loop {
+        match get_mqtt_options(mqtt_conf).await {
+            Ok(v) => break v,
+            Err(e) => {
+                error!("Setup MQTT client error, error: {}", e);
+                sleep(RECONNECT_DELAY).await;
+            }
+        }
+    };
+
+    info!(
+        "Connecting to MQTT broker, server: {}, client_id: {}",
+        mqtt_conf.server,
+        options.client_id()
+    );
+
+    let (client, eventloop) = AsyncClient::new(options, QUEUE_SIZE);
+    tokio::spawn(event_loop(eventloop, client.clone(), gateway_id.clone()));
+
+    while let Some(msg) = message_rx.recv().await {
+        let topic = get_topic(mqtt_conf, &gateway_id, &msg);
+        trace!("Publishing MQTT message, topic: {}", topic);
+
+        // Forwarded events are published using QoS 1, as the MQTT Forwarder does.
+        let qos = if msg.event {
+            QoS::AtLeastOnce
+        } else {
+            QoS::AtMostOnce
+        };
+
+        // The request queue of the client is only full when the broker is not reachable.
+        if let Err(e) = client.try_publish(topic, qos, msg.retain, msg.payload) {
+            warn!("Dropping MQTT message, error: {}", e);
+            DROP_COUNT.inc();
+            continue;
+        }
+        PUBLISH_COUNT.inc();
+    }
+}
+
+// Handles the connection to the MQTT broker. The client reconnects on the next poll after an
+// error. With the forwarder enabled, the gateway commands are subscribed on every (re)connect, as
+// the session is not persisted by the broker.
+async fn event_loop(mut eventloop: EventLoop, client: AsyncClient, gateway_id: String) {
+    let conf = config::get();
+    let mqtt_conf = &conf.integrations.mqtt;
+    let command_topic = get_command_topic(mqtt_conf, &gateway_id);
+
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                info!("Connected to MQTT broker, server: {}", mqtt_conf.server);
+                CONNECTED.set(1);
+
+                if mqtt_conf.forwarder.enabled {
+                    info!("Subscribing to MQTT commands, topic: {}", command_topic);
+                    if let Err(e) = client.try_subscribe(&command_topic, QoS::AtLeastOnce) {
+                        error!("MQTT subscribe error, error: {}", e);
+                    }
+                }
+            }
+            Ok(Event::Incoming(Incoming::Publish(p))) => {
+                tokio::spawn(handle_command(gateway_id.clone(), p));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("MQTT connection error, error: {}", e);
+                CONNECTED.set(0);
+                sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+// Handles the command received by the forwarder. Only the downlink and configuration commands
+// are supported, of which the downlink results in an ack event (DownlinkTxAck).
+async fn handle_command(gateway_id: String, p: Publish) {
+    let conf = config::get();
+    let command = match get_command(&conf.integrations.mqtt, &gateway_id, &p.topic) {
+        Some(v) => v,
+        None => {
+            warn!("Unexpected MQTT topic, topic: {}", p.topic);
+            return;
+        }
+    };
+
+    if !["down", "config"].contains(&command) {
+        warn!("Unsupported MQTT command, command: {}", command);
+        return;
+    }
+
+    match proxy::handle_command(command, &p.payload).await {
+        Ok(resp) => {
+            if command == "down" {
+                forward_event("ack", resp);
+            }
+        }
+        Err(e) => error!(
+            "Handle MQTT command error, command: {}, error: {}",
+            command, e
+        ),
+    }
+}
+
+fn get_command_topic(mqtt_conf: &config::Mqtt, gateway_id: &str) -> String {
+    format!(
+        "{}/gateway/{}/command/#",
+        mqtt_conf.forwarder.topic_prefix, gateway_id
+    )
+}
+
+// Returns the command of the given command topic.
+fn get_command<'a>(mqtt_conf: &config::Mqtt, gateway_id: &str, topic: &'a str) -> Option<&'a str> {
+    topic
+        .strip_prefix(&format!(
+            "{}/gateway/{}/command/",
+            mqtt_conf.forwarder.topic_prefix, gateway_id
+        ))
+        .filter(|v| !v.is_empty() && !v.contains('/'))
+}
+
+fn get_topic(mqtt_conf: &config::Mqtt, gateway_id: &str, msg: &Message) -> String {
+    if msg.event {
+        format!(
+            "{}/gateway/{}/event/{}",
+            mqtt_conf.forwarder.topic_prefix, gateway_id, msg.topic
+        )
+    } else {
+        format!("{}/{}/{}", mqtt_conf.topic_prefix, gateway_id, msg.topic)
+    }
+}
+
+// Returns the Gateway ID and the MQTT client options.
+async fn get_mqtt_options(mqtt_conf: &config::Mqtt) -> Result<(String, MqttOptions)> {
+    let gateway_id = hex::encode(backend::get_gateway_id().await?);
+    let client_id = if mqtt_conf.client_id.is_empty() {
+        format!("chirpstack-gateway-mesh-{}", gateway_id)
+    } else {
+        mqtt_conf.client_id.clone()
+    };
+    let (host, port, tls) = parse_server(&mqtt_conf.server)?;
+
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(mqtt_conf.keep_alive);
+    options.set_clean_session(true);
+    if !mqtt_conf.username.is_empty() || !mqtt_conf.password.is_empty() {
+        options.set_credentials(&mqtt_conf.username, &mqtt_conf.password);
+    }
+
+    if tls {
+        // Without CA certificate, the server certificate is validated against the system CA
+        // certificates.
+        options.set_transport(if mqtt_conf.ca_cert.is_empty() {
+            Transport::tls_with_default_config()
+        } else {
+            let client_auth = if mqtt_conf.tls_cert.is_empty() {
+                None
+            } else {
+                Some((
+                    fs::read(&mqtt_conf.tls_cert)?,
+                    fs::read(&mqtt_conf.tls_key)?,
+                ))
+            };
+            Transport::tls(fs::read(&mqtt_conf.ca_cert)?, client_auth, None)
+        });
+    }
+
+    Ok((gateway_id, options))
+}
+
+// Returns the host, port and whether TLS must be used for the given server URL.
+pub fn parse_server(server: &str) -> Result<(String, u16, bool)> {
+    let (addr, tls) = if let Some(v) = server.strip_prefix("tcp://") {
+        (v, false)
+    } else if let Some(v) = server.strip_prefix("ssl://") {
+        (v, true)
+    } else {
+        return Err(Error::Config(
+            "integrations.mqtt.server must be a tcp:// or ssl:// URL".into(),
+        ));
+    };
+
+    let (host, port) = match addr.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse().map_err(|_| {
+                Error::Config(format!("Invalid integrations.mqtt.server port: {}", port))
+            })?,
+        ),
+        None => (addr, if tls { 8883 } else { 1883 }),
+    };
+
+    if host.is_empty() {
+        return Err(Error::Config(
+            "integrations.mqtt.server does not contain a host".into(),
+        ));
+    }
+
+    Ok((host.to_string(), port, tls))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_topic() {
+        let mqtt_conf = config::Mqtt::default();
+
+        assert_eq!(
+            "mesh/0101010101010101/counters",
+            get_topic(
+                &mqtt_conf,
+                "0101010101010101",
+                &Message {
+                    topic: "counters".into(),
+                    payload: vec![],
+                    retain: false,
+                    event: false,
+                }
+            )
+        );
+        assert_eq!(
+            "eu868/gateway/0101010101010101/event/up",
+            get_topic(
+                &mqtt_conf,
+                "0101010101010101",
+                &Message {
+                    topic: "up".into(),
+                    payload: vec![],
+                    retain: false,
+                    event: true,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_command() {
+        let mqtt_conf = config::Mqtt::default();
+
+        assert_eq!(
+            "eu868/gateway/0101010101010101/command/#",
+            get_command_topic(&mqtt_conf, "0101010101010101")
+        );
+        assert_eq!(
+            Some("down"),
+            get_command(
+                &mqtt_conf,
+                "0101010101010101",
+                "eu868/gateway/0101010101010101/command/down"
+            )
+        );
+        assert_eq!(
+            None,
+            get_command(
+                &mqtt_conf,
+                "0101010101010101",
+                "eu868/gateway/0202020202020202/command/down"
+            )
+        );
+        assert_eq!(
+            None,
+            get_command(
+                &mqtt_conf,
+                "0101010101010101",
+                "eu868/gateway/0101010101010101/command/"
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_server() {
+        assert_eq!(
+            ("127.0.0.1".to_string(), 1883, false),
+            parse_server("tcp://127.0.0.1:1883").unwrap()
+        );
+        assert_eq!(
+            ("localhost".to_string(), 8883, true),
+            parse_server("ssl://localhost").unwrap()
+        );
+        assert_eq!(
+            ("localhost".to_string(), 8884, true),
+            parse_server("ssl://localhost:8884").unwrap()
+        );
+        assert_eq!(
+            "integrations.mqtt.server must be a tcp:// or ssl:// URL",
+            parse_server("mqtt://localhost").unwrap_err().to_string()
+        );
+        assert!(parse_server("tcp://localhost:port").is_err());
+        assert!(parse_server("tcp://").is_err());
+    }
+
+    #[test]
+    fn test_encode_counters() {
+        let metadata: HashMap<String, String> = [
+            ("mesh_rx_count_868100000".to_string(), "5".to_string()),
+            (
+                "mesh_concentratord_connected".to_string(),
+                "true".to_string(),
+            ),
+            ("config_version".to_string(), "1.0".to_string()),
+        ]
+        .into();
+
+        let v: serde_json::Value =
+            serde_json::from_slice(&encode_counters(&metadata).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "mesh_rx_count_868100000": 5.0,
+                "mesh_concentratord_connected": "true",
+            }),
+            v
+        );
+    }
+}
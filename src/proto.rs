@@ -0,0 +1,3 @@
+// Protobuf representation of the mesh packets and proxy API messages (see proto/mesh.proto).
+include!(concat!(env!("OUT_DIR"), "/mesh.rs"));
+include!(concat!(env!("OUT_DIR"), "/mesh.serde.rs"));
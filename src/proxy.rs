@@ -1,20 +1,101 @@
-use std::thread;
+use std::sync::Mutex;
+use std::time::Instant;
 
-use anyhow::Result;
 use chirpstack_api::gw;
 use chirpstack_api::prost::Message;
 use log::{error, info, trace};
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use serde::{de::DeserializeOwned, Serialize};
 use tokio::sync::{mpsc, oneshot};
 
 use crate::backend;
-use crate::config::Configuration;
+use crate::beacon;
+use crate::cache::Cache;
+use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
 use crate::helpers;
+use crate::logging;
 use crate::mesh;
+use crate::metrics;
+use crate::mqtt;
+use crate::proto;
+use crate::supervisor;
 
 static EVENT_CHAN: OnceCell<EventChannel> = OnceCell::new();
+static EVENT_SEQ: Mutex<u64> = Mutex::new(0);
+static EVENT_REPLAY_BUFFER: OnceCell<Mutex<Cache<proto::ProxyEvent>>> = OnceCell::new();
+
+static EVENT_COUNT: Lazy<Family<EventLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<EventLabels, Counter>::default();
+    metrics::register(
+        "proxy_event_count",
+        "Number of events published on the proxy API",
+        counter.clone(),
+    );
+    counter
+});
+static EVENT_QUEUE_LENGTH: Lazy<Gauge> = Lazy::new(|| {
+    let gauge = Gauge::default();
+    metrics::register(
+        "proxy_event_queue_length",
+        "Number of events waiting to be published on the proxy API",
+        gauge.clone(),
+    );
+    gauge
+});
+static EVENT_QUEUE_DURATION: Lazy<Family<EventLabels, Histogram>> = Lazy::new(|| {
+    let histogram = Family::<EventLabels, Histogram>::new_with_constructor(new_histogram as _);
+    metrics::register(
+        "proxy_event_queue_duration_seconds",
+        "Time between enqueueing an event and publishing it on the proxy API",
+        histogram.clone(),
+    );
+    histogram
+});
+static COMMAND_DURATION: Lazy<Family<CommandLabels, Histogram>> = Lazy::new(|| {
+    let histogram = Family::<CommandLabels, Histogram>::new_with_constructor(new_histogram as _);
+    metrics::register(
+        "proxy_command_duration_seconds",
+        "Time between receiving a proxy API command and sending its response",
+        histogram.clone(),
+    );
+    histogram
+});
+static COMMAND_ERROR_COUNT: Lazy<Family<CommandLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<CommandLabels, Counter>::default();
+    metrics::register(
+        "proxy_command_error_count",
+        "Number of proxy API commands that failed to be handled",
+        counter.clone(),
+    );
+    counter
+});
+static COMMAND_RESPONSE_DROP_COUNT: Lazy<Counter> = Lazy::new(|| {
+    let counter = Counter::default();
+    metrics::register(
+        "proxy_command_response_drop_count",
+        "Number of proxy API command responses that were dropped",
+        counter.clone(),
+    );
+    counter
+});
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct EventLabels {
+    event: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct CommandLabels {
+    command: String,
+}
 
-type Event = (String, Vec<u8>);
+type Event = (String, Vec<u8>, Instant, u64);
 type Command = ((String, Vec<u8>), oneshot::Sender<Vec<u8>>);
 type EventChannel = mpsc::UnboundedSender<Event>;
 type CommandChannel = mpsc::UnboundedReceiver<Command>;
@@ -25,72 +106,39 @@ pub async fn setup(conf: &Configuration) -> Result<()> {
     }
 
     info!(
-        "Setting up Concentratord proxy API, event_bind: {}, command_bind: {}",
-        conf.mesh.proxy_api.event_bind, conf.mesh.proxy_api.command_bind
+        "Setting up Concentratord proxy API, event_bind: {}, command_bind: {}, json: {}, event_seq_frame: {}",
+        conf.mesh.proxy_api.event_bind,
+        conf.mesh.proxy_api.command_bind,
+        conf.mesh.proxy_api.json,
+        conf.mesh.proxy_api.event_seq_frame
     );
 
-    // Setup ZMQ event.
-
-    // As the zmq::Context can't be shared between threads, we use a channel.
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<Event>();
-
-    // Spawn the zmq event handler to a dedicated thread.
-    thread::spawn({
-        let event_bind = conf.mesh.proxy_api.event_bind.clone();
-
-        move || {
-            let zmq_ctx = zmq::Context::new();
-            let sock = zmq_ctx.socket(zmq::PUB).unwrap();
-            sock.bind(&event_bind).unwrap();
-
-            while let Some(event) = event_rx.blocking_recv() {
-                sock.send(&event.0, zmq::SNDMORE).unwrap();
-                sock.send(&event.1, 0).unwrap();
-            }
-        }
-    });
-
-    // Set event channel.
-
-    EVENT_CHAN
-        .set(event_tx)
-        .map_err(|e| anyhow!("OnceCell error: {:?}", e))?;
+    // The metrics are registered on first use. Force these, such that these are exposed before
+    // the first event or command.
+    Lazy::force(&EVENT_COUNT);
+    Lazy::force(&EVENT_QUEUE_LENGTH);
+    Lazy::force(&EVENT_QUEUE_DURATION);
+    Lazy::force(&COMMAND_DURATION);
+    Lazy::force(&COMMAND_ERROR_COUNT);
+    Lazy::force(&COMMAND_RESPONSE_DROP_COUNT);
+
+    let zmq_ctx = zmq::Context::new();
+
+    // With the MQTT forwarder enabled, events are published to the MQTT broker instead.
+    if conf.integrations.mqtt.forwarder.enabled {
+        info!("MQTT forwarder is enabled, not setting up proxy API event socket");
+    } else {
+        setup_event(conf, &zmq_ctx)?;
+    }
 
     // Setup ZMQ command.
 
     let (command_tx, command_rx) = mpsc::unbounded_channel::<Command>();
 
     // Spawn the zmq command handler to a dedicated thread.
-    thread::spawn({
-        let command_bind = conf.mesh.proxy_api.command_bind.clone();
-
-        move || {
-            let zmq_ctx = zmq::Context::new();
-            let mut sock = zmq_ctx.socket(zmq::REP).unwrap();
-            sock.bind(&command_bind).unwrap();
-
-            loop {
-                match receive_zmq_command(&mut sock) {
-                    Ok(v) => {
-                        let (resp_tx, resp_rx) = oneshot::channel::<Vec<u8>>();
-                        command_tx.send(((v.0, v.1), resp_tx)).unwrap();
-
-                        match resp_rx.blocking_recv() {
-                            Ok(v) => sock.send(&v, 0).unwrap(),
-                            Err(e) => {
-                                error!("Receive command response error, error: {}", e);
-                                sock.send(vec![], 0).unwrap();
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("Error receiving ZMQ command: {}", e);
-                        sock.send(vec![], 0).unwrap();
-                    }
-                }
-            }
-        }
-    });
+    let sock = zmq_ctx.socket(zmq::REP)?;
+    sock.bind(&conf.mesh.proxy_api.command_bind)?;
+    supervisor::spawn_thread("Proxy command", move || zmq_command_loop(sock, command_tx));
 
     // Spawn command handler.
     tokio::spawn({
@@ -102,38 +150,138 @@ pub async fn setup(conf: &Configuration) -> Result<()> {
     Ok(())
 }
 
-pub async fn send_uplink(pl: &gw::UplinkFrame) -> Result<()> {
-    info!("Sending uplink event - {}", helpers::format_uplink(pl)?);
+fn setup_event(conf: &Configuration, zmq_ctx: &zmq::Context) -> Result<()> {
+    // As the zmq::Socket can't be shared between threads, we use a channel.
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<Event>();
 
-    let event_chan = EVENT_CHAN
-        .get()
-        .ok_or_else(|| anyhow!("EVENT_CHAN is not set"))?;
+    // Spawn the zmq event handler to a dedicated thread.
+    let sock = zmq_ctx.socket(zmq::PUB)?;
+    sock.bind(&conf.mesh.proxy_api.event_bind)?;
+
+    // The mirror socket is handled by the same thread, such that both sockets publish the
+    // events in the same order.
+    let mirror_sock = if conf.mesh.proxy_api.mirror_event_bind.is_empty() {
+        None
+    } else {
+        info!(
+            "Setting up proxy API event mirror, mirror_event_bind: {}",
+            conf.mesh.proxy_api.mirror_event_bind
+        );
+        let sock = zmq_ctx.socket(zmq::PUB)?;
+        sock.bind(&conf.mesh.proxy_api.mirror_event_bind)?;
+        Some(sock)
+    };
+
+    supervisor::spawn_thread("Proxy event", {
+        let event_seq_frame = conf.mesh.proxy_api.event_seq_frame;
+        move || zmq_event_loop(sock, mirror_sock, &mut event_rx, event_seq_frame)
+    });
+
+    // Set event channel.
+
+    EVENT_CHAN
+        .set(event_tx)
+        .map_err(|e| Error::Backend(format!("OnceCell error: {:?}", e)))?;
 
-    event_chan.send(("up".to_string(), pl.encode_to_vec()))?;
+    // Setup event replay buffer.
+
+    if conf.mesh.proxy_api.event_replay_buffer_size != 0 {
+        EVENT_REPLAY_BUFFER
+            .set(Mutex::new(Cache::new(
+                conf.mesh.proxy_api.event_replay_buffer_size,
+            )))
+            .map_err(|_| Error::Backend("OnceCell error".into()))?;
+    }
 
     Ok(())
 }
 
+pub async fn send_uplink(pl: &gw::UplinkFrame) -> Result<()> {
+    if logging::sample(logging::Category::Uplink) {
+        info!("Sending uplink event - {}", helpers::format_uplink(pl)?);
+    }
+    beacon::record_uplink();
+    send_event("up", encode(pl)?)
+}
+
+pub fn get_event_queue_length() -> i64 {
+    EVENT_QUEUE_LENGTH.get()
+}
+
 pub async fn send_stats(pl: &gw::GatewayStats) -> Result<()> {
     info!("Sending gateway stats event");
+    send_event("stats", encode(pl)?)
+}
 
-    let event_chan = EVENT_CHAN
-        .get()
-        .ok_or_else(|| anyhow!("EVENT_CHAN is not set"))?;
+pub async fn send_mesh_heartbeat(pl: &gw::MeshHeartbeat) -> Result<()> {
+    info!("Sending mesh heartbeat event");
+    send_event("mesh_heartbeat", encode(pl)?)
+}
 
-    event_chan.send(("stats".to_string(), pl.encode_to_vec()))?;
+pub async fn send_mesh_packet(pl: &proto::MeshPacketEvent) -> Result<()> {
+    trace!("Sending mesh packet event");
+    send_event("mesh_packet", encode(pl)?)
+}
 
-    Ok(())
+pub async fn send_mesh_config_mismatch(pl: &proto::MeshConfigMismatchEvent) -> Result<()> {
+    info!("Sending mesh config mismatch event");
+    send_event("mesh_config_mismatch", encode(pl)?)
 }
 
-pub async fn send_mesh_heartbeat(pl: &gw::MeshHeartbeat) -> Result<()> {
-    info!("Sending mesh heartbeat event");
+pub async fn send_mesh_downlink_alert(pl: &proto::MeshDownlinkAlertEvent) -> Result<()> {
+    info!("Sending mesh downlink alert event");
+    send_event("mesh_downlink_alert", encode(pl)?)
+}
+
+pub async fn send_mesh_relay_boot(pl: &proto::MeshRelayBootEvent) -> Result<()> {
+    info!("Sending mesh relay boot event");
+    send_event("mesh_relay_boot", encode(pl)?)
+}
+
+fn send_event(event: &str, b: Vec<u8>) -> Result<()> {
+    if config::get().integrations.mqtt.forwarder.enabled {
+        mqtt::forward_event(event, b);
+        EVENT_COUNT
+            .get_or_create(&EventLabels {
+                event: event.to_string(),
+            })
+            .inc();
+        return Ok(());
+    }
 
     let event_chan = EVENT_CHAN
         .get()
-        .ok_or_else(|| anyhow!("EVENT_CHAN is not set"))?;
+        .ok_or_else(|| Error::Backend("EVENT_CHAN is not set".into()))?;
+
+    // The lock is held until the event is enqueued, so that events are published in the order
+    // of their sequence number.
+    let mut seq = EVENT_SEQ.lock().unwrap();
+    *seq += 1;
+
+    if let Some(buffer) = EVENT_REPLAY_BUFFER.get() {
+        buffer.lock().unwrap().add(proto::ProxyEvent {
+            seq: *seq,
+            event: event.to_string(),
+            payload: b.clone(),
+        });
+    }
 
-    event_chan.send(("mesh_heartbeat".to_string(), pl.encode_to_vec()))?;
+    // The queue length is incremented before sending, as the event can be dequeued before send
+    // returns.
+    EVENT_QUEUE_LENGTH.inc();
+    if event_chan
+        .send((event.to_string(), b, Instant::now(), *seq))
+        .is_err()
+    {
+        EVENT_QUEUE_LENGTH.dec();
+        return Err(Error::Backend("Event channel has been closed".into()));
+    }
+    drop(seq);
+    EVENT_COUNT
+        .get_or_create(&EventLabels {
+            event: event.to_string(),
+        })
+        .inc();
 
     Ok(())
 }
@@ -142,12 +290,17 @@ async fn command_loop(mut command_rx: CommandChannel) {
     trace!("Starting command loop");
 
     while let Some(cmd) = command_rx.recv().await {
-        match handle_command(&cmd).await {
+        match handle_command(&cmd.0 .0, &cmd.0 .1).await {
             Ok(v) => {
                 _ = cmd.1.send(v);
             }
             Err(e) => {
                 error!("Handle command error: {}", e);
+                COMMAND_ERROR_COUNT
+                    .get_or_create(&CommandLabels {
+                        command: cmd.0 .0.clone(),
+                    })
+                    .inc();
                 let _ = cmd.1.send(vec![]);
             }
         }
@@ -156,36 +309,169 @@ async fn command_loop(mut command_rx: CommandChannel) {
     error!("Command loop has been interrupted");
 }
 
-async fn handle_command(cmd: &Command) -> Result<Vec<u8>> {
-    Ok(match cmd.0 .0.as_str() {
+// Handles the given command and returns the (encoded) response. This is also used for the
+// commands received by the MQTT forwarder.
+pub async fn handle_command(command: &str, b: &[u8]) -> Result<Vec<u8>> {
+    Ok(match command {
         "config" => {
-            let pl = gw::GatewayConfiguration::decode(cmd.0 .1.as_slice())?;
+            let pl: gw::GatewayConfiguration = decode(b)?;
             info!("Configuration command received, version: {}", pl.version);
             backend::send_gateway_configuration(&pl).await?;
             Vec::new()
         }
         "down" => {
-            let pl = gw::DownlinkFrame::decode(cmd.0 .1.as_slice())?;
-            info!(
-                "Downlink command received - {}",
-                helpers::format_downlink(&pl)?
-            );
-            mesh::handle_downlink(pl).await.map(|v| v.encode_to_vec())?
+            let pl: gw::DownlinkFrame = decode(b)?;
+            if logging::sample(logging::Category::Downlink) {
+                info!(
+                    "Downlink command received - {}",
+                    helpers::format_downlink(&pl)?
+                );
+            }
+            encode(&mesh::handle_downlink(pl).await?)?
         }
         "gateway_id" => {
             info!("Get gateway id command received");
             backend::get_gateway_id().await.map(|v| v.to_vec())?
         }
+        "get_events" => {
+            let pl: proto::GetEventsRequest = decode(b)?;
+            info!("Get events command received, since_seq: {}", pl.since_seq);
+            encode(&get_events(pl.since_seq)?)?
+        }
         _ => {
-            return Err(anyhow!("Unexpected command: {}", cmd.0 .0));
+            return Err(Error::InvalidMessage(format!(
+                "Unexpected command: {}",
+                command
+            )));
         }
     })
 }
 
+fn get_events(since_seq: u64) -> Result<proto::GetEventsResponse> {
+    let buffer = EVENT_REPLAY_BUFFER
+        .get()
+        .ok_or_else(|| Error::Config("Event replay buffer is disabled".into()))?
+        .lock()
+        .unwrap();
+
+    Ok(proto::GetEventsResponse {
+        events: buffer
+            .iter()
+            .filter(|v| v.seq > since_seq)
+            .cloned()
+            .collect(),
+    })
+}
+
+// Encode the given message using Protobuf or JSON, depending on the proxy API configuration.
+fn encode<T: Message + Serialize>(pl: &T) -> Result<Vec<u8>> {
+    if config::get().mesh.proxy_api.json {
+        Ok(serde_json::to_vec(pl)?)
+    } else {
+        Ok(pl.encode_to_vec())
+    }
+}
+
+// Decode the given message using Protobuf or JSON, depending on the proxy API configuration.
+fn decode<T: Message + DeserializeOwned + Default>(b: &[u8]) -> Result<T> {
+    if config::get().mesh.proxy_api.json {
+        Ok(serde_json::from_slice(b)?)
+    } else {
+        Ok(T::decode(b)?)
+    }
+}
+
+fn zmq_event_loop(
+    sock: zmq::Socket,
+    mirror_sock: Option<zmq::Socket>,
+    event_rx: &mut mpsc::UnboundedReceiver<Event>,
+    event_seq_frame: bool,
+) -> Result<()> {
+    while let Some(event) = event_rx.blocking_recv() {
+        EVENT_QUEUE_LENGTH.dec();
+        EVENT_QUEUE_DURATION
+            .get_or_create(&EventLabels {
+                event: event.0.clone(),
+            })
+            .observe(event.2.elapsed().as_secs_f64());
+
+        // A send error only affects this event, thus it is not returned.
+        if let Err(e) = send_zmq_event(&sock, &event, event_seq_frame) {
+            error!("Send ZMQ event error, event: {}, error: {}", event.0, e);
+        }
+
+        if let Some(mirror_sock) = &mirror_sock {
+            if let Err(e) = send_zmq_event(mirror_sock, &event, event_seq_frame) {
+                error!(
+                    "Send ZMQ mirror event error, event: {}, error: {}",
+                    event.0, e
+                );
+            }
+        }
+    }
+
+    Err(Error::Backend("Event channel has been closed".into()))
+}
+
+fn send_zmq_event(sock: &zmq::Socket, event: &Event, event_seq_frame: bool) -> Result<()> {
+    sock.send(&event.0, zmq::SNDMORE)?;
+    if event_seq_frame {
+        sock.send(&event.1, zmq::SNDMORE)?;
+        sock.send(&event.3.to_be_bytes()[..], 0)?;
+    } else {
+        sock.send(&event.1, 0)?;
+    }
+    Ok(())
+}
+
+fn zmq_command_loop(
+    mut sock: zmq::Socket,
+    command_tx: mpsc::UnboundedSender<Command>,
+) -> Result<()> {
+    loop {
+        let resp = match receive_zmq_command(&mut sock) {
+            Ok(v) => {
+                let start = Instant::now();
+                let labels = CommandLabels {
+                    command: v.0.clone(),
+                };
+
+                let (resp_tx, resp_rx) = oneshot::channel::<Vec<u8>>();
+                command_tx
+                    .send(((v.0, v.1), resp_tx))
+                    .map_err(|_| Error::Backend("Command channel has been closed".into()))?;
+
+                let resp = match resp_rx.blocking_recv() {
+                    Ok(v) => v,
+                    Err(e) => {
+                        error!("Receive command response error, error: {}", e);
+                        COMMAND_RESPONSE_DROP_COUNT.inc();
+                        vec![]
+                    }
+                };
+
+                COMMAND_DURATION
+                    .get_or_create(&labels)
+                    .observe(start.elapsed().as_secs_f64());
+
+                resp
+            }
+            Err(e) => {
+                error!("Error receiving ZMQ command: {}", e);
+                vec![]
+            }
+        };
+
+        // The REP socket must send a response before it can receive the next command. If this
+        // fails, the socket is in an unrecoverable state.
+        sock.send(resp, 0)?;
+    }
+}
+
 fn receive_zmq_command(sock: &mut zmq::Socket) -> Result<(String, Vec<u8>)> {
-    let msg = sock.recv_multipart(0).unwrap();
+    let msg = sock.recv_multipart(0)?;
     if msg.len() != 2 {
-        return Err(anyhow!("Command must have 2 frames"));
+        return Err(Error::InvalidMessage("Command must have 2 frames".into()));
     }
 
     let cmd = String::from_utf8(msg[0].to_vec())?;
@@ -193,3 +479,7 @@ fn receive_zmq_command(sock: &mut zmq::Socket) -> Result<(String, Vec<u8>)> {
 
     Ok((cmd, b))
 }
+
+fn new_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.0001, 2.0, 16))
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use crate::packets;
+
+// Transmitter Relay ID + receiver Relay ID.
+type LinkKey = ([u8; 4], [u8; 4]);
+
+// Link quality between two relays.
+static LINKS: Lazy<Mutex<HashMap<LinkKey, Link>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub rssi: i16,
+    pub snr: i8,
+    pub updated_at: Instant,
+}
+
+// Records the links as observed from the given heartbeat. Every Relay Gateway that relays a
+// heartbeat adds itself to the relay path, together with the RSSI / SNR of the received
+// heartbeat. This means that each relay path entry describes the link between the previous hop
+// and the relay of the path entry. The last hop is the link between the transmitter of the
+// heartbeat and this Relay Gateway.
+pub fn record_heartbeat(
+    pl: &packets::HeartbeatPayload,
+    hop_count: u8,
+    relay_id: [u8; 4],
+    rssi: i16,
+    snr: i8,
+) {
+    let now = Instant::now();
+    let mut links = LINKS.lock().unwrap();
+
+    // In case the relay path has been truncated, the previous hop of the first entry is unknown.
+    let mut prev = if usize::from(hop_count) == pl.relay_path.len() + 1 {
+        Some(pl.relay_id)
+    } else {
+        None
+    };
+
+    for path in &pl.relay_path {
+        if let Some(prev) = prev {
+            links.insert(
+                (prev, path.relay_id),
+                Link {
+                    rssi: path.rssi,
+                    snr: path.snr,
+                    updated_at: now,
+                },
+            );
+        }
+        prev = Some(path.relay_id);
+    }
+
+    if let Some(prev) = prev {
+        links.insert(
+            (prev, relay_id),
+            Link {
+                rssi,
+                snr,
+                updated_at: now,
+            },
+        );
+    }
+}
+
+// Returns the Relay ID of the relay that transmitted the given packet, if this can be derived
+// from the packet. This is the case for packets that have not been relayed yet and for
+// heartbeats (last entry of the relay path). Downlinks are sent by the Border Gateway and
+// always return None.
+pub fn get_transmitter(packet: &packets::MeshPacket) -> Option<[u8; 4]> {
+    match &packet.payload {
+        packets::Payload::Heartbeat(pl) => Some(
+            pl.relay_path
+                .last()
+                .map(|v| v.relay_id)
+                .unwrap_or(pl.relay_id),
+        ),
+        packets::Payload::Uplink(pl) if packet.mhdr.hop_count == 1 => Some(pl.relay_id),
+        packets::Payload::Event(pl) if packet.mhdr.hop_count == 1 => Some(pl.relay_id),
+        _ => None,
+    }
+}
+
+// Returns true if this Relay Gateway should relay a packet received from the given transmitter.
+// It returns false when an other (known to be relaying) Relay Gateway has a link with the
+// transmitter that is at least rssi_margin dB better than the link of this Relay Gateway, in
+// which case that relay is expected to relay the packet. Links older than max_age are ignored.
+pub fn should_relay(
+    transmitter: [u8; 4],
+    relay_id: [u8; 4],
+    rssi_margin: i16,
+    max_age: Duration,
+) -> bool {
+    let links = LINKS.lock().unwrap();
+    let now = Instant::now();
+
+    let own = match links
+        .get(&(transmitter, relay_id))
+        .filter(|v| now.duration_since(v.updated_at) <= max_age)
+    {
+        Some(v) => v,
+        // We have no (recent) knowledge about this link.
+        None => return true,
+    };
+
+    !links.iter().any(|((tx, rx), link)| {
+        *tx == transmitter
+            && *rx != relay_id
+            && now.duration_since(link.updated_at) <= max_age
+            && link.rssi >= own.rssi.saturating_add(rssi_margin)
+    })
+}
+
+// Returns the links from the route table.
+pub fn get_links() -> Vec<(LinkKey, Link)> {
+    LINKS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(k, v)| (*k, v.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn heartbeat(relay_id: [u8; 4], path: &[([u8; 4], i16)]) -> packets::HeartbeatPayload {
+        packets::HeartbeatPayload {
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_id,
+            relay_path: path
+                .iter()
+                .map(|(relay_id, rssi)| packets::RelayPath {
+                    relay_id: *relay_id,
+                    rssi: *rssi,
+                    snr: 0,
+                })
+                .collect(),
+            config_checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_routing() {
+        // Relay 10 heard by 11 (-60) and by us (-110).
+        record_heartbeat(
+            &heartbeat([10, 0, 0, 0], &[([11, 0, 0, 0], -60)]),
+            2,
+            [12, 0, 0, 0],
+            -100,
+            0,
+        );
+        record_heartbeat(&heartbeat([10, 0, 0, 0], &[]), 1, [12, 0, 0, 0], -110, 0);
+
+        // Relay 20 heard by 21 (-100) and by us (-98).
+        record_heartbeat(
+            &heartbeat([20, 0, 0, 0], &[([21, 0, 0, 0], -100)]),
+            2,
+            [12, 0, 0, 0],
+            -80,
+            0,
+        );
+        record_heartbeat(&heartbeat([20, 0, 0, 0], &[]), 1, [12, 0, 0, 0], -98, 0);
+
+        // Truncated relay path, the link to 31 is unknown.
+        record_heartbeat(
+            &heartbeat([30, 0, 0, 0], &[([31, 0, 0, 0], -50)]),
+            3,
+            [12, 0, 0, 0],
+            -80,
+            0,
+        );
+        record_heartbeat(&heartbeat([30, 0, 0, 0], &[]), 1, [12, 0, 0, 0], -110, 0);
+
+        let links = get_links();
+        assert!(links
+            .iter()
+            .any(|(k, v)| *k == ([10, 0, 0, 0], [11, 0, 0, 0]) && v.rssi == -60));
+        assert!(links
+            .iter()
+            .any(|(k, v)| *k == ([11, 0, 0, 0], [12, 0, 0, 0]) && v.rssi == -100));
+        assert!(!links.iter().any(|(k, _)| k.1 == [31, 0, 0, 0]));
+
+        struct Test {
+            name: String,
+            transmitter: [u8; 4],
+            rssi_margin: i16,
+            max_age: Duration,
+            expected: bool,
+        }
+
+        let tests = vec![
+            Test {
+                name: "other relay has a better link".into(),
+                transmitter: [10, 0, 0, 0],
+                rssi_margin: 6,
+                max_age: Duration::from_secs(60),
+                expected: false,
+            },
+            Test {
+                name: "other relay has a better link, but within margin".into(),
+                transmitter: [10, 0, 0, 0],
+                rssi_margin: 60,
+                max_age: Duration::from_secs(60),
+                expected: true,
+            },
+            Test {
+                name: "we have the better link".into(),
+                transmitter: [20, 0, 0, 0],
+                rssi_margin: 0,
+                max_age: Duration::from_secs(60),
+                expected: true,
+            },
+            Test {
+                name: "unknown link of other relay".into(),
+                transmitter: [30, 0, 0, 0],
+                rssi_margin: 6,
+                max_age: Duration::from_secs(60),
+                expected: true,
+            },
+            Test {
+                name: "unknown transmitter".into(),
+                transmitter: [40, 0, 0, 0],
+                rssi_margin: 6,
+                max_age: Duration::from_secs(60),
+                expected: true,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            assert_eq!(
+                tst.expected,
+                should_relay(tst.transmitter, [12, 0, 0, 0], tst.rssi_margin, tst.max_age)
+            );
+        }
+    }
+}
@@ -0,0 +1,1017 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::AtomicU64;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chirpstack_api::gw;
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{linear_buckets, Histogram};
+
+use crate::config;
+use crate::error::Error;
+use crate::metrics;
+use crate::topology;
+
+// Mesh RX stats per frequency, since the last stats interval.
+static MESH_RX_STATS: Lazy<Mutex<HashMap<u32, RxStats>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Last stats reported by the mesh Concentratord, since the last stats interval.
+static MESH_CONCENTRATORD_STATS: Lazy<Mutex<Option<gw::GatewayStats>>> =
+    Lazy::new(|| Mutex::new(None));
+
+// Stats reported by the Concentratord of the Relay Gateway, aggregated since the last stats
+// report.
+static RELAY_STATS: Lazy<Mutex<gw::GatewayStats>> =
+    Lazy::new(|| Mutex::new(gw::GatewayStats::default()));
+
+// Relayed uplinks acknowledged and not acknowledged by the Border Gateway, since the last stats
+// report.
+static RELAY_UPLINK_ACKS: Mutex<(u32, u32)> = Mutex::new((0, 0));
+
+// Mesh channel quality per frequency, used for bad-channel avoidance.
+static MESH_CHANNEL_QUALITY: Lazy<Mutex<HashMap<u32, ChannelQuality>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Mesh drops that were logged per reason, used for rate-limiting drop logging.
+static MESH_DROP_LOG: Lazy<Mutex<HashMap<String, DropLog>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Airtime of the mesh transmissions within the airtime window.
+static MESH_TX_AIRTIME: Lazy<Mutex<VecDeque<(Instant, Duration)>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
+
+// Airtime of the mesh transmissions and receptions per frequency, used for airtime-aware
+// frequency selection.
+type ChannelOccupancy = HashMap<u32, VecDeque<(Instant, Duration)>>;
+
+static MESH_CHANNEL_OCCUPANCY: Lazy<Mutex<ChannelOccupancy>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Mesh TX errors per TxAck status.
+static MESH_TX_ERRORS: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Window over which the mesh TX airtime utilization is calculated.
+pub const MESH_TX_AIRTIME_WINDOW: Duration = Duration::from_secs(3600);
+
+// Interval in which at most one drop is logged per reason.
+const MESH_DROP_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+static MESH_RX_COUNT: Lazy<Family<FrequencyLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<FrequencyLabels, Counter>::default();
+    metrics::register(
+        "mesh_rx_count",
+        "Number of frames received by the mesh Concentratord",
+        counter.clone(),
+    );
+    counter
+});
+static MESH_RX_CRC_ERROR_COUNT: Lazy<Family<FrequencyLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<FrequencyLabels, Counter>::default();
+    metrics::register(
+        "mesh_rx_crc_error_count",
+        "Number of frames received by the mesh Concentratord with CRC error",
+        counter.clone(),
+    );
+    counter
+});
+static MESH_DROP_COUNT: Lazy<Family<DropLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<DropLabels, Counter>::default();
+    metrics::register(
+        "mesh_drop_count",
+        "Number of mesh packets that were dropped",
+        counter.clone(),
+    );
+    counter
+});
+static ERROR_COUNT: Lazy<Family<ErrorLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<ErrorLabels, Counter>::default();
+    metrics::register(
+        "error_count",
+        "Number of errors while handling Concentratord events, by error kind",
+        counter.clone(),
+    );
+    counter
+});
+static MESH_TX_ERROR_COUNT: Lazy<Family<TxErrorLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<TxErrorLabels, Counter>::default();
+    metrics::register(
+        "mesh_tx_error_count",
+        "Number of mesh transmissions that failed, by payload type, frequency and TxAck status",
+        counter.clone(),
+    );
+    counter
+});
+static EVENT_SHED_COUNT: Lazy<Family<ConcentratordLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<ConcentratordLabels, Counter>::default();
+    metrics::register(
+        "event_shed_count",
+        "Number of uplinks that were dropped without processing, as these exceeded the event max age",
+        counter.clone(),
+    );
+    counter
+});
+static COMMAND_RETRY_COUNT: Lazy<Family<CommandLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<CommandLabels, Counter>::default();
+    metrics::register(
+        "command_retry_count",
+        "Number of Concentratord commands that were retried after a failed request",
+        counter.clone(),
+    );
+    counter
+});
+static COMMAND_ERROR_COUNT: Lazy<Family<CommandLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<CommandLabels, Counter>::default();
+    metrics::register(
+        "command_error_count",
+        "Number of Concentratord commands that failed (after retries)",
+        counter.clone(),
+    );
+    counter
+});
+static MESH_CONCENTRATORD_CONNECTED: Lazy<Gauge> = Lazy::new(|| {
+    let gauge = Gauge::default();
+    metrics::register(
+        "mesh_concentratord_connected",
+        "Mesh Concentratord event socket is connected (1) or disconnected (0)",
+        gauge.clone(),
+    );
+    gauge
+});
+static MESH_CONCENTRATORD_METADATA: Lazy<Family<MetadataLabels, Gauge<f64, AtomicU64>>> =
+    Lazy::new(|| {
+        let gauge = Family::<MetadataLabels, Gauge<f64, AtomicU64>>::default();
+        metrics::register(
+            "mesh_concentratord_metadata",
+            "Numeric metadata (e.g. temperature) of the stats reported by the mesh Concentratord",
+            gauge.clone(),
+        );
+        gauge
+    });
+static MESH_RELAY_CONFIG_MISMATCH: Lazy<Family<RelayLabels, Gauge>> = Lazy::new(|| {
+    let gauge = Family::<RelayLabels, Gauge>::default();
+    metrics::register(
+        "mesh_relay_config_mismatch",
+        "Config checksum of the Relay Gateway mismatches (1) or matches (0) the Border Gateway",
+        gauge.clone(),
+    );
+    gauge
+});
+static MESH_RELAY_ASYMMETRIC_LINK: Lazy<Family<RelayLabels, Gauge>> = Lazy::new(|| {
+    let gauge = Family::<RelayLabels, Gauge>::default();
+    metrics::register(
+        "mesh_relay_asymmetric_link",
+        "Relay Gateway does not receive (1) or does receive (0) the uplink ACKs of the Border Gateway",
+        gauge.clone(),
+    );
+    gauge
+});
+static MESH_RELAY_CHALLENGE_FAILED: Lazy<Family<RelayLabels, Gauge>> = Lazy::new(|| {
+    let gauge = Family::<RelayLabels, Gauge>::default();
+    metrics::register(
+        "mesh_relay_challenge_failed",
+        "Relay Gateway did not answer (1) or did answer (0) the last challenge of the Border Gateway",
+        gauge.clone(),
+    );
+    gauge
+});
+static MESH_RELAY_DOWNLINK_SUCCESS_RATE: Lazy<Family<RelayLabels, Gauge<f64, AtomicU64>>> =
+    Lazy::new(|| {
+        let gauge = Family::<RelayLabels, Gauge<f64, AtomicU64>>::default();
+        metrics::register(
+            "mesh_relay_downlink_success_rate",
+            "Success rate (0.0 - 1.0) of the downlinks relayed to the Relay Gateway, within the downlink ACK window",
+            gauge.clone(),
+        );
+        gauge
+    });
+static MESH_RX_RSSI: Lazy<Family<FrequencyLabels, Histogram>> = Lazy::new(|| {
+    let histogram = Family::<FrequencyLabels, Histogram>::new_with_constructor(new_histogram as _);
+    metrics::register(
+        "mesh_rx_rssi",
+        "RSSI of frames received by the mesh Concentratord with valid CRC",
+        histogram.clone(),
+    );
+    histogram
+});
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct FrequencyLabels {
+    frequency: u32,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct RelayLabels {
+    relay_id: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct MetadataLabels {
+    key: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct DropLabels {
+    reason: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct ErrorLabels {
+    kind: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct ConcentratordLabels {
+    concentratord: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct CommandLabels {
+    concentratord: String,
+    command: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct TxErrorLabels {
+    payload_type: String,
+    frequency: u32,
+    status: String,
+}
+
+struct DropLog {
+    logged_at: Instant,
+    suppressed: u64,
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+struct RxStats {
+    rx_count: u32,
+    crc_error_count: u32,
+    rssi_sum: i64,
+}
+
+impl RxStats {
+    // Returns the average RSSI of the frames with valid CRC.
+    fn rssi_avg(&self) -> Option<f32> {
+        let crc_ok_count = self.rx_count - self.crc_error_count;
+        if crc_ok_count == 0 {
+            return None;
+        }
+
+        Some(self.rssi_sum as f32 / crc_ok_count as f32)
+    }
+
+    fn crc_error_ratio(&self) -> f32 {
+        if self.rx_count == 0 {
+            return 0.0;
+        }
+
+        self.crc_error_count as f32 / self.rx_count as f32
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+struct ChannelQuality {
+    sample_count: u32,
+    failure_count: u32,
+    excluded_until: Option<Instant>,
+}
+
+impl ChannelQuality {
+    fn record(&mut self, ok: bool) {
+        self.sample_count = self.sample_count.saturating_add(1);
+        if !ok {
+            self.failure_count = self.failure_count.saturating_add(1);
+        }
+    }
+
+    fn failure_ratio(&self) -> f32 {
+        if self.sample_count == 0 {
+            return 0.0;
+        }
+
+        self.failure_count as f32 / self.sample_count as f32
+    }
+
+    fn reset(&mut self) {
+        self.sample_count = 0;
+        self.failure_count = 0;
+    }
+}
+
+pub fn record_mesh_rx(rx_info: &gw::UplinkRxInfo, tx_info: &gw::UplinkTxInfo) {
+    let labels = FrequencyLabels {
+        frequency: tx_info.frequency,
+    };
+    let crc_ok = rx_info.crc_status() == gw::CrcStatus::CrcOk;
+
+    MESH_RX_COUNT.get_or_create(&labels).inc();
+    if crc_ok {
+        MESH_RX_RSSI
+            .get_or_create(&labels)
+            .observe(rx_info.rssi.into());
+    } else {
+        MESH_RX_CRC_ERROR_COUNT.get_or_create(&labels).inc();
+    }
+
+    record_mesh_channel_sample(tx_info.frequency, crc_ok);
+
+    let mut rx_stats = MESH_RX_STATS.lock().unwrap();
+    let stats = rx_stats.entry(tx_info.frequency).or_default();
+    stats.rx_count += 1;
+    if crc_ok {
+        stats.rssi_sum += i64::from(rx_info.rssi);
+    } else {
+        stats.crc_error_count += 1;
+    }
+}
+
+// Adds the mesh RX stats since the previous call to the metadata of the given gateway stats, and
+// resets the stats.
+pub fn add_mesh_rx_stats(pl: &mut gw::GatewayStats) {
+    let rx_stats: HashMap<u32, RxStats> = MESH_RX_STATS.lock().unwrap().drain().collect();
+
+    for (freq, stats) in rx_stats {
+        pl.metadata.insert(
+            format!("mesh_rx_count_{}", freq),
+            stats.rx_count.to_string(),
+        );
+        pl.metadata.insert(
+            format!("mesh_rx_crc_error_ratio_{}", freq),
+            format!("{:.3}", stats.crc_error_ratio()),
+        );
+        if let Some(rssi_avg) = stats.rssi_avg() {
+            pl.metadata.insert(
+                format!("mesh_rx_rssi_avg_{}", freq),
+                format!("{:.1}", rssi_avg),
+            );
+        }
+    }
+}
+
+// Records if the config checksum of the given Relay Gateway mismatches. Returns true if this
+// differs from the previously recorded state (a relay is initially considered to match).
+pub fn record_relay_config_mismatch(relay_id: [u8; 4], mismatch: bool) -> bool {
+    let prev = MESH_RELAY_CONFIG_MISMATCH
+        .get_or_create(&RelayLabels {
+            relay_id: hex::encode(relay_id),
+        })
+        .set(mismatch.into());
+    prev != i64::from(mismatch)
+}
+
+// Records if the link of the given Relay Gateway is asymmetric.
+pub fn record_relay_asymmetric_link(relay_id: [u8; 4], asymmetric: bool) {
+    MESH_RELAY_ASYMMETRIC_LINK
+        .get_or_create(&RelayLabels {
+            relay_id: hex::encode(relay_id),
+        })
+        .set(asymmetric.into());
+}
+
+// Records if the given Relay Gateway did not answer the last challenge.
+pub fn record_relay_challenge_failed(relay_id: [u8; 4], failed: bool) {
+    MESH_RELAY_CHALLENGE_FAILED
+        .get_or_create(&RelayLabels {
+            relay_id: hex::encode(relay_id),
+        })
+        .set(failed.into());
+}
+
+// Records the downlink success rate of the given Relay Gateway.
+pub fn record_relay_downlink_success_rate(relay_id: [u8; 4], success_rate: f64) {
+    MESH_RELAY_DOWNLINK_SUCCESS_RATE
+        .get_or_create(&RelayLabels {
+            relay_id: hex::encode(relay_id),
+        })
+        .set(success_rate);
+}
+
+// Records the connection state of the mesh Concentratord event socket.
+pub fn record_mesh_concentratord_connected(connected: bool) {
+    MESH_CONCENTRATORD_CONNECTED.set(connected.into());
+}
+
+// Records the stats reported by the mesh Concentratord.
+pub fn record_mesh_concentratord_stats(pl: gw::GatewayStats) {
+    for (k, v) in &pl.metadata {
+        if let Ok(v) = v.parse::<f64>() {
+            MESH_CONCENTRATORD_METADATA
+                .get_or_create(&MetadataLabels { key: k.clone() })
+                .set(v);
+        }
+    }
+
+    *MESH_CONCENTRATORD_STATS.lock().unwrap() = Some(pl);
+}
+
+// Adds the mesh Concentratord stats since the previous call to the metadata of the given gateway
+// stats, and resets the stats. The metadata of the mesh Concentratord (e.g. temperature, PPS
+// status) is prefixed with mesh_concentratord_.
+pub fn add_mesh_concentratord_stats(pl: &mut gw::GatewayStats) {
+    pl.metadata.insert(
+        "mesh_concentratord_connected".to_string(),
+        (MESH_CONCENTRATORD_CONNECTED.get() == 1).to_string(),
+    );
+
+    let mesh_stats = match MESH_CONCENTRATORD_STATS.lock().unwrap().take() {
+        Some(v) => v,
+        None => return,
+    };
+
+    pl.metadata.insert(
+        "mesh_concentratord_rx_packets_received".to_string(),
+        mesh_stats.rx_packets_received.to_string(),
+    );
+    pl.metadata.insert(
+        "mesh_concentratord_rx_packets_received_ok".to_string(),
+        mesh_stats.rx_packets_received_ok.to_string(),
+    );
+    pl.metadata.insert(
+        "mesh_concentratord_tx_packets_emitted".to_string(),
+        mesh_stats.tx_packets_emitted.to_string(),
+    );
+    for (k, v) in mesh_stats.metadata {
+        pl.metadata.insert(format!("mesh_concentratord_{}", k), v);
+    }
+}
+
+// Adds the stats reported by the Concentratord to the aggregated Relay Gateway stats.
+pub fn record_relay_stats(pl: &gw::GatewayStats) {
+    let mut relay_stats = RELAY_STATS.lock().unwrap();
+    relay_stats.gateway_id.clone_from(&pl.gateway_id);
+    relay_stats.rx_packets_received = relay_stats
+        .rx_packets_received
+        .saturating_add(pl.rx_packets_received);
+    relay_stats.rx_packets_received_ok = relay_stats
+        .rx_packets_received_ok
+        .saturating_add(pl.rx_packets_received_ok);
+    relay_stats.tx_packets_received = relay_stats
+        .tx_packets_received
+        .saturating_add(pl.tx_packets_received);
+    relay_stats.tx_packets_emitted = relay_stats
+        .tx_packets_emitted
+        .saturating_add(pl.tx_packets_emitted);
+
+    for (freq, count) in &pl.rx_packets_per_frequency {
+        let v = relay_stats
+            .rx_packets_per_frequency
+            .entry(*freq)
+            .or_default();
+        *v = v.saturating_add(*count);
+    }
+    for (freq, count) in &pl.tx_packets_per_frequency {
+        let v = relay_stats
+            .tx_packets_per_frequency
+            .entry(*freq)
+            .or_default();
+        *v = v.saturating_add(*count);
+    }
+}
+
+// Records if a relayed uplink has been acknowledged by the Border Gateway.
+pub fn record_relay_uplink_ack(acked: bool) {
+    let mut uplink_acks = RELAY_UPLINK_ACKS.lock().unwrap();
+    if acked {
+        uplink_acks.0 = uplink_acks.0.saturating_add(1);
+    } else {
+        uplink_acks.1 = uplink_acks.1.saturating_add(1);
+    }
+}
+
+// Returns the acknowledged and not acknowledged relayed uplinks since the previous call, and
+// resets the counters.
+pub fn take_relay_uplink_acks() -> (u32, u32) {
+    std::mem::take(&mut *RELAY_UPLINK_ACKS.lock().unwrap())
+}
+
+// Returns the aggregated Relay Gateway stats since the previous call, and resets the stats.
+pub fn take_relay_stats() -> gw::GatewayStats {
+    std::mem::take(&mut *RELAY_STATS.lock().unwrap())
+}
+
+// Adds the mesh TX airtime utilization of the past hour to the metadata of the given gateway
+// stats, together with the last reported utilization of each Relay Gateway.
+pub fn add_mesh_tx_airtime_stats(pl: &mut gw::GatewayStats) {
+    pl.metadata.insert(
+        "mesh_tx_airtime_pct".to_string(),
+        format!("{:.2}", get_airtime_pct(get_mesh_tx_airtime())),
+    );
+
+    for relay in topology::get_relays() {
+        if let Some(tx_airtime) = &relay.tx_airtime {
+            pl.metadata.insert(
+                format!("mesh_relay_tx_airtime_pct_{}", hex::encode(relay.relay_id)),
+                format!("{:.2}", tx_airtime.airtime_pct),
+            );
+        }
+    }
+}
+
+// Records an uplink of the given Concentratord that was shed.
+pub fn record_event_shed(concentratord: &str) {
+    EVENT_SHED_COUNT
+        .get_or_create(&ConcentratordLabels {
+            concentratord: concentratord.to_string(),
+        })
+        .inc();
+}
+
+// Records the retry of a Concentratord command.
+pub fn record_command_retry(concentratord: &str, command: &str) {
+    COMMAND_RETRY_COUNT
+        .get_or_create(&CommandLabels {
+            concentratord: concentratord.to_string(),
+            command: command.to_string(),
+        })
+        .inc();
+}
+
+// Records a Concentratord command that failed after all retries.
+pub fn record_command_error(concentratord: &str, command: &str) {
+    COMMAND_ERROR_COUNT
+        .get_or_create(&CommandLabels {
+            concentratord: concentratord.to_string(),
+            command: command.to_string(),
+        })
+        .inc();
+}
+
+// Records a failed mesh transmission, such that scheduling issues (e.g. TOO_EARLY,
+// COLLISION_PACKET) can be told apart from RF issues (e.g. TX_FREQ, TX_POWER).
+pub fn record_mesh_tx_error(payload_type: &str, frequency: u32, status: gw::TxAckStatus) {
+    let status = status.as_str_name().to_lowercase();
+
+    MESH_TX_ERROR_COUNT
+        .get_or_create(&TxErrorLabels {
+            payload_type: payload_type.to_string(),
+            frequency,
+            status: status.clone(),
+        })
+        .inc();
+
+    *MESH_TX_ERRORS.lock().unwrap().entry(status).or_insert(0) += 1;
+}
+
+// Adds the number of failed mesh transmissions per TxAck status since the previous stats to the
+// metadata of the given gateway stats.
+pub fn add_mesh_tx_error_stats(pl: &mut gw::GatewayStats) {
+    let mut tx_errors = MESH_TX_ERRORS.lock().unwrap();
+
+    for (status, count) in tx_errors.drain() {
+        pl.metadata
+            .insert(format!("mesh_tx_error_{}", status), count.to_string());
+    }
+}
+
+// Records the airtime of a mesh transmission.
+pub fn record_mesh_tx_airtime(airtime: Duration) {
+    let mut tx_airtime = MESH_TX_AIRTIME.lock().unwrap();
+    tx_airtime.push_back((Instant::now(), airtime));
+    prune_mesh_tx_airtime(&mut tx_airtime);
+}
+
+// Returns the airtime of the mesh transmissions within the airtime window.
+pub fn get_mesh_tx_airtime() -> Duration {
+    let mut tx_airtime = MESH_TX_AIRTIME.lock().unwrap();
+    prune_mesh_tx_airtime(&mut tx_airtime);
+    tx_airtime.iter().map(|(_, airtime)| *airtime).sum()
+}
+
+// Records the airtime of a mesh transmission or reception on the given frequency.
+pub fn record_mesh_channel_occupancy(window: Duration, frequency: u32, airtime: Duration) {
+    let now = Instant::now();
+    let mut occupancy = MESH_CHANNEL_OCCUPANCY.lock().unwrap();
+    occupancy
+        .entry(frequency)
+        .or_default()
+        .push_back((now, airtime));
+    prune_mesh_channel_occupancy(&mut occupancy, now, window);
+}
+
+// Returns the airtime of the mesh transmissions and receptions on the given frequency within
+// the given window.
+pub fn get_mesh_channel_occupancy(window: Duration, frequency: u32) -> Duration {
+    let now = Instant::now();
+    let mut occupancy = MESH_CHANNEL_OCCUPANCY.lock().unwrap();
+    prune_mesh_channel_occupancy(&mut occupancy, now, window);
+    occupancy
+        .get(&frequency)
+        .map(|v| v.iter().map(|(_, airtime)| *airtime).sum())
+        .unwrap_or_default()
+}
+
+fn prune_mesh_channel_occupancy(occupancy: &mut ChannelOccupancy, now: Instant, window: Duration) {
+    for v in occupancy.values_mut() {
+        while v
+            .front()
+            .map(|(ts, _)| now.saturating_duration_since(*ts) > window)
+            .unwrap_or_default()
+        {
+            v.pop_front();
+        }
+    }
+    occupancy.retain(|_, v| !v.is_empty());
+}
+
+// Returns the given airtime as percentage of the airtime window.
+pub fn get_airtime_pct(airtime: Duration) -> f32 {
+    airtime.as_secs_f32() / MESH_TX_AIRTIME_WINDOW.as_secs_f32() * 100.0
+}
+
+// Records a mesh packet that was dropped for the given reason.
+pub fn record_mesh_drop(reason: &str) {
+    MESH_DROP_COUNT
+        .get_or_create(&DropLabels {
+            reason: reason.to_string(),
+        })
+        .inc();
+}
+
+// Records an error that occurred while handling an event.
+pub fn record_error(e: &Error) {
+    ERROR_COUNT
+        .get_or_create(&ErrorLabels {
+            kind: e.kind().to_string(),
+        })
+        .inc();
+}
+
+// Returns the number of drops with the given reason that were not logged since the previous
+// log, if the drop must be logged. Drops that are part of normal mesh operation (e.g. the
+// max hop count being exceeded at the mesh edge) are logged at most once per interval per
+// reason, as they are already counted by the mesh_drop_count metric.
+pub fn mesh_drop_log_allowed(reason: &str) -> Option<u64> {
+    let mut drop_log = MESH_DROP_LOG.lock().unwrap();
+
+    if let Some(log) = drop_log.get_mut(reason) {
+        if log.logged_at.elapsed() < MESH_DROP_LOG_INTERVAL {
+            log.suppressed += 1;
+            return None;
+        }
+
+        let suppressed = log.suppressed;
+        log.logged_at = Instant::now();
+        log.suppressed = 0;
+        return Some(suppressed);
+    }
+
+    drop_log.insert(
+        reason.to_string(),
+        DropLog {
+            logged_at: Instant::now(),
+            suppressed: 0,
+        },
+    );
+    Some(0)
+}
+
+// Records the result of a mesh transmission on the given frequency.
+pub fn record_mesh_tx(frequency: u32, ok: bool) {
+    record_mesh_channel_sample(frequency, ok);
+}
+
+// Returns true if the given mesh frequency must be temporarily excluded from the TX rotation,
+// because its failure rate exceeded the configured threshold. After the probe interval, the
+// frequency is included again and its failure rate is re-evaluated.
+pub fn is_mesh_channel_excluded(conf: &config::BadChannelAvoidance, frequency: u32) -> bool {
+    if !conf.enabled {
+        return false;
+    }
+
+    let mut channel_quality = MESH_CHANNEL_QUALITY.lock().unwrap();
+    let quality = channel_quality.entry(frequency).or_default();
+
+    if let Some(excluded_until) = quality.excluded_until {
+        if Instant::now() < excluded_until {
+            return true;
+        }
+
+        info!(
+            "Probing previously excluded mesh frequency, frequency: {}",
+            frequency
+        );
+        quality.excluded_until = None;
+        quality.reset();
+        return false;
+    }
+
+    if quality.sample_count < conf.min_samples.max(1) {
+        return false;
+    }
+
+    let failure_ratio = quality.failure_ratio();
+    quality.reset();
+
+    if failure_ratio >= conf.failure_threshold {
+        warn!(
+            "Excluding mesh frequency because of failure rate, frequency: {}, failure_ratio: {:.3}, probe_interval: {:?}",
+            frequency, failure_ratio, conf.probe_interval
+        );
+        quality.excluded_until = Some(Instant::now() + conf.probe_interval);
+        return true;
+    }
+
+    false
+}
+
+fn prune_mesh_tx_airtime(tx_airtime: &mut VecDeque<(Instant, Duration)>) {
+    while let Some((sent_at, _)) = tx_airtime.front() {
+        if sent_at.elapsed() < MESH_TX_AIRTIME_WINDOW {
+            break;
+        }
+        tx_airtime.pop_front();
+    }
+}
+
+fn record_mesh_channel_sample(frequency: u32, ok: bool) {
+    let mut channel_quality = MESH_CHANNEL_QUALITY.lock().unwrap();
+    channel_quality.entry(frequency).or_default().record(ok);
+}
+
+fn new_histogram() -> Histogram {
+    Histogram::new(linear_buckets(-140.0, 10.0, 14))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_add_mesh_rx_stats() {
+        let tx_info = gw::UplinkTxInfo {
+            frequency: 868100000,
+            ..Default::default()
+        };
+
+        for (crc_status, rssi) in [
+            (gw::CrcStatus::CrcOk, -80),
+            (gw::CrcStatus::CrcOk, -90),
+            (gw::CrcStatus::BadCrc, -120),
+            (gw::CrcStatus::CrcOk, -100),
+        ] {
+            record_mesh_rx(
+                &gw::UplinkRxInfo {
+                    crc_status: crc_status.into(),
+                    rssi,
+                    ..Default::default()
+                },
+                &tx_info,
+            );
+        }
+
+        let mut pl = gw::GatewayStats::default();
+        add_mesh_rx_stats(&mut pl);
+
+        assert_eq!(
+            [
+                ("mesh_rx_count_868100000".to_string(), "4".to_string()),
+                (
+                    "mesh_rx_crc_error_ratio_868100000".to_string(),
+                    "0.250".to_string()
+                ),
+                (
+                    "mesh_rx_rssi_avg_868100000".to_string(),
+                    "-90.0".to_string()
+                ),
+            ]
+            .iter()
+            .cloned()
+            .collect::<HashMap<String, String>>(),
+            pl.metadata
+        );
+
+        // The stats must be reset.
+        let mut pl = gw::GatewayStats::default();
+        add_mesh_rx_stats(&mut pl);
+        assert!(pl.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_is_mesh_channel_excluded() {
+        let frequency = 868300000;
+        let mut conf = config::BadChannelAvoidance {
+            enabled: true,
+            failure_threshold: 0.5,
+            min_samples: 4,
+            probe_interval: std::time::Duration::from_secs(60),
+        };
+
+        // Not enough samples.
+        record_mesh_tx(frequency, false);
+        record_mesh_tx(frequency, false);
+        record_mesh_tx(frequency, true);
+        assert!(!is_mesh_channel_excluded(&conf, frequency));
+
+        // Failure ratio 0.75 >= threshold.
+        record_mesh_tx(frequency, false);
+        assert!(is_mesh_channel_excluded(&conf, frequency));
+        assert!(is_mesh_channel_excluded(&conf, frequency));
+
+        // Expire the exclusion, the frequency is probed again.
+        MESH_CHANNEL_QUALITY
+            .lock()
+            .unwrap()
+            .get_mut(&frequency)
+            .unwrap()
+            .excluded_until = Some(Instant::now());
+        assert!(!is_mesh_channel_excluded(&conf, frequency));
+
+        // Failure ratio 0.25 < threshold.
+        record_mesh_tx(frequency, true);
+        record_mesh_tx(frequency, true);
+        record_mesh_tx(frequency, true);
+        record_mesh_tx(frequency, false);
+        assert!(!is_mesh_channel_excluded(&conf, frequency));
+
+        // Disabled.
+        conf.enabled = false;
+        for _ in 0..4 {
+            record_mesh_tx(frequency, false);
+        }
+        assert!(!is_mesh_channel_excluded(&conf, frequency));
+    }
+
+    #[test]
+    fn test_add_mesh_concentratord_stats() {
+        record_mesh_concentratord_connected(true);
+        record_mesh_concentratord_stats(gw::GatewayStats {
+            rx_packets_received: 10,
+            rx_packets_received_ok: 8,
+            tx_packets_emitted: 5,
+            metadata: [("temperature".to_string(), "45.5".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            ..Default::default()
+        });
+
+        let mut pl = gw::GatewayStats::default();
+        add_mesh_concentratord_stats(&mut pl);
+
+        assert_eq!(
+            [
+                ("mesh_concentratord_connected", "true"),
+                ("mesh_concentratord_rx_packets_received", "10"),
+                ("mesh_concentratord_rx_packets_received_ok", "8"),
+                ("mesh_concentratord_tx_packets_emitted", "5"),
+                ("mesh_concentratord_temperature", "45.5"),
+            ]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<String, String>>(),
+            pl.metadata
+        );
+
+        // The stats must be reset.
+        record_mesh_concentratord_connected(false);
+        let mut pl = gw::GatewayStats::default();
+        add_mesh_concentratord_stats(&mut pl);
+        assert_eq!(
+            [("mesh_concentratord_connected", "false")]
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<String, String>>(),
+            pl.metadata
+        );
+    }
+
+    #[test]
+    fn test_relay_stats() {
+        let pl = gw::GatewayStats {
+            gateway_id: "0102030405060708".into(),
+            rx_packets_received: 2,
+            rx_packets_received_ok: 1,
+            tx_packets_received: 1,
+            tx_packets_emitted: 1,
+            rx_packets_per_frequency: [(868100000, 2)].into(),
+            tx_packets_per_frequency: [(869525000, 1)].into(),
+            ..Default::default()
+        };
+        record_relay_stats(&pl);
+        record_relay_stats(&pl);
+
+        assert_eq!(
+            gw::GatewayStats {
+                gateway_id: "0102030405060708".into(),
+                rx_packets_received: 4,
+                rx_packets_received_ok: 2,
+                tx_packets_received: 2,
+                tx_packets_emitted: 2,
+                rx_packets_per_frequency: [(868100000, 4)].into(),
+                tx_packets_per_frequency: [(869525000, 2)].into(),
+                ..Default::default()
+            },
+            take_relay_stats()
+        );
+        assert_eq!(gw::GatewayStats::default(), take_relay_stats());
+    }
+
+    #[test]
+    fn test_record_relay_config_mismatch() {
+        let relay_id = [1, 2, 3, 4];
+        assert!(!record_relay_config_mismatch(relay_id, false));
+        assert!(record_relay_config_mismatch(relay_id, true));
+        assert!(!record_relay_config_mismatch(relay_id, true));
+        assert!(record_relay_config_mismatch(relay_id, false));
+    }
+
+    #[test]
+    fn test_mesh_tx_airtime() {
+        record_mesh_tx_airtime(Duration::from_secs(18));
+        record_mesh_tx_airtime(Duration::from_secs(18));
+        assert_eq!(Duration::from_secs(36), get_mesh_tx_airtime());
+        assert_eq!(1.0, get_airtime_pct(get_mesh_tx_airtime()));
+
+        let mut pl = gw::GatewayStats::default();
+        add_mesh_tx_airtime_stats(&mut pl);
+        assert_eq!(
+            Some(&"1.00".to_string()),
+            pl.metadata.get("mesh_tx_airtime_pct")
+        );
+
+        // Transmissions outside the airtime window are excluded.
+        MESH_TX_AIRTIME.lock().unwrap().front_mut().unwrap().0 -= MESH_TX_AIRTIME_WINDOW;
+        assert_eq!(Duration::from_secs(18), get_mesh_tx_airtime());
+    }
+
+    #[test]
+    fn test_mesh_channel_occupancy() {
+        let window = Duration::from_secs(600);
+
+        record_mesh_channel_occupancy(window, 867100000, Duration::from_secs(1));
+        record_mesh_channel_occupancy(window, 867100000, Duration::from_secs(2));
+        record_mesh_channel_occupancy(window, 867300000, Duration::from_secs(1));
+        assert_eq!(
+            Duration::from_secs(3),
+            get_mesh_channel_occupancy(window, 867100000)
+        );
+        assert_eq!(
+            Duration::from_secs(1),
+            get_mesh_channel_occupancy(window, 867300000)
+        );
+        assert_eq!(
+            Duration::ZERO,
+            get_mesh_channel_occupancy(window, 867500000)
+        );
+
+        // Airtime outside the window is excluded.
+        MESH_CHANNEL_OCCUPANCY
+            .lock()
+            .unwrap()
+            .get_mut(&867100000)
+            .unwrap()
+            .front_mut()
+            .unwrap()
+            .0 -= window * 2;
+        assert_eq!(
+            Duration::from_secs(2),
+            get_mesh_channel_occupancy(window, 867100000)
+        );
+    }
+
+    #[test]
+    fn test_add_mesh_tx_error_stats() {
+        record_mesh_tx_error("uplink", 868100000, gw::TxAckStatus::CollisionPacket);
+        record_mesh_tx_error("heartbeat", 868300000, gw::TxAckStatus::CollisionPacket);
+        record_mesh_tx_error("uplink", 868100000, gw::TxAckStatus::TooEarly);
+
+        let mut pl = gw::GatewayStats::default();
+        add_mesh_tx_error_stats(&mut pl);
+        assert_eq!(
+            Some(&"2".to_string()),
+            pl.metadata.get("mesh_tx_error_collision_packet")
+        );
+        assert_eq!(
+            Some(&"1".to_string()),
+            pl.metadata.get("mesh_tx_error_too_early")
+        );
+
+        // The counters are reset after being added to the stats.
+        let mut pl = gw::GatewayStats::default();
+        add_mesh_tx_error_stats(&mut pl);
+        assert!(pl.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_mesh_drop_log_allowed() {
+        assert_eq!(Some(0), mesh_drop_log_allowed("test_drop"));
+        assert_eq!(None, mesh_drop_log_allowed("test_drop"));
+        assert_eq!(None, mesh_drop_log_allowed("test_drop"));
+        assert_eq!(Some(0), mesh_drop_log_allowed("test_drop_other"));
+
+        // Once the interval has passed, the suppressed drops are returned.
+        MESH_DROP_LOG
+            .lock()
+            .unwrap()
+            .get_mut("test_drop")
+            .unwrap()
+            .logged_at -= MESH_DROP_LOG_INTERVAL;
+        assert_eq!(Some(2), mesh_drop_log_allowed("test_drop"));
+        assert_eq!(None, mesh_drop_log_allowed("test_drop"));
+    }
+}
@@ -0,0 +1,51 @@
+use std::thread;
+
+use anyhow::{anyhow, Error};
+use log::error;
+use once_cell::sync::Lazy;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::error::Result;
+
+// Errors of stopped threads. The threads are expected to run for the lifetime of the process,
+// thus any thread that stops is reported as an error.
+static THREAD_ERRORS: Lazy<(
+    mpsc::UnboundedSender<Error>,
+    Mutex<mpsc::UnboundedReceiver<Error>>,
+)> = Lazy::new(|| {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (tx, Mutex::new(rx))
+});
+
+// Spawns the given function to a dedicated thread. When the function returns, the error (if
+// any) is reported to the supervisor.
+pub fn spawn_thread<F>(name: &str, f: F)
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    let name = name.to_string();
+
+    thread::spawn(move || {
+        let err = match f() {
+            Ok(_) => anyhow!("{} thread has stopped", name),
+            Err(e) => Error::from(e).context(format!("{} thread error", name)),
+        };
+
+        error!("{:#}", err);
+        report(err);
+    });
+}
+
+// Reports the given error to the supervisor, e.g. when the process is in a state from which it
+// can't recover.
+pub fn report(err: Error) {
+    let _ = THREAD_ERRORS.0.send(err);
+}
+
+// Waits until a thread has stopped and returns its error.
+pub async fn wait() -> Error {
+    let mut rx = THREAD_ERRORS.1.lock().await;
+    rx.recv()
+        .await
+        .unwrap_or_else(|| anyhow!("Thread error channel has been closed"))
+}
@@ -0,0 +1,233 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+use tokio::time::sleep;
+
+use crate::config::Configuration;
+use crate::error::Result;
+use crate::{events, packets, topology};
+
+// Max. number of Relay IDs that fit in a single beacon event.
+const MAX_ASSIGNED_SLOTS: usize = (packets::EVENT_MAX_SIZE - 3) / 4;
+
+static SCHEDULE: Mutex<Option<Schedule>> = Mutex::new(None);
+
+#[derive(Clone, Debug, PartialEq)]
+struct Schedule {
+    // Start of the frame, this is the time the beacon was received.
+    frame_start: Instant,
+    slot_duration: Duration,
+    slot_count: u32,
+    // The slot(s) in which this relay is allowed to transmit. Empty if any time is allowed.
+    slots: Vec<u32>,
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    // Only the Border Gateway sends TDMA beacons.
+    if !conf.mesh.border_gateway || !conf.mesh.tdma.enabled {
+        return Ok(());
+    }
+
+    info!(
+        "Starting TDMA beacon loop, beacon_interval: {:?}, slot_duration: {:?}",
+        conf.mesh.tdma.beacon_interval, conf.mesh.tdma.slot_duration
+    );
+
+    tokio::spawn({
+        let beacon_interval = conf.mesh.tdma.beacon_interval;
+        let slot_duration = conf.mesh.tdma.slot_duration;
+        let free_slots = conf.mesh.tdma.free_slots;
+
+        async move {
+            loop {
+                if let Err(e) =
+                    events::send_tdma_beacon(get_beacon(slot_duration, free_slots)).await
+                {
+                    error!("Send TDMA beacon error, error: {}", e);
+                }
+                sleep(beacon_interval).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Returns the beacon, assigning a slot to each Relay Gateway known by the Border Gateway.
+fn get_beacon(slot_duration: Duration, free_slots: u8) -> packets::TdmaBeaconEvent {
+    let mut relay_ids: Vec<[u8; 4]> = topology::get_relays().iter().map(|v| v.relay_id).collect();
+    relay_ids.sort();
+    relay_ids.truncate(MAX_ASSIGNED_SLOTS.min((u8::MAX - free_slots).into()));
+
+    packets::TdmaBeaconEvent {
+        slot_duration: slot_duration.as_millis().try_into().unwrap_or(u16::MAX),
+        slot_count: relay_ids.len() as u8 + free_slots,
+        relay_ids,
+    }
+}
+
+// Updates the schedule from the received beacon (Relay Gateway only).
+pub fn record_beacon(pl: &packets::TdmaBeaconEvent, relay_id: [u8; 4]) {
+    let schedule = get_schedule(pl, relay_id, Instant::now());
+    info!(
+        "TDMA schedule updated, slot_duration: {:?}, slot_count: {}, slots: {:?}",
+        schedule.slot_duration, schedule.slot_count, schedule.slots
+    );
+    *SCHEDULE.lock().unwrap() = Some(schedule);
+}
+
+fn get_schedule(pl: &packets::TdmaBeaconEvent, relay_id: [u8; 4], now: Instant) -> Schedule {
+    let slot_count: u32 = pl.slot_count.into();
+    let assigned = pl.relay_ids.len() as u32;
+
+    let slots = if let Some(i) = pl.relay_ids.iter().position(|v| *v == relay_id) {
+        vec![i as u32]
+    } else {
+        // Relays without assigned slot share the free slots.
+        (assigned..slot_count).collect()
+    };
+
+    Schedule {
+        frame_start: now,
+        slot_duration: Duration::from_millis(pl.slot_duration.into()),
+        slot_count,
+        slots,
+    }
+}
+
+// Returns the duration to wait, before a transmission with the given airtime fits within
+// one of the slots of this Relay Gateway. This returns zero when no schedule is known.
+pub fn get_tx_delay(airtime: Duration) -> Duration {
+    match SCHEDULE.lock().unwrap().as_ref() {
+        Some(v) => get_tx_delay_for_schedule(v, airtime, Instant::now()),
+        None => Duration::ZERO,
+    }
+}
+
+fn get_tx_delay_for_schedule(schedule: &Schedule, airtime: Duration, now: Instant) -> Duration {
+    if schedule.slots.is_empty() || schedule.slot_duration.is_zero() {
+        return Duration::ZERO;
+    }
+
+    let frame_duration = schedule.slot_duration * schedule.slot_count;
+    let frame_offset = Duration::from_nanos(
+        (now.duration_since(schedule.frame_start).as_nanos() % frame_duration.as_nanos()) as u64,
+    );
+
+    // A transmission that exceeds the slot duration is sent at the start of the slot.
+    let latest_start = schedule.slot_duration.saturating_sub(airtime);
+
+    schedule
+        .slots
+        .iter()
+        .map(|slot| {
+            let slot_start = schedule.slot_duration * *slot;
+            if frame_offset >= slot_start && frame_offset <= slot_start + latest_start {
+                Duration::ZERO
+            } else if frame_offset < slot_start {
+                slot_start - frame_offset
+            } else {
+                frame_duration - frame_offset + slot_start
+            }
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_schedule() {
+        let now = Instant::now();
+        let beacon = packets::TdmaBeaconEvent {
+            slot_duration: 500,
+            slot_count: 4,
+            relay_ids: vec![[1, 1, 1, 1], [2, 2, 2, 2]],
+        };
+
+        assert_eq!(vec![1], get_schedule(&beacon, [2, 2, 2, 2], now).slots);
+        assert_eq!(vec![2, 3], get_schedule(&beacon, [3, 3, 3, 3], now).slots);
+    }
+
+    #[test]
+    fn test_get_tx_delay_for_schedule() {
+        struct Test {
+            name: String,
+            slots: Vec<u32>,
+            elapsed: Duration,
+            airtime: Duration,
+            expected: Duration,
+        }
+
+        let tests = vec![
+            Test {
+                name: "no slots".into(),
+                slots: vec![],
+                elapsed: Duration::from_millis(100),
+                airtime: Duration::from_millis(100),
+                expected: Duration::ZERO,
+            },
+            Test {
+                name: "within slot".into(),
+                slots: vec![0],
+                elapsed: Duration::from_millis(100),
+                airtime: Duration::from_millis(100),
+                expected: Duration::ZERO,
+            },
+            Test {
+                name: "within slot, next frame".into(),
+                slots: vec![1],
+                elapsed: Duration::from_millis(2600),
+                airtime: Duration::from_millis(100),
+                expected: Duration::ZERO,
+            },
+            Test {
+                name: "airtime exceeds remaining slot time".into(),
+                slots: vec![0],
+                elapsed: Duration::from_millis(450),
+                airtime: Duration::from_millis(100),
+                expected: Duration::from_millis(1550),
+            },
+            Test {
+                name: "before slot".into(),
+                slots: vec![2],
+                elapsed: Duration::from_millis(100),
+                airtime: Duration::from_millis(100),
+                expected: Duration::from_millis(900),
+            },
+            Test {
+                name: "nearest of multiple slots".into(),
+                slots: vec![0, 3],
+                elapsed: Duration::from_millis(600),
+                airtime: Duration::from_millis(100),
+                expected: Duration::from_millis(900),
+            },
+            Test {
+                name: "airtime exceeds slot duration".into(),
+                slots: vec![1],
+                elapsed: Duration::from_millis(400),
+                airtime: Duration::from_millis(800),
+                expected: Duration::from_millis(100),
+            },
+        ];
+
+        let frame_start = Instant::now();
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            let schedule = Schedule {
+                frame_start,
+                slot_duration: Duration::from_millis(500),
+                slot_count: 4,
+                slots: tst.slots.clone(),
+            };
+            assert_eq!(
+                tst.expected,
+                get_tx_delay_for_schedule(&schedule, tst.airtime, frame_start + tst.elapsed)
+            );
+        }
+    }
+}
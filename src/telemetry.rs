@@ -0,0 +1,99 @@
+use std::fs;
+use std::io;
+use std::process::Command;
+
+use log::warn;
+
+use crate::config::{Configuration, TelemetrySource};
+use crate::error::{Error, Result};
+use crate::packets;
+
+// Returns the telemetry event with the configured values. Values that can not be read are
+// omitted, such that the other values are still reported.
+pub fn get(conf: &Configuration) -> packets::TelemetryEvent {
+    let telemetry = &conf.mesh.telemetry;
+
+    packets::TelemetryEvent {
+        // V to mV.
+        battery_voltage: read_value("battery_voltage", &telemetry.battery_voltage)
+            .map(|v| (v * 1000.0).round() as u16),
+        // A to mA.
+        solar_current: read_value("solar_current", &telemetry.solar_current)
+            .map(|v| (v * 1000.0).round() as u16),
+        // Degrees Celsius to 0.1 degrees Celsius.
+        temperature: read_value("temperature", &telemetry.temperature)
+            .map(|v| (v * 10.0).round() as i16),
+    }
+}
+
+fn read_value(name: &str, source: &TelemetrySource) -> Option<f64> {
+    if !source.is_configured() {
+        return None;
+    }
+
+    match read(source) {
+        Ok(v) => Some(v * source.scale),
+        Err(e) => {
+            warn!("Read telemetry value error, value: {}, error: {}", name, e);
+            None
+        }
+    }
+}
+
+fn read(source: &TelemetrySource) -> Result<f64> {
+    let out = if !source.path.is_empty() {
+        fs::read_to_string(&source.path)?
+    } else {
+        let out = Command::new("sh").arg("-c").arg(&source.command).output()?;
+        if !out.status.success() {
+            return Err(Error::Io(io::Error::other(format!(
+                "Command exited with {}: {}",
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            ))));
+        }
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+
+    out.trim()
+        .parse()
+        .map_err(|_| Error::InvalidMessage(format!("Invalid telemetry value: {}", out.trim())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let path = std::env::temp_dir().join("chirpstack-gateway-mesh-telemetry-test");
+        fs::write(&path, "45123\n").unwrap();
+
+        let mut conf = Configuration::default();
+        conf.mesh.telemetry.battery_voltage = TelemetrySource {
+            command: "echo 12.6".into(),
+            ..Default::default()
+        };
+        conf.mesh.telemetry.solar_current = TelemetrySource {
+            command: "exit 1".into(),
+            ..Default::default()
+        };
+        conf.mesh.telemetry.temperature = TelemetrySource {
+            path: path.to_str().unwrap().into(),
+            scale: 0.001,
+            ..Default::default()
+        };
+
+        let event = get(&conf);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            packets::TelemetryEvent {
+                battery_voltage: Some(12600),
+                solar_current: None,
+                temperature: Some(451),
+            },
+            event
+        );
+    }
+}
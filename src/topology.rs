@@ -0,0 +1,948 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use log::{error, info, trace};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+use crate::config::{self, Configuration};
+use crate::error::Result;
+use crate::{events, helpers, linkquality, mqtt, packets, stats, webhook};
+
+static RELAYS: Lazy<Mutex<HashMap<[u8; 4], Relay>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Relay {
+    #[serde(with = "hex")]
+    pub relay_id: [u8; 4],
+    #[serde(with = "humantime_serde")]
+    pub last_seen_at: SystemTime,
+    pub hop_count: u8,
+    // RSSI and SNR of the last hop, as received by the Border Gateway.
+    pub rssi: i32,
+    pub snr: f32,
+    pub relay_path: Vec<RelayPath>,
+    // Set for entries loaded from the state file, until refreshed by a heartbeat.
+    pub stale: bool,
+    // Last reported attestation.
+    #[serde(default)]
+    pub attestation: Option<Attestation>,
+    // Last reported mesh TX airtime.
+    #[serde(default)]
+    pub tx_airtime: Option<TxAirtime>,
+    // Last reported uplink ACK statistics.
+    #[serde(default)]
+    pub uplink_acks: Option<UplinkAcks>,
+    // Set when the Relay Gateway reports not receiving the uplink ACKs of the Border Gateway,
+    // while the Border Gateway does receive its uplinks.
+    #[serde(default)]
+    pub asymmetric_link: bool,
+    // Set when the Relay Gateway announced its shutdown, until it is seen again.
+    #[serde(default)]
+    pub shutdown: bool,
+    // Last reported startup.
+    #[serde(default)]
+    pub boot: Option<Boot>,
+    // Last reported location.
+    #[serde(default)]
+    pub location: Option<Location>,
+    // Last reported power telemetry.
+    #[serde(default)]
+    pub telemetry: Option<Telemetry>,
+    // Set when the Relay Gateway was seen, but did not answer the last challenge of the Border
+    // Gateway, until it answers a challenge.
+    #[serde(default)]
+    pub challenge_failed: bool,
+    // Set while a maintenance window of the Relay Gateway is active, during which it is expected to
+    // be silent. This is not persisted.
+    #[serde(default, skip_deserializing)]
+    pub maintenance: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Attestation {
+    #[serde(with = "humantime_serde")]
+    pub reported_at: SystemTime,
+    #[serde(with = "hex")]
+    pub config_checksum: [u8; 4],
+    pub features: Vec<String>,
+    pub version: String,
+    pub firmware_version: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Boot {
+    #[serde(with = "humantime_serde")]
+    pub reported_at: SystemTime,
+    // Reason of the (re)start (power_cycle, restart or crash_recovery).
+    pub reason: String,
+    pub features: Vec<String>,
+    pub version: String,
+    // Summary of the panic preceding a crash recovery (if reported).
+    #[serde(default)]
+    pub crash: Option<Crash>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Crash {
+    #[serde(with = "hex")]
+    pub panic_hash: [u8; 4],
+    #[serde(with = "humantime_serde")]
+    pub uptime: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Location {
+    #[serde(with = "humantime_serde")]
+    pub reported_at: SystemTime,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Telemetry {
+    #[serde(with = "humantime_serde")]
+    pub reported_at: SystemTime,
+    // Battery voltage (V).
+    pub battery_voltage: Option<f32>,
+    // Solar current (A).
+    pub solar_current: Option<f32>,
+    // Temperature (degrees Celsius).
+    pub temperature: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TxAirtime {
+    #[serde(with = "humantime_serde")]
+    pub reported_at: SystemTime,
+    // Mesh TX airtime (percentage) during the past hour.
+    pub airtime_pct: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UplinkAcks {
+    #[serde(with = "humantime_serde")]
+    pub reported_at: SystemTime,
+    // Relayed uplinks acknowledged, since the previous report.
+    pub acked: u16,
+    // Relayed uplinks not acknowledged (after all retransmissions), since the previous report.
+    pub not_acked: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RelayPath {
+    #[serde(with = "hex")]
+    pub relay_id: [u8; 4],
+    pub rssi: i32,
+    pub snr: f32,
+}
+
+// Link between two nodes of the mesh, derived from the relay path of the heartbeats. The RSSI and
+// SNR are as measured by the receiving node. A to of None is the Border Gateway.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub from: [u8; 4],
+    pub to: Option<[u8; 4]>,
+    pub rssi: i32,
+    pub snr: f32,
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    // Only the Border Gateway receives the heartbeats of all Relay Gateways.
+    if !conf.mesh.border_gateway || conf.topology.state_file.is_empty() {
+        return Ok(());
+    }
+
+    if Path::new(&conf.topology.state_file).exists() {
+        info!(
+            "Loading topology state, state_file: {}",
+            conf.topology.state_file
+        );
+        load(&conf.topology.state_file)?;
+    }
+
+    if conf.topology.persist_interval.is_zero() {
+        return Ok(());
+    }
+
+    info!(
+        "Starting topology persist loop, state_file: {}, persist_interval: {:?}",
+        conf.topology.state_file, conf.topology.persist_interval
+    );
+
+    tokio::spawn({
+        let state_file = conf.topology.state_file.clone();
+        let persist_interval = conf.topology.persist_interval;
+
+        async move {
+            loop {
+                sleep(persist_interval).await;
+                if let Err(e) = save(&state_file) {
+                    error!("Persist topology state error, error: {}", e);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Persists the topology state to the configured state_file (if set).
+pub fn persist() -> Result<()> {
+    let conf = config::get();
+    if !conf.mesh.border_gateway || conf.topology.state_file.is_empty() {
+        return Ok(());
+    }
+
+    save(&conf.topology.state_file)
+}
+
+pub fn record_heartbeat(pl: &packets::HeartbeatPayload, hop_count: u8, rssi: i32, snr: f32) {
+    let relay = Relay {
+        relay_id: pl.relay_id,
+        // humantime_serde can't serialize a time before the UNIX epoch.
+        last_seen_at: helpers::system_time_now(),
+        hop_count,
+        rssi,
+        snr,
+        relay_path: pl
+            .relay_path
+            .iter()
+            .map(|v| RelayPath {
+                relay_id: v.relay_id,
+                rssi: v.rssi.into(),
+                snr: v.snr.into(),
+            })
+            .collect(),
+        stale: false,
+        attestation: None,
+        tx_airtime: None,
+        uplink_acks: None,
+        asymmetric_link: false,
+        shutdown: false,
+        boot: None,
+        location: None,
+        telemetry: None,
+        challenge_failed: false,
+        maintenance: false,
+    };
+
+    trace!(
+        "Recording relay heartbeat, relay_id: {}",
+        hex::encode(relay.relay_id)
+    );
+    linkquality::record(relay.relay_id, hop_count, rssi, snr);
+
+    // The reported events are not part of the heartbeat and must be preserved.
+    let mut relays = RELAYS.lock().unwrap();
+    let prev = relays.get(&relay.relay_id);
+    let changed = prev.map(|v| topology_changed(v, &relay)).unwrap_or(true);
+    let relay = match prev {
+        Some(prev) => Relay {
+            attestation: prev.attestation.clone(),
+            tx_airtime: prev.tx_airtime.clone(),
+            uplink_acks: prev.uplink_acks.clone(),
+            asymmetric_link: prev.asymmetric_link,
+            boot: prev.boot.clone(),
+            location: prev.location.clone(),
+            telemetry: prev.telemetry.clone(),
+            challenge_failed: prev.challenge_failed,
+            ..relay
+        },
+        None => relay,
+    };
+    relays.insert(relay.relay_id, relay.clone());
+
+    mqtt::publish_heartbeat(&relay);
+    webhook::send(webhook::Event::Heartbeat(relay.clone()));
+    if changed {
+        webhook::send(webhook::Event::Topology(relay));
+    }
+}
+
+pub fn record_attestation(
+    relay_id: [u8; 4],
+    pl: &packets::AttestationEvent,
+    hop_count: u8,
+    rssi: i32,
+    snr: f32,
+) {
+    trace!(
+        "Recording relay attestation, relay_id: {}",
+        hex::encode(relay_id)
+    );
+
+    record_event(relay_id, hop_count, rssi, snr, |relay, now| {
+        relay.attestation = Some(Attestation {
+            reported_at: now,
+            config_checksum: pl.config_checksum,
+            features: events::get_feature_names(pl.features),
+            version: pl.version.clone(),
+            firmware_version: pl.firmware_version.clone(),
+        });
+    });
+}
+
+pub fn record_boot(relay_id: [u8; 4], pl: &packets::BootEvent, hop_count: u8, rssi: i32, snr: f32) {
+    trace!("Recording relay boot, relay_id: {}", hex::encode(relay_id));
+
+    record_event(relay_id, hop_count, rssi, snr, |relay, now| {
+        relay.boot = Some(Boot {
+            reported_at: now,
+            reason: get_boot_reason_name(pl.reason).to_string(),
+            features: events::get_feature_names(pl.features),
+            version: pl.version.clone(),
+            crash: pl.crash.as_ref().map(|v| Crash {
+                panic_hash: v.panic_hash,
+                uptime: Duration::from_secs(v.uptime.into()),
+            }),
+        });
+    });
+}
+
+pub fn record_location(
+    relay_id: [u8; 4],
+    pl: &packets::LocationEvent,
+    hop_count: u8,
+    rssi: i32,
+    snr: f32,
+) {
+    trace!(
+        "Recording relay location, relay_id: {}",
+        hex::encode(relay_id)
+    );
+
+    record_event(relay_id, hop_count, rssi, snr, |relay, now| {
+        relay.location = Some(Location {
+            reported_at: now,
+            latitude: f64::from(pl.latitude) / 1e7,
+            longitude: f64::from(pl.longitude) / 1e7,
+            altitude: pl.altitude.into(),
+        });
+    });
+}
+
+pub fn record_telemetry(
+    relay_id: [u8; 4],
+    pl: &packets::TelemetryEvent,
+    hop_count: u8,
+    rssi: i32,
+    snr: f32,
+) {
+    trace!(
+        "Recording relay telemetry, relay_id: {}",
+        hex::encode(relay_id)
+    );
+
+    record_event(relay_id, hop_count, rssi, snr, |relay, now| {
+        relay.telemetry = Some(Telemetry {
+            reported_at: now,
+            battery_voltage: pl.battery_voltage.map(|v| f32::from(v) / 1000.0),
+            solar_current: pl.solar_current.map(|v| f32::from(v) / 1000.0),
+            temperature: pl.temperature.map(|v| f32::from(v) / 10.0),
+        });
+    });
+}
+
+pub fn record_tx_airtime(
+    relay_id: [u8; 4],
+    pl: &packets::TxAirtimeEvent,
+    hop_count: u8,
+    rssi: i32,
+    snr: f32,
+) {
+    trace!(
+        "Recording relay TX airtime, relay_id: {}",
+        hex::encode(relay_id)
+    );
+
+    record_event(relay_id, hop_count, rssi, snr, |relay, now| {
+        relay.tx_airtime = Some(TxAirtime {
+            reported_at: now,
+            airtime_pct: stats::get_airtime_pct(Duration::from_millis(pl.airtime.into())),
+        });
+    });
+}
+
+// Records the uplink ACK statistics reported by the Relay Gateway. This returns true if the
+// asymmetric_link flag of the relay has changed.
+pub fn record_uplink_acks(
+    relay_id: [u8; 4],
+    pl: &packets::StatsEvent,
+    hop_count: u8,
+    rssi: i32,
+    snr: f32,
+) -> bool {
+    trace!(
+        "Recording relay uplink ACKs, relay_id: {}",
+        hex::encode(relay_id)
+    );
+
+    let conf = config::get();
+    let mut changed = false;
+
+    record_event(relay_id, hop_count, rssi, snr, |relay, now| {
+        let uplink_acks = UplinkAcks {
+            reported_at: now,
+            acked: pl.uplinks_acked,
+            not_acked: pl.uplinks_not_acked,
+        };
+
+        // Without relayed uplinks, the previous state is kept.
+        if uplink_acks.acked != 0 || uplink_acks.not_acked != 0 {
+            let asymmetric_link =
+                is_asymmetric_link(&uplink_acks, conf.topology.asymmetric_link_ratio);
+            changed = relay.asymmetric_link != asymmetric_link;
+            relay.asymmetric_link = asymmetric_link;
+        }
+
+        relay.uplink_acks = Some(uplink_acks);
+    });
+
+    changed
+}
+
+// Records an uplink relayed by the Relay Gateway. This only updates the last-seen timestamp, such
+// that a Relay Gateway of which the heartbeats are suppressed (see heartbeat_suppress_if_active)
+// is not considered offline while it is relaying uplinks.
+pub fn record_uplink(relay_id: [u8; 4], hop_count: u8, rssi: i32, snr: f32) {
+    trace!(
+        "Recording relayed uplink, relay_id: {}",
+        hex::encode(relay_id)
+    );
+    record_event(relay_id, hop_count, rssi, snr, |_, _| {});
+}
+
+// Records the shutdown announced by the Relay Gateway. This returns the updated relay.
+pub fn record_shutdown(relay_id: [u8; 4], hop_count: u8, rssi: i32, snr: f32) -> Relay {
+    trace!(
+        "Recording relay shutdown, relay_id: {}",
+        hex::encode(relay_id)
+    );
+
+    let mut out = None;
+    record_event(relay_id, hop_count, rssi, snr, |relay, _| {
+        relay.shutdown = true;
+        out = Some(relay.clone());
+    });
+
+    // record_event always calls the given function.
+    out.unwrap()
+}
+
+// Records the challenge response of the Relay Gateway. The response is valid if it matches the
+// last challenge of the Border Gateway.
+pub fn record_challenge_response(
+    relay_id: [u8; 4],
+    valid: bool,
+    hop_count: u8,
+    rssi: i32,
+    snr: f32,
+) {
+    trace!(
+        "Recording relay challenge response, relay_id: {}, valid: {}",
+        hex::encode(relay_id),
+        valid
+    );
+
+    record_event(relay_id, hop_count, rssi, snr, |relay, _| {
+        relay.challenge_failed = !valid;
+    });
+}
+
+// Flags the Relay Gateway as not having answered the last challenge. This does not update the
+// last-seen timestamp.
+pub fn record_challenge_failed(relay_id: [u8; 4]) {
+    if let Some(relay) = RELAYS.lock().unwrap().get_mut(&relay_id) {
+        relay.challenge_failed = true;
+    }
+}
+
+pub fn get_relays() -> Vec<Relay> {
+    let relays = RELAYS.lock().unwrap();
+    let mut out: Vec<Relay> = relays.values().cloned().collect();
+    out.sort_by_key(|v| v.relay_id);
+    out
+}
+
+// Returns true if the given relay is offline, i.e. it is unknown, it was loaded from the state file
+// and has not been seen since startup, or it announced its shutdown.
+pub fn is_offline(relay_id: [u8; 4]) -> bool {
+    RELAYS
+        .lock()
+        .unwrap()
+        .get(&relay_id)
+        .map(|v| v.stale || v.shutdown)
+        .unwrap_or(true)
+}
+
+// Returns the last reported location of the given relay.
+pub fn get_location(relay_id: [u8; 4]) -> Option<Location> {
+    RELAYS
+        .lock()
+        .unwrap()
+        .get(&relay_id)
+        .and_then(|v| v.location.clone())
+}
+
+// Returns the last reported telemetry of the given relay.
+pub fn get_telemetry(relay_id: [u8; 4]) -> Option<Telemetry> {
+    RELAYS
+        .lock()
+        .unwrap()
+        .get(&relay_id)
+        .and_then(|v| v.telemetry.clone())
+}
+
+// Returns the relays, with the maintenance flag set for the Relay Gateways of which a maintenance
+// window is active.
+pub fn get_relays_with_maintenance(conf: &Configuration) -> Vec<Relay> {
+    let now = helpers::system_time_now();
+    let mut relays = get_relays();
+    for relay in relays.iter_mut() {
+        relay.maintenance = conf.mesh.in_maintenance(relay.relay_id, now);
+    }
+    relays
+}
+
+// Returns the relays as stored in the given state file.
+pub fn read(state_file: &str) -> Result<Vec<Relay>> {
+    let b = fs::read(state_file)?;
+    Ok(serde_json::from_slice(&b)?)
+}
+
+// Returns the links between the given relays and the Border Gateway.
+pub fn get_links(relays: &[Relay]) -> Vec<Link> {
+    let mut links: BTreeMap<([u8; 4], Option<[u8; 4]>), Link> = BTreeMap::new();
+
+    for relay in relays {
+        // The relay path might have been truncated (see heartbeat_relay_path_max_length), in
+        // which case the first hop is unknown.
+        let mut from = if relay.relay_path.len() + 1 == relay.hop_count as usize {
+            Some(relay.relay_id)
+        } else {
+            None
+        };
+
+        for hop in &relay.relay_path {
+            if let Some(from) = from {
+                links.insert(
+                    (from, Some(hop.relay_id)),
+                    Link {
+                        from,
+                        to: Some(hop.relay_id),
+                        rssi: hop.rssi,
+                        snr: hop.snr,
+                    },
+                );
+            }
+            from = Some(hop.relay_id);
+        }
+
+        if let Some(from) = from {
+            links.insert(
+                (from, None),
+                Link {
+                    from,
+                    to: None,
+                    rssi: relay.rssi,
+                    snr: relay.snr,
+                },
+            );
+        }
+    }
+
+    links.into_values().collect()
+}
+
+// Returns the topology in Graphviz DOT format. Links are labeled and weighted by RSSI, such that
+// the layout keeps the strongest links short. Stale relays and relays that announced their
+// shutdown are dashed, relays with an asymmetric link are colored red.
+pub fn to_dot(relays: &[Relay]) -> String {
+    let mut out = String::from("digraph mesh {\n  \"border\" [shape=box];\n");
+
+    for relay in relays {
+        out.push_str(&format!(
+            "  \"{}\" [label=\"{}\\nhop_count: {}\"{}{}];\n",
+            hex::encode(relay.relay_id),
+            hex::encode(relay.relay_id),
+            relay.hop_count,
+            if relay.stale || relay.shutdown {
+                ", style=dashed"
+            } else {
+                ""
+            },
+            if relay.asymmetric_link {
+                ", color=red"
+            } else {
+                ""
+            },
+        ));
+    }
+
+    for link in get_links(relays) {
+        out.push_str(&format!(
+            "  \"{}\" -> \"{}\" [label=\"{} dBm\", weight={}];\n",
+            hex::encode(link.from),
+            link.to
+                .map(hex::encode)
+                .unwrap_or_else(|| "border".to_string()),
+            link.rssi,
+            (link.rssi + 150).max(1),
+        ));
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+// Returns the topology in GeoJSON format, using the given relay locations. Relays without location
+// are omitted, as are links to the Border Gateway.
+// The configured relay locations take precedence over the locations reported by the Relay
+// Gateways.
+pub fn to_geojson(relays: &[Relay], locations: &[config::RelayLocation]) -> serde_json::Value {
+    let mut coordinates: HashMap<[u8; 4], [f64; 3]> = relays
+        .iter()
+        .filter_map(|r| {
+            r.location
+                .as_ref()
+                .map(|v| (r.relay_id, [v.longitude, v.latitude, v.altitude]))
+        })
+        .collect();
+    coordinates.extend(
+        locations
+            .iter()
+            .map(|v| (v.relay_id, [v.longitude, v.latitude, v.altitude])),
+    );
+
+    let mut features: Vec<serde_json::Value> = vec![];
+
+    for relay in relays {
+        if let Some(point) = coordinates.get(&relay.relay_id) {
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": point,
+                },
+                "properties": {
+                    "relay_id": hex::encode(relay.relay_id),
+                    "last_seen_at": humantime::format_rfc3339(relay.last_seen_at).to_string(),
+                    "hop_count": relay.hop_count,
+                    "rssi": relay.rssi,
+                    "snr": relay.snr,
+                    "stale": relay.stale,
+                    "maintenance": relay.maintenance,
+                    "asymmetric_link": relay.asymmetric_link,
+                    "shutdown": relay.shutdown,
+                },
+            }));
+        }
+    }
+
+    for link in get_links(relays) {
+        let to = match link.to {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if let (Some(from_point), Some(to_point)) =
+            (coordinates.get(&link.from), coordinates.get(&to))
+        {
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": [from_point, to_point],
+                },
+                "properties": {
+                    "from": hex::encode(link.from),
+                    "to": hex::encode(to),
+                    "rssi": link.rssi,
+                    "snr": link.snr,
+                },
+            }));
+        }
+    }
+
+    serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+// Updates the relay (or creates it, if it does not yet exist) using the given function.
+fn record_event<F>(relay_id: [u8; 4], hop_count: u8, rssi: i32, snr: f32, f: F)
+where
+    F: FnOnce(&mut Relay, SystemTime),
+{
+    linkquality::record(relay_id, hop_count, rssi, snr);
+
+    let now = helpers::system_time_now();
+    let mut relays = RELAYS.lock().unwrap();
+    let relay = relays.entry(relay_id).or_insert_with(|| Relay {
+        relay_id,
+        last_seen_at: now,
+        hop_count,
+        rssi,
+        snr,
+        relay_path: vec![],
+        stale: false,
+        attestation: None,
+        tx_airtime: None,
+        uplink_acks: None,
+        asymmetric_link: false,
+        shutdown: false,
+        boot: None,
+        location: None,
+        telemetry: None,
+        challenge_failed: false,
+        maintenance: false,
+    });
+
+    relay.last_seen_at = now;
+    relay.stale = false;
+    relay.shutdown = false;
+    f(relay, now);
+}
+
+fn get_boot_reason_name(reason: packets::BootReason) -> &'static str {
+    match reason {
+        packets::BootReason::PowerCycle => "power_cycle",
+        packets::BootReason::Restart => "restart",
+        packets::BootReason::CrashRecovery => "crash_recovery",
+    }
+}
+
+// Returns true if the ratio of the not acknowledged uplinks is at least the given ratio. A ratio
+// of 0 disables the detection.
+fn is_asymmetric_link(uplink_acks: &UplinkAcks, ratio: f64) -> bool {
+    let total = u32::from(uplink_acks.acked) + u32::from(uplink_acks.not_acked);
+    ratio > 0.0 && total != 0 && f64::from(uplink_acks.not_acked) / f64::from(total) >= ratio
+}
+
+// Returns true if the relay was stale, or if its hop count or relay path has changed.
+fn topology_changed(prev: &Relay, relay: &Relay) -> bool {
+    prev.stale
+        || prev.hop_count != relay.hop_count
+        || !prev
+            .relay_path
+            .iter()
+            .map(|v| v.relay_id)
+            .eq(relay.relay_path.iter().map(|v| v.relay_id))
+}
+
+fn save(state_file: &str) -> Result<()> {
+    let b = serde_json::to_vec(&get_relays())?;
+
+    // Write to a temporary file first, so that a crash while writing does not
+    // corrupt the previous state.
+    let tmp_file = format!("{}.tmp", state_file);
+    fs::write(&tmp_file, b)?;
+    fs::rename(&tmp_file, state_file)?;
+
+    trace!("Topology state persisted, state_file: {}", state_file);
+
+    Ok(())
+}
+
+fn load(state_file: &str) -> Result<()> {
+    let loaded = read(state_file)?;
+
+    let mut relays = RELAYS.lock().unwrap();
+    for mut relay in loaded {
+        relay.stale = true;
+        relays.entry(relay.relay_id).or_insert(relay);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let state_file = std::env::temp_dir().join("chirpstack-gateway-mesh-topology-test.json");
+        let state_file = state_file.to_str().unwrap();
+
+        record_heartbeat(
+            &packets::HeartbeatPayload {
+                timestamp: 0,
+                timestamp_monotonic: false,
+                relay_id: [1, 2, 3, 4],
+                relay_path: vec![packets::RelayPath {
+                    relay_id: [5, 6, 7, 8],
+                    rssi: -100,
+                    snr: -5,
+                }],
+                config_checksum: None,
+            },
+            2,
+            -80,
+            7.5,
+        );
+
+        let relays = get_relays();
+        assert_eq!(1, relays.len());
+        assert!(!relays[0].stale);
+
+        save(state_file).unwrap();
+        RELAYS.lock().unwrap().clear();
+        load(state_file).unwrap();
+        fs::remove_file(state_file).unwrap();
+
+        let loaded = get_relays();
+        assert_eq!(1, loaded.len());
+        assert!(loaded[0].stale);
+
+        // humantime_serde uses a resolution of seconds.
+        assert_eq!(
+            Relay {
+                stale: true,
+                last_seen_at: loaded[0].last_seen_at,
+                ..relays[0].clone()
+            },
+            loaded[0]
+        );
+        assert_eq!(
+            relays[0]
+                .last_seen_at
+                .duration_since(loaded[0].last_seen_at)
+                .unwrap()
+                .as_secs(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let relay =
+            |relay_id: [u8; 4], hop_count: u8, rssi: i32, relay_path: Vec<RelayPath>| Relay {
+                relay_id,
+                last_seen_at: SystemTime::UNIX_EPOCH,
+                hop_count,
+                rssi,
+                snr: 5.0,
+                relay_path,
+                stale: false,
+                attestation: None,
+                tx_airtime: None,
+                uplink_acks: None,
+                asymmetric_link: false,
+                shutdown: false,
+                boot: None,
+                location: None,
+                telemetry: None,
+                challenge_failed: false,
+                maintenance: false,
+            };
+        let path = |relay_id: [u8; 4], rssi: i32| RelayPath {
+            relay_id,
+            rssi,
+            snr: 2.0,
+        };
+
+        let relays = vec![
+            relay([1, 1, 1, 1], 1, -80, vec![]),
+            relay([2, 2, 2, 2], 2, -80, vec![path([1, 1, 1, 1], -100)]),
+            // Truncated relay path, the link from 03030303 is unknown.
+            relay([3, 3, 3, 3], 3, -80, vec![path([1, 1, 1, 1], -90)]),
+        ];
+
+        assert_eq!(
+            vec![
+                Link {
+                    from: [1, 1, 1, 1],
+                    to: None,
+                    rssi: -80,
+                    snr: 5.0,
+                },
+                Link {
+                    from: [2, 2, 2, 2],
+                    to: Some([1, 1, 1, 1]),
+                    rssi: -100,
+                    snr: 2.0,
+                },
+            ],
+            get_links(&relays)
+        );
+
+        let mut relays = relays;
+        relays[1].asymmetric_link = true;
+        relays[0].shutdown = true;
+        let reported_location = |latitude: f64, longitude: f64| Location {
+            reported_at: SystemTime::UNIX_EPOCH,
+            latitude,
+            longitude,
+            altitude: 10.0,
+        };
+        // Overridden by the configured location.
+        relays[0].location = Some(reported_location(50.0, 3.0));
+        relays[2].location = Some(reported_location(52.2, 4.2));
+
+        let dot = to_dot(&relays);
+        assert!(dot.contains("  \"01010101\" [label=\"01010101\\nhop_count: 1\", style=dashed];\n"));
+        assert!(dot.contains("  \"02020202\" [label=\"02020202\\nhop_count: 2\", color=red];\n"));
+        assert!(dot.starts_with("digraph mesh {\n"));
+        assert!(dot.contains("  \"01010101\" -> \"border\" [label=\"-80 dBm\", weight=70];\n"));
+        assert!(dot.contains("  \"02020202\" -> \"01010101\" [label=\"-100 dBm\", weight=50];\n"));
+
+        let geojson = to_geojson(
+            &relays,
+            &[
+                config::RelayLocation {
+                    relay_id: [1, 1, 1, 1],
+                    latitude: 52.0,
+                    longitude: 4.0,
+                    altitude: 0.0,
+                },
+                config::RelayLocation {
+                    relay_id: [2, 2, 2, 2],
+                    latitude: 52.1,
+                    longitude: 4.1,
+                    altitude: 0.0,
+                },
+            ],
+        );
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(4, features.len());
+        assert_eq!("01010101", features[0]["properties"]["relay_id"]);
+        assert_eq!(true, features[0]["properties"]["shutdown"]);
+        assert_eq!(
+            serde_json::json!([4.0, 52.0, 0.0]),
+            features[0]["geometry"]["coordinates"]
+        );
+        assert_eq!("03030303", features[2]["properties"]["relay_id"]);
+        assert_eq!(
+            serde_json::json!([4.2, 52.2, 10.0]),
+            features[2]["geometry"]["coordinates"]
+        );
+        assert_eq!("LineString", features[3]["geometry"]["type"]);
+        assert_eq!("02020202", features[3]["properties"]["from"]);
+        assert_eq!("01010101", features[3]["properties"]["to"]);
+    }
+
+    #[test]
+    fn test_is_asymmetric_link() {
+        let uplink_acks = |acked, not_acked| UplinkAcks {
+            reported_at: SystemTime::UNIX_EPOCH,
+            acked,
+            not_acked,
+        };
+
+        assert!(!is_asymmetric_link(&uplink_acks(0, 0), 0.5));
+        assert!(!is_asymmetric_link(&uplink_acks(3, 1), 0.5));
+        assert!(is_asymmetric_link(&uplink_acks(1, 1), 0.5));
+        assert!(is_asymmetric_link(&uplink_acks(0, 4), 0.5));
+
+        // Disabled.
+        assert!(!is_asymmetric_link(&uplink_acks(0, 4), 0.0));
+    }
+}
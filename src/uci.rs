@@ -0,0 +1,259 @@
+use crate::config::Configuration;
+use crate::error::{Error, Result};
+
+// Converts the given UCI configuration (as exported by `uci export`) to the TOML configuration
+// format.
+//
+// The section type refers to the top-level table, the section name to the nested table within
+// this table (nested levels separated by a double underscore). Anonymous sections and sections
+// named after their type refer to the top-level table itself. Example:
+//
+//   config mesh 'mesh'
+//     option border_gateway '1'
+//     list frequencies '868100000'
+//
+//   config mesh 'data_rate'
+//     option spreading_factor '7'
+//
+//   config mesh 'join_requests__data_rate'
+//     option spreading_factor '9'
+//
+// The value types are derived from the default configuration. Arrays of tables (e.g.
+// mesh.border_routes) are not supported.
+pub fn to_toml(content: &str) -> Result<String> {
+    let defaults = toml::Value::try_from(Configuration::default())?;
+    let mut out = toml::Table::new();
+    let mut section: Option<Vec<String>> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        let tokens = tokenize(line).map_err(|e| Error::Config(format!("Line {}: {}", i + 1, e)))?;
+        let (keyword, args) = match tokens.split_first() {
+            Some(v) => v,
+            None => continue,
+        };
+
+        match (keyword.as_str(), args) {
+            ("package", _) => {}
+            ("config", [section_type]) => {
+                section = Some(vec![section_type.clone()]);
+            }
+            ("config", [section_type, name]) => {
+                let mut path = vec![section_type.clone()];
+                if name != section_type {
+                    path.extend(name.split("__").map(|v| v.to_string()));
+                }
+                section = Some(path);
+            }
+            ("option", [key, value]) | ("list", [key, value]) => {
+                let path = section.as_ref().ok_or_else(|| {
+                    Error::Config(format!(
+                        "Line {}: {} outside config section",
+                        i + 1,
+                        keyword
+                    ))
+                })?;
+                let table = get_table_mut(&mut out, path)?;
+                let default = get_default(&defaults, path, key);
+
+                if keyword == "list" {
+                    let value = parse_value(
+                        default.and_then(|v| v.as_array()).and_then(|v| v.first()),
+                        value,
+                    )
+                    .map_err(|e| Error::Config(format!("Line {}: {}", i + 1, e)))?;
+
+                    match table
+                        .entry(key.clone())
+                        .or_insert_with(|| toml::Value::Array(vec![]))
+                    {
+                        toml::Value::Array(v) => v.push(value),
+                        _ => {
+                            return Err(Error::Config(format!(
+                                "Line {}: {} is not a list",
+                                i + 1,
+                                key
+                            )))
+                        }
+                    }
+                } else {
+                    let value = parse_value(default, value)
+                        .map_err(|e| Error::Config(format!("Line {}: {}", i + 1, e)))?;
+                    table.insert(key.clone(), value);
+                }
+            }
+            _ => {
+                return Err(Error::Config(format!("Line {}: invalid statement", i + 1)));
+            }
+        }
+    }
+
+    Ok(toml::to_string(&out)?)
+}
+
+// Splits the given line into tokens. Tokens are separated by whitespace and can be quoted using
+// single or double quotes.
+fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => break,
+            c if c.is_whitespace() => continue,
+            '\'' | '"' => {
+                let mut token = String::new();
+                loop {
+                    match chars.next() {
+                        Some(v) if v == c => break,
+                        Some(v) => token.push(v),
+                        None => return Err(Error::Config("unterminated quote".into())),
+                    }
+                }
+                tokens.push(token);
+            }
+            _ => {
+                let mut token = c.to_string();
+                while let Some(v) = chars.next_if(|v| !v.is_whitespace()) {
+                    token.push(v);
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn get_table_mut<'a>(table: &'a mut toml::Table, path: &[String]) -> Result<&'a mut toml::Table> {
+    let (key, path) = match path.split_first() {
+        Some(v) => v,
+        None => return Ok(table),
+    };
+
+    match table
+        .entry(key.clone())
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+    {
+        toml::Value::Table(v) => get_table_mut(v, path),
+        _ => Err(Error::Config(format!("{} is not a section", key))),
+    }
+}
+
+fn get_default<'a>(
+    defaults: &'a toml::Value,
+    path: &[String],
+    key: &str,
+) -> Option<&'a toml::Value> {
+    path.iter()
+        .try_fold(defaults, |v, k| v.get(k))
+        .and_then(|v| v.get(key))
+}
+
+// Parses the UCI (string) value into the type of the given default value. If there is no default
+// value (e.g. an optional or empty list value), the type is inferred from the value.
+fn parse_value(default: Option<&toml::Value>, value: &str) -> Result<toml::Value> {
+    Ok(match default {
+        Some(toml::Value::Boolean(_)) => toml::Value::Boolean(match value {
+            "1" | "true" | "yes" | "on" => true,
+            "0" | "false" | "no" | "off" => false,
+            _ => return Err(Error::Config(format!("invalid boolean value: {}", value))),
+        }),
+        Some(toml::Value::Integer(_)) => toml::Value::Integer(
+            value
+                .parse()
+                .map_err(|_| Error::Config(format!("invalid integer value: {}", value)))?,
+        ),
+        Some(toml::Value::Float(_)) => toml::Value::Float(
+            value
+                .parse()
+                .map_err(|_| Error::Config(format!("invalid float value: {}", value)))?,
+        ),
+        Some(_) => toml::Value::String(value.to_string()),
+        None => {
+            if let Ok(v) = value.parse::<i64>() {
+                toml::Value::Integer(v)
+            } else if let Ok(v) = value.parse::<f64>() {
+                toml::Value::Float(v)
+            } else if let Ok(v) = value.parse::<bool>() {
+                toml::Value::Boolean(v)
+            } else {
+                toml::Value::String(value.to_string())
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_toml() {
+        let content = r#"
+package chirpstack-gateway-mesh
+
+config mesh 'mesh'
+	option border_gateway '1'
+	option signing_key '0102030405060708090a0b0c0d0e0f10'
+	option heartbeat_interval '5m'
+	list frequencies '865062500'
+	list frequencies '865402500'
+
+config mesh 'data_rate'
+	option spreading_factor '7'
+	option code_rate '4/5'
+
+config mesh 'join_requests__data_rate'
+	option spreading_factor '9'
+
+config mappings
+	list tx_power '27'
+	list tx_power '24'
+"#;
+
+        let out = to_toml(content).unwrap();
+        let conf: Configuration = toml::from_str(&out).unwrap();
+
+        assert!(conf.mesh.border_gateway);
+        assert_eq!(
+            "0102030405060708090a0b0c0d0e0f10",
+            conf.mesh.signing_key.to_string()
+        );
+        assert_eq!(
+            std::time::Duration::from_secs(300),
+            conf.mesh.heartbeat_interval
+        );
+        assert_eq!(vec![865062500, 865402500], conf.mesh.frequencies);
+        assert_eq!(7, conf.mesh.data_rate.spreading_factor);
+        assert_eq!(
+            9,
+            conf.mesh
+                .join_requests
+                .data_rate
+                .as_ref()
+                .unwrap()
+                .spreading_factor
+        );
+        assert_eq!(vec![27, 24], conf.mappings.tx_power);
+    }
+
+    #[test]
+    fn test_to_toml_errors() {
+        assert_eq!(
+            "Line 2: invalid boolean value: maybe",
+            to_toml("config mesh\noption border_gateway 'maybe'")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Line 1: option outside config section",
+            to_toml("option border_gateway '1'")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Line 1: unterminated quote",
+            to_toml("config mesh 'mesh").unwrap_err().to_string()
+        );
+    }
+}
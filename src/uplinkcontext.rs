@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use log::{error, info, trace};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
+use crate::helpers;
+
+// Max. Uplink ID (12 bits).
+const UPLINK_ID_MAX: u16 = 4095;
+
+static STORE: Mutex<Store> = Mutex::new(Store {
+    uplink_id: 0,
+    entries: VecDeque::new(),
+});
+
+#[derive(Serialize, Deserialize)]
+struct Store {
+    // Last assigned Uplink ID.
+    uplink_id: u16,
+    // Ordered by insertion, oldest first.
+    entries: VecDeque<Entry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Entry {
+    uplink_id: u16,
+    #[serde(with = "hex")]
+    context: Vec<u8>,
+    #[serde(with = "humantime_serde")]
+    stored_at: SystemTime,
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    if conf.uplink_context.state_file.is_empty() {
+        return Ok(());
+    }
+
+    if Path::new(&conf.uplink_context.state_file).exists() {
+        info!(
+            "Loading uplink context state, state_file: {}",
+            conf.uplink_context.state_file
+        );
+        load(&conf.uplink_context.state_file, conf.uplink_context.ttl)?;
+    }
+
+    if conf.uplink_context.persist_interval.is_zero() {
+        return Ok(());
+    }
+
+    info!(
+        "Starting uplink context persist loop, state_file: {}, persist_interval: {:?}",
+        conf.uplink_context.state_file, conf.uplink_context.persist_interval
+    );
+
+    tokio::spawn({
+        let state_file = conf.uplink_context.state_file.clone();
+        let persist_interval = conf.uplink_context.persist_interval;
+
+        async move {
+            loop {
+                sleep(persist_interval).await;
+                if let Err(e) = save(&state_file) {
+                    error!("Persist uplink context state error, error: {}", e);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Persists the uplink context state to the configured state_file (if set).
+pub fn persist() -> Result<()> {
+    let conf = config::get();
+    if conf.uplink_context.state_file.is_empty() {
+        return Ok(());
+    }
+
+    save(&conf.uplink_context.state_file)
+}
+
+// Stores the given context and returns the assigned Uplink ID. Expired entries and the oldest
+// entries exceeding max_entries are evicted.
+pub fn store(ctx: &[u8]) -> u16 {
+    let conf = config::get();
+    let mut store = STORE.lock().unwrap();
+
+    store.uplink_id = if store.uplink_id >= UPLINK_ID_MAX {
+        0
+    } else {
+        store.uplink_id + 1
+    };
+    let uplink_id = store.uplink_id;
+
+    let now = helpers::system_time_now();
+    evict(&mut store.entries, conf.uplink_context.ttl, now);
+    store.entries.retain(|v| v.uplink_id != uplink_id);
+    while !store.entries.is_empty() && store.entries.len() >= conf.uplink_context.max_entries {
+        store.entries.pop_front();
+    }
+
+    store.entries.push_back(Entry {
+        uplink_id,
+        context: ctx.to_vec(),
+        stored_at: now,
+    });
+
+    uplink_id
+}
+
+pub fn get(uplink_id: u16) -> Result<Vec<u8>> {
+    let conf = config::get();
+    let store = STORE.lock().unwrap();
+    let now = helpers::system_time_now();
+
+    store
+        .entries
+        .iter()
+        .find(|v| v.uplink_id == uplink_id && !is_expired(v, conf.uplink_context.ttl, now))
+        .map(|v| v.context.clone())
+        .ok_or_else(|| Error::Routing(format!("No uplink context for uplink_id: {}", uplink_id)))
+}
+
+fn evict(entries: &mut VecDeque<Entry>, ttl: Duration, now: SystemTime) {
+    while entries
+        .front()
+        .map(|v| is_expired(v, ttl, now))
+        .unwrap_or_default()
+    {
+        entries.pop_front();
+    }
+}
+
+fn is_expired(entry: &Entry, ttl: Duration, now: SystemTime) -> bool {
+    !ttl.is_zero()
+        && now
+            .duration_since(entry.stored_at)
+            .map(|v| v > ttl)
+            .unwrap_or_default()
+}
+
+fn save(state_file: &str) -> Result<()> {
+    let b = serde_json::to_vec(&*STORE.lock().unwrap())?;
+
+    // Write to a temporary file first, so that a crash while writing does not
+    // corrupt the previous state.
+    let tmp_file = format!("{}.tmp", state_file);
+    fs::write(&tmp_file, b)?;
+    fs::rename(&tmp_file, state_file)?;
+
+    trace!("Uplink context state persisted, state_file: {}", state_file);
+
+    Ok(())
+}
+
+fn load(state_file: &str, ttl: Duration) -> Result<()> {
+    let b = fs::read(state_file)?;
+    let mut loaded: Store = serde_json::from_slice(&b)?;
+    evict(&mut loaded.entries, ttl, helpers::system_time_now());
+
+    // The Uplink ID continues from the loaded state, such that the loaded contexts are not
+    // overwritten by new uplinks.
+    *STORE.lock().unwrap() = loaded;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_evict() {
+        let now = SystemTime::now();
+        let mut entries: VecDeque<Entry> = [30, 20, 10]
+            .iter()
+            .enumerate()
+            .map(|(i, age)| Entry {
+                uplink_id: i as u16,
+                context: vec![i as u8],
+                stored_at: now - Duration::from_secs(*age),
+            })
+            .collect();
+
+        evict(&mut entries, Duration::ZERO, now);
+        assert_eq!(3, entries.len());
+
+        evict(&mut entries, Duration::from_secs(15), now);
+        assert_eq!(
+            vec![2],
+            entries.iter().map(|v| v.uplink_id).collect::<Vec<u16>>()
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs;
+use std::io;
+use std::process;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use prometheus_client::metrics::gauge::Gauge;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::config::Configuration;
+use crate::error::Result;
+use crate::{metrics, supervisor};
+
+// Last time the event loop was seen alive.
+static LAST_TICK: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+
+static MEMORY_RSS: Lazy<Gauge> = Lazy::new(|| {
+    let gauge = Gauge::default();
+    metrics::register(
+        "watchdog_memory_rss_bytes",
+        "Resident memory (RSS) of the process",
+        gauge.clone(),
+    );
+    gauge
+});
+
+pub fn setup(conf: &Configuration) -> Result<()> {
+    if !conf.watchdog.enabled {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up watchdog, interval: {:?}, stall_timeout: {:?}, memory_limit: {} MiB",
+        conf.watchdog.interval, conf.watchdog.stall_timeout, conf.watchdog.memory_limit
+    );
+
+    *LAST_TICK.lock().unwrap() = Instant::now();
+
+    tokio::spawn({
+        let watchdog_interval = conf.watchdog.interval;
+        let memory_limit = conf.watchdog.memory_limit;
+
+        async move {
+            tick_loop(watchdog_interval, memory_limit).await;
+        }
+    });
+
+    // The stall check runs on a dedicated thread, as it must keep running when the event loop
+    // is stalled.
+    supervisor::spawn_thread("Watchdog", {
+        let watchdog_interval = conf.watchdog.interval;
+        let stall_timeout = conf.watchdog.stall_timeout;
+
+        move || stall_loop(watchdog_interval, stall_timeout)
+    });
+
+    Ok(())
+}
+
+async fn tick_loop(watchdog_interval: Duration, memory_limit: u64) {
+    let mut ticker = interval(watchdog_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        *LAST_TICK.lock().unwrap() = Instant::now();
+
+        let rss = match get_memory_rss() {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Get memory usage error, error: {}", e);
+                continue;
+            }
+        };
+        MEMORY_RSS.set(rss.try_into().unwrap_or(i64::MAX));
+
+        if memory_limit != 0 && rss > memory_limit * 1024 * 1024 {
+            error!(
+                "Memory limit exceeded, rss: {} MiB, memory_limit: {} MiB",
+                rss / 1024 / 1024,
+                memory_limit
+            );
+            supervisor::report(anyhow!("Watchdog memory limit exceeded"));
+            return;
+        }
+    }
+}
+
+fn stall_loop(watchdog_interval: Duration, stall_timeout: Duration) -> Result<()> {
+    loop {
+        thread::sleep(watchdog_interval);
+
+        let stalled = LAST_TICK.lock().unwrap().elapsed();
+        if stalled > stall_timeout {
+            // As the event loop is stalled, the error can't be handled by the supervisor.
+            error!(
+                "Event loop stalled, exiting, stalled: {:?}, stall_timeout: {:?}, rss: {}",
+                stalled,
+                stall_timeout,
+                match get_memory_rss() {
+                    Ok(v) => format!("{} MiB", v / 1024 / 1024),
+                    Err(e) => format!("unknown ({})", e),
+                }
+            );
+            process::exit(1);
+        }
+    }
+}
+
+// Returns the resident memory (RSS) of the process in bytes.
+fn get_memory_rss() -> Result<u64> {
+    let status = fs::read_to_string("/proc/self/status")?;
+    parse_memory_rss(&status)
+}
+
+fn parse_memory_rss(status: &str) -> Result<u64> {
+    for line in status.lines() {
+        if let Some(v) = line.strip_prefix("VmRSS:") {
+            let kb: u64 = v
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            return Ok(kb * 1024);
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, "VmRSS not found").into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_memory_rss() {
+        assert_eq!(
+            12 * 1024 * 1024,
+            parse_memory_rss("Name:\ttest\nVmRSS:\t   12288 kB\nThreads:\t4\n").unwrap()
+        );
+        assert!(parse_memory_rss("Name:\ttest\n").is_err());
+        assert!(parse_memory_rss("VmRSS:\tinvalid kB\n").is_err());
+    }
+
+    #[test]
+    fn test_get_memory_rss() {
+        assert!(get_memory_rss().unwrap() > 0);
+    }
+}
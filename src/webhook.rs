@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+use log::{error, info, trace, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client, Url};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+use crate::config::{self, Configuration};
+use crate::error::{Error, Result};
+use crate::{backend, helpers, metrics, topology};
+
+// Max number of events waiting to be posted. Events are dropped when the queue is full, e.g.
+// when the webhook endpoint is unreachable.
+const QUEUE_SIZE: usize = 100;
+
+static EVENT_CHAN: OnceCell<mpsc::Sender<Event>> = OnceCell::new();
+
+static EVENT_COUNT: Lazy<Family<EventLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<EventLabels, Counter>::default();
+    metrics::register(
+        "webhook_event_count",
+        "Number of events posted to the webhook",
+        counter.clone(),
+    );
+    counter
+});
+static EVENT_ERROR_COUNT: Lazy<Family<EventLabels, Counter>> = Lazy::new(|| {
+    let counter = Family::<EventLabels, Counter>::default();
+    metrics::register(
+        "webhook_event_error_count",
+        "Number of events that failed to be posted to the webhook, or were dropped as the queue was full",
+        counter.clone(),
+    );
+    counter
+});
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct EventLabels {
+    event: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    // Heartbeat received from a Relay Gateway.
+    Heartbeat(topology::Relay),
+    // A Relay Gateway has not been seen for the configured relay_offline_timeout, or it announced
+    // its shutdown (shutdown is set).
+    RelayOffline {
+        #[serde(with = "hex")]
+        relay_id: [u8; 4],
+        #[serde(with = "humantime_serde")]
+        last_seen_at: SystemTime,
+        shutdown: bool,
+    },
+    // A Relay Gateway has been added to the topology, or its hop count or relay path changed.
+    Topology(topology::Relay),
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Heartbeat(_) => "heartbeat",
+            Event::RelayOffline { .. } => "relay_offline",
+            Event::Topology(_) => "topology",
+        }
+    }
+}
+
+pub async fn setup(conf: &Configuration) -> Result<()> {
+    // Only the Border Gateway receives the heartbeats of all Relay Gateways.
+    if !conf.mesh.border_gateway || conf.webhook.url.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up webhook, url: {}, events: {:?}",
+        conf.webhook.url, conf.webhook.events
+    );
+
+    let url = parse_url(&conf.webhook.url)?;
+    // The connection is secured using TLS in case of an https:// URL. The server certificate is
+    // validated against the Mozilla root certificates.
+    let client = Client::builder()
+        .default_headers(parse_headers(&conf.webhook.headers)?)
+        .timeout(conf.webhook.timeout)
+        .use_rustls_tls()
+        .build()
+        .map_err(|e| Error::Backend(format!("Webhook client error: {}", e)))?;
+
+    let (event_tx, event_rx) = mpsc::channel::<Event>(QUEUE_SIZE);
+    EVENT_CHAN
+        .set(event_tx)
+        .map_err(|e| Error::Backend(format!("OnceCell error: {:?}", e)))?;
+
+    tokio::spawn(event_loop(client, url, event_rx));
+
+    if !conf.webhook.relay_offline_timeout.is_zero()
+        && conf.webhook.events.iter().any(|v| v == "relay_offline")
+    {
+        tokio::spawn({
+            let relay_offline_timeout = conf.webhook.relay_offline_timeout;
+
+            async move {
+                relay_offline_loop(relay_offline_timeout).await;
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// Queues the given event for posting to the webhook, if configured and if the event is selected
+// in the webhook.events configuration.
+pub fn send(event: Event) {
+    let event_tx = match EVENT_CHAN.get() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let conf = config::get();
+    if !conf.webhook.events.iter().any(|v| v == event.name()) {
+        return;
+    }
+
+    trace!("Queueing webhook event, event: {}", event.name());
+
+    let event_name = event.name();
+    if let Err(e) = event_tx.try_send(event) {
+        warn!(
+            "Dropping webhook event, event: {}, error: {}",
+            event_name, e
+        );
+        EVENT_ERROR_COUNT
+            .get_or_create(&EventLabels {
+                event: event_name.to_string(),
+            })
+            .inc();
+    }
+}
+
+async fn event_loop(client: Client, url: Url, mut event_rx: mpsc::Receiver<Event>) {
+    trace!("Starting webhook event loop");
+    while let Some(event) = event_rx.recv().await {
+        let labels = EventLabels {
+            event: event.name().to_string(),
+        };
+
+        match post_event(&client, &url, &event).await {
+            Ok(_) => {
+                trace!("Webhook event posted, event: {}", event.name());
+                EVENT_COUNT.get_or_create(&labels).inc();
+            }
+            Err(e) => {
+                error!(
+                    "Post webhook event error, event: {}, error: {}",
+                    event.name(),
+                    e
+                );
+                EVENT_ERROR_COUNT.get_or_create(&labels).inc();
+            }
+        }
+    }
+}
+
+async fn post_event(client: &Client, url: &Url, event: &Event) -> Result<()> {
+    let body = encode_event(&hex::encode(backend::get_gateway_id().await?), event)?;
+
+    let resp = client
+        .post(url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(Error::Backend(format!(
+            "Webhook returned status code {}",
+            status.as_u16()
+        )));
+    }
+
+    Ok(())
+}
+
+// Returns the JSON body for the given event.
+fn encode_event(gateway_id: &str, event: &Event) -> Result<Vec<u8>> {
+    let mut v = serde_json::to_value(event)?;
+    if let Some(obj) = v.as_object_mut() {
+        obj.insert("gateway_id".into(), gateway_id.into());
+        obj.insert(
+            "time".into(),
+            humantime::format_rfc3339_millis(helpers::system_time_now())
+                .to_string()
+                .into(),
+        );
+    }
+    Ok(serde_json::to_vec(&v)?)
+}
+
+// Returns the parsed webhook URL. Both http:// and https:// URLs are supported.
+pub fn parse_url(url: &str) -> Result<Url> {
+    let url = Url::parse(url).map_err(|e| Error::Config(format!("Invalid webhook.url: {}", e)))?;
+
+    if !["http", "https"].contains(&url.scheme()) {
+        return Err(Error::Config(
+            "webhook.url must be an http:// or https:// URL".into(),
+        ));
+    }
+
+    if url.host_str().unwrap_or_default().is_empty() {
+        return Err(Error::Config("webhook.url does not contain a host".into()));
+    }
+
+    Ok(url)
+}
+
+// Returns the parsed webhook headers.
+pub fn parse_headers(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut out = HeaderMap::new();
+    for (k, v) in headers {
+        let name = HeaderName::try_from(k.as_str())
+            .map_err(|e| Error::Config(format!("Invalid webhook.headers name {}: {}", k, e)))?;
+        let value = HeaderValue::try_from(v.as_str()).map_err(|e| {
+            Error::Config(format!("Invalid webhook.headers value for {}: {}", k, e))
+        })?;
+        out.insert(name, value);
+    }
+    Ok(out)
+}
+
+async fn relay_offline_loop(relay_offline_timeout: Duration) {
+    info!(
+        "Starting relay offline loop, relay_offline_timeout: {:?}",
+        relay_offline_timeout
+    );
+
+    // Relays for which the relay_offline event has been sent.
+    let mut offline: HashSet<[u8; 4]> = HashSet::new();
+
+    loop {
+        sleep(relay_offline_timeout / 4).await;
+
+        for relay in get_offline_relays(&mut offline, relay_offline_timeout) {
+            warn!(
+                "Relay Gateway is offline, relay_id: {}, relay_offline_timeout: {:?}",
+                hex::encode(relay.relay_id),
+                relay_offline_timeout
+            );
+
+            send(Event::RelayOffline {
+                relay_id: relay.relay_id,
+                last_seen_at: relay.last_seen_at,
+                shutdown: false,
+            });
+        }
+    }
+}
+
+// Returns the relays that went offline since the previous call. Relays are seen by their
+// heartbeats, events and relayed uplinks. Relays loaded from the topology state file are only
+// considered once seen again. Relays that announced their
+// shutdown have already been reported. Relays in an active maintenance window are expected to be
+// silent, these are reported after the window in case they are still offline.
+fn get_offline_relays(
+    offline: &mut HashSet<[u8; 4]>,
+    relay_offline_timeout: Duration,
+) -> Vec<topology::Relay> {
+    let mut out = vec![];
+
+    for relay in topology::get_relays_with_maintenance(&config::get()) {
+        if relay.shutdown {
+            offline.insert(relay.relay_id);
+            continue;
+        }
+
+        if relay.maintenance {
+            continue;
+        }
+
+        let is_offline = !relay.stale
+            && relay
+                .last_seen_at
+                .elapsed()
+                .map(|v| v > relay_offline_timeout)
+                .unwrap_or_default();
+
+        if !is_offline {
+            offline.remove(&relay.relay_id);
+            continue;
+        }
+
+        if offline.insert(relay.relay_id) {
+            out.push(relay);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        let headers: HashMap<String, String> =
+            [("Authorization".to_string(), "Bearer secret".to_string())].into();
+        let headers = parse_headers(&headers).unwrap();
+        assert_eq!("Bearer secret", headers["authorization"]);
+
+        let headers: HashMap<String, String> =
+            [("Invalid Header".to_string(), "value".to_string())].into();
+        assert!(parse_headers(&headers).is_err());
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            Some(8080),
+            parse_url("http://localhost:8080/events").unwrap().port()
+        );
+        assert_eq!(
+            "https",
+            parse_url("https://localhost/events").unwrap().scheme()
+        );
+        assert_eq!(
+            "webhook.url must be an http:// or https:// URL",
+            parse_url("ftp://localhost").unwrap_err().to_string()
+        );
+        assert!(parse_url("localhost:8080").is_err());
+        assert!(parse_url("http://").is_err());
+    }
+
+    #[test]
+    fn test_encode_event() {
+        let b = encode_event(
+            "0101010101010101",
+            &Event::RelayOffline {
+                relay_id: [1, 2, 3, 4],
+                last_seen_at: SystemTime::UNIX_EPOCH,
+                shutdown: false,
+            },
+        )
+        .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&b).unwrap();
+
+        assert_eq!("relay_offline", v["event"]);
+        assert_eq!("0101010101010101", v["gateway_id"]);
+        assert_eq!("01020304", v["relay_id"]);
+        assert_eq!("1970-01-01T00:00:00Z", v["last_seen_at"]);
+        assert_eq!(false, v["shutdown"]);
+        assert!(v["time"].is_string());
+    }
+}
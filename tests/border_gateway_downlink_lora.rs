@@ -35,7 +35,7 @@ async fn test_border_gateway_downlink_lora() {
                 }),
                 timing: Some(gw::Timing {
                     parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
-                        delay: Some(prost_types::Duration {
+                        delay: Some(pbjson_types::Duration {
                             seconds: 5,
                             nanos: 0,
                         }),
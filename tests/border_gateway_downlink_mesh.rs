@@ -37,7 +37,7 @@ async fn test_border_gateway_downlink_mesh() {
                 }),
                 timing: Some(gw::Timing {
                     parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
-                        delay: Some(prost_types::Duration {
+                        delay: Some(pbjson_types::Duration {
                             seconds: 3,
                             ..Default::default()
                         }),
@@ -91,6 +91,7 @@ async fn test_border_gateway_downlink_mesh() {
                 mhdr: packets::MHDR {
                     payload_type: packets::PayloadType::Downlink,
                     hop_count: 1,
+                    key_index: 0,
                 },
                 payload: packets::Payload::Downlink(packets::DownlinkPayload {
                     metadata: packets::DownlinkMetadata {
@@ -0,0 +1,184 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::{packets, proto};
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway relays a downlink to the
+    Relay Gateway, after which the Relay Gateway acknowledges the downlink with
+    a TOO_LATE status. As this drops the downlink success rate of the Relay
+    Gateway below the alert threshold, the Border Gateway must publish a
+    mesh_downlink_alert event.
+*/
+#[tokio::test]
+async fn test_border_gateway_downlink_mesh_ack_alert() {
+    let mut conf = common::get_config(true);
+    conf.mesh.downlink_ack.enabled = true;
+    conf.mesh.downlink_ack.window = 1;
+    conf.mesh.downlink_ack.alert_threshold = 1.0;
+    common::setup_with_config(conf).await;
+
+    let down = gw::DownlinkFrame {
+        downlink_id: 1,
+        gateway_id: "0101010101010101".into(),
+        items: vec![gw::DownlinkFrameItem {
+            phy_payload: vec![9, 8, 7, 6],
+            tx_info: Some(gw::DownlinkTxInfo {
+                frequency: 868500000,
+                power: 16,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        polarization_inversion: true,
+                        ..Default::default()
+                    })),
+                }),
+                timing: Some(gw::Timing {
+                    parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                        delay: Some(pbjson_types::Duration {
+                            seconds: 3,
+                            ..Default::default()
+                        }),
+                    })),
+                }),
+                context: vec![1, 2, 3, 1, 2, 3, 4, 0, 123],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Publish downlink command.
+    let mut cmd_sock = common::FORWARDER_COMMAND_SOCK.get().unwrap().lock().await;
+    cmd_sock
+        .send(
+            vec![
+                bytes::Bytes::from("down"),
+                bytes::Bytes::from(down.encode_to_vec()),
+            ]
+            .try_into()
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // We expect the wrapped downlink to be received by the mesh concentratord.
+    {
+        let mut mesh_cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = mesh_cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+        let tx_ack = gw::DownlinkTxAck {
+            downlink_id: down.downlink_id,
+            items: vec![gw::DownlinkTxAckItem {
+                status: gw::TxAckStatus::Ok.into(),
+            }],
+            ..Default::default()
+        };
+        mesh_cmd_sock
+            .send(tx_ack.encode_to_vec().into())
+            .await
+            .unwrap();
+    }
+
+    let msg = cmd_sock.recv().await.unwrap();
+    let tx_ack = gw::DownlinkTxAck::decode(msg.get(0).cloned().unwrap()).unwrap();
+    assert_eq!(
+        vec![gw::DownlinkTxAckItem {
+            status: gw::TxAckStatus::Ok.into(),
+        }],
+        tx_ack.items
+    );
+
+    // The Relay Gateway acknowledges the downlink.
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            relay_id: [1, 2, 3, 4],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            events: vec![packets::Event::DownlinkAck(packets::DownlinkAckEvent {
+                uplink_id: 123,
+                status: gw::TxAckStatus::TooLate as u8,
+            })],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -100,
+            snr: 2.5,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the MeshDownlinkAlertEvent to be received by the forwarder.
+    let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+
+    let msg = event_sock.recv().await.unwrap();
+    let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+    assert_eq!("mesh_downlink_alert", cmd);
+    assert_eq!(
+        proto::MeshDownlinkAlertEvent {
+            gateway_id: "0101010101010101".to_string(),
+            relay_id: vec![1, 2, 3, 4],
+            success_rate: 0.0,
+            alert_threshold: 1.0,
+            alert: true,
+        },
+        proto::MeshDownlinkAlertEvent::decode(msg.get(1).cloned().unwrap()).unwrap()
+    );
+}
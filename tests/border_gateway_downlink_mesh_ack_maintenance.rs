@@ -0,0 +1,122 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::config;
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway relays a downlink to a
+    Relay Gateway in an active maintenance window, after which the Relay
+    Gateway does not acknowledge the downlink. As the Relay Gateway is expected
+    to be silent, the Border Gateway must not count the downlink as failed and
+    must not publish a mesh_downlink_alert event.
+*/
+#[tokio::test]
+async fn test_border_gateway_downlink_mesh_ack_maintenance() {
+    let mut conf = common::get_config(true);
+    conf.mesh.downlink_ack.enabled = true;
+    conf.mesh.downlink_ack.timeout = Duration::from_millis(100);
+    conf.mesh.downlink_ack.window = 1;
+    conf.mesh.downlink_ack.alert_threshold = 1.0;
+    conf.mesh.maintenance_windows = vec![config::MaintenanceWindow {
+        name: "power cycle".into(),
+        relay_id_prefixes: vec!["01020304".parse().unwrap()],
+        start: "00:00".into(),
+        duration: Duration::from_secs(86400),
+    }];
+    common::setup_with_config(conf).await;
+
+    let down = gw::DownlinkFrame {
+        downlink_id: 1,
+        gateway_id: "0101010101010101".into(),
+        items: vec![gw::DownlinkFrameItem {
+            phy_payload: vec![9, 8, 7, 6],
+            tx_info: Some(gw::DownlinkTxInfo {
+                frequency: 868500000,
+                power: 16,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        polarization_inversion: true,
+                        ..Default::default()
+                    })),
+                }),
+                timing: Some(gw::Timing {
+                    parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                        delay: Some(pbjson_types::Duration {
+                            seconds: 3,
+                            ..Default::default()
+                        }),
+                    })),
+                }),
+                context: vec![1, 2, 3, 1, 2, 3, 4, 0, 123],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Publish downlink command.
+    let mut cmd_sock = common::FORWARDER_COMMAND_SOCK.get().unwrap().lock().await;
+    cmd_sock
+        .send(
+            vec![
+                bytes::Bytes::from("down"),
+                bytes::Bytes::from(down.encode_to_vec()),
+            ]
+            .try_into()
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // We expect the wrapped downlink to be received by the mesh concentratord.
+    {
+        let mut mesh_cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = mesh_cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+        let tx_ack = gw::DownlinkTxAck {
+            downlink_id: down.downlink_id,
+            items: vec![gw::DownlinkTxAckItem {
+                status: gw::TxAckStatus::Ok.into(),
+            }],
+            ..Default::default()
+        };
+        mesh_cmd_sock
+            .send(tx_ack.encode_to_vec().into())
+            .await
+            .unwrap();
+    }
+
+    let msg = cmd_sock.recv().await.unwrap();
+    let tx_ack = gw::DownlinkTxAck::decode(msg.get(0).cloned().unwrap()).unwrap();
+    assert_eq!(
+        vec![gw::DownlinkTxAckItem {
+            status: gw::TxAckStatus::Ok.into(),
+        }],
+        tx_ack.items
+    );
+
+    // The Relay Gateway does not acknowledge the downlink. As it is in maintenance, receiving the
+    // mesh_downlink_alert event from the event socket should timeout.
+    let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+    let resp = timeout(Duration::from_secs(1), event_sock.recv()).await;
+    assert!(resp.is_err());
+}
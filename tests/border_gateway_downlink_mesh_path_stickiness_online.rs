@@ -0,0 +1,179 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway receives a downlink for an
+    end-device of which the uplink was relayed by two Relay Gateways, and the
+    Relay Gateway in the downlink context is online. With path stickiness
+    enabled, the downlink must still be relayed to the Relay Gateway in the
+    downlink context.
+*/
+#[tokio::test]
+async fn test_border_gateway_downlink_mesh_path_stickiness_online() {
+    let mut conf = common::get_config(true);
+    conf.mesh.path_stickiness.enabled = true;
+    common::setup_with_config(conf).await;
+
+    // The same uplink is relayed by Relay ID 01020304 (Uplink ID 123) and Relay ID 05060708
+    // (Uplink ID 456).
+    for (relay_id, uplink_id) in [([1, 2, 3, 4], 123), ([5, 6, 7, 8], 456)] {
+        let mut packet = packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Uplink,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Uplink(packets::UplinkPayload {
+                metadata: packets::UplinkMetadata {
+                    uplink_id,
+                    dr: 0,
+                    rssi: -60,
+                    snr: 6,
+                    channel: 2,
+                    border_id: 0,
+                },
+                relay_id,
+                timestamp: None,
+                timestamp_monotonic: false,
+                // Unconfirmed data up, DevAddr 04030201.
+                phy_payload: vec![0x40, 1, 2, 3, 4, 0, 0, 0],
+            }),
+            mic: None,
+        };
+        packet.set_mic(Aes128Key::null()).unwrap();
+
+        let up = gw::UplinkFrame {
+            phy_payload: packet.to_vec().unwrap(),
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                rssi: -110,
+                snr: -3.5,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Publish uplink event.
+        {
+            let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // We expect to receive the unwrapped uplink to be received by the forwarder.
+        {
+            let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+            let msg = event_sock.recv().await.unwrap();
+
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("up", cmd);
+        }
+    }
+
+    // The downlink context refers to Relay ID 01020304, Uplink ID 123.
+    let down = gw::DownlinkFrame {
+        downlink_id: 1,
+        gateway_id: "0101010101010101".into(),
+        items: vec![gw::DownlinkFrameItem {
+            // Unconfirmed data down, DevAddr 04030201.
+            phy_payload: vec![0x60, 1, 2, 3, 4, 0, 0, 0],
+            tx_info: Some(gw::DownlinkTxInfo {
+                frequency: 868500000,
+                power: 16,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        polarization_inversion: true,
+                        ..Default::default()
+                    })),
+                }),
+                timing: Some(gw::Timing {
+                    parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                        delay: Some(pbjson_types::Duration {
+                            seconds: 3,
+                            ..Default::default()
+                        }),
+                    })),
+                }),
+                context: vec![1, 2, 3, 1, 2, 3, 4, 0, 123],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Publish downlink command.
+    {
+        let mut cmd_sock = common::FORWARDER_COMMAND_SOCK.get().unwrap().lock().await;
+        cmd_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("down"),
+                    bytes::Bytes::from(down.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the wrapped downlink to be received by the mesh concentratord, targeting the
+    // Relay Gateway in the downlink context.
+    let down: gw::DownlinkFrame = {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    let down_item = down.items.first().unwrap();
+    let mesh_packet = packets::MeshPacket::from_slice(&down_item.phy_payload).unwrap();
+
+    match mesh_packet.payload {
+        packets::Payload::Downlink(v) => {
+            assert_eq!([1, 2, 3, 4], v.relay_id);
+            assert_eq!(123, v.metadata.uplink_id);
+        }
+        _ => panic!("Expected Downlink payload"),
+    }
+}
@@ -0,0 +1,109 @@
+#[macro_use]
+extern crate anyhow;
+
+use std::time::Duration;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway receives two downlinks for the
+    same end-device (DevAddr), while the downlink rate limit allows one downlink per
+    interval. The second downlink must not be forwarded to the mesh and the Forwarder
+    must receive a QUEUE_FULL TxAck.
+*/
+#[tokio::test]
+async fn test_border_gateway_downlink_mesh_rate_limit() {
+    let mut conf = common::get_config(true);
+    conf.mesh.downlink_rate_limit.enabled = true;
+    conf.mesh.downlink_rate_limit.max_downlinks = 1;
+    conf.mesh.downlink_rate_limit.interval = Duration::from_secs(60);
+    common::setup_with_config(conf).await;
+
+    for downlink_id in [1, 2] {
+        let down = gw::DownlinkFrame {
+            downlink_id,
+            gateway_id: "0101010101010101".into(),
+            items: vec![gw::DownlinkFrameItem {
+                // Unconfirmed data down, DevAddr 04030201.
+                phy_payload: vec![0x60, 1, 2, 3, 4, 0, 0, 0],
+                tx_info: Some(gw::DownlinkTxInfo {
+                    frequency: 868500000,
+                    power: 16,
+                    modulation: Some(gw::Modulation {
+                        parameters: Some(gw::modulation::Parameters::Lora(
+                            gw::LoraModulationInfo {
+                                bandwidth: 125000,
+                                spreading_factor: 12,
+                                code_rate: gw::CodeRate::Cr45.into(),
+                                polarization_inversion: true,
+                                ..Default::default()
+                            },
+                        )),
+                    }),
+                    timing: Some(gw::Timing {
+                        parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                            delay: Some(pbjson_types::Duration {
+                                seconds: 3,
+                                ..Default::default()
+                            }),
+                        })),
+                    }),
+                    context: vec![1, 2, 3, 1, 2, 3, 4, 0, downlink_id as u8],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut cmd_sock = common::FORWARDER_COMMAND_SOCK.get().unwrap().lock().await;
+        cmd_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("down"),
+                    bytes::Bytes::from(down.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // We expect only the first downlink to be received by the mesh concentratord.
+        if downlink_id == 1 {
+            let mut mesh_cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+                .get()
+                .unwrap()
+                .lock()
+                .await;
+            let msg = mesh_cmd_sock.recv().await.unwrap();
+
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("down", cmd);
+
+            let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+            assert_eq!(1, down.downlink_id);
+        }
+
+        let msg = cmd_sock.recv().await.unwrap();
+        let tx_ack = gw::DownlinkTxAck::decode(msg.get(0).cloned().unwrap()).unwrap();
+
+        if downlink_id == 2 {
+            assert_eq!(
+                gw::DownlinkTxAck {
+                    gateway_id: "0101010101010101".into(),
+                    downlink_id: 2,
+                    items: vec![gw::DownlinkTxAckItem {
+                        status: gw::TxAckStatus::QueueFull.into(),
+                    }],
+                    ..Default::default()
+                },
+                tx_ack
+            );
+        }
+    }
+}
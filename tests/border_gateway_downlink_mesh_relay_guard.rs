@@ -0,0 +1,99 @@
+#[macro_use]
+extern crate anyhow;
+
+use std::time::{Duration, Instant};
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway receives two downlinks for the
+    same Relay Gateway back-to-back. With the relay downlink guard enabled, the second
+    mesh transmission must be delayed by the airtime of the first + the margin.
+*/
+#[tokio::test]
+async fn test_border_gateway_downlink_mesh_relay_guard() {
+    let mut conf = common::get_config(true);
+    conf.mesh.relay_downlink_guard.enabled = true;
+    conf.mesh.relay_downlink_guard.margin = Duration::from_millis(500);
+    common::setup_with_config(conf).await;
+
+    // The second downlink must be delayed by at least the margin.
+    let mut received_at: Vec<(u32, Instant)> = Vec::new();
+
+    for downlink_id in [1, 2] {
+        let down = gw::DownlinkFrame {
+            downlink_id,
+            gateway_id: "0101010101010101".into(),
+            items: vec![gw::DownlinkFrameItem {
+                phy_payload: vec![9, 8, 7, 6],
+                tx_info: Some(gw::DownlinkTxInfo {
+                    frequency: 868500000,
+                    power: 16,
+                    modulation: Some(gw::Modulation {
+                        parameters: Some(gw::modulation::Parameters::Lora(
+                            gw::LoraModulationInfo {
+                                bandwidth: 125000,
+                                spreading_factor: 12,
+                                code_rate: gw::CodeRate::Cr45.into(),
+                                polarization_inversion: true,
+                                ..Default::default()
+                            },
+                        )),
+                    }),
+                    timing: Some(gw::Timing {
+                        parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                            delay: Some(pbjson_types::Duration {
+                                seconds: 3,
+                                ..Default::default()
+                            }),
+                        })),
+                    }),
+                    context: vec![1, 2, 3, 1, 2, 3, 4, 0, downlink_id as u8],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut cmd_sock = common::FORWARDER_COMMAND_SOCK.get().unwrap().lock().await;
+        cmd_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("down"),
+                    bytes::Bytes::from(down.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // We expect the wrapped downlink to be received by the mesh concentratord.
+        {
+            let mut mesh_cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+                .get()
+                .unwrap()
+                .lock()
+                .await;
+            let msg = mesh_cmd_sock.recv().await.unwrap();
+
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("down", cmd);
+
+            let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+            received_at.push((down.downlink_id, Instant::now()));
+        }
+
+        // Wait for the downlink response.
+        let _ = cmd_sock.recv().await.unwrap();
+    }
+
+    assert_eq!(1, received_at[0].0);
+    assert_eq!(2, received_at[1].0);
+    assert!(received_at[1].1 - received_at[0].1 >= Duration::from_millis(450));
+}
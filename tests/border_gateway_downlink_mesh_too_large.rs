@@ -0,0 +1,84 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway receives a downlink which,
+    after mesh encapsulation, exceeds the max_payload_size. The downlink must
+    not be forwarded to the mesh and the Forwarder must receive an error TxAck.
+*/
+#[tokio::test]
+async fn test_border_gateway_downlink_mesh_too_large() {
+    let mut conf = common::get_config(true);
+    conf.mesh.max_payload_size = 20;
+    common::setup_with_config(conf).await;
+
+    let down = gw::DownlinkFrame {
+        downlink_id: 1,
+        gateway_id: "0101010101010101".into(),
+        items: vec![gw::DownlinkFrameItem {
+            phy_payload: vec![0; 20],
+            tx_info: Some(gw::DownlinkTxInfo {
+                frequency: 868500000,
+                power: 16,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        polarization_inversion: true,
+                        ..Default::default()
+                    })),
+                }),
+                timing: Some(gw::Timing {
+                    parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+                        delay: Some(pbjson_types::Duration {
+                            seconds: 3,
+                            ..Default::default()
+                        }),
+                    })),
+                }),
+                context: vec![1, 2, 3, 1, 2, 3, 4, 0, 123],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Publish downlink command and read the TxAck.
+    let tx_ack: gw::DownlinkTxAck = {
+        let mut cmd_sock = common::FORWARDER_COMMAND_SOCK.get().unwrap().lock().await;
+        cmd_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("down"),
+                    bytes::Bytes::from(down.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let msg = cmd_sock.recv().await.unwrap();
+        gw::DownlinkTxAck::decode(msg.get(0).cloned().unwrap()).unwrap()
+    };
+
+    assert_eq!(
+        gw::DownlinkTxAck {
+            gateway_id: "0101010101010101".into(),
+            downlink_id: 1,
+            items: vec![gw::DownlinkTxAckItem {
+                status: gw::TxAckStatus::InternalError.into(),
+            }],
+            ..Default::default()
+        },
+        tx_ack
+    );
+}
@@ -0,0 +1,101 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::proto;
+use zeromq::{SocketRecv, SocketSend};
+
+mod common;
+
+/*
+   This tests the scenario when the event replay buffer is enabled and the
+   forwarder requests the events since a given sequence number. In this case,
+   the Border Gateway must return the buffered events after that sequence
+   number.
+*/
+#[tokio::test]
+async fn test_border_gateway_get_events() {
+    let mut conf = common::get_config(true);
+    conf.mesh.proxy_api.event_replay_buffer_size = 8;
+    common::setup_with_config(conf).await;
+
+    let ups: Vec<gw::UplinkFrame> = (1..=2)
+        .map(|i| gw::UplinkFrame {
+            phy_payload: vec![i, 2, 3, 4, 5, 6, 7, 8],
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                gateway_id: "0101010101010101".to_string(),
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .collect();
+
+    for up in &ups {
+        // Publish uplink event.
+        {
+            let mut event_sock = common::BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // We expect to receive the uplink.
+        {
+            let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+            let msg = event_sock.recv().await.unwrap();
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("up", cmd);
+        }
+    }
+
+    // Request the events since the first event.
+    let resp = {
+        let mut cmd_sock = common::FORWARDER_COMMAND_SOCK.get().unwrap().lock().await;
+        cmd_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("get_events"),
+                    bytes::Bytes::from(proto::GetEventsRequest { since_seq: 1 }.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let msg = cmd_sock.recv().await.unwrap();
+        proto::GetEventsResponse::decode(msg.get(0).cloned().unwrap()).unwrap()
+    };
+
+    assert_eq!(
+        proto::GetEventsResponse {
+            events: vec![proto::ProxyEvent {
+                seq: 2,
+                event: "up".into(),
+                payload: ups[1].encode_to_vec(),
+            }],
+        },
+        resp
+    );
+}
@@ -0,0 +1,127 @@
+#[macro_use]
+extern crate anyhow;
+
+use std::time::Duration;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::{packets, proto, topology};
+
+mod common;
+
+/*
+    This tests the scenario that the Border Gateway receives a mesh event
+    packet containing a boot event, including the summary of the panic that
+    preceded the restart. The Border Gateway must record the boot in
+    the topology and publish the mesh_relay_boot event.
+*/
+#[tokio::test]
+async fn test_border_gateway_mesh_event_boot() {
+    common::setup(true).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            relay_id: [2, 2, 2, 2],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            events: vec![packets::Event::Boot(packets::BootEvent {
+                reason: packets::BootReason::CrashRecovery,
+                features: 0x40,
+                boot_id: [5, 6, 7, 8],
+                version: "4.0.0".into(),
+                crash: Some(packets::CrashSummary {
+                    panic_hash: [1, 2, 3, 4],
+                    uptime: 3600,
+                }),
+            })],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -100,
+            snr: 2.5,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the MeshRelayBootEvent to be received by the forwarder.
+    {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+
+        let msg = event_sock.recv().await.unwrap();
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("mesh_relay_boot", cmd);
+        assert_eq!(
+            proto::MeshRelayBootEvent {
+                gateway_id: "0101010101010101".to_string(),
+                relay_id: vec![2, 2, 2, 2],
+                boot: Some(proto::BootEvent {
+                    reason: proto::BootReason::CrashRecovery.into(),
+                    features: 0x40,
+                    version: "4.0.0".into(),
+                    crash: Some(proto::CrashSummary {
+                        panic_hash: vec![1, 2, 3, 4],
+                        uptime: 3600,
+                    }),
+                    boot_id: vec![5, 6, 7, 8],
+                }),
+            },
+            proto::MeshRelayBootEvent::decode(msg.get(1).cloned().unwrap()).unwrap()
+        );
+    }
+
+    let relays = topology::get_relays();
+    assert_eq!(1, relays.len());
+    let relay = &relays[0];
+    assert_eq!([2, 2, 2, 2], relay.relay_id);
+
+    let boot = relay.boot.as_ref().unwrap();
+    assert_eq!("crash_recovery", boot.reason);
+    assert_eq!(vec!["tdma"], boot.features);
+    assert_eq!("4.0.0", boot.version);
+
+    let crash = boot.crash.as_ref().unwrap();
+    assert_eq!([1, 2, 3, 4], crash.panic_hash);
+    assert_eq!(Duration::from_secs(3600), crash.uptime);
+}
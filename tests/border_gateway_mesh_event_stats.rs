@@ -0,0 +1,120 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario that the Border Gateway receives a mesh event
+    packet containing a stats event. The Border Gateway must forward these
+    as gateway stats of the Relay Gateway.
+*/
+#[tokio::test]
+async fn test_border_gateway_mesh_event_stats() {
+    common::setup(true).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            relay_id: [2, 2, 2, 2],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            events: vec![packets::Event::Stats(packets::StatsEvent {
+                gateway_id: [2, 2, 2, 2, 2, 2, 2, 2],
+                rx_packets_received: 10,
+                rx_packets_received_ok: 9,
+                tx_packets_received: 2,
+                tx_packets_emitted: 1,
+                tx_airtime: 72000,
+                uplinks_acked: 0,
+                uplinks_not_acked: 0,
+                rx_packets_per_frequency: vec![packets::FrequencyCount {
+                    frequency: 868100000,
+                    count: 10,
+                }],
+                tx_packets_per_frequency: vec![packets::FrequencyCount {
+                    frequency: 869525000,
+                    count: 1,
+                }],
+            })],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -100,
+            snr: 2.5,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the stats of the Relay Gateway to be received by the forwarder.
+    let stats: gw::GatewayStats = {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+
+        let msg = event_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("stats", cmd);
+
+        gw::GatewayStats::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    assert_eq!("0202020202020202", stats.gateway_id);
+    assert_eq!(10, stats.rx_packets_received);
+    assert_eq!(9, stats.rx_packets_received_ok);
+    assert_eq!(2, stats.tx_packets_received);
+    assert_eq!(1, stats.tx_packets_emitted);
+    assert_eq!(Some(&10), stats.rx_packets_per_frequency.get(&868100000));
+    assert_eq!(Some(&1), stats.tx_packets_per_frequency.get(&869525000));
+    assert_eq!(
+        Some(&"02020202".to_string()),
+        stats.metadata.get("relay_id")
+    );
+    assert_eq!(
+        Some(&"2.00".to_string()),
+        stats.metadata.get("mesh_tx_airtime_pct")
+    );
+}
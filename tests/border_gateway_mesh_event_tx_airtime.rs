@@ -0,0 +1,106 @@
+#[macro_use]
+extern crate anyhow;
+
+use std::time::Duration;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::sleep;
+use zeromq::SocketSend;
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::{packets, stats, topology};
+
+mod common;
+
+/*
+    This tests the scenario that the Border Gateway receives a mesh event
+    packet containing a TX airtime event. The Border Gateway must record the
+    TX airtime in the relay registry and add it to the gateway stats.
+*/
+#[tokio::test]
+async fn test_border_gateway_mesh_event_tx_airtime() {
+    common::setup(true).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 2,
+            key_index: 0,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            relay_id: [2, 2, 2, 2],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            events: vec![packets::Event::TxAirtime(packets::TxAirtimeEvent {
+                airtime: 72000,
+            })],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -100,
+            snr: 2.5,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // The event is handled asynchronously.
+    let mut relays = vec![];
+    for _ in 0..50 {
+        relays = topology::get_relays();
+        if !relays.is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    assert_eq!(1, relays.len());
+    let relay = &relays[0];
+    assert_eq!([2, 2, 2, 2], relay.relay_id);
+    assert_eq!(2, relay.hop_count);
+    assert_eq!(-100, relay.rssi);
+
+    let tx_airtime = relay.tx_airtime.as_ref().unwrap();
+    assert_eq!(2.0, tx_airtime.airtime_pct);
+
+    let mut pl = gw::GatewayStats::default();
+    stats::add_mesh_tx_airtime_stats(&mut pl);
+    assert_eq!(
+        Some(&"2.00".to_string()),
+        pl.metadata.get("mesh_relay_tx_airtime_pct_02020202")
+    );
+}
@@ -1,5 +1,3 @@
-use std::time::UNIX_EPOCH;
-
 #[macro_use]
 extern crate anyhow;
 
@@ -24,10 +22,12 @@ async fn test_border_gateway_mesh_heartbeat() {
         mhdr: packets::MHDR {
             payload_type: packets::PayloadType::Heartbeat,
             hop_count: 1,
+            key_index: 0,
         },
         payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
             relay_id: [2, 2, 2, 2],
-            timestamp: UNIX_EPOCH,
+            timestamp: 0,
+            timestamp_monotonic: false,
             relay_path: vec![
                 packets::RelayPath {
                     relay_id: [1, 2, 3, 4],
@@ -40,6 +40,7 @@ async fn test_border_gateway_mesh_heartbeat() {
                     snr: -12,
                 },
             ],
+            config_checksum: None,
         }),
         mic: None,
     };
@@ -95,7 +96,10 @@ async fn test_border_gateway_mesh_heartbeat() {
     assert_eq!(
         gw::MeshHeartbeat {
             gateway_id: "0101010101010101".to_string(),
-            time: Some(UNIX_EPOCH.into()),
+            time: Some(pbjson_types::Timestamp {
+                seconds: 0,
+                nanos: 0,
+            }),
             relay_id: "02020202".to_string(),
             relay_path: vec![
                 gw::MeshHeartbeatRelayPath {
@@ -0,0 +1,101 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::config;
+use chirpstack_gateway_mesh::{heartbeat, packets, proto};
+
+mod common;
+
+/*
+    This tests the scenario that the Border Gateway receives a mesh heartbeat
+    packet with a config checksum that does not match its own configuration.
+    The Border Gateway must publish a mesh_config_mismatch event, before
+    forwarding the heartbeat to the Forwarder application.
+*/
+#[tokio::test]
+async fn test_border_gateway_mesh_heartbeat_config_mismatch() {
+    common::setup(true).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Heartbeat,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+            relay_id: [2, 2, 2, 2],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_path: vec![],
+            config_checksum: Some([1, 2, 3, 4]),
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let expected_config_checksum = heartbeat::get_config_checksum(&config::get())
+        .unwrap()
+        .to_vec();
+    assert_ne!(vec![1, 2, 3, 4], expected_config_checksum);
+
+    // We expect the MeshConfigMismatchEvent and MeshHeartbeat to be received by the forwarder.
+    let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+
+    let msg = event_sock.recv().await.unwrap();
+    let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+    assert_eq!("mesh_config_mismatch", cmd);
+    assert_eq!(
+        proto::MeshConfigMismatchEvent {
+            gateway_id: "0101010101010101".to_string(),
+            relay_id: vec![2, 2, 2, 2],
+            config_checksum: vec![1, 2, 3, 4],
+            expected_config_checksum,
+        },
+        proto::MeshConfigMismatchEvent::decode(msg.get(1).cloned().unwrap()).unwrap()
+    );
+
+    let msg = event_sock.recv().await.unwrap();
+    let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+    assert_eq!("mesh_heartbeat", cmd);
+}
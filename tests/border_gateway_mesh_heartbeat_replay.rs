@@ -0,0 +1,100 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario that the Border Gateway receives two mesh heartbeat
+    packets of the same Relay Gateway, of which the second has a timestamp older
+    than the replay window (e.g. a captured heartbeat that is transmitted again).
+    With replay protection enabled, the Border Gateway must only forward the
+    first heartbeat to the Forwarder application.
+*/
+#[tokio::test]
+async fn test_border_gateway_mesh_heartbeat_replay() {
+    let mut conf = common::get_config(true);
+    conf.mesh.packet_format_version = 2;
+    conf.mesh.replay_protection.enabled = true;
+    common::setup_with_config(conf).await;
+
+    for (i, timestamp) in [1_700_000_002_000, 1_699_999_000_000]
+        .into_iter()
+        .enumerate()
+    {
+        let mut packet = packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Heartbeat,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+                relay_id: [2, 2, 2, 2],
+                timestamp,
+                timestamp_monotonic: false,
+                relay_path: vec![],
+                config_checksum: None,
+            }),
+            mic: None,
+        };
+        packet.set_mic(Aes128Key::null()).unwrap();
+
+        let up = gw::UplinkFrame {
+            phy_payload: packet.to_vec().unwrap(),
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Publish uplink event.
+        {
+            let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        if i == 0 {
+            let msg = event_sock.recv().await.unwrap();
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("mesh_heartbeat", cmd);
+
+            let mesh_heartbeat = gw::MeshHeartbeat::decode(msg.get(1).cloned().unwrap()).unwrap();
+            assert_eq!("02020202", mesh_heartbeat.relay_id);
+        } else {
+            // As the second heartbeat has been dropped, receiving from the event socket should
+            // timeout.
+            let resp = timeout(Duration::from_secs(1), event_sock.recv()).await;
+            assert!(resp.is_err());
+        }
+    }
+}
@@ -0,0 +1,143 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use zeromq::SocketSend;
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario that the Border Gateway receives a mesh heartbeat
+    packet, with the webhook configured. The Border Gateway will post the
+    heartbeat event and, as the Relay Gateway is new, the topology event to
+    the webhook.
+*/
+#[tokio::test]
+async fn test_border_gateway_mesh_heartbeat_webhook() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    let mut conf = common::get_config(true);
+    conf.webhook.url = format!("http://{}/events", listener.local_addr().unwrap());
+    conf.webhook
+        .headers
+        .insert("Authorization".into(), "Bearer secret".into());
+    common::setup_with_config(conf).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Heartbeat,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+            relay_id: [2, 2, 2, 2],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_path: vec![packets::RelayPath {
+                relay_id: [1, 2, 3, 4],
+                rssi: -120,
+                snr: -12,
+            }],
+            config_checksum: None,
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -80,
+            snr: 7.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the heartbeat event, followed by the topology event.
+    for expected_event in ["heartbeat", "topology"] {
+        let (head, body) = recv_request(&listener).await;
+        assert!(head.starts_with("POST /events HTTP/1.1\r\n"));
+        assert!(head.contains("\r\nauthorization: Bearer secret\r\n"));
+
+        let event: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(expected_event, event["event"]);
+        assert_eq!("0101010101010101", event["gateway_id"]);
+        assert_eq!("02020202", event["relay_id"]);
+        assert_eq!(1, event["hop_count"]);
+        assert_eq!(-80, event["rssi"]);
+        assert_eq!("01020304", event["relay_path"][0]["relay_id"]);
+    }
+}
+
+// Accepts a single request and responds with 204. It returns the request head and body.
+async fn recv_request(listener: &TcpListener) -> (String, Vec<u8>) {
+    let (mut stream, _) = listener.accept().await.unwrap();
+
+    let mut b: Vec<u8> = vec![];
+    let mut buf = [0; 1024];
+    let (head, body) = loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        assert_ne!(0, n);
+        b.extend_from_slice(&buf[..n]);
+
+        let s = String::from_utf8_lossy(&b).to_string();
+        if let Some(i) = s.find("\r\n\r\n") {
+            let content_length: usize = s[..i]
+                .lines()
+                .find_map(|v| {
+                    v.split_once(": ")
+                        .filter(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+                        .map(|(_, v)| v)
+                })
+                .unwrap()
+                .parse()
+                .unwrap();
+
+            if b.len() - (i + 4) >= content_length {
+                break (s[..i + 4].to_string(), b[i + 4..].to_vec());
+            }
+        }
+    };
+
+    stream
+        .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+
+    (head, body)
+}
@@ -0,0 +1,126 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::{helpers, packets, proto};
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway receives a mesh encapsulated
+    LoRaWAN uplink frame, with mesh packet events enabled. The Border Gateway
+    must publish the mesh packet as mesh_packet event, before forwarding the
+    unwrapped LoRaWAN frame.
+*/
+#[tokio::test]
+async fn test_border_gateway_mesh_packet_event() {
+    let mut conf = common::get_config(true);
+    conf.mesh.proxy_api.mesh_packet_events = true;
+    common::setup_with_config(conf).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Uplink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                rssi: -60,
+                snr: 6,
+                channel: 2,
+                border_id: 0,
+            },
+            relay_id: [1, 2, 3, 4],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![9, 8, 7, 6],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -80,
+            snr: 7.5,
+            gw_time: Some(pbjson_types::Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect to receive the mesh packet event.
+    let mesh_packet_event: proto::MeshPacketEvent = {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        let msg = event_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("mesh_packet", cmd);
+
+        proto::MeshPacketEvent::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    assert_eq!(
+        proto::MeshPacketEvent {
+            gateway_id: "0101010101010101".into(),
+            mesh_packet: Some(helpers::mesh_packet_to_proto(&packet)),
+            phy_payload: up.phy_payload.clone(),
+            frequency: 868100000,
+            rssi: -80,
+            snr: 7.5,
+            time: Some(pbjson_types::Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            }),
+        },
+        mesh_packet_event
+    );
+
+    // We expect to receive the unwrapped uplink after the mesh packet event.
+    {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        let msg = event_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("up", cmd);
+    }
+}
@@ -0,0 +1,87 @@
+#[macro_use]
+extern crate anyhow;
+
+use std::time::Duration;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::sleep;
+use zeromq::{Socket, SocketRecv, SocketSend};
+
+mod common;
+
+/*
+   This tests the scenario when the Border Gateway receives a regular LoRaWAN
+   uplink frame, with the mirror event socket enabled. The uplink must be
+   published on both the proxy API event socket and the mirror socket.
+*/
+#[tokio::test]
+async fn test_border_gateway_uplink_lora_mirror() {
+    let mut conf = common::get_config(true);
+    conf.mesh.proxy_api.mirror_event_bind = "ipc:///tmp/gateway_mesh_mirror_event".into();
+    let mirror_event_bind = conf.mesh.proxy_api.mirror_event_bind.clone();
+    common::setup_with_config(conf).await;
+
+    let mut mirror_sock = zeromq::SubSocket::new();
+    mirror_sock.connect(&mirror_event_bind).await.unwrap();
+    mirror_sock.subscribe("").await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    let up = gw::UplinkFrame {
+        phy_payload: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect to receive the same uplink on the forwarder socket.
+    let msg = {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock.recv().await.unwrap()
+    };
+
+    let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+    assert_eq!("up", cmd);
+
+    let up_received = gw::UplinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+    assert_eq!(up, up_received);
+
+    // And a copy on the mirror socket.
+    let msg = mirror_sock.recv().await.unwrap();
+
+    let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+    assert_eq!("up", cmd);
+
+    let up_received = gw::UplinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+    assert_eq!(up, up_received);
+}
@@ -0,0 +1,75 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+mod common;
+
+/*
+   This tests the scenario when the Border Gateway receives a regular LoRaWAN
+   uplink frame, with the event sequence number frame enabled. The uplink
+   published on the proxy API must have a third frame containing the sequence
+   number.
+*/
+#[tokio::test]
+async fn test_border_gateway_uplink_lora_seq() {
+    let mut conf = common::get_config(true);
+    conf.mesh.proxy_api.event_seq_frame = true;
+    common::setup_with_config(conf).await;
+
+    let up = gw::UplinkFrame {
+        phy_payload: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect to receive the same uplink, with sequence number 1.
+    let msg = {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock.recv().await.unwrap()
+    };
+
+    assert_eq!(3, msg.len());
+
+    let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+    assert_eq!("up", cmd);
+
+    let up_received = gw::UplinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+    assert_eq!(up, up_received);
+
+    let seq = msg.get(2).map(|v| v.to_vec()).unwrap();
+    assert_eq!(1u64.to_be_bytes().to_vec(), seq);
+}
@@ -23,6 +23,7 @@ async fn test_border_gateway_uplink_mesh() {
         mhdr: packets::MHDR {
             payload_type: packets::PayloadType::Uplink,
             hop_count: 1,
+            key_index: 0,
         },
         payload: packets::Payload::Uplink(packets::UplinkPayload {
             metadata: packets::UplinkMetadata {
@@ -31,8 +32,11 @@ async fn test_border_gateway_uplink_mesh() {
                 rssi: -60,
                 snr: 6,
                 channel: 2,
+                border_id: 0,
             },
             relay_id: [1, 2, 3, 4],
+            timestamp: None,
+            timestamp_monotonic: false,
             phy_payload: vec![9, 8, 7, 6],
         }),
         mic: None,
@@ -54,6 +58,8 @@ async fn test_border_gateway_uplink_mesh() {
         }),
         rx_info: Some(gw::UplinkRxInfo {
             crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -110,
+            snr: -3.5,
             ..Default::default()
         }),
         ..Default::default()
@@ -118,6 +124,8 @@ async fn test_border_gateway_uplink_mesh() {
             metadata: [
                 ("relay_id".to_string(), "01020304".to_string()),
                 ("hop_count".to_string(), "1".to_string()),
+                ("last_hop_rssi".to_string(), "-110".to_string()),
+                ("last_hop_snr".to_string(), "-3.5".to_string()),
             ]
             .iter()
             .cloned()
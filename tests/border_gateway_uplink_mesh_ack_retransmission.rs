@@ -0,0 +1,143 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway (with uplink_ack enabled) receives
+    a relayed uplink, of which the uplink ACK is not received by the Relay Gateway. In
+    this case, the Border Gateway must acknowledge the retransmission of the Relay
+    Gateway again, without forwarding the duplicate uplink to the Forwarder application.
+*/
+#[tokio::test]
+async fn test_border_gateway_uplink_mesh_ack_retransmission() {
+    let mut conf = common::get_config(true);
+    conf.mesh.uplink_ack.enabled = true;
+    common::setup_with_config(conf).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Uplink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                rssi: -60,
+                snr: 6,
+                channel: 2,
+                border_id: 0,
+            },
+            relay_id: [1, 2, 3, 4],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![9, 8, 7, 6],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -110,
+            snr: -3.5,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // The first uplink ACK is lost, after which the Relay Gateway retransmits the uplink.
+    for i in 0..2 {
+        // Publish uplink event.
+        {
+            let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // We expect to receive the unwrapped uplink to be received by the forwarder.
+        if i == 0 {
+            let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+            let msg = event_sock.recv().await.unwrap();
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("up", cmd);
+
+            let up = gw::UplinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+            assert_eq!(vec![9, 8, 7, 6], up.phy_payload);
+        }
+
+        // We expect the uplink to be acknowledged (both times).
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+        let down_item = down.items.first().unwrap();
+        let mesh_packet = packets::MeshPacket::from_slice(&down_item.phy_payload).unwrap();
+        if let packets::Payload::Event(v) = &mesh_packet.payload {
+            assert_eq!(
+                vec![packets::Event::UplinkAck(packets::UplinkAckEvent {
+                    relay_id: [1, 2, 3, 4],
+                    uplink_id: 123,
+                })],
+                v.events
+            );
+        } else {
+            panic!("Expected Event payload");
+        }
+
+        let tx_ack = gw::DownlinkTxAck {
+            downlink_id: down.downlink_id,
+            items: vec![gw::DownlinkTxAckItem {
+                status: gw::TxAckStatus::Ok.into(),
+            }],
+            ..Default::default()
+        };
+        cmd_sock.send(tx_ack.encode_to_vec().into()).await.unwrap();
+    }
+
+    // The retransmission is a duplicate, thus receiving from the forwarder event socket should
+    // timeout.
+    {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        let resp = timeout(Duration::from_secs(1), event_sock.recv()).await;
+        assert!(resp.is_err());
+    }
+}
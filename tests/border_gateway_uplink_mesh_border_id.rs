@@ -0,0 +1,95 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway is configured with a Border ID
+    and receives mesh encapsulated uplinks targeted to a different and to its own
+    Border ID. Only the latter must be forwarded to the Forwarder application.
+*/
+#[tokio::test]
+async fn test_border_gateway_uplink_mesh_border_id() {
+    let mut conf = common::get_config(true);
+    conf.mesh.border_id = 1;
+    common::setup_with_config(conf).await;
+
+    for (uplink_id, border_id) in [(123, 2), (124, 1)] {
+        let mut packet = packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Uplink,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Uplink(packets::UplinkPayload {
+                metadata: packets::UplinkMetadata {
+                    uplink_id,
+                    dr: 0,
+                    rssi: -60,
+                    snr: 6,
+                    channel: 2,
+                    border_id,
+                },
+                relay_id: [1, 2, 3, 4],
+                timestamp: None,
+                timestamp_monotonic: false,
+                phy_payload: vec![border_id],
+            }),
+            mic: None,
+        };
+        packet.set_mic(Aes128Key::null()).unwrap();
+
+        let up = gw::UplinkFrame {
+            phy_payload: packet.to_vec().unwrap(),
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect to only receive the uplink targeted to Border ID 1.
+    let up: gw::UplinkFrame = {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        let msg = event_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("up", cmd);
+
+        gw::UplinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    assert_eq!(vec![1], up.phy_payload);
+}
@@ -0,0 +1,102 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario when the Border Gateway receives mesh encapsulated
+    LoRaWAN uplink frames, with the filters applied to relayed uplinks. The
+    Border Gateway must only forward the uplink matching the DevAddr filter.
+*/
+#[tokio::test]
+async fn test_border_gateway_uplink_mesh_filters() {
+    let mut conf = common::get_config(true);
+    conf.mesh.filters.dev_addr_prefixes = vec![lrwn_filters::DevAddrPrefix::new([1, 0, 0, 0], 8)];
+    conf.mesh.filters.relayed_uplinks = true;
+    common::setup_with_config(conf).await;
+
+    // The first uplink (DevAddr 02030405) does not match the filter, the second uplink
+    // (DevAddr 01020304) does.
+    for (uplink_id, phy_payload) in [
+        (1, vec![0x40, 0x05, 0x04, 0x03, 0x02, 0x00]),
+        (2, vec![0x40, 0x04, 0x03, 0x02, 0x01, 0x00]),
+    ] {
+        let mut packet = packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Uplink,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Uplink(packets::UplinkPayload {
+                metadata: packets::UplinkMetadata {
+                    uplink_id,
+                    dr: 0,
+                    rssi: -60,
+                    snr: 6,
+                    channel: 2,
+                    border_id: 0,
+                },
+                relay_id: [1, 2, 3, 4],
+                timestamp: None,
+                timestamp_monotonic: false,
+                phy_payload,
+            }),
+            mic: None,
+        };
+        packet.set_mic(Aes128Key::null()).unwrap();
+
+        let up = gw::UplinkFrame {
+            phy_payload: packet.to_vec().unwrap(),
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Publish uplink event.
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect only the matching uplink to be received by the forwarder.
+    let up: gw::UplinkFrame = {
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        let msg = event_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("up", cmd);
+
+        gw::UplinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    assert_eq!(vec![0x40, 0x04, 0x03, 0x02, 0x01, 0x00], up.phy_payload);
+}
@@ -0,0 +1,103 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario that the Border Gateway receives two mesh uplink
+    packets of the same Relay Gateway, of which the second does not contain a
+    timestamp (version 1 packet). With replay protection enabled, the Border
+    Gateway must only forward the first uplink to the Forwarder application.
+*/
+#[tokio::test]
+async fn test_border_gateway_uplink_mesh_replay() {
+    let mut conf = common::get_config(true);
+    conf.mesh.packet_format_version = 2;
+    conf.mesh.replay_protection.enabled = true;
+    common::setup_with_config(conf).await;
+
+    for (i, timestamp) in [Some(1_700_000_002_000), None].into_iter().enumerate() {
+        let mut packet = packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Uplink,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Uplink(packets::UplinkPayload {
+                metadata: packets::UplinkMetadata {
+                    uplink_id: 123,
+                    dr: 0,
+                    rssi: -60,
+                    snr: 6,
+                    channel: 2,
+                    border_id: 0,
+                },
+                relay_id: [2, 2, 2, 2],
+                timestamp,
+                timestamp_monotonic: false,
+                phy_payload: vec![9, 8, 7, i as u8],
+            }),
+            mic: None,
+        };
+        packet.set_mic(Aes128Key::null()).unwrap();
+
+        let up = gw::UplinkFrame {
+            phy_payload: packet.to_vec().unwrap(),
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Publish uplink event.
+        {
+            let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut event_sock = common::FORWARDER_EVENT_SOCK.get().unwrap().lock().await;
+        if i == 0 {
+            let msg = event_sock.recv().await.unwrap();
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("up", cmd);
+
+            let up = gw::UplinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+            assert_eq!(vec![9, 8, 7, 0], up.phy_payload);
+        } else {
+            // As the second uplink has been dropped, receiving from the event socket should
+            // timeout.
+            let resp = timeout(Duration::from_secs(1), event_sock.recv()).await;
+            assert!(resp.is_err());
+        }
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use std::time::Duration;
 
 use once_cell::sync::OnceCell;
@@ -18,7 +20,11 @@ pub static MESH_BACKEND_EVENT_SOCK: OnceCell<Mutex<zeromq::PubSocket>> = OnceCel
 pub static MESH_BACKEND_COMMAND_SOCK: OnceCell<Mutex<zeromq::RepSocket>> = OnceCell::new();
 
 pub async fn setup(border_gateway: bool) {
-    let conf = get_config(border_gateway);
+    setup_with_config(get_config(border_gateway)).await;
+}
+
+pub async fn setup_with_config(conf: Configuration) {
+    let border_gateway = conf.mesh.border_gateway;
     let _ = config::set(conf);
     init_backend(border_gateway).await;
     init_mesh().await;
@@ -42,6 +48,7 @@ pub fn get_config(border_gateway: bool) -> Configuration {
             proxy_api: config::ProxyApi {
                 event_bind: "ipc:///tmp/gateway_mesh_event".into(),
                 command_bind: "ipc:///tmp/gateway_mesh_command".into(),
+                ..Default::default()
             },
             max_hop_count: 3,
             ..Default::default()
@@ -55,6 +62,9 @@ pub fn get_config(border_gateway: bool) -> Configuration {
                 event_url: "ipc:///tmp/mesh_concentratord_event".into(),
                 command_url: "ipc:///tmp/mesh_concentratord_command".into(),
             },
+            // The mock Concentratord does not always respond to commands.
+            command_retries: 0,
+            ..Default::default()
         },
         mappings: config::Mappings {
             channels: vec![868100000, 868300000, 868500000],
@@ -159,8 +169,12 @@ async fn init_backend(border_gateway: bool) {
 }
 
 async fn init_mesh() {
-    chirpstack_gateway_mesh::logging::setup("chirpstack-gateway-mesh", log::Level::Trace, false)
-        .unwrap();
+    chirpstack_gateway_mesh::logging::setup(
+        "chirpstack-gateway-mesh",
+        log::Level::Trace,
+        &Default::default(),
+    )
+    .unwrap();
 
     tokio::spawn({
         let conf = config::get();
@@ -25,6 +25,7 @@ async fn test_relay_gateway_downlink_lora() {
         mhdr: packets::MHDR {
             payload_type: packets::PayloadType::Downlink,
             hop_count: 1,
+            key_index: 0,
         },
         payload: packets::Payload::Downlink(packets::DownlinkPayload {
             metadata: packets::DownlinkMetadata {
@@ -116,7 +117,7 @@ async fn test_relay_gateway_downlink_lora() {
                     }),
                     timing: Some(gw::Timing {
                         parameters: Some(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
-                            delay: Some(prost_types::Duration {
+                            delay: Some(pbjson_types::Duration {
                                 seconds: 5,
                                 nanos: 0
                             }),
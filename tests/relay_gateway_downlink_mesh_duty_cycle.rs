@@ -0,0 +1,107 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::dutycycle;
+
+mod common;
+
+/*
+    This tests the scenario when duty-cycle enforcement is enabled and the Relay Gateway
+    receives a downlink for an other Relay Gateway, while the budget of the band has been
+    used. The downlink must be dropped rather than delayed, as waiting for the budget could
+    exceed the RX delay of the device.
+*/
+#[tokio::test]
+async fn test_relay_gateway_downlink_mesh_duty_cycle() {
+    let mut conf = common::get_config(false);
+    conf.mesh.duty_cycle.enabled = true;
+    conf.mesh.duty_cycle.max_delay = Duration::from_secs(3600);
+
+    // Use the full budget (1% of 1 hour) of the 868.0 - 868.6 MHz band.
+    assert_eq!(
+        Some(Duration::ZERO),
+        dutycycle::reserve_tx(
+            &conf.mesh.duty_cycle,
+            868100000,
+            Duration::from_secs(36),
+            Duration::ZERO
+        )
+    );
+    common::setup_with_config(conf).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Downlink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Downlink(packets::DownlinkPayload {
+            metadata: packets::DownlinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                frequency: 867100000,
+                tx_power: 1,
+                delay: 5,
+            },
+            relay_id: [1, 2, 3, 4],
+            phy_payload: vec![9, 8, 7, 6, 5],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish Uplink
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the downlink to be dropped, thus receiving from the command socket should
+    // timeout.
+    {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let resp = timeout(Duration::from_secs(2), cmd_sock.recv()).await;
+        assert!(resp.is_err());
+    }
+}
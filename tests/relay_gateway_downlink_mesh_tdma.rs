@@ -0,0 +1,117 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::tdma;
+
+mod common;
+
+/*
+    This tests the scenario when TDMA is enabled and the Relay Gateway receives a
+    downlink for an other Relay Gateway, outside its TDMA slot. The downlink must be
+    relayed immediately, as waiting for the slot could exceed the RX delay of the device.
+*/
+#[tokio::test]
+async fn test_relay_gateway_downlink_mesh_tdma() {
+    let mut conf = common::get_config(false);
+    conf.mesh.tdma.enabled = true;
+    common::setup_with_config(conf).await;
+
+    // The slot of this Relay Gateway (02020202) starts in 30 seconds.
+    tdma::record_beacon(
+        &packets::TdmaBeaconEvent {
+            slot_duration: 30000,
+            slot_count: 2,
+            relay_ids: vec![[1, 1, 1, 1], [2, 2, 2, 2]],
+        },
+        [2, 2, 2, 2],
+    );
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Downlink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Downlink(packets::DownlinkPayload {
+            metadata: packets::DownlinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                frequency: 867100000,
+                tx_power: 1,
+                delay: 5,
+            },
+            relay_id: [1, 2, 3, 4],
+            phy_payload: vec![9, 8, 7, 6, 5],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish Uplink
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the downlink to be relayed without waiting for the TDMA slot.
+    let down: gw::DownlinkFrame = {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = timeout(Duration::from_secs(5), cmd_sock.recv())
+            .await
+            .expect("Downlink was delayed")
+            .unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    let down_item = down.items.first().unwrap();
+    let mesh_packet = packets::Packet::from_slice(&down_item.phy_payload).unwrap();
+
+    packet.mhdr.hop_count += 1;
+    packet.set_mic(Aes128Key::null()).unwrap();
+    assert_eq!(packets::Packet::Mesh(packet), mesh_packet);
+}
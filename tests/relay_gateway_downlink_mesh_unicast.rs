@@ -0,0 +1,152 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+
+mod common;
+
+/*
+    This tests the scenario when unicast downlinks are enabled and the Relay Gateway
+    receives a downlink for an other Relay Gateway. The downlink must only be relayed
+    once the Relay Gateway has relayed a packet of the destination Relay Gateway.
+*/
+#[tokio::test]
+async fn test_relay_gateway_downlink_mesh_unicast() {
+    let mut conf = common::get_config(false);
+    conf.mesh.max_hop_count = 3;
+    conf.mesh.unicast_downlinks.enabled = true;
+    common::setup_with_config(conf).await;
+
+    let mut downlink = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Downlink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Downlink(packets::DownlinkPayload {
+            metadata: packets::DownlinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                frequency: 867100000,
+                tx_power: 1,
+                delay: 5,
+            },
+            relay_id: [1, 2, 3, 4],
+            phy_payload: vec![9, 8, 7, 6, 5],
+        }),
+        mic: None,
+    };
+    downlink.set_mic(Aes128Key::null()).unwrap();
+    send_mesh_packet(&downlink).await;
+
+    // The path to relay 01020304 is unknown, thus the downlink must be dropped.
+    {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+
+        let resp = timeout(Duration::from_secs(1), cmd_sock.recv()).await;
+        assert!(resp.is_err());
+    }
+
+    // Relay an uplink of relay 01020304.
+    let mut uplink = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Uplink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                rssi: 0,
+                snr: 0,
+                channel: 0,
+                border_id: 0,
+            },
+            relay_id: [1, 2, 3, 4],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![4, 3, 2, 1],
+        }),
+        mic: None,
+    };
+    uplink.set_mic(Aes128Key::null()).unwrap();
+    send_mesh_packet(&uplink).await;
+    recv_mesh_packet().await;
+
+    // Send the downlink again (with an other uplink ID, to avoid deduplication).
+    if let packets::Payload::Downlink(v) = &mut downlink.payload {
+        v.metadata.uplink_id = 124;
+    }
+    downlink.set_mic(Aes128Key::null()).unwrap();
+    send_mesh_packet(&downlink).await;
+
+    // This relay is now on the path, thus the downlink must be relayed.
+    let mesh_packet = recv_mesh_packet().await;
+    downlink.mhdr.hop_count += 1;
+    downlink.set_mic(Aes128Key::null()).unwrap();
+    assert_eq!(packets::Packet::Mesh(downlink), mesh_packet);
+}
+
+async fn send_mesh_packet(packet: &packets::MeshPacket) {
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".into(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -60,
+            snr: 5.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+    event_sock
+        .send(
+            vec![
+                bytes::Bytes::from("up"),
+                bytes::Bytes::from(up.encode_to_vec()),
+            ]
+            .try_into()
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+}
+
+async fn recv_mesh_packet() -> packets::Packet {
+    let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+        .get()
+        .unwrap()
+        .lock()
+        .await;
+    let msg = cmd_sock.recv().await.unwrap();
+
+    let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+    assert_eq!("down", cmd);
+
+    let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+    packets::Packet::from_slice(&down.items.first().unwrap().phy_payload).unwrap()
+}
@@ -45,11 +45,12 @@ async fn test_relay_gateway_mesh_heartbeat() {
         // Assert the time is ~ now()
         assert!(
             SystemTime::now()
-                .duration_since(v.timestamp)
-                .unwrap_or_default()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .saturating_sub(Duration::from_millis(v.timestamp))
                 < Duration::from_secs(5)
         );
-        v.timestamp = UNIX_EPOCH;
+        v.timestamp = 0;
     }
 
     assert_eq!(
@@ -57,11 +58,14 @@ async fn test_relay_gateway_mesh_heartbeat() {
             mhdr: packets::MHDR {
                 payload_type: packets::PayloadType::Heartbeat,
                 hop_count: 1,
+                key_index: 0,
             },
             payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
                 relay_id: [2, 2, 2, 2],
-                timestamp: UNIX_EPOCH,
+                timestamp: 0,
+                timestamp_monotonic: false,
                 relay_path: vec![],
+                config_checksum: None,
             }),
             mic: None,
         },
@@ -0,0 +1,157 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::challenge;
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway (with challenge enabled) receives a
+    Challenge event from the Border Gateway. In this case, the Relay Gateway must relay
+    the event and must echo the challenge in a Challenge response event, which is sent
+    after its next heartbeat.
+*/
+#[tokio::test]
+async fn test_relay_gateway_relay_mesh_event_challenge() {
+    let mut conf = common::get_config(false);
+    conf.mesh.challenge.enabled = true;
+    common::setup_with_config(conf).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_id: [9, 9, 9, 9],
+            events: vec![packets::Event::Challenge(packets::ChallengeEvent {
+                challenge: [1, 2, 3, 4],
+            })],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868300000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -60,
+            snr: 12.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish Uplink
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the challenge to be relayed.
+    {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+        let down_item = down.items.first().unwrap();
+        let mesh_packet = packets::Packet::from_slice(&down_item.phy_payload).unwrap();
+
+        packet.mhdr.hop_count += 1;
+        packet.set_mic(Aes128Key::null()).unwrap();
+        assert_eq!(packets::Packet::Mesh(packet), mesh_packet);
+
+        let tx_ack = gw::DownlinkTxAck {
+            downlink_id: down.downlink_id,
+            items: vec![gw::DownlinkTxAckItem {
+                status: gw::TxAckStatus::Ok.into(),
+            }],
+            ..Default::default()
+        };
+        cmd_sock.send(tx_ack.encode_to_vec().into()).await.unwrap();
+    }
+
+    // The heartbeat loop sends the challenge response after the next heartbeat.
+    let _ = challenge::report_response().await;
+
+    let down: gw::DownlinkFrame = {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    let down_item = down.items.first().unwrap();
+    let mut mesh_packet = packets::MeshPacket::from_slice(&down_item.phy_payload).unwrap();
+    mesh_packet.mic = None;
+
+    if let packets::Payload::Event(v) = &mut mesh_packet.payload {
+        v.timestamp = 0;
+        v.timestamp_monotonic = false;
+    }
+
+    assert_eq!(
+        packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Event,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Event(packets::EventPayload {
+                timestamp: 0,
+                timestamp_monotonic: false,
+                relay_id: [2, 2, 2, 2],
+                events: vec![packets::Event::ChallengeResponse(packets::ChallengeEvent {
+                    challenge: [1, 2, 3, 4],
+                })],
+            }),
+            mic: None,
+        },
+        mesh_packet
+    );
+}
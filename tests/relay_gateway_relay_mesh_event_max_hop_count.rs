@@ -0,0 +1,142 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway (with adaptive_max_hop_count enabled)
+    receives a Max hop count event from the Border Gateway, advertising a max hop count
+    lower than the configured max_hop_count. In this case, the Relay Gateway must relay
+    the event and must drop a Mesh Heartbeat that exceeds the advertised max hop count.
+*/
+#[tokio::test]
+async fn test_relay_gateway_relay_mesh_event_max_hop_count() {
+    let mut conf = common::get_config(false);
+    conf.mesh.max_hop_count = 3;
+    conf.mesh.adaptive_max_hop_count.enabled = true;
+    common::setup_with_config(conf).await;
+
+    let mut event_packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_id: [9, 9, 9, 9],
+            events: vec![packets::Event::MaxHopCount(packets::MaxHopCountEvent {
+                max_hop_count: 2,
+            })],
+        }),
+        mic: None,
+    };
+    event_packet.set_mic(Aes128Key::null()).unwrap();
+
+    let mut heartbeat_packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Heartbeat,
+            hop_count: 2,
+            key_index: 0,
+        },
+        payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+            relay_id: [1, 2, 3, 4],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_path: vec![packets::RelayPath {
+                relay_id: [5, 5, 5, 5],
+                rssi: -100,
+                snr: -5,
+            }],
+            config_checksum: None,
+        }),
+        mic: None,
+    };
+    heartbeat_packet.set_mic(Aes128Key::null()).unwrap();
+
+    for (i, packet) in [&event_packet, &heartbeat_packet].iter().enumerate() {
+        let up = gw::UplinkFrame {
+            phy_payload: packet.to_vec().unwrap(),
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868300000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                gateway_id: "0101010101010101".to_string(),
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                rssi: -60,
+                snr: 12.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Publish Uplink
+        {
+            let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+
+        if i == 0 {
+            // We expect the event to be relayed, as it is within the advertised max hop count.
+            let msg = cmd_sock.recv().await.unwrap();
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("down", cmd);
+
+            let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+            let down_item = down.items.first().unwrap();
+            let mesh_packet = packets::Packet::from_slice(&down_item.phy_payload).unwrap();
+
+            let mut expected = event_packet.clone();
+            expected.mhdr.hop_count += 1;
+            expected.set_mic(Aes128Key::null()).unwrap();
+            assert_eq!(packets::Packet::Mesh(expected), mesh_packet);
+
+            let tx_ack = gw::DownlinkTxAck {
+                downlink_id: down.downlink_id,
+                items: vec![gw::DownlinkTxAckItem {
+                    status: gw::TxAckStatus::Ok.into(),
+                }],
+                ..Default::default()
+            };
+            cmd_sock.send(tx_ack.encode_to_vec().into()).await.unwrap();
+        } else {
+            // The heartbeat would exceed the advertised max hop count (but not the configured
+            // max_hop_count), thus receiving from the cmd socket should timeout.
+            let resp = timeout(Duration::from_secs(1), cmd_sock.recv()).await;
+            assert!(resp.is_err());
+        }
+    }
+}
@@ -1,5 +1,3 @@
-use std::time::UNIX_EPOCH;
-
 #[macro_use]
 extern crate anyhow;
 
@@ -25,11 +23,14 @@ async fn test_relay_gateway_relay_mesh_heartbeat() {
         mhdr: packets::MHDR {
             payload_type: packets::PayloadType::Heartbeat,
             hop_count: 1,
+            key_index: 0,
         },
         payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
             relay_id: [1, 2, 3, 4],
-            timestamp: UNIX_EPOCH,
+            timestamp: 0,
+            timestamp_monotonic: false,
             relay_path: vec![],
+            config_checksum: None,
         }),
         mic: None,
     };
@@ -0,0 +1,110 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway receives two Mesh Heartbeat
+    packets from the same Relay Gateway within the heartbeat_relay_interval.
+    In this case, the Relay Gateway must only relay the first heartbeat.
+*/
+#[tokio::test]
+async fn test_relay_gateway_relay_mesh_heartbeat_interval() {
+    let mut conf = common::get_config(false);
+    conf.mesh.heartbeat_relay_interval = Duration::from_secs(3600);
+    common::setup_with_config(conf).await;
+
+    for (i, timestamp) in [0, 60].iter().enumerate() {
+        let mut packet = packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Heartbeat,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+                relay_id: [1, 2, 3, 4],
+                timestamp: *timestamp,
+                timestamp_monotonic: false,
+                relay_path: vec![],
+                config_checksum: None,
+            }),
+            mic: None,
+        };
+        packet.set_mic(Aes128Key::null()).unwrap();
+
+        let up = gw::UplinkFrame {
+            phy_payload: packet.to_vec().unwrap(),
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868300000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                gateway_id: "0101010101010101".to_string(),
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                rssi: -60,
+                snr: 12.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Publish Uplink
+        {
+            let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+
+        if i == 0 {
+            // We expect the first heartbeat to be relayed.
+            let msg = cmd_sock.recv().await.unwrap();
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("down", cmd);
+
+            let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+            let tx_ack = gw::DownlinkTxAck {
+                downlink_id: down.downlink_id,
+                items: vec![gw::DownlinkTxAckItem {
+                    status: gw::TxAckStatus::Ok.into(),
+                }],
+                ..Default::default()
+            };
+            cmd_sock.send(tx_ack.encode_to_vec().into()).await.unwrap();
+        } else {
+            // As the second heartbeat has been discarded, receiving from the cmd socket should
+            // timeout.
+            let resp = timeout(Duration::from_secs(1), cmd_sock.recv()).await;
+            assert!(resp.is_err());
+        }
+    }
+}
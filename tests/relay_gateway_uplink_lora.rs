@@ -83,6 +83,7 @@ async fn test_relay_gateway_uplink_lora() {
                 mhdr: packets::MHDR {
                     payload_type: packets::PayloadType::Uplink,
                     hop_count: 1,
+                    key_index: 0,
                 },
                 payload: packets::Payload::Uplink(packets::UplinkPayload {
                     metadata: packets::UplinkMetadata {
@@ -91,8 +92,11 @@ async fn test_relay_gateway_uplink_lora() {
                         rssi: -60,
                         snr: 12,
                         channel: 1,
+                        border_id: 0,
                     },
                     relay_id: [2, 2, 2, 2],
+                    timestamp: None,
+                    timestamp_monotonic: false,
                     phy_payload: vec![1, 2, 3, 4, 5, 6, 7, 8],
                 }),
                 mic: None,
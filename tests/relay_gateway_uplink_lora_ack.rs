@@ -0,0 +1,162 @@
+#[macro_use]
+extern crate anyhow;
+
+use std::time::Duration;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::timeout;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+use chirpstack_gateway_mesh::packets;
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway relays an uplink LoRaWAN
+    frame with uplink acknowledgements enabled. As the uplink is not
+    acknowledged within the timeout, it must be retransmitted. Once the
+    Border Gateway acknowledges the uplink, it must not be retransmitted.
+*/
+#[tokio::test]
+async fn test_relay_gateway_uplink_lora_ack() {
+    let mut conf = common::get_config(false);
+    conf.mesh.uplink_ack.enabled = true;
+    conf.mesh.uplink_ack.timeout = Duration::from_millis(500);
+    conf.mesh.uplink_ack.max_retransmissions = 2;
+    common::setup_with_config(conf).await;
+
+    let up = gw::UplinkFrame {
+        phy_payload: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868300000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -60,
+            snr: 12.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the relayed uplink, followed by the retransmission of the same mesh packet.
+    let mut phy_payloads: Vec<Vec<u8>> = Vec::new();
+    for _ in 0..2 {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+        phy_payloads.push(down.items[0].phy_payload.clone());
+    }
+    assert_eq!(phy_payloads[0], phy_payloads[1]);
+
+    let uplink_id = match packets::MeshPacket::from_slice(&phy_payloads[0])
+        .unwrap()
+        .payload
+    {
+        packets::Payload::Uplink(v) => v.metadata.uplink_id,
+        _ => panic!("Expected Uplink payload"),
+    };
+
+    // The Border Gateway acknowledges the uplink.
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Event,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Event(packets::EventPayload {
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_id: [1, 1, 1, 1],
+            events: vec![packets::Event::UplinkAck(packets::UplinkAckEvent {
+                relay_id: [2, 2, 2, 2],
+                uplink_id,
+            })],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // As the uplink has been acknowledged (and the ACK is not re-relayed), receiving from the
+    // cmd socket should timeout.
+    {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+
+        let resp = timeout(Duration::from_secs(2), cmd_sock.recv()).await;
+        assert!(resp.is_err());
+    }
+}
@@ -0,0 +1,77 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use zeromq::{SocketRecv, SocketSend};
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway relays an uplink LoRaWAN
+    frame, but the mesh Concentratord does not respond to the down command.
+    The Relay Gateway must retry the command with the same downlink.
+*/
+#[tokio::test]
+async fn test_relay_gateway_uplink_lora_command_retry() {
+    let mut conf = common::get_config(false);
+    conf.backend.command_retries = 1;
+    common::setup_with_config(conf).await;
+
+    let up = gw::UplinkFrame {
+        phy_payload: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868300000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -60,
+            snr: 12.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event.
+    {
+        let mut event_sock = common::BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect the same down command twice, as the first one is not responded to.
+    let mut downs: Vec<gw::DownlinkFrame> = Vec::new();
+    for _ in 0..2 {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        downs.push(gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap());
+    }
+
+    assert_eq!(downs[0], downs[1]);
+}
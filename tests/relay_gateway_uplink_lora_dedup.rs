@@ -0,0 +1,90 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway receives the same uplink
+    LoRaWAN frame twice (on different channels) within the uplink_dedup_window.
+    In this case, the Relay Gateway must only relay the first uplink.
+*/
+#[tokio::test]
+async fn test_relay_gateway_uplink_lora_dedup() {
+    let mut conf = common::get_config(false);
+    conf.mesh.uplink_dedup_window = Duration::from_secs(3600);
+    common::setup_with_config(conf).await;
+
+    for (i, frequency) in [868100000, 868300000].iter().enumerate() {
+        let up = gw::UplinkFrame {
+            phy_payload: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: *frequency,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                gateway_id: "0101010101010101".to_string(),
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                rssi: -60,
+                snr: 12.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Publish Uplink
+        {
+            let mut event_sock = common::BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+            event_sock
+                .send(
+                    vec![
+                        bytes::Bytes::from("up"),
+                        bytes::Bytes::from(up.encode_to_vec()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+
+        if i == 0 {
+            // We expect the first uplink to be relayed.
+            let msg = cmd_sock.recv().await.unwrap();
+            let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+            assert_eq!("down", cmd);
+
+            let down = gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap();
+            let tx_ack = gw::DownlinkTxAck {
+                downlink_id: down.downlink_id,
+                items: vec![gw::DownlinkTxAckItem {
+                    status: gw::TxAckStatus::Ok.into(),
+                }],
+                ..Default::default()
+            };
+            cmd_sock.send(tx_ack.encode_to_vec().into()).await.unwrap();
+        } else {
+            // As the second uplink has been discarded, receiving from the cmd socket should
+            // timeout.
+            let resp = timeout(Duration::from_secs(1), cmd_sock.recv()).await;
+            assert!(resp.is_err());
+        }
+    }
+}
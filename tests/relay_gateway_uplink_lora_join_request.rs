@@ -0,0 +1,140 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::config;
+use chirpstack_gateway_mesh::packets;
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway is configured to relay
+    join-requests only, using a different data-rate. The data uplink must be
+    dropped, the join-request must be relayed using the join-request data-rate.
+*/
+#[tokio::test]
+async fn test_relay_gateway_uplink_lora_join_request() {
+    let mut conf = common::get_config(false);
+    conf.mesh.join_requests.relay_only = true;
+    conf.mesh.join_requests.data_rate = Some(config::DataRate {
+        modulation: config::Modulation::LORA,
+        spreading_factor: 9,
+        bandwidth: 125000,
+        code_rate: Some(config::CodeRate::Cr45),
+        bitrate: 0,
+    });
+    common::setup_with_config(conf).await;
+
+    // Unconfirmed data-up, followed by a join-request.
+    for phy_payload in [vec![0x40, 1, 2, 3, 4], vec![0x00, 1, 2, 3, 4]] {
+        let up = gw::UplinkFrame {
+            phy_payload,
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868300000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 12,
+                        code_rate: gw::CodeRate::Cr45.into(),
+                        ..Default::default()
+                    })),
+                }),
+            }),
+            rx_info: Some(gw::UplinkRxInfo {
+                gateway_id: "0101010101010101".to_string(),
+                crc_status: gw::CrcStatus::CrcOk.into(),
+                rssi: -60,
+                snr: 12.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let mut event_sock = common::BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect only the join-request to be received by the mesh concentratord.
+    let down: gw::DownlinkFrame = {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    let down_item = down.items.first().unwrap();
+    let mesh_packet = packets::MeshPacket::from_slice(&down_item.phy_payload).unwrap();
+
+    assert_eq!(
+        {
+            let mut packet = packets::MeshPacket {
+                mhdr: packets::MHDR {
+                    payload_type: packets::PayloadType::Uplink,
+                    hop_count: 1,
+                    key_index: 0,
+                },
+                payload: packets::Payload::Uplink(packets::UplinkPayload {
+                    metadata: packets::UplinkMetadata {
+                        uplink_id: 1,
+                        dr: 0,
+                        rssi: -60,
+                        snr: 12,
+                        channel: 1,
+                        border_id: 0,
+                    },
+                    relay_id: [2, 2, 2, 2],
+                    timestamp: None,
+                    timestamp_monotonic: false,
+                    phy_payload: vec![0x00, 1, 2, 3, 4],
+                }),
+                mic: None,
+            };
+            packet.set_mic(Aes128Key::null()).unwrap();
+            packet
+        },
+        mesh_packet
+    );
+
+    assert_eq!(
+        &gw::DownlinkTxInfo {
+            frequency: 868100000,
+            power: 16,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 9,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                }))
+            }),
+            timing: Some(gw::Timing {
+                parameters: Some(gw::timing::Parameters::Immediately(
+                    gw::ImmediatelyTimingInfo {}
+                )),
+            }),
+            ..Default::default()
+        },
+        down_item.tx_info.as_ref().unwrap()
+    );
+}
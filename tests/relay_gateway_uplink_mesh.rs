@@ -24,6 +24,7 @@ async fn test_relay_gateway_uplink_mesh() {
             mhdr: packets::MHDR {
                 payload_type: packets::PayloadType::Uplink,
                 hop_count: 1,
+                key_index: 0,
             },
             payload: packets::Payload::Uplink(packets::UplinkPayload {
                 metadata: packets::UplinkMetadata {
@@ -32,8 +33,11 @@ async fn test_relay_gateway_uplink_mesh() {
                     rssi: 0,
                     snr: 0,
                     channel: 0,
+                    border_id: 0,
                 },
                 relay_id: [1, 2, 3, 4],
+                timestamp: None,
+                timestamp_monotonic: false,
                 phy_payload: vec![4, 3, 2, 1],
             }),
             mic: None,
@@ -0,0 +1,120 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway, configured with a relay jitter, receives an
+    uplink mesh encapsulated frame. The Relay gateway will then re-transmit this frame within the
+    relay jitter.
+*/
+#[tokio::test]
+async fn test_relay_gateway_uplink_mesh_jitter() {
+    let mut conf = common::get_config(false);
+    conf.mesh.relay_jitter = Duration::from_millis(500);
+    common::setup_with_config(conf).await;
+
+    let mut packet = packets::Packet::Mesh({
+        let mut packet = packets::MeshPacket {
+            mhdr: packets::MHDR {
+                payload_type: packets::PayloadType::Uplink,
+                hop_count: 1,
+                key_index: 0,
+            },
+            payload: packets::Payload::Uplink(packets::UplinkPayload {
+                metadata: packets::UplinkMetadata {
+                    uplink_id: 123,
+                    dr: 0,
+                    rssi: 0,
+                    snr: 0,
+                    channel: 0,
+                    border_id: 0,
+                },
+                relay_id: [1, 2, 3, 4],
+                timestamp: None,
+                timestamp_monotonic: false,
+                phy_payload: vec![4, 3, 2, 1],
+            }),
+            mic: None,
+        };
+        packet.set_mic(Aes128Key::null()).unwrap();
+        packet
+    });
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868300000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -60,
+            snr: 12.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect packet to be wrapped as 'downlink' and received by the
+    // mesh concentratord.
+    let down: gw::DownlinkFrame = {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = timeout(Duration::from_secs(2), cmd_sock.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    let down_item = down.items.first().unwrap();
+    let mesh_packet = packets::Packet::from_slice(&down_item.phy_payload).unwrap();
+
+    // The hop_count must be incremented.
+    if let packets::Packet::Mesh(v) = &mut packet {
+        v.mhdr.hop_count += 1;
+        v.set_mic(Aes128Key::null()).unwrap();
+    }
+
+    assert_eq!(packet, mesh_packet);
+}
@@ -0,0 +1,121 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::{config, packets};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+
+mod common;
+
+/*
+    This tests the scenario when the Relay Gateway receives an uplink mesh encapsulated frame,
+    signed with the signing key of key index 0, while the Relay Gateway signs using the signing
+    key of key index 1 (key rotation). The Relay Gateway must validate the frame using the key of
+    key index 0 and re-transmit it using the key of key index 1.
+*/
+#[tokio::test]
+async fn test_relay_gateway_uplink_mesh_key_index() {
+    let new_key = Aes128Key::from_bytes([1; 16]);
+
+    let mut conf = common::get_config(false);
+    conf.mesh.packet_format_version = 2;
+    conf.mesh.signing_key_index = 1;
+    conf.mesh.signing_keys = vec![config::SigningKey {
+        key_index: 1,
+        key: new_key,
+    }];
+    common::setup_with_config(conf).await;
+
+    let mut packet = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Uplink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                rssi: 0,
+                snr: 0,
+                channel: 0,
+                border_id: 0,
+            },
+            relay_id: [1, 2, 3, 4],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![4, 3, 2, 1],
+        }),
+        mic: None,
+    };
+    packet.set_mic(Aes128Key::null()).unwrap();
+
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868300000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi: -60,
+            snr: 12.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    // Publish uplink event
+    {
+        let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+        event_sock
+            .send(
+                vec![
+                    bytes::Bytes::from("up"),
+                    bytes::Bytes::from(up.encode_to_vec()),
+                ]
+                .try_into()
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // We expect packet to be wrapped as 'downlink' and received by the
+    // mesh concentratord.
+    let down: gw::DownlinkFrame = {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+
+        gw::DownlinkFrame::decode(msg.get(1).cloned().unwrap()).unwrap()
+    };
+
+    let down_item = down.items.first().unwrap();
+    let mesh_packet = packets::MeshPacket::from_slice(&down_item.phy_payload).unwrap();
+
+    // The hop_count must be incremented and the packet must be signed using key index 1.
+    packet.mhdr.hop_count += 1;
+    packet.mhdr.key_index = 1;
+    packet.set_mic(new_key).unwrap();
+
+    assert_eq!(packet, mesh_packet);
+    assert!(mesh_packet.validate_mic(new_key).unwrap());
+}
@@ -0,0 +1,156 @@
+#[macro_use]
+extern crate anyhow;
+
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+use chirpstack_gateway_mesh::packets;
+use tokio::time::{timeout, Duration};
+use zeromq::{SocketRecv, SocketSend};
+
+use chirpstack_gateway_mesh::aes128::Aes128Key;
+
+mod common;
+
+/*
+    This tests the scenario when routing is enabled and the Relay Gateway has learned
+    (from the relay path of a heartbeat) that an other relay has a better link with the
+    transmitter of an uplink. In this case the Relay Gateway must not re-transmit it.
+*/
+#[tokio::test]
+async fn test_relay_gateway_uplink_mesh_routing() {
+    let mut conf = common::get_config(false);
+    conf.mesh.max_hop_count = 3;
+    conf.mesh.routing.enabled = true;
+    common::setup_with_config(conf).await;
+
+    // Heartbeat of relay 03030303, relayed by relay 04040404 (good link).
+    let mut heartbeat = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Heartbeat,
+            hop_count: 2,
+            key_index: 0,
+        },
+        payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+            relay_id: [3, 3, 3, 3],
+            timestamp: 0,
+            timestamp_monotonic: false,
+            relay_path: vec![packets::RelayPath {
+                relay_id: [4, 4, 4, 4],
+                rssi: -50,
+                snr: 10,
+            }],
+            config_checksum: None,
+        }),
+        mic: None,
+    };
+    heartbeat.set_mic(Aes128Key::null()).unwrap();
+    send_mesh_packet(&heartbeat, -60).await;
+
+    // The heartbeat is relayed, as the transmitter (04040404) has no other known links.
+    {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+        let msg = cmd_sock.recv().await.unwrap();
+
+        let cmd = String::from_utf8(msg.get(0).map(|v| v.to_vec()).unwrap()).unwrap();
+        assert_eq!("down", cmd);
+    }
+
+    // Uplink of relay 03030303, received directly using a weak link.
+    let mut uplink = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Uplink,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Uplink(packets::UplinkPayload {
+            metadata: packets::UplinkMetadata {
+                uplink_id: 123,
+                dr: 0,
+                rssi: 0,
+                snr: 0,
+                channel: 0,
+                border_id: 0,
+            },
+            relay_id: [3, 3, 3, 3],
+            timestamp: None,
+            timestamp_monotonic: false,
+            phy_payload: vec![4, 3, 2, 1],
+        }),
+        mic: None,
+    };
+    uplink.set_mic(Aes128Key::null()).unwrap();
+
+    // Heartbeat of relay 03030303, received directly using a weak link.
+    let mut heartbeat = packets::MeshPacket {
+        mhdr: packets::MHDR {
+            payload_type: packets::PayloadType::Heartbeat,
+            hop_count: 1,
+            key_index: 0,
+        },
+        payload: packets::Payload::Heartbeat(packets::HeartbeatPayload {
+            relay_id: [3, 3, 3, 3],
+            timestamp: 1,
+            timestamp_monotonic: false,
+            relay_path: vec![],
+            config_checksum: None,
+        }),
+        mic: None,
+    };
+    heartbeat.set_mic(Aes128Key::null()).unwrap();
+    send_mesh_packet(&heartbeat, -110).await;
+    send_mesh_packet(&uplink, -110).await;
+
+    // As relay 04040404 has a better link with 03030303, both packets must be dropped.
+    {
+        let mut cmd_sock = common::MESH_BACKEND_COMMAND_SOCK
+            .get()
+            .unwrap()
+            .lock()
+            .await;
+
+        let resp = timeout(Duration::from_secs(1), cmd_sock.recv()).await;
+        assert!(resp.is_err());
+    }
+}
+
+async fn send_mesh_packet(packet: &packets::MeshPacket, rssi: i32) {
+    let up = gw::UplinkFrame {
+        phy_payload: packet.to_vec().unwrap(),
+        tx_info: Some(gw::UplinkTxInfo {
+            frequency: 868300000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+        }),
+        rx_info: Some(gw::UplinkRxInfo {
+            gateway_id: "0101010101010101".to_string(),
+            crc_status: gw::CrcStatus::CrcOk.into(),
+            rssi,
+            snr: 5.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let mut event_sock = common::MESH_BACKEND_EVENT_SOCK.get().unwrap().lock().await;
+    event_sock
+        .send(
+            vec![
+                bytes::Bytes::from("up"),
+                bytes::Bytes::from(up.encode_to_vec()),
+            ]
+            .try_into()
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+}